@@ -1 +1,50 @@
+//===- lib.rs - Bebop library facade ---------------------------------------===//
+//
+// Copyright 2026 The Aerospace Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===----------------------------------------------------------------------===//
+//
+// Public API for embedding bebop as a library.
+//
+// Each simulator and support library already lives in its own workspace crate
+// under src/nodes. This crate re-exports them under one stable path so callers
+// can depend on `bebop` alone and pick backends with the same features as the
+// CLI (bemu, verilator, p2e). `bemu-model` pulls in the accelerator model
+// without Spike or the console server:
+//
+//   bebop::bemu::Npu          accelerator model, no Spike hart
+//   bebop::bemu::NpuSim       the same model stepped cycle by cycle
+//   bebop::bemu::BemuInstance Spike + accelerator (bemu only)
+//   bebop::bank_hash          bank hashing and trace comparison
+//   bebop::rtl_trace          RTL-side trace writers
+//   bebop::golden             f64 reference matmul/conv/pool/norm
+//
+// Depending on the node crates directly keeps working; these are aliases.
+//
+//===----------------------------------------------------------------------===//
 
+pub use bebop_bank_hash as bank_hash;
+pub use bebop_dasm as dasm;
+pub use bebop_fd_redirect as fd_redirect;
+pub use bebop_golden as golden;
+pub use bebop_rtl_trace as rtl_trace;
+pub use bebop_uart as uart;
+
+#[cfg(feature = "bemu-model")]
+pub use bebop_bemu as bemu;
+#[cfg(feature = "p2e")]
+pub use bebop_p2e as p2e;
+#[cfg(feature = "verilator")]
+pub use bebop_verilator as verilator;
@@ -17,9 +17,19 @@
 //===----------------------------------------------------------------------===//
 //
 // Bebop CLI entry point.
-// It dispatches the CLI parsing into two separate execution paths:
+// It dispatches the CLI parsing into separate execution paths:
 // - build: to build simulator artifacts (build)
 // - simulation: to run workloads on simulator built artifacts (run)
+// - script: to drive the BEMU accelerator model from a Rhai script (script)
+// - bench-suite: to time a fixed kernel set on the BEMU model (bench-suite)
+// - snapshot-diff: to compare two BEMU snapshots field by field (snapshot-diff)
+// - bank-diff: to compare two BEMU bank dumps element by element (bank-diff)
+// - program: to run a text file of accelerator instructions (program)
+// - serve: to drive the BEMU model over HTTP (serve)
+// - replay: to re-execute a recorded instruction stream (replay)
+// - debug: to step an instruction program with breakpoints (debug)
+// - lower: to lower a JSON layer description to an instruction program (lower)
+// - compare: to run a program on two configurations in lockstep (compare)
 //
 //===----------------------------------------------------------------------===//
 
@@ -31,6 +41,19 @@ mod simulation;
 #[derive(Debug, Parser)]
 #[command(name = "bebop", about = "Bebop CLI")]
 pub struct Cli {
+    #[arg(
+        long,
+        global = true,
+        help = "Abort on events that are normally warned about and dropped (unclassified functs, bad trace packets)"
+    )]
+    pub strict: bool,
+    #[arg(
+        long,
+        global = true,
+        value_name = "INSTS",
+        help = "Print BEMU [RTRACE] lines for the comma-separated instructions (e.g. mvin,matmul), or `all`"
+    )]
+    pub rtrace: Option<String>,
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -41,6 +64,27 @@ pub enum Commands {
     Build(BuildCommand),
     /// Run a workload on a built simulator artifact.
     Run(RunCommand),
+    /// Run a Rhai stimulus script against the BEMU accelerator model.
+    Script(ScriptCommand),
+    /// Run the fixed BEMU kernel suite and print a markdown table.
+    BenchSuite(BenchSuiteCommand),
+    /// Compare two BEMU state snapshots and list the differing fields.
+    SnapshotDiff(SnapshotDiffCommand),
+    /// Compare two BEMU bank dumps element by element.
+    BankDiff(SnapshotDiffCommand),
+    /// Run a text file of accelerator instructions on the BEMU model.
+    Program(ProgramCommand),
+    /// Serve a BEMU accelerator model over HTTP for remote control.
+    Serve(ServeCommand),
+    /// Re-execute a recorded instruction stream on the BEMU model.
+    Replay(ReplayCommand),
+    /// Step an instruction program on the BEMU model with breakpoints.
+    Debug(DebugCommand),
+    /// Lower a JSON layer description to a BEMU instruction program.
+    Lower(LowerCommand),
+    /// Run an instruction program on two BEMU configurations in lockstep and
+    /// report the first instruction whose DRAM writes differ.
+    Compare(CompareCommand),
 }
 
 #[derive(Debug, Args)]
@@ -77,6 +121,268 @@ pub struct RunCommand {
     pub target: RunTarget,
 }
 
+#[derive(Debug, Args)]
+pub struct ScriptCommand {
+    #[arg(value_name = "FILE")]
+    pub file: PathBuf,
+    #[arg(long, value_name = "N", help = "Soak: rerun the script N times on a reset model")]
+    pub soak: Option<u64>,
+    #[arg(long, value_name = "SECS", help = "Soak: keep rerunning the script for SECS seconds")]
+    pub soak_secs: Option<u64>,
+    #[arg(long, value_name = "FILE", help = "Write the soak stability report (JSON) to FILE")]
+    pub report: Option<PathBuf>,
+    #[command(flatten)]
+    pub model: ModelArgs,
+    #[arg(
+        long,
+        help = "Abort on a DMA with a DRAM address that is not 16-byte aligned instead of charging a split-beat penalty"
+    )]
+    pub fault_misaligned_dma: bool,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Count ISA coverage bins and merge them into FILE (created if missing)"
+    )]
+    pub coverage: Option<PathBuf>,
+}
+
+/// Options that shape and observe the BEMU model, for every command that
+/// runs one.
+#[derive(Debug, Default, Args)]
+pub struct ModelArgs {
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Architecture TOML: banking, array shape, bank ports, energy and cache from its [arch.*] tables, extra mnemonics from [[isa.inst]]"
+    )]
+    pub arch: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "SEED",
+        num_args = 0..=1,
+        help = "Fill DRAM and banks with seeded garbage instead of zeros (random seed if omitted)"
+    )]
+    pub random_init: Option<Option<u64>>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "DRAM row-buffer and bandwidth timing (TOML); DRAM has zero latency without it"
+    )]
+    pub dram_timing: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Inject bit flips into banks and DRAM reads as the TOML file says, and report how ECC handled them"
+    )]
+    pub faults: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Write utilization and roofline statistics (JSON) to FILE"
+    )]
+    pub stats: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct BenchSuiteCommand {
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Write the markdown table to FILE instead of stdout"
+    )]
+    pub output: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "CSV",
+        help = "Run the calibration microbenchmarks against reference `kernel,cycles` measurements and suggest bank latencies"
+    )]
+    pub calibrate: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        requires = "calibrate",
+        help = "Architecture TOML to calibrate from, as for --arch; only its bank latencies are varied"
+    )]
+    pub arch: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "SEED",
+        num_args = 0..=1,
+        requires = "calibrate",
+        help = "Run the calibration kernels on seeded garbage instead of zeros, as for --random-init"
+    )]
+    pub random_init: Option<Option<u64>>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        requires = "calibrate",
+        help = "DRAM timing of the calibration runs, as for --dram-timing"
+    )]
+    pub dram_timing: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        requires = "calibrate",
+        help = "Faults injected into the calibration runs, as for --faults"
+    )]
+    pub faults: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct SnapshotDiffCommand {
+    #[arg(value_name = "LEFT")]
+    pub left: PathBuf,
+    #[arg(value_name = "RIGHT")]
+    pub right: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct ProgramCommand {
+    #[arg(value_name = "FILE")]
+    pub file: PathBuf,
+    #[command(flatten)]
+    pub model: ModelArgs,
+    #[arg(
+        long,
+        help = "Check mvin, matmul, conv and mvout results against the golden model and fail on a mismatch"
+    )]
+    pub golden_check: bool,
+    #[arg(
+        long,
+        help = "Warn when matmul or conv reads bank lines written as another type or row layout"
+    )]
+    pub tile_tags: bool,
+    #[arg(
+        long,
+        value_name = "SPEC",
+        help = "Log every instruction that touches dram:ADDR:LEN (DMA reads and writes) or bank:V:OFF:LEN (writes); repeatable"
+    )]
+    pub watch: Vec<String>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Bank partition TOML; FILE and each --stream then run as separate tenants, one instruction from each in turn"
+    )]
+    pub tenants: Option<PathBuf>,
+    #[arg(
+        long = "stream",
+        value_name = "FILE",
+        requires = "tenants",
+        help = "Instruction program of the next tenant after FILE; repeatable"
+    )]
+    pub streams: Vec<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct ReplayCommand {
+    #[arg(value_name = "FILE", help = "Recording written by `run bemu --record`")]
+    pub file: PathBuf,
+    #[command(flatten)]
+    pub model: ModelArgs,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Write an accelerator state snapshot to FILE at the end"
+    )]
+    pub snapshot: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Print the recorded instructions as program text instead of replaying them"
+    )]
+    pub disassemble: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct DebugCommand {
+    #[arg(value_name = "FILE", help = "Instruction program, as accepted by `program`")]
+    pub file: PathBuf,
+    #[command(flatten)]
+    pub model: ModelArgs,
+    #[arg(
+        long,
+        value_name = "UNITS",
+        num_args = 0..=1,
+        default_missing_value = "1",
+        help = "Step the pipelined model one cycle at a time with UNITS execution units (default 1)"
+    )]
+    pub cycles: Option<usize>,
+}
+
+#[derive(Debug, Args)]
+pub struct LowerCommand {
+    #[arg(value_name = "FILE", help = "Layer description (JSON)")]
+    pub file: PathBuf,
+    #[arg(
+        short,
+        long,
+        value_name = "FILE",
+        help = "Write the program to FILE instead of stdout"
+    )]
+    pub output: Option<PathBuf>,
+    #[arg(long, help = "Run the lowered program and print per-layer cycles")]
+    pub run: bool,
+    #[command(flatten)]
+    pub model: ModelArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct CompareCommand {
+    #[arg(value_name = "FILE", help = "Instruction program, as accepted by `program`")]
+    pub file: PathBuf,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Architecture TOML of the left model, as for --arch; the defaults without it"
+    )]
+    pub left_arch: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Architecture TOML of the right model, as for --arch; the defaults without it"
+    )]
+    pub right_arch: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "SEED",
+        num_args = 0..=1,
+        help = "Fill both models' DRAM and banks with the same seeded garbage instead of zeros (random seed if omitted)"
+    )]
+    pub random_init: Option<Option<u64>>,
+    #[arg(long, value_name = "FILE", help = "DRAM timing of both models, as for --dram-timing")]
+    pub dram_timing: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Faults injected into both models, as for --faults"
+    )]
+    pub faults: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct ServeCommand {
+    #[arg(
+        long,
+        value_name = "HOST:PORT",
+        help = "Listen address; overrides [serve] addr of --arch [default: 127.0.0.1:7878]"
+    )]
+    pub addr: Option<String>,
+    #[arg(long, value_name = "BYTES", default_value_t = 64 << 20, help = "Guest DRAM size")]
+    pub mem_size: usize,
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 1,
+        help = "Accelerator models to serve, selected per request with a Bebop-Hart header; they share guest DRAM"
+    )]
+    pub harts: usize,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Scratchpad banking and systolic array shape from the [arch.buckyball] and [arch.systolic] tables of a TOML file, listen address from [serve]"
+    )]
+    pub arch: Option<PathBuf>,
+}
+
 #[derive(Debug, Subcommand)]
 pub enum RunTarget {
     /// Run a workload on a Verilator-based simulator artifact.
@@ -106,12 +412,61 @@ pub enum RunTarget {
     },
     /// Run a workload on BEMU.
     Bemu {
-        #[arg(long, value_name = "ELF")]
-        elf: PathBuf,
+        #[arg(
+            long,
+            value_name = "ELF",
+            required = true,
+            help = "Workload ELF; repeat to run several back to back on one warm simulator"
+        )]
+        elf: Vec<PathBuf>,
         #[arg(long, value_name = "DIR")]
         log_dir: PathBuf,
         #[arg(long, help = "Run with proxy kernel (Linux mode, starts in S-mode)")]
         pk: bool,
+        #[command(flatten)]
+        model: ModelArgs,
+        #[arg(
+            long,
+            value_name = "FILE",
+            help = "Write an accelerator state snapshot to FILE at the end"
+        )]
+        snapshot: Option<PathBuf>,
+        #[arg(
+            long,
+            help = "Abort on a DMA with a DRAM address that is not 16-byte aligned instead of charging a split-beat penalty"
+        )]
+        fault_misaligned_dma: bool,
+        #[arg(
+            long,
+            value_name = "CYCLES",
+            help = "Checkpoint the accelerator into <log-dir>/checkpoints every CYCLES cycles"
+        )]
+        checkpoint_every: Option<u64>,
+        #[arg(
+            long,
+            value_name = "K",
+            default_value_t = 3,
+            help = "Keep only the last K checkpoints"
+        )]
+        checkpoint_keep: usize,
+        #[arg(
+            long,
+            value_name = "CKPT",
+            help = "Restore the accelerator from a checkpoint before the first workload; the workload starts from its entry point"
+        )]
+        resume: Option<PathBuf>,
+        #[arg(
+            long,
+            value_name = "FILE",
+            help = "Count ISA coverage bins and merge them into FILE (created if missing)"
+        )]
+        coverage: Option<PathBuf>,
+        #[arg(
+            long,
+            value_name = "FILE",
+            help = "Record every accelerator instruction to FILE for `bebop replay`"
+        )]
+        record: Option<PathBuf>,
     },
     /// Run a workload on a P2E simulator artifact.
     P2e {
@@ -142,11 +497,31 @@ pub enum RunTarget {
 
 fn main() {
     let cli = Cli::parse();
+    if cli.strict {
+        // Read by the node crates, which have no access to the CLI.
+        std::env::set_var("BEBOP_STRICT", "1");
+    }
+    if let Some(insts) = &cli.rtrace {
+        std::env::set_var("BEMU_RTRACE", insts);
+    }
     let result = match cli.command {
         Commands::Build(command) => simulation::build(command),
         Commands::Run(command) => simulation::run(command),
+        Commands::Script(command) => simulation::script(command),
+        Commands::BenchSuite(command) => simulation::bench_suite(command),
+        Commands::SnapshotDiff(command) => simulation::snapshot_diff(command),
+        Commands::BankDiff(command) => simulation::bank_diff(command),
+        Commands::Program(command) => simulation::program(command),
+        Commands::Serve(command) => simulation::serve(command),
+        Commands::Replay(command) => simulation::replay(command),
+        Commands::Debug(command) => simulation::debug(command),
+        Commands::Lower(command) => simulation::lower(command),
+        Commands::Compare(command) => simulation::compare(command),
     };
 
+    #[cfg(feature = "lock-audit")]
+    eprint!("{}", bebop_lock_audit::report());
+
     if let Err(e) = result {
         eprintln!("Error: {}", e);
         std::process::exit(1);
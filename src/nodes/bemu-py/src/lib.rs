@@ -0,0 +1,323 @@
+//===- lib.rs - BEMU accelerator model for Python --------------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// PyO3 wrapper around bebop_bemu::Npu and NpuSim, for design-space sweeps
+// driven from Python. Like bemu-wasm it depends on bebop-bemu without the
+// `host` feature, so there is no Spike in the module. Byte buffers are
+// `bytes`, statistics are dicts, and a model abort raises PanicException.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use bebop_bemu::{Arbitration, NpuSimConfig};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// DRAM size when Python does not pass one; many models fit in one process.
+pub const PY_MEM_SIZE: usize = 64 << 20;
+
+fn value_error(e: String) -> PyErr {
+    PyValueError::new_err(e)
+}
+
+/// The perf report as a dict, through its JSON form.
+fn perf_dict<'py>(py: Python<'py>, model: &bebop_bemu::Npu) -> PyResult<Bound<'py, PyAny>> {
+    let json = serde_json::to_string(&model.perf_report()).map_err(|e| value_error(e.to_string()))?;
+    py.import("json")?.call_method1("loads", (json,))
+}
+
+#[pyclass(unsendable)]
+pub struct Npu {
+    model: bebop_bemu::Npu,
+}
+
+#[pymethods]
+impl Npu {
+    #[new]
+    #[pyo3(signature = (mem_size = PY_MEM_SIZE))]
+    fn new(mem_size: usize) -> Self {
+        Npu {
+            model: bebop_bemu::Npu::new(mem_size),
+        }
+    }
+
+    /// Execute one instruction by funct7 and return rd.
+    fn inst(&mut self, funct: u32, xs1: u64, xs2: u64) -> u64 {
+        self.model.exec(funct, xs1, xs2, 0)
+    }
+
+    /// Execute a binary-encoded RoCC custom-0..3 instruction word.
+    fn rocc(&mut self, insn: u32, xs1: u64, xs2: u64) -> PyResult<u64> {
+        self.model
+            .exec_rocc(insn, xs1, xs2, 0)
+            .map_err(|e| value_error(e.to_string()))
+    }
+
+    fn reset(&mut self) {
+        self.model.reset();
+    }
+
+    fn write_dram(&mut self, addr: u64, data: &[u8]) {
+        self.model.write_dram(addr, data);
+    }
+
+    fn read_dram(&self, addr: u64, len: usize) -> Vec<u8> {
+        self.model.read_dram(addr, len)
+    }
+
+    /// Bytes of `vbank`, or `None` if it is not mapped or the range runs
+    /// past its end.
+    fn read_bank(&self, vbank: u32, offset: usize, len: usize) -> Option<Vec<u8>> {
+        self.model.bank_range(vbank, offset, len).ok().map(<[u8]>::to_vec)
+    }
+
+    fn write_bank(&mut self, vbank: u32, offset: usize, data: &[u8]) -> PyResult<()> {
+        self.model
+            .write_bank_range(vbank, offset, data)
+            .map_err(|e| value_error(e.to_string()))
+    }
+
+    /// Run a text instruction program (see `bebop program`) and return the
+    /// report it prints.
+    fn run_program(&mut self, path: &str) -> PyResult<String> {
+        let report = self.model.run_program(path.as_ref()).map_err(value_error)?;
+        Ok(report.to_string())
+    }
+
+    fn cycles(&self) -> u64 {
+        self.model.total_latency()
+    }
+
+    fn instructions(&self) -> u64 {
+        self.model.instruction_count()
+    }
+
+    /// Utilization and roofline report, as `bebop program --stats` writes it.
+    fn stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        perf_dict(py, &self.model)
+    }
+}
+
+#[pyclass(unsendable)]
+pub struct NpuSim {
+    sim: bebop_bemu::NpuSim,
+}
+
+#[pymethods]
+impl NpuSim {
+    /// `arbitration` is "round-robin" or "scoreboard". `dma_units` adds units
+    /// that take only DMA instructions. An `issue_width` of 0 issues to every
+    /// free unit; a `rob_depth` of 0 means no reorder buffer.
+    /// `timeline` records every instruction for `write_timeline`.
+    /// `out_of_order` renames written banks and retires out of order.
+    #[new]
+    #[pyo3(signature = (
+        mem_size = PY_MEM_SIZE,
+        units = 1,
+        queue_depth = 64,
+        arbitration = "round-robin",
+        issue_width = 0,
+        rob_depth = 0,
+        timeline = false,
+        dma_units = 0,
+        out_of_order = false
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        mem_size: usize,
+        units: usize,
+        queue_depth: usize,
+        arbitration: &str,
+        issue_width: usize,
+        rob_depth: usize,
+        timeline: bool,
+        dma_units: usize,
+        out_of_order: bool,
+    ) -> PyResult<Self> {
+        let arbitration = match arbitration {
+            "round-robin" => Arbitration::RoundRobin,
+            "scoreboard" => Arbitration::Scoreboard,
+            other => return Err(value_error(format!("unknown arbitration {other:?}"))),
+        };
+        Ok(NpuSim {
+            sim: bebop_bemu::NpuSim::new(NpuSimConfig {
+                mem_size,
+                units,
+                dma_units,
+                queue_depth,
+                issue_width,
+                rob_depth,
+                arbitration,
+                out_of_order,
+                timeline,
+            }),
+        })
+    }
+
+    /// Queue one instruction; fails when the issue queue is full.
+    fn push_inst(&mut self, funct: u32, xs1: u64, xs2: u64) -> PyResult<()> {
+        self.sim.push_inst(funct, xs1, xs2).map_err(value_error)
+    }
+
+    /// Advance `n` cycles and return how many instructions retired.
+    #[pyo3(signature = (n = 1))]
+    fn tick(&mut self, n: u64) -> u64 {
+        self.sim.tick(n)
+    }
+
+    /// Run until every queued instruction retired; returns the cycles taken.
+    fn run_until_idle(&mut self) -> u64 {
+        self.sim.run_until_idle()
+    }
+
+    fn is_idle(&self) -> bool {
+        self.sim.is_idle()
+    }
+
+    /// Empty the pipeline, zero the counters and reset the model; DRAM is
+    /// kept.
+    fn reset(&mut self) {
+        self.sim.reset();
+    }
+
+    fn write_dram(&mut self, addr: u64, data: &[u8]) {
+        self.sim.npu_mut().write_dram(addr, data);
+    }
+
+    fn read_dram(&self, addr: u64, len: usize) -> Vec<u8> {
+        self.sim.npu().read_dram(addr, len)
+    }
+
+    fn read_bank(&self, vbank: u32, offset: usize, len: usize) -> Option<Vec<u8>> {
+        self.sim.npu().bank_range(vbank, offset, len).ok().map(<[u8]>::to_vec)
+    }
+
+    /// Pipeline counters of the simulator.
+    fn stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let s = self.sim.stats();
+        let d = PyDict::new(py);
+        d.set_item("cycle", s.cycle)?;
+        d.set_item("pushed", s.pushed)?;
+        d.set_item("issued", s.issued)?;
+        d.set_item("retired", s.retired)?;
+        d.set_item("busy_cycles", s.busy_cycles)?;
+        d.set_item("hazard_stalls", s.hazard_stalls)?;
+        d.set_item("port_stalls", s.port_stalls)?;
+        d.set_item("drain_stalls", s.drain_stalls)?;
+        d.set_item("unit_stalls", s.unit_stalls)?;
+        d.set_item("width_stalls", s.width_stalls)?;
+        d.set_item("rob_stalls", s.rob_stalls)?;
+        d.set_item("queued", s.queued)?;
+        d.set_item("unit_busy_cycles", self.sim.unit_busy_cycles().to_vec())?;
+        d.set_item("rob_occupancy", self.sim.rob_occupancy().to_vec())?;
+        Ok(d)
+    }
+
+    /// Utilization and roofline report of the model underneath.
+    fn perf<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        perf_dict(py, self.sim.npu())
+    }
+
+    /// Write retired instructions as Chrome trace-event JSON; needs
+    /// `timeline=True`.
+    fn write_timeline(&self, path: &str) -> PyResult<()> {
+        self.sim.write_timeline(path.as_ref()).map_err(value_error)
+    }
+
+    /// Write occupancy and stall causes as CSV, one row per `every` cycles
+    /// from now on; the file is complete after `stop_occupancy`.
+    #[pyo3(signature = (path, every = 1))]
+    fn write_occupancy(&mut self, path: &str, every: u64) -> PyResult<()> {
+        self.sim.write_occupancy(path.as_ref(), every).map_err(value_error)
+    }
+
+    fn stop_occupancy(&mut self) {
+        self.sim.stop_occupancy();
+    }
+}
+
+#[pymodule]
+fn bemu(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Npu>()?;
+    m.add_class::<NpuSim>()?;
+    m.add("DRAM_BASE", bebop_bemu::DRAM_BASE)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bebop_bemu::DRAM_BASE;
+
+    fn attach<R>(f: impl for<'py> FnOnce(Python<'py>) -> R) -> R {
+        Python::initialize();
+        Python::attach(f)
+    }
+
+    #[test]
+    fn npu_moves_dram_into_a_bank() {
+        let mut npu = Npu::new(1 << 20);
+        npu.inst(32, 1, (1 << 5) | (1 << 10));
+        npu.write_dram(DRAM_BASE, &[7; 16]);
+        npu.inst(33, 1 | (1 << 30), DRAM_BASE | (1 << 39));
+        assert_eq!(npu.read_bank(1, 0, 16), Some(vec![7; 16]));
+        assert_eq!(npu.read_bank(2, 0, 16), None, "bank 2 is not mapped");
+        assert_eq!(npu.read_bank(1, usize::MAX, 2), None);
+        assert_eq!(npu.instructions(), 2);
+
+        attach(|py| {
+            let stats = npu.stats(py).unwrap();
+            let read: u64 = stats.get_item("dram_read_bytes").unwrap().extract().unwrap();
+            assert_eq!(read, 16);
+        });
+    }
+
+    #[test]
+    fn model_errors_raise_value_error() {
+        attach(|py| {
+            let mut npu = Npu::new(1 << 20);
+            let err = npu.rocc(0x13, 0, 0).unwrap_err();
+            assert!(err.is_instance_of::<PyValueError>(py));
+            assert_eq!(err.value(py).to_string(), "not a RoCC custom instruction: 0x00000013");
+
+            let err = npu.write_bank(3, 0, &[1]).unwrap_err();
+            assert_eq!(err.value(py).to_string(), "bank 3 is not mapped");
+
+            let err = NpuSim::new(1 << 20, 1, 4, "fifo", 0, 0, false, 0, false).err().unwrap();
+            assert_eq!(err.value(py).to_string(), "unknown arbitration \"fifo\"");
+        });
+    }
+
+    #[test]
+    fn sim_steps_until_idle() {
+        let mut sim = NpuSim::new(1 << 20, 2, 4, "scoreboard", 0, 0, false, 0, false).unwrap();
+        sim.write_dram(DRAM_BASE, &[5; 16]);
+        sim.push_inst(32, 1, (1 << 5) | (1 << 10)).unwrap();
+        sim.push_inst(33, 1 | (1 << 30), DRAM_BASE | (1 << 39)).unwrap();
+        assert_eq!(sim.tick(1), 1, "mset retires; the mvin waited for it");
+        assert!(!sim.is_idle());
+        sim.run_until_idle();
+        assert!(sim.is_idle());
+        assert_eq!(sim.read_bank(1, 0, 16), Some(vec![5; 16]));
+        assert_eq!(sim.read_bank(1, 1 << 30, 1), None);
+
+        attach(|py| {
+            let stats = sim.stats(py).unwrap();
+            let retired: u64 = stats.get_item("retired").unwrap().unwrap().extract().unwrap();
+            assert_eq!(retired, 2);
+        });
+    }
+}
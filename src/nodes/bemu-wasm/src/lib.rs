@@ -0,0 +1,131 @@
+//===- lib.rs - BEMU accelerator model for the browser ---------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// wasm-bindgen wrapper around bebop_bemu::Npu. It depends on bebop-bemu
+// without the `host` feature, so there is no Spike, no socket and no thread
+// in the build. Register values are u64, i.e. BigInt on the JS side.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use bebop_bemu::Npu as Model;
+use wasm_bindgen::prelude::*;
+
+/// DRAM size when JS does not pass one. The 1 GiB host default does not fit
+/// comfortably in a browser tab.
+pub const WASM_MEM_SIZE: usize = 16 << 20;
+
+#[wasm_bindgen]
+pub struct Npu {
+    model: Model,
+}
+
+#[wasm_bindgen]
+impl Npu {
+    #[wasm_bindgen(constructor)]
+    pub fn new(mem_size: Option<usize>) -> Npu {
+        Npu {
+            model: Model::new(mem_size.unwrap_or(WASM_MEM_SIZE)),
+        }
+    }
+
+    /// Execute one instruction by funct7 and return rd.
+    pub fn inst(&mut self, funct: u32, xs1: u64, xs2: u64) -> u64 {
+        self.model.exec(funct, xs1, xs2, 0)
+    }
+
+    /// Execute a binary-encoded RoCC custom-0..3 instruction word.
+    pub fn rocc(&mut self, insn: u32, xs1: u64, xs2: u64) -> Result<u64, JsError> {
+        self.model
+            .exec_rocc(insn, xs1, xs2, 0)
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    pub fn reset(&mut self) {
+        self.model.reset();
+    }
+
+    #[wasm_bindgen(js_name = writeDram)]
+    pub fn write_dram(&mut self, addr: u64, bytes: &[u8]) {
+        self.model.write_dram(addr, bytes);
+    }
+
+    #[wasm_bindgen(js_name = readDram)]
+    pub fn read_dram(&self, addr: u64, len: usize) -> Vec<u8> {
+        self.model.read_dram(addr, len)
+    }
+
+    /// Bytes `offset..offset + len` of `vbank`, or `undefined` if the bank is
+    /// not mapped or the range runs past its end.
+    #[wasm_bindgen(js_name = readBank)]
+    pub fn read_bank(&self, vbank: u32, offset: usize, len: usize) -> Option<Vec<u8>> {
+        self.model.bank_range(vbank, offset, len).ok().map(<[u8]>::to_vec)
+    }
+
+    #[wasm_bindgen(js_name = writeBank)]
+    pub fn write_bank(&mut self, vbank: u32, offset: usize, bytes: &[u8]) -> Result<(), JsError> {
+        self.model
+            .write_bank_range(vbank, offset, bytes)
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.model.total_latency()
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.model.instruction_count()
+    }
+}
+
+// `JsError` needs a JS host, so only the paths that do not throw run under
+// a native `cargo test`.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bebop_bemu::DRAM_BASE;
+
+    #[test]
+    fn moves_dram_into_a_bank_and_back() {
+        let mut npu = Npu::new(Some(1 << 20));
+        npu.inst(32, 1, (1 << 5) | (1 << 10));
+        npu.inst(32, 2, (1 << 5) | (1 << 10));
+        npu.write_dram(DRAM_BASE, &[3; 16]);
+        npu.inst(33, 1 | (1 << 30), DRAM_BASE | (1 << 39));
+        assert_eq!(npu.read_bank(1, 0, 16), Some(vec![3; 16]));
+
+        npu.write_bank(2, 0, &[9; 16]).unwrap();
+        let mvout = (16 << 25) | (11 << 20) | (10 << 15) | (0b011 << 12) | 0x0b;
+        npu.rocc(mvout, 2 | (1 << 30), (DRAM_BASE + 0x40) | (1 << 39)).unwrap();
+        assert_eq!(npu.read_dram(DRAM_BASE + 0x40, 16), [9; 16]);
+        assert_eq!(npu.instructions(), 4);
+        assert!(npu.cycles() > 0);
+    }
+
+    #[test]
+    fn bank_reads_outside_a_bank_are_undefined() {
+        let mut npu = Npu::new(None);
+        assert_eq!(npu.read_bank(1, 0, 1), None, "not mapped yet");
+        npu.inst(32, 1, (1 << 5) | (1 << 10));
+        let size = npu.model.bank(1).unwrap().len();
+        assert_eq!(npu.read_bank(1, size - 1, 1), Some(vec![0]));
+        assert_eq!(npu.read_bank(1, size, 1), None);
+        assert_eq!(npu.read_bank(1, usize::MAX, 2), None);
+
+        npu.reset();
+        assert_eq!(npu.read_bank(1, 0, 1), None);
+        assert_eq!(npu.instructions(), 0);
+    }
+}
@@ -0,0 +1,40 @@
+// Driver for the BEMU demo page. Build the package first:
+//   wasm-pack build --target web --out-dir www/pkg
+import init, { Npu } from "./pkg/bebop_bemu_wasm.js";
+
+await init();
+const npu = new Npu();
+const $ = (id) => document.getElementById(id);
+const hex = (v) => "0x" + v.toString(16);
+
+function issue(funct, xs1, xs2) {
+  const rd = npu.inst(funct, xs1, xs2);
+  const row = $("log").insertRow();
+  for (const cell of [npu.instructions(), funct, hex(xs1), hex(xs2), hex(rd), npu.cycles()]) {
+    row.insertCell().textContent = cell;
+  }
+  refresh();
+}
+
+function refresh() {
+  $("stats").textContent = `instructions=${npu.instructions()} cycles=${npu.cycles()}`;
+}
+
+// Allocate bank 1, move one 16-byte row in from DRAM and back out again.
+function demo() {
+  const src = 0x80001000n, dst = 0x80002000n;
+  npu.writeDram(src, Uint8Array.from({ length: 16 }, (_, i) => i + 1));
+  issue(32, 1n, (1n << 5n) | (1n << 10n));
+  issue(33, 1n | (1n << 30n), src | (1n << 39n));
+  issue(16, 1n | (1n << 30n), dst | (1n << 39n));
+  $("stats").textContent += `  bank1[0..4]=${npu.readBank(1, 0, 4)}  dram[dst..+4]=${npu.readDram(dst, 4)}`;
+}
+
+$("issue").onclick = () => issue(Number($("funct").value), BigInt($("xs1").value), BigInt($("xs2").value));
+$("demo").onclick = demo;
+$("reset").onclick = () => {
+  npu.reset();
+  $("log").replaceChildren();
+  refresh();
+};
+refresh();
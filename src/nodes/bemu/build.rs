@@ -24,6 +24,10 @@
 // 3. How to register instructions?
 //  Manually register in INSTRUCTIONS array, build.rs generates dispatch code.
 //
+// 4. Without the `host` feature?
+//  Only the chip instruction module is generated; Spike is neither needed nor
+//  built, so the accelerator model compiles on machines without a toolchain.
+//
 //===-----------------------------------------------------------------===//-----===//
 
 use std::env;
@@ -33,9 +37,6 @@ use std::process::{Command, Stdio};
 
 fn main() {
     let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR"));
-    let native_dir = native_dir(&manifest_dir);
-    let spike_dir = native_dir.join("spike");
-
     let out_dir = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR"));
     let chip_inst = chip_inst(&manifest_dir);
     fs::write(
@@ -44,6 +45,17 @@ fn main() {
     )
     .expect("write active chip module");
 
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=BEBOP_BEMU_CHIP_INST");
+    println!("cargo:rerun-if-changed={}", chip_inst.display());
+
+    if env::var_os("CARGO_FEATURE_HOST").is_none() {
+        return;
+    }
+
+    let native_dir = native_dir(&manifest_dir);
+    let spike_dir = native_dir.join("spike");
+
     let spike_install_dir = out_dir.join("spike_install");
     let spike_build_dir = out_dir.join("spike_build");
 
@@ -52,10 +64,7 @@ fn main() {
     }
 
     // Incremental compilation check
-    println!("cargo:rerun-if-changed=build.rs");
-    println!("cargo:rerun-if-env-changed=BEBOP_BEMU_CHIP_INST");
     println!("cargo:rerun-if-env-changed=BEBOP_BEMU_NATIVE_DIR");
-    println!("cargo:rerun-if-changed={}", chip_inst.display());
     println!("cargo:rerun-if-changed={}", native_dir.join("rocc.cc").display());
     println!("cargo:rerun-if-changed={}", native_dir.join("spike.cc").display());
     println!("cargo:rerun-if-changed={}", native_dir.join("btif.cc").display());
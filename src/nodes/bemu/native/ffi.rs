@@ -1,4 +1,3 @@
-use bebop_bank_hash::bank_hash;
 use bebop_dtb::DtbBuilder;
 use bebop_elf::{load_elf, LoadInfo, TlsInfo};
 use bebop_syscall::{add_guest_mapping, handle_syscall_with_state, set_guest_mappings, SyscallState};
@@ -6,9 +5,18 @@ use bebop_uart::Uart;
 use std::os::raw::{c_char, c_void};
 use std::path::Path;
 
-use crate::bank::{BankConfig, BankMap, BANK_NUM, BANK_SIZE};
-use crate::inst;
-use crate::trace::{with_trace_ptr, TraceConfig, TraceState};
+use crate::bank::{ArrayGeometry, BankGeometry, BankPorts};
+use crate::cache::CacheConfig;
+use crate::coverage::Coverage;
+use crate::dma::{DmaStats, MisalignedDma};
+use crate::dram::DramTiming;
+use crate::energy::EnergyTable;
+use crate::manifest::Manifest;
+use crate::npu::{Npu, DEFAULT_MEM_SIZE};
+use crate::perf::PerfReport;
+use crate::snapshot::Snapshot;
+use crate::trace::TraceConfig;
+use crate::warnings::Warnings;
 
 const DRAM_BASE: u64 = 0x80000000;
 // UART base address (matches test workloads)
@@ -22,55 +30,21 @@ const SYS_BRK: u64 = 214;
 const SYS_MMAP: u64 = 222;
 
 struct EmuState {
-    memory: Vec<u8>,
-    banks: Vec<Vec<u8>>,
-    bank_cfgs: Vec<BankConfig>,
-    bank_map: BankMap,
-    mmio_banks: [[u8; 1024]; 16],
-    mmio_region_table: [crate::inst::instruction::MmioRegion; 32],
-    total_lat: u64,
-    npu_instruction_id: u64,
+    npu: Npu,
     uart: Uart,
     syscall: SyscallState,
     pk_vm: Option<PkVm>,
-    trace: TraceState,
 }
 
 impl EmuState {
     fn new(log_dir: &Path, trace_config: TraceConfig) -> Result<Self, String> {
-        // 1GB Here is important, for baremetal mode, when we set this to 4GB,
-        // it will running for a long time.
-        const MEM_SIZE: usize = 1 << 30;
         Ok(Self {
-            // memory is maintained by bemu not spike
-            memory: vec![0; MEM_SIZE],
-            banks: vec![vec![0; BANK_SIZE]; BANK_NUM],
-            bank_cfgs: vec![BankConfig::default(); BANK_NUM],
-            bank_map: BankMap::new(BANK_NUM),
-            mmio_banks: [[0u8; 1024]; 16],
-            mmio_region_table: [crate::inst::instruction::MmioRegion::default(); 32],
-            total_lat: 0,
-            npu_instruction_id: 0,
+            npu: Npu::with_trace(DEFAULT_MEM_SIZE, log_dir, trace_config)?,
             uart: Uart::new(),
             syscall: SyscallState::new(),
             pk_vm: None,
-            trace: TraceState::new(log_dir, trace_config).map_err(|e| e.to_string())?,
         })
     }
-
-    fn reset_accel(&mut self) {
-        for b in &mut self.banks {
-            b.fill(0);
-        }
-        self.bank_cfgs.fill(BankConfig::default());
-        self.bank_map = BankMap::new(BANK_NUM);
-        for bank in &mut self.mmio_banks {
-            bank.fill(0);
-        }
-        self.mmio_region_table = [crate::inst::instruction::MmioRegion::default(); 32];
-        self.total_lat = 0;
-        self.npu_instruction_id = 0;
-    }
 }
 
 #[derive(Clone, Copy)]
@@ -224,82 +198,12 @@ pub extern "C" fn buckyball_init(_state: *mut c_void) {}
 
 #[no_mangle]
 pub extern "C" fn buckyball_reset(state: *mut c_void) {
-    unsafe { state_mut(state) }.reset_accel();
+    unsafe { state_mut(state) }.npu.reset();
 }
 
 #[no_mangle]
 pub extern "C" fn buckyball_exec(state: *mut c_void, funct7: u8, xs1: u64, xs2: u64, pc: u64) -> u64 {
-    let state = unsafe { state_mut(state) };
-    let lat = inst::decode::cycles_after_issue(funct7 as u32, xs1, xs2);
-    state.total_lat += lat;
-    state.trace.set_bemu_clk(state.total_lat);
-    state.npu_instruction_id = state.npu_instruction_id.wrapping_add(1);
-    let instruction_id = state.npu_instruction_id;
-    let trace = &mut state.trace as *mut TraceState;
-    let btrace = state.trace.btrace_enabled();
-
-    unsafe {
-        with_trace_ptr(trace, || {
-            crate::trace::itrace(crate::trace::ITraceEvent {
-                funct: funct7 as u32,
-                pc,
-                rs1: xs1,
-                rs2: xs2,
-            });
-        })
-    };
-
-    let EmuState {
-        memory,
-        banks,
-        bank_cfgs,
-        bank_map,
-        mmio_banks,
-        mmio_region_table,
-        uart: _,
-        syscall: _,
-        pk_vm: _,
-        trace: _,
-        ..
-    } = state;
-
-    let before_hashes: Vec<u64> = if btrace {
-        banks.iter().map(|bank| bank_hash(bank)).collect()
-    } else {
-        Vec::new()
-    };
-
-    let result = unsafe {
-        with_trace_ptr(trace, || {
-            let mut ctx = inst::instruction::ExecContext {
-                memory,
-                banks,
-                cfgs: bank_cfgs,
-                bank_map,
-                mmio_banks,
-                mmio_region_table,
-            };
-
-            inst::decode::execute_known(funct7 as u32, xs1, xs2, &mut ctx)
-                .unwrap_or_else(|| panic!("unknown funct7: {}", funct7))
-        })
-    };
-
-    if btrace {
-        let op_type = format!("funct7_{}", funct7);
-        unsafe {
-            with_trace_ptr(trace, || {
-                for (bank_id, bank) in banks.iter().enumerate() {
-                    let hash = bank_hash(bank);
-                    if before_hashes[bank_id] != hash {
-                        crate::trace::bemu_bank_hash(instruction_id, bank_id as u32, funct7 as u32, &op_type, hash, pc);
-                    }
-                }
-            })
-        };
-    }
-
-    result
+    unsafe { state_mut(state) }.npu.exec(funct7 as u32, xs1, xs2, pc)
 }
 
 /// Handle system call from guest program
@@ -326,10 +230,10 @@ pub extern "C" fn handle_syscall_ffi(
         a3,
         a4,
         a5,
-        &mut state.memory,
+        &mut state.npu.memory,
     );
     if let Some(mut pk_vm) = state.pk_vm.take() {
-        let map_result = map_syscall_result(&mut state.memory, &mut pk_vm, old_brk, syscall_num, a0, a1, result);
+        let map_result = map_syscall_result(&mut state.npu, &mut pk_vm, old_brk, syscall_num, a0, a1, result);
         state.pk_vm = Some(pk_vm);
         if let Err(e) = map_result {
             eprintln!("[ERROR] pk syscall mapping failed: {e}");
@@ -341,7 +245,7 @@ pub extern "C" fn handle_syscall_ffi(
 }
 
 fn map_syscall_result(
-    memory: &mut [u8],
+    npu: &mut Npu,
     pk_vm: &mut PkVm,
     old_brk: u64,
     syscall_num: u64,
@@ -358,16 +262,17 @@ fn map_syscall_result(
             let start = align_up(old_brk, PAGE_SIZE);
             let end = align_up(result, PAGE_SIZE);
             if end > start {
-                pk_vm.alloc_user_pages(memory, start, end - start, 0x2 | 0x4)?;
+                pk_vm.alloc_user_pages(&mut npu.memory, start, end - start, 0x2 | 0x4)?;
             }
             if let Some(first) = pk_vm.maps.first() {
-                crate::bank::set_fast_addr_map(first.virt, first.phys, result.saturating_sub(first.virt));
+                npu.addr_map
+                    .set_fast(first.virt, first.phys, result.saturating_sub(first.virt));
             }
         }
         SYS_MMAP => {
             let len = align_up(a1, PAGE_SIZE);
             if result != 0 && len != 0 {
-                pk_vm.alloc_user_pages(memory, result, len, 0x2 | 0x4)?;
+                pk_vm.alloc_user_pages(&mut npu.memory, result, len, 0x2 | 0x4)?;
             }
         }
         _ => {
@@ -476,7 +381,87 @@ impl NativeSpike {
     }
 
     pub fn total_latency(&self) -> u64 {
-        self.state.total_lat
+        self.state.npu.total_latency()
+    }
+
+    pub fn warnings(&self) -> &Warnings {
+        self.state.npu.warnings()
+    }
+
+    pub fn randomize(&mut self, seed: u64) {
+        self.state.npu.randomize(seed);
+    }
+
+    pub fn manifest(&self) -> Manifest {
+        self.state.npu.manifest()
+    }
+
+    pub fn npu(&self) -> &Npu {
+        &self.state.npu
+    }
+
+    pub fn npu_mut(&mut self) -> &mut Npu {
+        &mut self.state.npu
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        self.state.npu.snapshot()
+    }
+
+    pub fn save_checkpoint(&self, path: &Path) -> Result<(), String> {
+        self.state.npu.save_checkpoint(path)
+    }
+
+    pub fn load_checkpoint(&mut self, path: &Path) -> Result<(), String> {
+        self.state.npu.load_checkpoint(path)
+    }
+
+    pub fn set_misaligned_dma(&mut self, policy: MisalignedDma) {
+        self.state.npu.set_misaligned_dma(policy);
+    }
+
+    pub fn dma_stats(&self) -> DmaStats {
+        self.state.npu.dma_stats()
+    }
+
+    pub fn perf_report(&self) -> PerfReport {
+        self.state.npu.perf_report()
+    }
+
+    pub fn set_dram_timing(&mut self, timing: Option<DramTiming>) -> Result<(), String> {
+        self.state.npu.set_dram_timing(timing)
+    }
+
+    pub fn set_bank_geometry(&mut self, geometry: BankGeometry) -> Result<(), String> {
+        self.state.npu.set_bank_geometry(geometry)
+    }
+
+    pub fn set_array_geometry(&mut self, array: ArrayGeometry) -> Result<(), String> {
+        self.state.npu.set_array_geometry(array)
+    }
+
+    pub fn set_bank_ports(&mut self, ports: BankPorts) -> Result<(), String> {
+        self.state.npu.set_bank_ports(ports)
+    }
+
+    pub fn set_energy_table(&mut self, table: Option<EnergyTable>) -> Result<(), String> {
+        self.state.npu.set_energy_table(table)
+    }
+
+    pub fn set_cache(&mut self, config: Option<CacheConfig>) -> Result<(), String> {
+        self.state.npu.set_cache(config)
+    }
+
+    pub fn enable_coverage(&mut self) {
+        self.state.npu.enable_coverage();
+    }
+
+    pub fn record_trace(&mut self, path: &Path) -> Result<(), String> {
+        self.state.npu.record_trace(path)
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.state.npu.coverage()
     }
 }
 
@@ -504,8 +489,8 @@ pub fn create_spike(
     let isa_c = CString::new(isa).map_err(|e| e.to_string())?;
     let log_c = CString::new(log_path).map_err(|e| e.to_string())?;
     let mut state = Box::new(EmuState::new(log_dir, trace_config)?);
-    let mem_ptr = state.memory.as_mut_ptr();
-    let mem_size = state.memory.len();
+    let mem_ptr = state.npu.memory.as_mut_ptr();
+    let mem_size = state.npu.memory.len();
     let uart_ptr = &mut state.uart as *mut Uart as *mut u8;
     let state_ptr = &mut *state as *mut EmuState as *mut c_void;
 
@@ -541,9 +526,9 @@ struct HartInit {
 }
 
 fn load_elf_memory(state: &mut EmuState, elf_path: &str) -> Result<LoadInfo, String> {
-    let load = load_elf(elf_path, &mut state.memory, DRAM_BASE)?;
+    let load = load_elf(elf_path, &mut state.npu.memory, DRAM_BASE)?;
     let entry = load.entry;
-    let mem_end = DRAM_BASE + state.memory.len() as u64;
+    let mem_end = DRAM_BASE + state.npu.memory.len() as u64;
 
     if entry < DRAM_BASE || entry >= mem_end {
         return Err(format!(
@@ -567,8 +552,8 @@ fn load_elf_memory(state: &mut EmuState, elf_path: &str) -> Result<LoadInfo, Str
 }
 
 fn hart_init(ctx: *mut c_void, state: &mut EmuState, load: LoadInfo, mem_mb: usize, pk: bool) -> Result<(), String> {
-    let mem_end = DRAM_BASE + state.memory.len() as u64;
-    crate::bank::clear_addr_cache();
+    let mem_end = DRAM_BASE + state.npu.memory.len() as u64;
+    state.npu.addr_map.clear();
     state.syscall = SyscallState::new();
     state.pk_vm = None;
     set_guest_mappings(&[]);
@@ -588,7 +573,7 @@ fn hart_init(ctx: *mut c_void, state: &mut EmuState, load: LoadInfo, mem_mb: usi
         state
             .syscall
             .set_mem_bounds(load.analysis.min_vaddr, USER_TOP - USER_STACK_SIZE);
-        crate::bank::set_fast_addr_map(
+        state.npu.addr_map.set_fast(
             load.analysis.min_vaddr,
             DRAM_BASE,
             brk_start.saturating_sub(load.analysis.min_vaddr),
@@ -598,18 +583,18 @@ fn hart_init(ctx: *mut c_void, state: &mut EmuState, load: LoadInfo, mem_mb: usi
     let tp = if pk {
         None
     } else {
-        setup_tls(&mut state.memory, load.tls)?
+        setup_tls(&mut state.npu.memory, load.tls)?
     };
-    let dtb_addr = install_dtb(&mut state.memory, mem_mb)?;
+    let dtb_addr = install_dtb(&mut state.npu.memory, mem_mb)?;
 
     let trap_handler_addr = if pk {
-        install_pk_trap_handler(&mut state.memory)?
+        install_pk_trap_handler(&mut state.npu.memory)?
     } else {
         0
     };
     let (entry, satp, initial_regs) = if pk {
-        let pk_vm = setup_pk_vm(&mut state.memory, &load)?;
-        let regs = setup_pk_stack(&mut state.memory, &pk_vm, &load)?;
+        let pk_vm = setup_pk_vm(&mut state.npu.memory, &load)?;
+        let regs = setup_pk_stack(&mut state.npu.memory, &pk_vm, &load)?;
         let satp = pk_vm.satp();
         state.pk_vm = Some(pk_vm);
         (load.analysis.original_entry, satp, regs)
@@ -883,3 +868,67 @@ fn align_down(value: u64, align: u64) -> u64 {
 fn align_up(value: u64, align: u64) -> u64 {
     (value + align - 1) & !(align - 1)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A static ELF with one PT_LOAD segment holding `bytes` at `vaddr`.
+    fn write_elf(path: &Path, vaddr: u64, bytes: &[u8]) {
+        let (ehsize, phsize) = (64u16, 56u16);
+        let offset = u64::from(ehsize + phsize);
+        let mut elf = vec![0x7f, b'E', b'L', b'F', 2, 1, 1];
+        elf.resize(16, 0);
+        elf.extend(2u16.to_le_bytes()); // ET_EXEC
+        elf.extend(243u16.to_le_bytes()); // EM_RISCV
+        elf.extend(1u32.to_le_bytes());
+        elf.extend(vaddr.to_le_bytes());
+        elf.extend(u64::from(ehsize).to_le_bytes());
+        elf.extend(0u64.to_le_bytes());
+        elf.extend(0u32.to_le_bytes());
+        for half in [ehsize, phsize, 1, 64, 0, 0] {
+            elf.extend(half.to_le_bytes());
+        }
+        elf.extend(1u32.to_le_bytes()); // PT_LOAD
+        elf.extend(5u32.to_le_bytes());
+        for word in [offset, vaddr, vaddr, bytes.len() as u64, bytes.len() as u64, 8] {
+            elf.extend(word.to_le_bytes());
+        }
+        elf.extend(bytes);
+        std::fs::write(path, elf).unwrap();
+    }
+
+    #[test]
+    fn second_workload_loads_onto_a_warm_accelerator() {
+        let mut state = EmuState {
+            npu: Npu::new(1 << 20),
+            uart: Uart::new(),
+            syscall: SyscallState::new(),
+            pk_vm: None,
+        };
+        state.npu.exec(32, 1, (1 << 5) | (1 << 10), 0);
+        state.npu.bank_mut(1).unwrap()[0x40] = 0x7f;
+        let (cycle, instructions) = (state.npu.total_lat, state.npu.npu_instruction_id);
+
+        let dir = std::env::temp_dir();
+        let first = dir.join(format!("bemu-warm-a-{}.elf", std::process::id()));
+        let second = dir.join(format!("bemu-warm-b-{}.elf", std::process::id()));
+        write_elf(&first, DRAM_BASE, &[0x11; 16]);
+        write_elf(&second, DRAM_BASE + 0x1000, &[0x22; 16]);
+        let a = load_elf_memory(&mut state, first.to_str().unwrap());
+        let b = load_elf_memory(&mut state, second.to_str().unwrap());
+        let _ = (std::fs::remove_file(&first), std::fs::remove_file(&second));
+        assert_eq!(a.unwrap().entry, DRAM_BASE);
+        assert_eq!(b.unwrap().entry, DRAM_BASE + 0x1000);
+
+        // Loading a workload only writes its image: banks, counters and
+        // whatever the previous workload left in DRAM all carry over.
+        assert_eq!(state.npu.bank_mut(1).unwrap()[0x40], 0x7f);
+        assert_eq!(
+            (state.npu.total_lat, state.npu.npu_instruction_id),
+            (cycle, instructions)
+        );
+        assert_eq!(state.npu.memory[..16], [0x11; 16]);
+        assert_eq!(state.npu.memory[0x1000..0x1010], [0x22; 16]);
+    }
+}
@@ -1,5 +1,16 @@
+use crate::bank::{ArrayGeometry, BankGeometry, BankPorts};
+use crate::cache::CacheConfig;
+use crate::coverage::Coverage;
+use crate::dma::{DmaStats, MisalignedDma};
+use crate::dram::DramTiming;
+use crate::energy::EnergyTable;
 use crate::ffi::{create_spike, NativeSpike};
+use crate::manifest::Manifest;
+use crate::npu::Npu;
+use crate::perf::PerfReport;
+use crate::snapshot::Snapshot;
 use crate::trace::TraceConfig;
+use crate::warnings::Warnings;
 use std::path::Path;
 
 pub struct SpikeInstance {
@@ -43,4 +54,84 @@ impl SpikeInstance {
     pub fn total_latency(&self) -> u64 {
         self.native.total_latency()
     }
+
+    pub fn warnings(&self) -> &Warnings {
+        self.native.warnings()
+    }
+
+    pub fn randomize(&mut self, seed: u64) {
+        self.native.randomize(seed);
+    }
+
+    pub fn manifest(&self) -> Manifest {
+        self.native.manifest()
+    }
+
+    pub fn npu(&self) -> &Npu {
+        self.native.npu()
+    }
+
+    pub fn npu_mut(&mut self) -> &mut Npu {
+        self.native.npu_mut()
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        self.native.snapshot()
+    }
+
+    pub fn save_checkpoint(&self, path: &Path) -> Result<(), String> {
+        self.native.save_checkpoint(path)
+    }
+
+    pub fn load_checkpoint(&mut self, path: &Path) -> Result<(), String> {
+        self.native.load_checkpoint(path)
+    }
+
+    pub fn set_misaligned_dma(&mut self, policy: MisalignedDma) {
+        self.native.set_misaligned_dma(policy);
+    }
+
+    pub fn dma_stats(&self) -> DmaStats {
+        self.native.dma_stats()
+    }
+
+    pub fn perf_report(&self) -> PerfReport {
+        self.native.perf_report()
+    }
+
+    pub fn set_dram_timing(&mut self, timing: Option<DramTiming>) -> Result<(), String> {
+        self.native.set_dram_timing(timing)
+    }
+
+    pub fn set_bank_geometry(&mut self, geometry: BankGeometry) -> Result<(), String> {
+        self.native.set_bank_geometry(geometry)
+    }
+
+    pub fn set_array_geometry(&mut self, array: ArrayGeometry) -> Result<(), String> {
+        self.native.set_array_geometry(array)
+    }
+
+    pub fn set_bank_ports(&mut self, ports: BankPorts) -> Result<(), String> {
+        self.native.set_bank_ports(ports)
+    }
+
+    pub fn set_energy_table(&mut self, table: Option<EnergyTable>) -> Result<(), String> {
+        self.native.set_energy_table(table)
+    }
+
+    pub fn set_cache(&mut self, config: Option<CacheConfig>) -> Result<(), String> {
+        self.native.set_cache(config)
+    }
+
+    pub fn enable_coverage(&mut self) {
+        self.native.enable_coverage();
+    }
+
+    pub fn record_trace(&mut self, path: &Path) -> Result<(), String> {
+        self.native.record_trace(path)
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.native.coverage()
+    }
 }
@@ -1,29 +1,102 @@
 pub const BANK_NUM: usize = 32;
 pub const BANK_WIDTH: usize = 128;
 pub const BANK_LINES: usize = 1024;
-pub const BANK_SIZE: usize = BANK_LINES * (BANK_WIDTH / 8);
 pub const MATRIX_SIZE: usize = 16;
 const PAGE_SIZE: u64 = 4096;
-use std::sync::atomic::{AtomicU64, Ordering};
+use crate::numfmt::NumFormat;
+use std::cell::Cell;
+use std::ops::Range;
 
-static FAST_VIRT_BASE: AtomicU64 = AtomicU64::new(0);
-static FAST_PHYS_BASE: AtomicU64 = AtomicU64::new(0);
-static FAST_LEN: AtomicU64 = AtomicU64::new(0);
+/// Scratchpad banking, for design-space exploration without recompiling.
+/// The default is the BANK_* constants with single-cycle access.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct BankGeometry {
+    pub num_banks: usize,
+    /// Lines per bank.
+    pub bank_depth: usize,
+    /// Bits per line. Every instruction moves 16-byte lines, so only 128 is
+    /// accepted; the field exists so configs state it explicitly.
+    pub word_bits: usize,
+    /// Extra cycles for an instruction that reads a bank.
+    pub read_latency: u64,
+    /// Extra cycles for an instruction that writes a bank.
+    pub write_latency: u64,
+}
+
+impl Default for BankGeometry {
+    fn default() -> Self {
+        Self {
+            num_banks: BANK_NUM,
+            bank_depth: BANK_LINES,
+            word_bits: BANK_WIDTH,
+            read_latency: 0,
+            write_latency: 0,
+        }
+    }
+}
+
+impl BankGeometry {
+    pub fn validate(&self) -> Result<(), String> {
+        // Bank ids are 10-bit instruction fields.
+        if self.num_banks == 0 || self.num_banks > 1024 {
+            return Err(format!(
+                "bank geometry: num_banks must be 1..=1024, got {}",
+                self.num_banks
+            ));
+        }
+        if self.bank_depth == 0 {
+            return Err("bank geometry: bank_depth must be > 0".to_string());
+        }
+        if self.word_bits != BANK_WIDTH {
+            return Err(format!(
+                "bank geometry: word_bits must be {BANK_WIDTH}, got {}",
+                self.word_bits
+            ));
+        }
+        Ok(())
+    }
 
-thread_local! {
-    static ADDR_CACHE: std::cell::Cell<Option<(u64, usize)>> = const { std::cell::Cell::new(None) };
+    /// Bytes per bank.
+    pub fn bank_bytes(&self) -> usize {
+        self.bank_depth * (self.word_bits / 8)
+    }
 }
 
-pub fn clear_addr_cache() {
-    FAST_LEN.store(0, Ordering::Relaxed);
-    ADDR_CACHE.with(|cache| cache.set(None));
+/// Shape of the systolic array matmul and conv run on, in processing
+/// elements. It only affects timing: operands wider than the array are split
+/// into tiles, each one more pass through the array.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct ArrayGeometry {
+    pub rows: usize,
+    pub cols: usize,
 }
 
-pub fn set_fast_addr_map(virt: u64, phys: u64, len: u64) {
-    FAST_VIRT_BASE.store(virt, Ordering::Relaxed);
-    FAST_PHYS_BASE.store(phys, Ordering::Relaxed);
-    FAST_LEN.store(len, Ordering::Release);
-    ADDR_CACHE.with(|cache| cache.set(None));
+impl Default for ArrayGeometry {
+    fn default() -> Self {
+        Self {
+            rows: MATRIX_SIZE,
+            cols: MATRIX_SIZE,
+        }
+    }
+}
+
+impl ArrayGeometry {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.rows == 0 || self.cols == 0 {
+            return Err(format!(
+                "array geometry: rows and cols must be > 0, got {}x{}",
+                self.rows, self.cols
+            ));
+        }
+        Ok(())
+    }
+
+    /// MACs per cycle when every PE is busy.
+    pub fn peak_macs(&self) -> u64 {
+        (self.rows * self.cols) as u64
+    }
 }
 
 /// Mirrors RTL `PrivateMemBackend.mappingTable`:
@@ -38,12 +111,15 @@ pub struct MapEntry {
 #[derive(Clone, Debug)]
 pub struct BankMap {
     pub slots: Vec<MapEntry>,
+    /// Physical banks mset may allocate from; all of them when `None`.
+    pub pool: Option<Range<usize>>,
 }
 
 impl BankMap {
     pub fn new(num_physical: usize) -> Self {
         Self {
             slots: vec![MapEntry::default(); num_physical],
+            pool: None,
         }
     }
 
@@ -56,7 +132,8 @@ impl BankMap {
     }
 
     pub fn first_free_pbank(&self) -> Option<usize> {
-        self.slots.iter().position(|e| !e.valid)
+        let pool = self.pool.clone().unwrap_or(0..self.slots.len());
+        pool.into_iter().find(|&p| self.slots.get(p).is_some_and(|e| !e.valid))
     }
 
     pub fn bind_group(&mut self, p: usize, v: u32, group: u32) {
@@ -76,71 +153,255 @@ impl BankMap {
     }
 }
 
+/// Width of the signed integer elements a bank holds.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ElemWidth {
+    #[default]
+    I8,
+    I16,
+    I32,
+}
+
+impl ElemWidth {
+    /// mset `xs2[12:11]`: 0 = 8-bit, 1 = 16-bit, 2 = 32-bit.
+    pub fn from_code(code: u64) -> Option<Self> {
+        match code {
+            0 => Some(ElemWidth::I8),
+            1 => Some(ElemWidth::I16),
+            2 => Some(ElemWidth::I32),
+            _ => None,
+        }
+    }
+
+    pub fn bytes(self) -> usize {
+        match self {
+            ElemWidth::I8 => 1,
+            ElemWidth::I16 => 2,
+            ElemWidth::I32 => 4,
+        }
+    }
+
+    pub fn bits(self) -> u32 {
+        self.bytes() as u32 * 8
+    }
+
+    /// Sign-extended element at `i`.
+    pub fn load(self, bank: &[u8], i: usize) -> i32 {
+        let b = &bank[i * self.bytes()..];
+        match self {
+            ElemWidth::I8 => b[0] as i8 as i32,
+            ElemWidth::I16 => i16::from_le_bytes([b[0], b[1]]) as i32,
+            ElemWidth::I32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        }
+    }
+
+    /// Store `v` at element `i`, saturating to the width. Returns whether it
+    /// saturated.
+    pub fn store(self, bank: &mut [u8], i: usize, v: i64) -> bool {
+        let n = self.bytes();
+        let (lo, hi) = match self {
+            ElemWidth::I8 => (i8::MIN as i64, i8::MAX as i64),
+            ElemWidth::I16 => (i16::MIN as i64, i16::MAX as i64),
+            ElemWidth::I32 => (i32::MIN as i64, i32::MAX as i64),
+        };
+        let c = v.clamp(lo, hi);
+        bank[i * n..(i + 1) * n].copy_from_slice(&c.to_le_bytes()[..n]);
+        c != v
+    }
+}
+
+/// Add the i32 elements of `data` into `bank` from byte `offset`, saturating.
+/// Returns how many sums saturated.
+pub fn accumulate_i32(bank: &mut [u8], offset: usize, data: &[u8]) -> usize {
+    let mut saturated = 0;
+    for (k, word) in data.chunks_exact(4).enumerate() {
+        let i = offset / 4 + k;
+        let sum = ElemWidth::I32.load(bank, i) as i64 + i32::from_le_bytes([word[0], word[1], word[2], word[3]]) as i64;
+        if ElemWidth::I32.store(bank, i, sum) {
+            saturated += 1;
+        }
+    }
+    saturated
+}
+
+/// Add `data` into accumulator `bank` from byte `offset`: f32 sums for an
+/// fp32 accumulator, which do not saturate, and i32 sums otherwise. Returns
+/// how many sums saturated.
+pub fn accumulate_into(cfg: &BankConfig, bank: &mut [u8], offset: usize, data: &[u8]) -> usize {
+    if cfg.format != NumFormat::Fp32 {
+        return accumulate_i32(bank, offset, data);
+    }
+    for (k, word) in data.chunks_exact(4).enumerate() {
+        let i = offset + k * 4;
+        let sum = f32::from_le_bytes(bank[i..i + 4].try_into().unwrap())
+            + f32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+        bank[i..i + 4].copy_from_slice(&sum.to_le_bytes());
+    }
+    0
+}
+
 #[derive(Default, Clone, Copy, Debug)]
 pub struct BankConfig {
     pub allocated: bool,
     pub cols: u64,
+    pub width: ElemWidth,
+    /// Writes add into the bank instead of overwriting it.
+    pub accumulator: bool,
+    /// Number format of the elements; `width` is the one it occupies.
+    pub format: NumFormat,
 }
 
 /// DRAM is mapped at this base address from the guest's perspective.
 /// Must match `DRAM_BASE` in spike.cc.
 pub const DRAM_BASE: u64 = 0x80000000;
 
-#[inline]
-fn dram_offset(mem_len: usize, addr: u64) -> usize {
-    let fast_len = FAST_LEN.load(Ordering::Acquire);
-    if fast_len != 0 {
-        let virt = FAST_VIRT_BASE.load(Ordering::Relaxed);
-        if addr >= virt && addr < virt + fast_len {
-            let phys = FAST_PHYS_BASE.load(Ordering::Relaxed) + (addr - virt);
-            let off = (phys - DRAM_BASE) as usize;
-            if off < mem_len {
-                return off;
+/// Guest-virtual to DRAM-offset translation of one Npu. Spike-driven runs
+/// map the program image and heap linearly, which `set_fast` short-circuits;
+/// other addresses go through the guest mappings, caching the last page.
+/// Each Npu owns its map, so simulations in one process do not share it.
+#[derive(Clone, Debug, Default)]
+pub struct AddrMap {
+    /// `(virt, phys, len)` of the linear window.
+    fast: Option<(u64, u64, u64)>,
+    /// Last page looked up and its DRAM offset.
+    page: Cell<Option<(u64, usize)>>,
+}
+
+impl AddrMap {
+    /// Forget the linear window and the cached page.
+    #[cfg(feature = "host")]
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Map `len` bytes at guest `virt` linearly to physical `phys`.
+    #[cfg(feature = "host")]
+    pub fn set_fast(&mut self, virt: u64, phys: u64, len: u64) {
+        self.fast = (len != 0).then_some((virt, phys, len));
+        self.page.set(None);
+    }
+
+    /// Split `len` bytes at `addr` at page boundaries and at the edges of the
+    /// linear window, and translate each piece, as `(buffer offset, DRAM
+    /// offset, length)`. A page is mapped in one piece, but neighbouring guest
+    /// pages need not be neighbours in DRAM, and a window may start or end
+    /// mid-page. A piece whose ends still do not line up (a window running
+    /// past the end of DRAM) comes back as single bytes.
+    fn pieces(&self, mem_len: usize, addr: u64, len: usize) -> Vec<(usize, usize, usize)> {
+        let mut pieces = Vec::new();
+        let mut done = 0;
+        while done < len {
+            let at = addr + done as u64;
+            let mut n = ((PAGE_SIZE - at % PAGE_SIZE) as usize).min(len - done);
+            if let Some((virt, _, window)) = self.fast {
+                for edge in [virt, virt + window] {
+                    if at < edge {
+                        n = n.min((edge - at) as usize);
+                    }
+                }
             }
+            let (first, last) = (self.offset(mem_len, at), self.offset(mem_len, at + n as u64 - 1));
+            if last == first + n - 1 {
+                pieces.push((done, first, n));
+            } else {
+                pieces.extend((0..n).map(|i| (done + i, self.offset(mem_len, at + i as u64), 1)));
+            }
+            done += n;
         }
+        pieces
     }
 
-    let page = addr & !(PAGE_SIZE - 1);
-    let page_off = (addr - page) as usize;
-    if let Some(off) = ADDR_CACHE.with(|cache| {
-        cache.get().and_then(|(cached_page, cached_off)| {
+    #[inline]
+    fn offset(&self, mem_len: usize, addr: u64) -> usize {
+        if let Some((virt, phys, len)) = self.fast {
+            if addr >= virt && addr < virt + len {
+                let off = (phys + (addr - virt) - DRAM_BASE) as usize;
+                if off < mem_len {
+                    return off;
+                }
+            }
+        }
+
+        let page = addr & !(PAGE_SIZE - 1);
+        let page_off = (addr - page) as usize;
+        if let Some(off) = self.page.get().and_then(|(cached_page, cached_off)| {
             if cached_page == page {
                 cached_off.checked_add(page_off)
             } else {
                 None
             }
-        })
-    }) {
-        if off < mem_len {
+        }) {
+            if off < mem_len {
+                return off;
+            }
+        }
+
+        if let Some(off) = bebop_syscall::translate_guest_addr(addr, 1, mem_len) {
+            if let Some(page_base_off) = off.checked_sub(page_off) {
+                self.page.set(Some((page, page_base_off)));
+            }
             return off;
         }
-    }
 
-    if let Some(off) = bebop_syscall::translate_guest_addr(addr, 1, mem_len) {
-        if let Some(page_base_off) = off.checked_sub(page_off) {
-            ADDR_CACHE.with(|cache| cache.set(Some((page, page_base_off))));
+        let mem_end = DRAM_BASE + mem_len as u64;
+        if addr >= DRAM_BASE && addr < mem_end {
+            return (addr - DRAM_BASE) as usize;
         }
-        return off;
-    }
 
-    let mem_end = DRAM_BASE + mem_len as u64;
-    if addr >= DRAM_BASE && addr < mem_end {
-        return (addr - DRAM_BASE) as usize;
+        panic!(
+            "DRAM access out of range: addr=0x{:x} (valid range 0x{:x}-0x{:x})",
+            addr, DRAM_BASE, mem_end
+        );
     }
-
-    panic!(
-        "DRAM access out of range: addr=0x{:x} (valid range 0x{:x}-0x{:x})",
-        addr, DRAM_BASE, mem_end
-    );
 }
 
+/// Copy guest DRAM at `addr` into `buf`, one memcpy per page.
 #[inline]
-pub fn mem_read(mem: &[u8], addr: u64) -> u8 {
-    mem[dram_offset(mem.len(), addr)]
+pub fn mem_read_into(mem: &[u8], map: &AddrMap, addr: u64, buf: &mut [u8]) {
+    for (i, off, n) in map.pieces(mem.len(), addr, buf.len()) {
+        buf[i..i + n].copy_from_slice(&mem[off..off + n]);
+    }
 }
 
+/// Copy `data` into guest DRAM at `addr`, one memcpy per page.
 #[inline]
-pub fn mem_write(mem: &mut [u8], addr: u64, v: u8) {
-    let off = dram_offset(mem.len(), addr);
-    mem[off] = v;
+pub fn mem_write_from(mem: &mut [u8], map: &AddrMap, addr: u64, data: &[u8]) {
+    for (i, off, n) in map.pieces(mem.len(), addr, data.len()) {
+        mem[off..off + n].copy_from_slice(&data[i..i + n]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn row_crossing_a_fast_window_edge_mid_page() {
+        // Bytes 0x100..0x200 of the first page are a window onto 0x2000; the
+        // rest of the page is mapped one to one.
+        let map = AddrMap {
+            fast: Some((DRAM_BASE + 0x100, DRAM_BASE + 0x2000, 0x100)),
+            page: Cell::new(None),
+        };
+        let mem: Vec<u8> = (0..0x4000).map(|i| (i % 251) as u8).collect();
+        let bytewise = |addr: u64, len: usize| -> Vec<usize> {
+            (0..len as u64).map(|i| map.offset(mem.len(), addr + i)).collect()
+        };
+
+        let mut buf = vec![0; 0x300];
+        mem_read_into(&mem, &map, DRAM_BASE + 0x80, &mut buf);
+        let want: Vec<u8> = bytewise(DRAM_BASE + 0x80, buf.len()).iter().map(|&o| mem[o]).collect();
+        assert_eq!(buf, want);
+        assert_eq!(buf[0x80], mem[0x2000]);
+        assert_eq!(buf[0x180], mem[0x200]);
+
+        let mut written = mem.clone();
+        let data = vec![0xaa; 0x300];
+        mem_write_from(&mut written, &map, DRAM_BASE + 0x80, &data);
+        let mut want = mem.clone();
+        for o in bytewise(DRAM_BASE + 0x80, data.len()) {
+            want[o] = 0xaa;
+        }
+        assert_eq!(written, want);
+    }
 }
@@ -1,6 +1,8 @@
 #[allow(clippy::module_inception)]
 mod bank;
 mod mmio;
+mod ports;
 
 pub use bank::*;
 pub use mmio::*;
+pub use ports::*;
@@ -0,0 +1,119 @@
+//===- ports.rs - Bank port model -----------------------------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// By default a bank serves any number of reads and writes per cycle. A port
+// model limits that per physical bank:
+//
+//   ideal   unlimited (the default)
+//   single  one port, read or write
+//   1r1w    one read port and one write port
+//   2r1w    two read ports and one write port
+//   dual    two ports, each read or write
+//
+// An instruction streams one line per cycle through each bank operand. When
+// its operands share a physical bank with fewer ports than streams, the
+// streams take turns, and the instruction takes that many times as long.
+// The extra cycles are counted as bank conflict cycles. In NpuSim, two
+// instructions that only read the same bank may overlap when its ports can
+// serve both.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PortKind {
+    #[default]
+    #[serde(rename = "ideal")]
+    Ideal,
+    #[serde(rename = "single")]
+    Single,
+    #[serde(rename = "1r1w")]
+    R1W1,
+    #[serde(rename = "2r1w")]
+    R2W1,
+    #[serde(rename = "dual")]
+    Dual,
+}
+
+impl PortKind {
+    /// Cycles one line of `reads` read streams and `writes` write streams
+    /// takes through these ports; 1 when they all fit.
+    pub fn beats(self, reads: u64, writes: u64) -> u64 {
+        let beats = match self {
+            PortKind::Ideal => 1,
+            PortKind::Single => reads + writes,
+            PortKind::R1W1 => reads.max(writes),
+            PortKind::R2W1 => reads.div_ceil(2).max(writes),
+            PortKind::Dual => (reads + writes).div_ceil(2),
+        };
+        beats.max(1)
+    }
+}
+
+/// Port kind of every physical bank: `default`, except the banks listed in
+/// `banks`. From the `[arch.ports]` table of an `--arch` file:
+///
+/// ```toml
+/// [arch.ports]
+/// default = "1r1w"
+/// banks = { 0 = "dual", 7 = "single" }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct BankPorts {
+    pub default: PortKind,
+    pub banks: BTreeMap<usize, PortKind>,
+}
+
+impl BankPorts {
+    pub fn kind(&self, pbank: usize) -> PortKind {
+        self.banks.get(&pbank).copied().unwrap_or(self.default)
+    }
+
+    pub fn is_ideal(&self) -> bool {
+        self.default == PortKind::Ideal && self.banks.values().all(|&k| k == PortKind::Ideal)
+    }
+
+    pub fn validate(&self, num_banks: usize) -> Result<(), String> {
+        match self.banks.keys().find(|&&b| b >= num_banks) {
+            Some(b) => Err(format!("bank ports: bank {b} does not exist, there are {num_banks}")),
+            None => Ok(()),
+        }
+    }
+}
+
+/// How an instruction uses one bank operand.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Access {
+    Read,
+    Write,
+}
+
+/// The bank operands of a built-in instruction, by vbank.
+pub(crate) fn bank_operands(funct: u32, xs1: u64) -> Vec<(u64, Access)> {
+    let (b0, b1, b2) = (xs1 & 0x3ff, (xs1 >> 10) & 0x3ff, (xs1 >> 20) & 0x3ff);
+    match funct {
+        16 | 17 => vec![(b0, Access::Read)],
+        33 | 41 => vec![(b0, Access::Write)],
+        39 if (xs1 >> 10) & 1 == 1 => vec![(b0, Access::Read)],
+        39 => vec![(b0, Access::Write)],
+        37 | 50 | 51 | 55 | 56 | 57 => vec![(b0, Access::Read), (b1, Access::Write)],
+        48 | 49 | 54 => vec![(b0, Access::Read), (b1, Access::Read), (b2, Access::Write)],
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,218 @@
+//===- bankdump.rs - Bank contents dump and element diff -------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// A bank dump holds the full contents of selected vbanks, with the element
+// width each was configured with, as JSON. Unlike a snapshot (snapshot.rs),
+// which reports the first differing byte per bank, `BankDump::diff` lists
+// every differing element with its bank offset, its line and index within
+// the line, and both values decoded as signed integers of the bank's width.
+// That is what it takes to see which part of a tile a kernel got wrong.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+use crate::error::NpuError;
+use crate::npu::Npu;
+
+const BANK_DUMP_VERSION: u32 = 1;
+const LINE_BYTES: usize = 16;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BankDump {
+    pub version: u32,
+    pub cycle: u64,
+    pub banks: Vec<DumpedBank>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DumpedBank {
+    pub vbank: u32,
+    /// Element width in bits.
+    pub bits: u32,
+    /// Contents, hex.
+    pub data: String,
+}
+
+/// One differing element, e.g.
+/// `bank 3 @0x0044 line 4 [1] i32: 17 != -3`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ElementDiff {
+    pub vbank: u32,
+    pub offset: usize,
+    pub bits: u32,
+    pub left: i64,
+    pub right: i64,
+}
+
+impl fmt::Display for ElementDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes = self.bits as usize / 8;
+        write!(
+            f,
+            "bank {} @{:#06x} line {} [{}] i{}: {} != {}",
+            self.vbank,
+            self.offset,
+            self.offset / LINE_BYTES,
+            self.offset % LINE_BYTES / bytes,
+            self.bits,
+            self.left,
+            self.right
+        )
+    }
+}
+
+impl BankDump {
+    /// Dump `vbanks`, each of which must be mapped.
+    pub fn capture(npu: &Npu, vbanks: &[u32]) -> Result<Self, String> {
+        let banks = vbanks
+            .iter()
+            .map(|&vbank| {
+                let bytes = npu.bank(vbank).ok_or(NpuError::UnmappedBank { vbank })?;
+                Ok(DumpedBank {
+                    vbank,
+                    bits: npu.bank_cfgs.get(vbank as usize).map_or(8, |c| c.width.bits()),
+                    data: bytes.iter().map(|b| format!("{b:02x}")).collect(),
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self {
+            version: BANK_DUMP_VERSION,
+            cycle: npu.total_latency(),
+            banks,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, json).map_err(|e| format!("failed to write bank dump {}: {e}", path.display()))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json =
+            std::fs::read_to_string(path).map_err(|e| format!("failed to read bank dump {}: {e}", path.display()))?;
+        let dump: Self =
+            serde_json::from_str(&json).map_err(|e| format!("invalid bank dump {}: {e}", path.display()))?;
+        if dump.version != BANK_DUMP_VERSION {
+            return Err(format!(
+                "bank dump {} has version {}, expected {BANK_DUMP_VERSION}",
+                path.display(),
+                dump.version
+            ));
+        }
+        for bank in &dump.banks {
+            if !matches!(bank.bits, 8 | 16 | 32) || bank.data.len() % 2 != 0 {
+                return Err(format!("invalid bank dump {}: bank {}", path.display(), bank.vbank));
+            }
+        }
+        Ok(dump)
+    }
+
+    /// Every element that differs from `other`, by bank then offset. Both
+    /// dumps must hold the same banks with the same widths and sizes.
+    pub fn diff(&self, other: &BankDump) -> Result<Vec<ElementDiff>, String> {
+        let mut out = Vec::new();
+        for a in &self.banks {
+            let b = other
+                .banks
+                .iter()
+                .find(|b| b.vbank == a.vbank)
+                .ok_or_else(|| format!("bank {} is only in one dump", a.vbank))?;
+            if (a.bits, a.data.len()) != (b.bits, b.data.len()) {
+                return Err(format!(
+                    "bank {} is {} bytes of i{} in one dump and {} bytes of i{} in the other",
+                    a.vbank,
+                    a.data.len() / 2,
+                    a.bits,
+                    b.data.len() / 2,
+                    b.bits
+                ));
+            }
+            let (left, right) = (elements(a), elements(b));
+            let bytes = a.bits as usize / 8;
+            out.extend(
+                left.iter()
+                    .zip(&right)
+                    .enumerate()
+                    .filter(|(_, (l, r))| l != r)
+                    .map(|(i, (&l, &r))| ElementDiff {
+                        vbank: a.vbank,
+                        offset: i * bytes,
+                        bits: a.bits,
+                        left: l,
+                        right: r,
+                    }),
+            );
+        }
+        if let Some(b) = other
+            .banks
+            .iter()
+            .find(|b| self.banks.iter().all(|a| a.vbank != b.vbank))
+        {
+            return Err(format!("bank {} is only in one dump", b.vbank));
+        }
+        Ok(out)
+    }
+}
+
+/// The elements of a dumped bank, little-endian and sign-extended.
+fn elements(bank: &DumpedBank) -> Vec<i64> {
+    let bytes: Vec<u8> = (0..bank.data.len() / 2)
+        .map(|i| u8::from_str_radix(&bank.data[2 * i..2 * i + 2], 16).unwrap_or(0))
+        .collect();
+    let width = bank.bits as usize / 8;
+    bytes
+        .chunks_exact(width)
+        .map(|e| {
+            let mut v = [0u8; 8];
+            v[..width].copy_from_slice(e);
+            let shift = 64 - bank.bits;
+            (i64::from_le_bytes(v) << shift) >> shift
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_decodes_elements_at_the_bank_width() {
+        let mut npu = Npu::new(1 << 16);
+        npu.exec(32, 1, (1 << 5) | (1 << 10), 0); // i8
+        npu.exec(32, 2, (1 << 5) | (1 << 10) | (2 << 11), 0); // i32
+        let a = BankDump::capture(&npu, &[1, 2]).unwrap();
+        assert!(a.diff(&a).unwrap().is_empty());
+
+        npu.bank_mut(1).unwrap()[0x21] = 0xff;
+        npu.bank_mut(2).unwrap()[0x44..0x48].copy_from_slice(&(-3i32).to_le_bytes());
+        let b = BankDump::capture(&npu, &[1, 2]).unwrap();
+        let d: Vec<String> = a.diff(&b).unwrap().iter().map(ToString::to_string).collect();
+        assert_eq!(
+            d,
+            [
+                "bank 1 @0x0021 line 2 [1] i8: 0 != -1",
+                "bank 2 @0x0044 line 4 [1] i32: 0 != -3"
+            ]
+        );
+
+        let only_one = BankDump::capture(&npu, &[1]).unwrap();
+        assert!(a.diff(&only_one).is_err());
+        assert!(BankDump::capture(&npu, &[7]).is_err());
+    }
+}
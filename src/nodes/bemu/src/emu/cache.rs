@@ -0,0 +1,263 @@
+//===- cache.rs - Memory-side cache between DMA and DRAM -------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// An optional shared L2 that every DMA row passes through on its way to
+// DRAM. It is set-associative with LRU replacement, write-back and
+// write-allocate; a write miss fetches the line like a read miss. The cache
+// only decides timing: data still lives in guest DRAM.
+//
+// Per transfer:
+//
+//   - the first lookup costs `hit_latency`,
+//   - each line a row touches either hits, at no further cost, or misses,
+//   - misses fill in groups of `mshrs`, each group waiting `miss_latency`,
+//   - only the missed lines go on to DRAM, one line-sized access each, so
+//     the DRAM timing model (dram.rs), if configured, charges them too.
+//
+// Evicting a dirty line counts a writeback. Writebacks drain through a write
+// buffer and cost no cycles.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Memory-side cache shape and timing, in bytes and accelerator cycles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CacheConfig {
+    /// Capacity.
+    pub size: u64,
+    pub ways: u64,
+    pub line_bytes: u64,
+    /// Line fills in flight at once.
+    pub mshrs: u64,
+    #[serde(default)]
+    pub hit_latency: u64,
+    pub miss_latency: u64,
+}
+
+impl CacheConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.ways == 0 || self.mshrs == 0 {
+            return Err("cache: ways and mshrs must be > 0".to_string());
+        }
+        if !self.line_bytes.is_power_of_two() {
+            return Err(format!(
+                "cache: line_bytes must be a power of two, got {}",
+                self.line_bytes
+            ));
+        }
+        let set_bytes = self.ways * self.line_bytes;
+        if self.size == 0 || !self.size.is_multiple_of(set_bytes) {
+            return Err(format!(
+                "cache: size {} must be a non-zero multiple of ways * line_bytes ({set_bytes})",
+                self.size
+            ));
+        }
+        Ok(())
+    }
+
+    fn sets(&self) -> u64 {
+        self.size / (self.ways * self.line_bytes)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheStats {
+    /// Lines found in the cache / fetched from DRAM.
+    pub hits: u64,
+    pub misses: u64,
+    /// Dirty lines evicted.
+    pub writebacks: u64,
+    /// Extra cycles charged by the cache.
+    pub cycles: u64,
+}
+
+impl CacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            n => self.hits as f64 / n as f64,
+        }
+    }
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} hits, {} misses ({:.1}% hit rate), {} writebacks, +{} cycles",
+            self.hits,
+            self.misses,
+            100.0 * self.hit_rate(),
+            self.writebacks,
+            self.cycles
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct Line {
+    tag: u64,
+    dirty: bool,
+    /// Access clock at the last hit or fill, for LRU.
+    used: u64,
+}
+
+/// Cache contents and statistics. Checkpoints carry it so a resumed run
+/// sees the same hits as an uninterrupted one.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cache {
+    config: CacheConfig,
+    /// `ways` slots per set, set-major.
+    lines: Vec<Option<Line>>,
+    clock: u64,
+    stats: CacheStats,
+    /// Lookups and misses of the current transfer.
+    #[serde(skip)]
+    first: bool,
+    #[serde(skip)]
+    misses: u64,
+}
+
+impl Cache {
+    pub(crate) fn new(config: CacheConfig) -> Self {
+        Self {
+            config,
+            lines: vec![None; (config.sets() * config.ways) as usize],
+            clock: 0,
+            stats: CacheStats::default(),
+            first: true,
+            misses: 0,
+        }
+    }
+
+    pub fn config(&self) -> CacheConfig {
+        self.config
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Whether this is a well-formed cache of its configuration, for
+    /// contents read back from a checkpoint.
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        self.config.validate()?;
+        if self.lines.len() as u64 != self.config.sets() * self.config.ways {
+            return Err("cache lines do not match the cache configuration".to_string());
+        }
+        Ok(())
+    }
+
+    /// Drop every line, as after power-up.
+    pub(crate) fn reset(&mut self) {
+        *self = Self::new(self.config);
+    }
+
+    /// The next access starts a new transfer.
+    pub(crate) fn begin(&mut self) {
+        self.first = true;
+        self.misses = 0;
+    }
+
+    /// Look up the lines of a `len`-byte row at `addr`. Returns the extra
+    /// cycles and the address of each line that has to be fetched from DRAM.
+    pub(crate) fn access(&mut self, addr: u64, len: u64, write: bool) -> (u64, Vec<u64>) {
+        let c = self.config;
+        let mut cycles = if std::mem::take(&mut self.first) {
+            c.hit_latency
+        } else {
+            0
+        };
+        let mut fills = Vec::new();
+        let sets = c.sets();
+        for line in addr / c.line_bytes..=(addr + len.max(1) - 1) / c.line_bytes {
+            self.clock += 1;
+            let (set, tag) = (line % sets, line / sets);
+            let ways = &mut self.lines[(set * c.ways) as usize..((set + 1) * c.ways) as usize];
+            if let Some(hit) = ways.iter_mut().flatten().find(|l| l.tag == tag) {
+                hit.used = self.clock;
+                hit.dirty |= write;
+                self.stats.hits += 1;
+                continue;
+            }
+            self.stats.misses += 1;
+            let victim = ways
+                .iter_mut()
+                .min_by_key(|l| l.map_or(0, |l| l.used))
+                .expect("cache sets have at least one way");
+            if victim.is_some_and(|l| l.dirty) {
+                self.stats.writebacks += 1;
+            }
+            *victim = Some(Line {
+                tag,
+                dirty: write,
+                used: self.clock,
+            });
+            if self.misses.is_multiple_of(c.mshrs) {
+                cycles += c.miss_latency;
+            }
+            self.misses += 1;
+            fills.push(line * c.line_bytes);
+        }
+        self.stats.cycles += cycles;
+        (cycles, fills)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuse_hits_and_lru_evicts_the_oldest_way() {
+        // 2 sets of 2 ways, 64-byte lines.
+        let config = CacheConfig {
+            size: 256,
+            ways: 2,
+            line_bytes: 64,
+            mshrs: 2,
+            hit_latency: 3,
+            miss_latency: 20,
+        };
+        config.validate().unwrap();
+        let mut cache = Cache::new(config);
+
+        // Four lines miss in two MSHR groups.
+        cache.begin();
+        let (cycles, fills) = cache.access(0, 256, false);
+        assert_eq!(cycles, 3 + 2 * 20);
+        assert_eq!(fills, [0, 64, 128, 192]);
+
+        // The same tile again hits everywhere.
+        cache.begin();
+        assert_eq!(cache.access(0, 256, true), (3, vec![]));
+
+        // Line 4 maps to set 0 and evicts line 0, the least recently used.
+        cache.begin();
+        assert_eq!(cache.access(256, 16, false), (3 + 20, vec![256]));
+        cache.begin();
+        assert_eq!(cache.access(128, 16, false).1, Vec::<u64>::new());
+        cache.begin();
+        assert_eq!(cache.access(0, 16, false).1, [0]);
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.writebacks), (5, 6, 1));
+        assert!(CacheConfig { size: 100, ..config }.validate().is_err());
+    }
+}
@@ -0,0 +1,505 @@
+//===- checkpoint.rs - Full accelerator state save and restore -------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// A snapshot (snapshot.rs) is for diffing and hashes DRAM. A checkpoint holds
+// everything needed to carry on from where the model stopped:
+//
+//   - counters, the random-fill stream position and every bank config,
+//   - the vbank -> pbank map, MMIO regions, MMIO SRAM and every bank,
+//   - each non-zero DRAM page in full,
+//   - DMA policy, QoS caps, statistics, DRAM timing and open rows,
+//   - the utilization counters behind `Npu::perf_report`,
+//   - for NpuSim, the issue queue and the instructions in flight.
+//
+// Warnings, coverage and trace files belong to the run that produced them
+// and are not saved. Spike's hart state lives in C++ and is not captured:
+// a restored checkpoint resumes the accelerator, not the guest program.
+//
+// `Checkpoints` saves one every N cycles of a long run and keeps only the
+// most recent ones, so a late failure can be resumed from shortly before it.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Path, PathBuf};
+
+use crate::bank::{BankConfig, BankMap, ElemWidth, DRAM_BASE};
+use crate::cache::Cache;
+use crate::dma::{Dma, DmaStats, Fp32Paths, MisalignedDma, Throttle};
+use crate::dram::{DramModel, DramTiming};
+use crate::fill::Fill;
+use crate::inst::instruction::MmioRegion;
+use crate::npu::Npu;
+use crate::numfmt::NumFormat;
+use crate::perf::PerfCounters;
+
+const CHECKPOINT_VERSION: u32 = 1;
+const DRAM_PAGE: usize = 4096;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DmaState {
+    pub policy: MisalignedDma,
+    pub stats: DmaStats,
+    pub dram_cap: Option<Throttle>,
+    pub bank_cap: Option<Throttle>,
+    pub dram_timing: Option<DramTiming>,
+    /// Open row per DRAM bank, when `dram_timing` is set.
+    pub open_rows: Vec<Option<u64>>,
+    /// Memory-side cache contents, when there is one.
+    #[serde(default)]
+    pub cache: Option<Cache>,
+    /// Scales and activation of the fp32 mvin and mvout paths.
+    #[serde(default)]
+    pub fp32: Fp32Paths,
+}
+
+/// An instruction held by an NpuSim execution unit.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InFlightState {
+    /// (funct, xs1, xs2)
+    pub inst: (u32, u64, u64),
+    pub done_at: u64,
+    /// Issue order.
+    pub seq: u64,
+    /// (vbank, pbank) of each bank read, when banks are renamed.
+    #[serde(default)]
+    pub reads: Vec<(u64, usize)>,
+    /// (vbank, prior pbank, renamed pbank) of a renamed write.
+    #[serde(default)]
+    pub write: Option<(u64, usize, usize)>,
+}
+
+/// Instructions queued in an NpuSim and the ones in its execution units.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SimState {
+    pub cycle: u64,
+    pub pushed: u64,
+    pub issued: u64,
+    pub retired: u64,
+    pub busy_cycles: u64,
+    pub hazard_stalls: u64,
+    #[serde(default)]
+    pub port_stalls: u64,
+    #[serde(default)]
+    pub drain_stalls: u64,
+    #[serde(default)]
+    pub unit_stalls: u64,
+    #[serde(default)]
+    pub width_stalls: u64,
+    #[serde(default)]
+    pub rob_stalls: u64,
+    #[serde(default)]
+    pub expanded: u64,
+    #[serde(default)]
+    pub renamed: u64,
+    /// (funct, xs1, xs2) in issue order.
+    pub queue: Vec<(u32, u64, u64)>,
+    /// Per execution unit.
+    pub units: Vec<Option<InFlightState>>,
+    /// Finished instructions waiting in the reorder buffer.
+    #[serde(default)]
+    pub rob: Vec<InFlightState>,
+    #[serde(default)]
+    pub rob_occupancy: Vec<u64>,
+    pub unit_busy: Vec<u64>,
+    pub next_unit: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub version: u32,
+    pub mem_size: usize,
+    pub cycle: u64,
+    pub instructions: u64,
+    /// (seed, stream state) of the random fill.
+    pub fill: Option<(u64, u64)>,
+    /// Per vbank: (allocated, cols, element width code).
+    pub bank_cfgs: Vec<(bool, u64, u64)>,
+    /// Vbanks allocated as accumulators.
+    #[serde(default)]
+    pub accumulators: Vec<u32>,
+    /// Vbank -> number format code, for banks not holding integers.
+    #[serde(default)]
+    pub formats: BTreeMap<u32, u64>,
+    /// Vbank -> line exponents in hex, for bfp8 banks.
+    #[serde(default)]
+    pub bfp_exponents: BTreeMap<u32, String>,
+    /// Per pbank: the (vbank, group) bound to it.
+    pub bank_map: Vec<Option<(u32, u32)>>,
+    /// Per main bank: (mmio_addr, size_rows) of a valid region.
+    pub mmio_regions: Vec<Option<(u16, u8)>>,
+    /// MMIO SRAM, hex.
+    pub mmio: String,
+    /// Physical banks, hex.
+    pub banks: Vec<String>,
+    /// DRAM page address -> contents in hex, for pages that are not all zero.
+    pub dram_pages: BTreeMap<u64, String>,
+    pub dma: DmaState,
+    #[serde(default)]
+    pub perf: PerfCounters,
+    pub sim: Option<SimState>,
+}
+
+impl Checkpoint {
+    pub fn capture(npu: &Npu) -> Self {
+        let dram_pages = npu
+            .memory
+            .chunks(DRAM_PAGE)
+            .enumerate()
+            .filter(|(_, page)| page.iter().any(|&b| b != 0))
+            .map(|(i, page)| (DRAM_BASE + (i * DRAM_PAGE) as u64, to_hex(page)))
+            .collect();
+        let dma = &npu.dma;
+        Self {
+            version: CHECKPOINT_VERSION,
+            mem_size: npu.memory.len(),
+            cycle: npu.total_lat,
+            instructions: npu.npu_instruction_id,
+            fill: npu.fill.seed().map(|seed| (seed, npu.fill.state)),
+            bank_cfgs: npu
+                .bank_cfgs
+                .iter()
+                .map(|c| (c.allocated, c.cols, c.width as u64))
+                .collect(),
+            accumulators: (0..npu.bank_cfgs.len() as u32)
+                .filter(|&v| npu.bank_cfgs[v as usize].accumulator)
+                .collect(),
+            formats: (0..npu.bank_cfgs.len() as u32)
+                .map(|v| (v, npu.bank_cfgs[v as usize].format))
+                .filter(|(_, f)| f.is_float())
+                .map(|(v, f)| (v, f.code()))
+                .collect(),
+            bfp_exponents: (0..npu.bank_cfgs.len() as u32)
+                .filter(|&v| npu.bank_cfgs[v as usize].format == NumFormat::Bfp8)
+                .map(|v| {
+                    (
+                        v,
+                        to_hex(&npu.bfp_exps[v as usize].iter().map(|&e| e as u8).collect::<Vec<_>>()),
+                    )
+                })
+                .collect(),
+            bank_map: npu.bmt(),
+            mmio_regions: npu
+                .mmio_region_table
+                .iter()
+                .map(|r| r.valid.then_some((r.mmio_addr, r.size_rows)))
+                .collect(),
+            mmio: to_hex(npu.mmio_banks.as_flattened()),
+            banks: npu.banks.iter().map(|b| to_hex(b)).collect(),
+            dram_pages,
+            dma: DmaState {
+                policy: dma.policy,
+                stats: dma.stats,
+                dram_cap: dma.dram_cap,
+                bank_cap: dma.bank_cap,
+                dram_timing: dma.dram.as_ref().map(|m| m.timing),
+                open_rows: dma.dram.as_ref().map_or_else(Vec::new, |m| m.open_rows.clone()),
+                cache: dma.cache.clone(),
+                fp32: dma.fp32,
+            },
+            perf: npu.perf.clone(),
+            sim: None,
+        }
+    }
+
+    /// Replace the state of `npu` with this checkpoint. `npu` is left
+    /// untouched if the checkpoint does not fit the model.
+    pub fn restore(&self, npu: &mut Npu) -> Result<(), String> {
+        if self.banks.len() != npu.banks.len() || self.bank_cfgs.len() != npu.bank_cfgs.len() {
+            return Err(format!(
+                "checkpoint has {} banks, model has {}",
+                self.banks.len(),
+                npu.banks.len()
+            ));
+        }
+        let banks = self
+            .banks
+            .iter()
+            .enumerate()
+            .map(|(i, hex)| from_hex(hex, npu.banks[i].len(), &format!("bank {i}")))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mmio = from_hex(&self.mmio, npu.mmio_banks.as_flattened().len(), "mmio")?;
+        let mut memory = vec![0; self.mem_size];
+        for (&addr, hex) in &self.dram_pages {
+            let off = addr
+                .checked_sub(DRAM_BASE)
+                .map(|off| off as usize)
+                .filter(|off| off + DRAM_PAGE <= self.mem_size.next_multiple_of(DRAM_PAGE))
+                .ok_or_else(|| format!("DRAM page 0x{addr:x} is outside the checkpoint's memory"))?;
+            let len = DRAM_PAGE.min(self.mem_size - off);
+            memory[off..off + len].copy_from_slice(&from_hex(hex, len, &format!("DRAM page 0x{addr:x}"))?);
+        }
+        let bank_cfgs = self
+            .bank_cfgs
+            .iter()
+            .enumerate()
+            .map(|(v, &(allocated, cols, width))| {
+                let width = ElemWidth::from_code(width).ok_or_else(|| format!("invalid element width code {width}"))?;
+                let accumulator = self.accumulators.contains(&(v as u32));
+                let code = self.formats.get(&(v as u32)).copied().unwrap_or(0);
+                let format = NumFormat::from_code(code).ok_or_else(|| format!("invalid number format code {code}"))?;
+                Ok(BankConfig {
+                    allocated,
+                    cols,
+                    width,
+                    accumulator,
+                    format,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let dram = match self.dma.dram_timing {
+            Some(timing) => {
+                timing.validate()?;
+                let mut model = DramModel::new(timing);
+                if self.dma.open_rows.len() != model.open_rows.len() {
+                    return Err("checkpoint open rows do not match its DRAM timing".to_string());
+                }
+                model.open_rows.clone_from(&self.dma.open_rows);
+                Some(model)
+            }
+            None => None,
+        };
+        if let Some(cache) = &self.dma.cache {
+            cache.validate()?;
+        }
+
+        let depth = npu.geometry.bank_depth;
+        let mut bfp_exps = vec![vec![0; depth]; npu.bank_cfgs.len()];
+        for (&v, hex) in &self.bfp_exponents {
+            let exps = bfp_exps
+                .get_mut(v as usize)
+                .ok_or_else(|| format!("bfp8 exponents of unknown bank {v}"))?;
+            for (e, b) in exps
+                .iter_mut()
+                .zip(from_hex(hex, depth, &format!("bfp8 exponents of bank {v}"))?)
+            {
+                *e = b as i8;
+            }
+        }
+
+        npu.memory = memory;
+        npu.banks = banks;
+        npu.bank_cfgs = bank_cfgs;
+        npu.bfp_exps = bfp_exps;
+        npu.bank_map = BankMap::new(npu.banks.len());
+        for (p, slot) in self.bank_map.iter().enumerate() {
+            if let Some((vbank, group)) = *slot {
+                npu.bank_map.bind_group(p, vbank, group);
+            }
+        }
+        for (dst, src) in npu.mmio_banks.iter_mut().zip(mmio.chunks(1024)) {
+            dst.copy_from_slice(src);
+        }
+        npu.mmio_region_table = [MmioRegion::default(); 32];
+        for (dst, src) in npu.mmio_region_table.iter_mut().zip(&self.mmio_regions) {
+            if let Some((mmio_addr, size_rows)) = *src {
+                *dst = MmioRegion {
+                    valid: true,
+                    mmio_addr,
+                    size_rows,
+                };
+            }
+        }
+        npu.total_lat = self.cycle;
+        npu.npu_instruction_id = self.instructions;
+        npu.fill = match self.fill {
+            Some((seed, state)) => Fill::resume(seed, state),
+            None => Fill::default(),
+        };
+        npu.dma = Dma {
+            policy: self.dma.policy,
+            fp32: self.dma.fp32,
+            stats: self.dma.stats,
+            dram_cap: self.dma.dram_cap,
+            bank_cap: self.dma.bank_cap,
+            dram,
+            cache: self.dma.cache.clone(),
+            stall: 0,
+            log: None,
+        };
+        npu.perf = self.perf.clone();
+        npu.warnings.clear();
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, json).map_err(|e| format!("failed to write checkpoint {}: {e}", path.display()))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json =
+            std::fs::read_to_string(path).map_err(|e| format!("failed to read checkpoint {}: {e}", path.display()))?;
+        let ckpt: Self =
+            serde_json::from_str(&json).map_err(|e| format!("invalid checkpoint {}: {e}", path.display()))?;
+        if ckpt.version != CHECKPOINT_VERSION {
+            return Err(format!(
+                "checkpoint {} has version {}, expected {CHECKPOINT_VERSION}",
+                path.display(),
+                ckpt.version
+            ));
+        }
+        Ok(ckpt)
+    }
+}
+
+/// Checkpoints written every `every` cycles into `dir`, keeping the last `keep`.
+pub struct Checkpoints {
+    dir: PathBuf,
+    every: u64,
+    keep: usize,
+    next_at: u64,
+    saved: VecDeque<PathBuf>,
+}
+
+impl Checkpoints {
+    pub fn new(dir: &Path, every: u64, keep: usize) -> Result<Self, String> {
+        if every == 0 || keep == 0 {
+            return Err("checkpoint interval and count must be > 0".to_string());
+        }
+        std::fs::create_dir_all(dir).map_err(|e| format!("failed to create checkpoint dir {}: {e}", dir.display()))?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            every,
+            keep,
+            next_at: every,
+            saved: VecDeque::new(),
+        })
+    }
+
+    /// Save `npu` if its cycle count has reached the next scheduled
+    /// checkpoint, and drop the oldest beyond `keep`. Returns the path
+    /// written, if any.
+    pub fn poll(&mut self, npu: &Npu) -> Result<Option<PathBuf>, String> {
+        let cycle = npu.total_lat;
+        if cycle < self.next_at {
+            return Ok(None);
+        }
+        // One instruction may span several intervals; checkpoint once.
+        self.next_at = (cycle / self.every + 1) * self.every;
+        let path = self.dir.join(format!("ckpt-{cycle:012}.json"));
+        Checkpoint::capture(npu).save(&path)?;
+        self.saved.push_back(path.clone());
+        while self.saved.len() > self.keep {
+            let old = self.saved.pop_front().expect("saved is not empty");
+            std::fs::remove_file(&old).map_err(|e| format!("failed to remove checkpoint {}: {e}", old.display()))?;
+        }
+        Ok(Some(path))
+    }
+
+    /// Checkpoints still on disk, oldest first.
+    pub fn saved(&self) -> impl Iterator<Item = &Path> {
+        self.saved.iter().map(PathBuf::as_path)
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn from_hex(hex: &str, len: usize, what: &str) -> Result<Vec<u8>, String> {
+    if hex.len() != 2 * len {
+        return Err(format!("{what}: expected {len} bytes, got {}", hex.len() / 2));
+    }
+    (0..len)
+        .map(|i| u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).map_err(|_| format!("{what}: invalid hex")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NpuSim, NpuSimConfig};
+
+    #[test]
+    fn restored_model_continues_like_the_original() {
+        let mut npu = Npu::new(1 << 20);
+        npu.randomize(7);
+        npu.set_dram_timing(Some(DramTiming {
+            t_cas: 2,
+            t_rcd: 3,
+            t_rp: 4,
+            row_bytes: 256,
+            banks: 2,
+            bytes_per_cycle: 0,
+            window: 0,
+        }))
+        .unwrap();
+        npu.exec(32, 1, (1 << 5) | (1 << 10), 0);
+        npu.exec(36, 0, 1 | (2 << 16), 0);
+        npu.exec(33, 1 | (4 << 30), DRAM_BASE | (1 << 39), 0);
+
+        let path = std::env::temp_dir().join(format!("bemu-restore-{}.json", std::process::id()));
+        Checkpoint::capture(&npu).save(&path).unwrap();
+        let mut resumed = Npu::new(1 << 16);
+        Checkpoint::load(&path).unwrap().restore(&mut resumed).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(resumed.snapshot(), npu.snapshot());
+
+        // Open rows, QoS caps and the fill stream carry over.
+        for n in [&mut npu, &mut resumed] {
+            n.exec(32, 2, (1 << 5) | (1 << 10), 0);
+            n.exec(16, 1 | (4 << 30), (DRAM_BASE + 0x40) | (1 << 39), 0);
+        }
+        assert_eq!(resumed.snapshot(), npu.snapshot());
+        assert_eq!(resumed.dma_stats(), npu.dma_stats());
+    }
+
+    #[test]
+    fn sim_checkpoint_keeps_queued_and_in_flight_instructions() {
+        let config = NpuSimConfig {
+            mem_size: 1 << 20,
+            queue_depth: 4,
+            ..NpuSimConfig::default()
+        };
+        let mut sim = NpuSim::new(config);
+        sim.npu_mut().write_dram(DRAM_BASE, &[0x5a; 64]);
+        sim.push_inst(32, 1, (1 << 5) | (1 << 10)).unwrap();
+        sim.push_inst(33, 1 | (4 << 30), DRAM_BASE | (1 << 39)).unwrap();
+        sim.push_inst(0, 0, 0).unwrap();
+        sim.tick(2); // mvin in flight, fence queued
+
+        let mut resumed = NpuSim::new(config);
+        resumed.restore(&sim.checkpoint()).unwrap();
+        assert_eq!(resumed.stats(), sim.stats());
+        assert_eq!(resumed.run_until_idle(), sim.run_until_idle());
+        assert_eq!(resumed.read_bank(1).unwrap()[..64], [0x5a; 64]);
+        assert_eq!(resumed.stats(), sim.stats());
+    }
+
+    #[test]
+    fn checkpoints_rotate_keeping_the_latest() {
+        let dir = std::env::temp_dir().join(format!("bemu-ckpt-{}", std::process::id()));
+        let mut npu = Npu::new(1 << 16);
+        let mut ckpt = Checkpoints::new(&dir, 100, 2).unwrap();
+        let taken: Vec<_> = [50, 100, 150, 420, 430, 500]
+            .into_iter()
+            .filter_map(|cycle| {
+                npu.total_lat = cycle;
+                ckpt.poll(&npu).unwrap()
+            })
+            .collect();
+        assert_eq!(taken.len(), 3, "at 100, 420 and 500");
+        let kept: Vec<_> = ckpt.saved().map(Path::to_path_buf).collect();
+        assert_eq!(kept, taken[1..]);
+        assert!(!taken[0].exists());
+        let mut resumed = Npu::new(1 << 16);
+        Checkpoint::load(&kept[1]).unwrap().restore(&mut resumed).unwrap();
+        assert_eq!(resumed.total_latency(), 500);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
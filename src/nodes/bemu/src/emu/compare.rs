@@ -0,0 +1,160 @@
+//===- compare.rs - Lockstep comparison of two models ----------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// Runs one program on two Npus in lockstep, typically the same kernel on two
+// `--arch` configurations, and checks that they leave the same data in DRAM.
+// Cycle counts are free to differ.
+//
+// BEMU finishes an instruction's DMA before the next one starts, so every
+// instruction boundary is a fence. After each instruction the DRAM rows
+// either model wrote are compared byte for byte, and the run stops at the
+// first instruction that left them different. Rows only one model wrote are
+// compared too, so a model that stores to the wrong address is caught at the
+// store rather than at some later read.
+//
+// Both models must start from the same DRAM image.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use std::fmt;
+
+use crate::npu::Npu;
+use crate::program::{Program, ProgramInst};
+
+/// First DRAM byte the two models disagree on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// The instruction whose writes differ.
+    pub inst: ProgramInst,
+    pub addr: u64,
+    pub left: u8,
+    pub right: u8,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {} {}: DRAM 0x{:x} is 0x{:02x} on the left and 0x{:02x} on the right",
+            self.inst.line, self.inst.mnemonic, self.addr, self.left, self.right
+        )
+    }
+}
+
+/// Outcome of a lockstep run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Comparison {
+    /// Instructions both models executed, the divergent one included.
+    pub executed: usize,
+    pub left_cycles: u64,
+    pub right_cycles: u64,
+    pub divergence: Option<Divergence>,
+}
+
+/// Execute `program` on `left` and `right` side by side until their DRAM
+/// writes disagree or the program ends.
+pub fn compare(program: &Program, left: &mut Npu, right: &mut Npu) -> Comparison {
+    let start = (left.total_latency(), right.total_latency());
+    left.track_dram_writes();
+    right.track_dram_writes();
+    left.take_dram_writes();
+    right.take_dram_writes();
+
+    let mut executed = 0;
+    let mut divergence = None;
+    for inst in &program.insts {
+        left.exec(inst.funct, inst.xs1, inst.xs2, 0);
+        right.exec(inst.funct, inst.xs1, inst.xs2, 0);
+        executed += 1;
+        let rows: Vec<(u64, u64)> = left
+            .take_dram_writes()
+            .into_iter()
+            .chain(right.take_dram_writes())
+            .collect();
+        divergence = rows.into_iter().find_map(|(addr, len)| {
+            let (l, r) = (left.read_dram(addr, len as usize), right.read_dram(addr, len as usize));
+            let i = l.iter().zip(&r).position(|(a, b)| a != b)?;
+            Some(Divergence {
+                inst: inst.clone(),
+                addr: addr + i as u64,
+                left: l[i],
+                right: r[i],
+            })
+        });
+        if divergence.is_some() {
+            break;
+        }
+    }
+    Comparison {
+        executed,
+        left_cycles: left.total_latency() - start.0,
+        right_cycles: right.total_latency() - start.1,
+        divergence,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::DRAM_BASE;
+    use crate::cache::CacheConfig;
+
+    #[test]
+    fn divergence_names_the_store_that_differs() {
+        let program = Program::parse(
+            "mset bank=1\n\
+             mvin bank=1 addr=0x80001000 rows=2\n\
+             mvout bank=1 addr=0x80002000 rows=2\n\
+             fence\n",
+        )
+        .unwrap();
+        let input: Vec<u8> = (0..32).collect();
+
+        // A cache changes the timing but not the data.
+        let mut left = Npu::new(1 << 20);
+        let mut right = Npu::new(1 << 20);
+        right
+            .set_cache(Some(CacheConfig {
+                size: 1024,
+                ways: 2,
+                line_bytes: 64,
+                mshrs: 1,
+                hit_latency: 1,
+                miss_latency: 30,
+            }))
+            .unwrap();
+        left.write_dram(DRAM_BASE + 0x1000, &input);
+        right.write_dram(DRAM_BASE + 0x1000, &input);
+        let same = compare(&program, &mut left, &mut right);
+        assert_eq!(same.executed, 4);
+        assert_eq!(same.divergence, None);
+        assert!(same.right_cycles > same.left_cycles);
+
+        // Different inputs show up at the mvout that stores them.
+        let mut left = Npu::new(1 << 20);
+        let mut right = Npu::new(1 << 20);
+        left.write_dram(DRAM_BASE + 0x1000, &input);
+        right.write_dram(DRAM_BASE + 0x1000, &input);
+        right.write_dram(DRAM_BASE + 0x1010, &[0xff]);
+        let diff = compare(&program, &mut left, &mut right);
+        assert_eq!(diff.executed, 3);
+        let d = diff.divergence.unwrap();
+        assert_eq!(
+            (d.inst.line, d.addr, d.left, d.right),
+            (3, DRAM_BASE + 0x2010, 16, 0xff)
+        );
+    }
+}
@@ -0,0 +1,456 @@
+//===- coverage.rs - ISA-level coverage of issued instructions -------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// Line coverage says a decoder branch compiled into a hit; it does not say
+// whether any workload ever issued a strided mvout to a multi-group bank.
+// Each instruction here has a few fields split into bins that follow the
+// decoder's branches (row count, stride, alignment, bank layout, ...). Every
+// issued instruction bumps one bin per field. The report lists each bin's
+// hits, so bins at zero point at the tests still missing.
+//
+// Coverage files merge: pass the same file to every run of a suite and it
+// holds the union.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::bank::{BankConfig, ElemWidth, MATRIX_SIZE};
+use crate::dma::split_beat_penalty;
+use crate::inst::decode::{
+    rs1_b0, rs1_b1, rs1_b2, rs1_is_tile, rs1_iter, xs2_mem_stride, xs2_mset, xs2_mset_acc, xs2_mset_width, INSTRUCTIONS,
+};
+use crate::inst::f49_conv::ConvShape;
+use crate::inst::f53_loop_ws::dims as loop_ws_dims;
+use crate::inst::f55_im2col::Im2col;
+use crate::inst::f56_pool::Pool;
+use crate::inst::f57_norm::Norm;
+use crate::perf::COUNTERS;
+
+const ROWS: &[&str] = &["1", "2-16", "17+"];
+const STRIDE: &[&str] = &["1", ">1"];
+const ALIGN: &[&str] = &["beat", "split"];
+const SHAPE: &[&str] = &["1d", "2d"];
+
+/// (funct, field, bins) of every coverage point.
+const SPACE: &[(u32, &str, &[&str])] = &[
+    (16, "rows", ROWS),
+    (16, "stride", STRIDE),
+    (16, "align", ALIGN),
+    (16, "path", &["single", "multi-group", "multi-group-acc"]),
+    (16, "shape", SHAPE),
+    (32, "op", &["alloc", "free"]),
+    (32, "prior", &["unallocated", "allocated"]),
+    (32, "cols", &["0-1", "2+"]),
+    (32, "width", &["i8", "i16", "i32"]),
+    (32, "kind", &["scratchpad", "accumulator"]),
+    (33, "rows", ROWS),
+    (33, "stride", STRIDE),
+    (33, "align", ALIGN),
+    (33, "path", &["single", "multi-group"]),
+    (33, "shape", SHAPE),
+    (34, "size", &["invalidate", "set"]),
+    (34, "fits", &["yes", "overflow"]),
+    (35, "rows", &["0", "1", "2+"]),
+    (35, "col", &["0", "1-15", "16", "clamped"]),
+    (35, "align", ALIGN),
+    (36, "port", &["dram", "bank"]),
+    (36, "cap", &["off", "on"]),
+    (37, "conv", &["same", "widen", "narrow"]),
+    (38, "op", &["remap-block", "remap-cyclic", "query"]),
+    (39, "dir", &["load", "store"]),
+    (39, "descs", &["0", "1", "2+"]),
+    (40, "counter", COUNTERS),
+    (48, "a_width", &["i8", "i16", "i32"]),
+    (48, "c_width", &["i8", "i16", "i32"]),
+    (48, "acc", &["overwrite", "accumulate"]),
+    (48, "lines", &["1", "2+"]),
+    (49, "kernel", &["1x1", "square", "rect"]),
+    (49, "stride", STRIDE),
+    (49, "pad", &["0", ">0"]),
+    (49, "dilation", &["none", "kernel", "input", "both"]),
+    (49, "rot180", &["off", "on"]),
+    (50, "width", &["i8", "i16", "i32"]),
+    (50, "place", &["in-place", "copy"]),
+    (51, "width", &["i8", "i16", "i32"]),
+    (51, "rows", &["partial", "full"]),
+    (53, "k_tiles", &["1", "2+"]),
+    (53, "n_tiles", &["1", "2+"]),
+    (54, "a_width", &["i8", "i16", "i32"]),
+    (54, "density", &["empty", "<50%", ">=50%"]),
+    (55, "kernel", &["1x1", "square", "rect"]),
+    (55, "pad", &["0", ">0"]),
+    (55, "k_tiles", &["1", "2+"]),
+    (56, "mode", &["max", "avg"]),
+    (56, "window", &["disjoint", "overlapping"]),
+    (56, "pad", &["0", ">0"]),
+    (57, "op", &["reset", "sum", "sum_exp", "layernorm", "softmax"]),
+    (57, "out_width", &["i8", "i16", "i32"]),
+];
+
+fn rows_bin(rows: u64) -> &'static str {
+    match rows {
+        0 | 1 => "1",
+        2..=16 => "2-16",
+        _ => "17+",
+    }
+}
+
+fn align_bin(addr: u64) -> &'static str {
+    if split_beat_penalty(addr, 1) == 0 {
+        "beat"
+    } else {
+        "split"
+    }
+}
+
+fn width_bin(width: ElemWidth) -> &'static str {
+    match width {
+        ElemWidth::I8 => "i8",
+        ElemWidth::I16 => "i16",
+        ElemWidth::I32 => "i32",
+    }
+}
+
+fn kernel_bin(s: &ConvShape) -> &'static str {
+    match (s.kernel_h, s.kernel_w) {
+        (1, 1) => "1x1",
+        (h, w) if h == w => "square",
+        _ => "rect",
+    }
+}
+
+/// The (field, bin) pairs an instruction hits, given the bank configs it
+/// will execute against.
+fn sample(funct: u32, xs1: u64, xs2: u64, cfgs: &[BankConfig]) -> Vec<(&'static str, &'static str)> {
+    let cfg = |bank: u64| cfgs.get(bank as usize).copied().unwrap_or_default();
+    match funct {
+        16 | 33 => {
+            let rows = rs1_iter(xs1);
+            let (addr, stride) = xs2_mem_stride(xs2);
+            let path = match (cfg(rs1_b0(xs1)).cols > 1, rows > MATRIX_SIZE as u64) {
+                (false, _) => "single",
+                (true, true) if funct == 16 => "multi-group-acc",
+                (true, _) => "multi-group",
+            };
+            vec![
+                ("rows", rows_bin(rows)),
+                ("stride", if stride > 1 { ">1" } else { "1" }),
+                ("align", align_bin(addr)),
+                ("path", path),
+                ("shape", if rs1_is_tile(xs1) { "2d" } else { "1d" }),
+            ]
+        }
+        32 => {
+            let (_, cols, alloc) = xs2_mset(xs2);
+            let width = width_bin(ElemWidth::from_code(xs2_mset_width(xs2)).unwrap_or(ElemWidth::I8));
+            let prior = if cfg(rs1_b0(xs1)).allocated {
+                "allocated"
+            } else {
+                "unallocated"
+            };
+            vec![
+                ("op", if alloc == 1 { "alloc" } else { "free" }),
+                ("prior", prior),
+                ("cols", if cols > 1 { "2+" } else { "0-1" }),
+                ("width", width),
+                ("kind", if xs2_mset_acc(xs2) { "accumulator" } else { "scratchpad" }),
+            ]
+        }
+        34 => {
+            let size_rows = (xs2 >> 16) & 0xFF;
+            let fits = (xs2 & 0xFFFF) + size_rows * 1024 <= 16384;
+            vec![
+                ("size", if size_rows == 0 { "invalidate" } else { "set" }),
+                ("fits", if fits { "yes" } else { "overflow" }),
+            ]
+        }
+        35 => {
+            let row = rs1_iter(xs1);
+            let col = (xs2 >> 56) & 0xFF;
+            vec![
+                (
+                    "rows",
+                    match row {
+                        0 => "0",
+                        1 => "1",
+                        _ => "2+",
+                    },
+                ),
+                (
+                    "col",
+                    match col {
+                        0 => "0",
+                        1..=15 => "1-15",
+                        16 => "16",
+                        _ => "clamped",
+                    },
+                ),
+                ("align", align_bin(xs2 & 0x7F_FFFF_FFFF)),
+            ]
+        }
+        36 => vec![
+            ("port", if xs1 & 1 == 0 { "dram" } else { "bank" }),
+            ("cap", if xs2 & 0xFFFF == 0 { "off" } else { "on" }),
+        ],
+        37 => {
+            let (src, dst) = (cfg(rs1_b0(xs1)).width.bytes(), cfg(rs1_b1(xs1)).width.bytes());
+            let conv = match dst.cmp(&src) {
+                std::cmp::Ordering::Equal => "same",
+                std::cmp::Ordering::Greater => "widen",
+                std::cmp::Ordering::Less => "narrow",
+            };
+            vec![("conv", conv)]
+        }
+        38 => {
+            let op = match (xs2 & 1 == 1, (xs2 >> 1) & 1 == 1) {
+                (true, _) => "query",
+                (false, false) => "remap-block",
+                (false, true) => "remap-cyclic",
+            };
+            vec![("op", op)]
+        }
+        39 => {
+            let descs = match rs1_iter(xs1) {
+                0 => "0",
+                1 => "1",
+                _ => "2+",
+            };
+            vec![
+                ("dir", if (xs1 >> 10) & 1 == 0 { "load" } else { "store" }),
+                ("descs", descs),
+            ]
+        }
+        40 => COUNTERS
+            .get((xs1 & 0xFF) as usize)
+            .map(|&counter| vec![("counter", counter)])
+            .unwrap_or_default(),
+        48 => vec![
+            ("a_width", width_bin(cfg(rs1_b0(xs1)).width)),
+            ("c_width", width_bin(cfg(rs1_b2(xs1)).width)),
+            (
+                "acc",
+                if xs2 & 1 == 0 && !cfg(rs1_b2(xs1)).accumulator {
+                    "overwrite"
+                } else {
+                    "accumulate"
+                },
+            ),
+            (
+                "lines",
+                if (xs2 >> 8) & 0xff > 1 || (xs2 >> 16) & 0xff > 1 {
+                    "2+"
+                } else {
+                    "1"
+                },
+            ),
+        ],
+        49 => {
+            let s = ConvShape::decode(xs2);
+            vec![
+                ("kernel", kernel_bin(&s)),
+                ("stride", if s.stride > 1 { ">1" } else { "1" }),
+                ("pad", if s.padding > 0 { ">0" } else { "0" }),
+                (
+                    "dilation",
+                    match (s.dilation > 1, s.input_dilation > 1) {
+                        (false, false) => "none",
+                        (true, false) => "kernel",
+                        (false, true) => "input",
+                        (true, true) => "both",
+                    },
+                ),
+                ("rot180", if s.rot180 { "on" } else { "off" }),
+            ]
+        }
+        50 => vec![
+            ("width", width_bin(cfg(rs1_b0(xs1)).width)),
+            ("place", if rs1_b0(xs1) == rs1_b1(xs1) { "in-place" } else { "copy" }),
+        ],
+        51 => {
+            let width = cfg(rs1_b0(xs1)).width;
+            let full = rs1_iter(xs1) as usize == 16 / width.bytes();
+            vec![
+                ("width", width_bin(width)),
+                ("rows", if full { "full" } else { "partial" }),
+            ]
+        }
+        53 => {
+            let (_, k, n) = loop_ws_dims(xs2);
+            let tiles = |d: usize| if d > 16 { "2+" } else { "1" };
+            vec![("k_tiles", tiles(k)), ("n_tiles", tiles(n))]
+        }
+        54 => {
+            let width = cfg(rs1_b0(xs1)).width;
+            let dense = rs1_iter(xs1) * (16 / width.bytes()) as u64;
+            let density = match xs2 >> 32 {
+                0 => "empty",
+                nnz if 2 * nnz < dense => "<50%",
+                _ => ">=50%",
+            };
+            vec![("a_width", width_bin(width)), ("density", density)]
+        }
+        55 => {
+            let s = ConvShape::decode(xs2);
+            let (_, tiles) = Im2col::dims(&s, cfg(rs1_b0(xs1)).width.bytes());
+            vec![
+                ("kernel", kernel_bin(&s)),
+                ("pad", if s.padding > 0 { ">0" } else { "0" }),
+                ("k_tiles", if tiles > 1 { "2+" } else { "1" }),
+            ]
+        }
+        56 => {
+            let s = Pool::shape(xs2);
+            let overlapping = s.stride < s.kernel_h || s.stride < s.kernel_w;
+            vec![
+                ("mode", if (xs2 >> 61) & 1 == 1 { "avg" } else { "max" }),
+                ("window", if overlapping { "overlapping" } else { "disjoint" }),
+                ("pad", if s.padding > 0 { ">0" } else { "0" }),
+            ]
+        }
+        57 => match Norm::op_name(xs2) {
+            Some(op @ ("layernorm" | "softmax")) => {
+                vec![("op", op), ("out_width", width_bin(cfg(rs1_b1(xs1)).width))]
+            }
+            Some(op) => vec![("op", op)],
+            None => Vec::new(),
+        },
+        _ => Vec::new(),
+    }
+}
+
+fn name(funct: u32) -> String {
+    INSTRUCTIONS
+        .iter()
+        .find(|(f, _)| *f == funct)
+        .map_or_else(|| format!("funct7_{funct}"), |(_, n)| n.to_string())
+}
+
+/// Issue counts per funct and hit counts per bin.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Coverage {
+    pub functs: BTreeMap<u32, u64>,
+    /// `<name>.<field>=<bin>` -> hits, e.g. `mvout.stride=>1`.
+    pub bins: BTreeMap<String, u64>,
+}
+
+impl Coverage {
+    /// Every registered funct and every bin, all at zero.
+    pub fn new() -> Self {
+        let functs = INSTRUCTIONS.iter().map(|&(f, _)| (f, 0)).collect();
+        let bins = SPACE
+            .iter()
+            .filter(|(f, _, _)| INSTRUCTIONS.iter().any(|(g, _)| g == f))
+            .flat_map(|&(f, field, bins)| bins.iter().map(move |bin| (format!("{}.{field}={bin}", name(f)), 0)))
+            .collect();
+        Self { functs, bins }
+    }
+
+    pub(crate) fn record(&mut self, funct: u32, xs1: u64, xs2: u64, cfgs: &[BankConfig]) {
+        *self.functs.entry(funct).or_default() += 1;
+        let prefix = name(funct);
+        for (field, bin) in sample(funct, xs1, xs2, cfgs) {
+            *self.bins.entry(format!("{prefix}.{field}={bin}")).or_default() += 1;
+        }
+    }
+
+    /// Add the counts of `other`.
+    pub fn merge(&mut self, other: &Coverage) {
+        for (f, n) in &other.functs {
+            *self.functs.entry(*f).or_default() += n;
+        }
+        for (b, n) in &other.bins {
+            *self.bins.entry(b.clone()).or_default() += n;
+        }
+    }
+
+    /// Bins and functs never hit.
+    pub fn gaps(&self) -> Vec<String> {
+        let functs = self.functs.iter().filter(|(_, n)| **n == 0).map(|(f, _)| name(*f));
+        let bins = self.bins.iter().filter(|(_, n)| **n == 0).map(|(b, _)| b.clone());
+        functs.chain(bins).collect()
+    }
+
+    /// Merge into the coverage already in `path`, if any, and write it back.
+    pub fn save_merged(&self, path: &Path) -> Result<Coverage, String> {
+        let mut total = Coverage::new();
+        if path.exists() {
+            let json = std::fs::read_to_string(path)
+                .map_err(|e| format!("failed to read coverage {}: {e}", path.display()))?;
+            let old: Coverage =
+                serde_json::from_str(&json).map_err(|e| format!("invalid coverage {}: {e}", path.display()))?;
+            total.merge(&old);
+        }
+        total.merge(self);
+        let json = serde_json::to_string_pretty(&total).map_err(|e| e.to_string())?;
+        std::fs::write(path, json).map_err(|e| format!("failed to write coverage {}: {e}", path.display()))?;
+        Ok(total)
+    }
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hit = self.bins.values().filter(|n| **n > 0).count();
+        writeln!(f, "{hit}/{} bins covered", self.bins.len())?;
+        for (funct, n) in &self.functs {
+            writeln!(f, "{:<12} {:>10}", name(*funct), n)?;
+            let prefix = format!("{}.", name(*funct));
+            for (bin, n) in self.bins.iter().filter(|(b, _)| b.starts_with(&prefix)) {
+                let mark = if *n == 0 { "  <- never hit" } else { "" };
+                writeln!(f, "  {:<30} {:>8}{mark}", &bin[prefix.len()..], n)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bank::DRAM_BASE;
+    use crate::npu::Npu;
+
+    #[test]
+    fn coverage_bins_follow_decoder_branches() {
+        let mut npu = Npu::new(1 << 20);
+        npu.enable_coverage();
+        npu.exec(32, 1, (2 << 5) | (1 << 10), 0); // two groups
+        npu.exec(16, 1 | (32 << 30), (DRAM_BASE + 4) | (2 << 39), 0);
+
+        let cov = npu.coverage().unwrap();
+        assert_eq!(cov.functs[&16], 1);
+        for bin in [
+            "mvout.rows=17+",
+            "mvout.stride=>1",
+            "mvout.align=split",
+            "mvout.path=multi-group-acc",
+        ] {
+            assert_eq!(cov.bins[bin], 1, "{bin}");
+        }
+        let gaps = cov.gaps();
+        assert!(gaps.contains(&"mvin".to_string()));
+        assert!(gaps.contains(&"mvout.stride=1".to_string()));
+        assert!(!gaps.contains(&"mset.cols=2+".to_string()));
+    }
+}
@@ -0,0 +1,187 @@
+//===- cycle_debugger.rs - Cycle-stepped program debugger ------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// Feeds a text program (program.rs) into an NpuSim and advances it one cycle
+// at a time, printing what each cycle issued, completed and retired and why
+// queued work stalled. debugger.rs steps whole instructions on an Npu, where
+// nothing overlaps; this one shows the pipeline. Commands, one per line:
+//
+//   tick [N]            advance N cycles (default 1), listing every cycle
+//                       in which something happened
+//   continue            advance until the program has retired, listing
+//                       nothing
+//   info                cycle, queue length, issue and retire counts
+//   bank V [OFF [LEN]]  hex dump of a bank as of the last retire
+//   dram ADDR [LEN]     hex dump of DRAM
+//
+// Program instructions enter the issue queue as soon as it has room, before
+// each cycle.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use std::fmt::Write as _;
+
+use crate::debugger::hex_dump;
+use crate::npusim::NpuSim;
+use crate::program::{parse_value, Program};
+
+/// Bytes `bank` and `dram` print when no length is given.
+const DEFAULT_DUMP: usize = 64;
+
+/// A program fed through an NpuSim under cycle-by-cycle control.
+pub struct CycleDebugger {
+    sim: NpuSim,
+    program: Program,
+    /// Index of the next instruction to queue.
+    next: usize,
+}
+
+impl CycleDebugger {
+    pub fn new(sim: NpuSim, program: Program) -> Self {
+        Self { sim, program, next: 0 }
+    }
+
+    pub fn sim(&self) -> &NpuSim {
+        &self.sim
+    }
+
+    pub fn finished(&self) -> bool {
+        self.next >= self.program.insts.len() && self.sim.is_idle()
+    }
+
+    fn fill_queue(&mut self) {
+        while let Some(inst) = self.program.insts.get(self.next) {
+            if self.sim.push_inst(inst.funct, inst.xs1, inst.xs2).is_err() {
+                break;
+            }
+            self.next += 1;
+        }
+    }
+
+    /// Advance up to `n` cycles, stopping early once the program has
+    /// retired. Returns one block per cycle in which anything happened.
+    pub fn tick(&mut self, n: u64) -> String {
+        let mut out = String::new();
+        for _ in 0..n {
+            if self.finished() {
+                break;
+            }
+            self.fill_queue();
+            let cycle = self.sim.stats().cycle;
+            let events = self.sim.step_cycle();
+            if events.is_empty() {
+                continue;
+            }
+            let _ = writeln!(out, "cycle {cycle}:");
+            for event in events {
+                let _ = writeln!(out, "  {event}");
+            }
+        }
+        out + &self.status()
+    }
+
+    fn status(&self) -> String {
+        match self.finished() {
+            true => format!("program finished at cycle {}", self.sim.stats().cycle),
+            false => format!("at cycle {}", self.sim.stats().cycle),
+        }
+    }
+
+    /// Run one command line and return what to print.
+    pub fn command(&mut self, line: &str) -> Result<String, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let num = |i: usize| -> Result<u64, String> {
+            let w = words
+                .get(i)
+                .ok_or_else(|| format!("`{}` needs more operands", words[0]))?;
+            parse_value(w).ok_or_else(|| format!("not a number: {w}"))
+        };
+        let opt = |i: usize, default: u64| if words.len() > i { num(i) } else { Ok(default) };
+        match words.as_slice() {
+            [] => Ok(String::new()),
+            ["tick" | "t", ..] => Ok(self.tick(opt(1, 1)?)),
+            ["continue" | "c"] => {
+                while !self.finished() {
+                    self.fill_queue();
+                    self.sim.tick(1);
+                }
+                Ok(self.status())
+            }
+            ["info"] => {
+                let stats = self.sim.stats();
+                let next = match self.program.insts.get(self.next) {
+                    Some(inst) => format!("next to queue line {}: {}", inst.line, inst.mnemonic),
+                    None => "program fully queued".to_string(),
+                };
+                Ok(format!(
+                    "cycle {}, {} queued, {} issued, {} retired; {next}",
+                    stats.cycle, stats.queued, stats.issued, stats.retired
+                ))
+            }
+            ["bank", ..] => {
+                let vbank = num(1)? as u32;
+                let (offset, len) = (opt(2, 0)? as usize, opt(3, DEFAULT_DUMP as u64)? as usize);
+                let bank = self
+                    .sim
+                    .read_bank(vbank)
+                    .ok_or_else(|| format!("bank {vbank} is not mapped"))?;
+                let bytes = bank
+                    .get(offset..offset + len)
+                    .ok_or_else(|| format!("[0x{offset:x}, 0x{:x}) is outside bank {vbank}", offset + len))?;
+                Ok(hex_dump(offset as u64, bytes))
+            }
+            ["dram", ..] => {
+                let addr = num(1)?;
+                let len = opt(2, DEFAULT_DUMP as u64)? as usize;
+                Ok(hex_dump(addr, &self.sim.npu().read_dram(addr, len)))
+            }
+            [other, ..] => Err(format!("unknown command: {other}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::npusim::NpuSimConfig;
+
+    #[test]
+    fn tick_lists_issues_stalls_and_retires() {
+        let program = Program::parse(
+            "mset bank=1\n\
+             mset bank=2\n\
+             mvin bank=1 addr=0x80001000 rows=1\n\
+             mvin bank=2 addr=0x80002000 rows=1\n",
+        )
+        .unwrap();
+        let sim = NpuSim::new(NpuSimConfig {
+            mem_size: 1 << 20,
+            units: 2,
+            ..NpuSimConfig::default()
+        });
+        let mut dbg = CycleDebugger::new(sim, program);
+
+        let out = dbg.command("tick").unwrap();
+        assert!(out.starts_with("cycle 0:\n  issue    #0 unit 0: mset bank=1"), "{out}");
+        assert!(out.ends_with("at cycle 1"), "{out}");
+
+        let out = dbg.command("tick 100").unwrap();
+        assert!(out.contains("stall    Hazard"), "{out}");
+        assert!(out.contains("retire   #3: mvin bank=2"), "{out}");
+        assert!(out.ends_with(&format!("program finished at cycle {}", dbg.sim().stats().cycle)));
+        assert_eq!(dbg.sim().stats().retired, 4);
+    }
+}
@@ -0,0 +1,432 @@
+//===- debugger.rs - Interactive program debugger --------------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// Steps a text program (program.rs) through an Npu one instruction at a time
+// and stops on breakpoints. Commands, one per line:
+//
+//   step [N]                     execute N instructions (default 1)
+//   continue                     run to the next breakpoint or the end
+//   break funct F                before an instruction with funct (or mnemonic)
+//                                F issues
+//   break retire N               after the Nth instruction since reset retires
+//   break bank V OFF LEN         after an instruction changes vbank V bytes
+//                                [OFF, OFF+LEN)
+//   break cycle N                after the instruction that reaches cycle N
+//   delete ID / breakpoints      remove one / list all
+//   watch dram ADDR LEN          after an instruction reads or writes DRAM
+//                                [ADDR, ADDR+LEN) by DMA (watch.rs)
+//   watch bank V OFF LEN         after an instruction changes vbank V bytes
+//                                [OFF, OFF+LEN), naming the first one
+//   unwatch ID / watchpoints     remove one / list all
+//   bank V [OFF [LEN]]           hex dump of a bank (default: first 64 bytes)
+//   dram ADDR [LEN]              hex dump of DRAM
+//   dump FILE V...               write vbanks V... to FILE for `bebop
+//                                bank-diff` (bankdump.rs)
+//   bmt                          bound entries of the bank mapping table
+//   info                         cycle, instruction count, next instruction
+//
+// Numbers are decimal or 0x hex.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use std::fmt::{self, Write as _};
+use std::path::Path;
+
+use crate::isa::base_isa;
+use crate::npu::Npu;
+use crate::program::{funct_of, parse_value, Program};
+use crate::watch::Watchpoint;
+
+/// Bytes `bank` and `dram` print when no length is given.
+const DEFAULT_DUMP: usize = 64;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Before an instruction with this funct issues.
+    Funct(u32),
+    /// After the instruction that brings `Npu::instruction_count` to this.
+    Retire(u64),
+    /// After an instruction that changes any byte of the range. Mapping the
+    /// bank in the first place does not count.
+    BankWrite { vbank: u32, offset: usize, len: usize },
+    /// After the instruction during which the cycle count reaches this.
+    Cycle(u64),
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Funct(funct) => write!(f, "funct {funct}"),
+            Breakpoint::Retire(n) => write!(f, "retire {n}"),
+            Breakpoint::BankWrite { vbank, offset, len } => {
+                write!(f, "bank {vbank} [0x{offset:x}, 0x{:x})", offset + len)
+            }
+            Breakpoint::Cycle(c) => write!(f, "cycle {c}"),
+        }
+    }
+}
+
+/// A program stepped through an Npu under breakpoint control.
+pub struct Debugger {
+    npu: Npu,
+    program: Program,
+    /// Index of the next instruction to execute.
+    next: usize,
+    /// (id, breakpoint); ids are never reused.
+    breakpoints: Vec<(usize, Breakpoint)>,
+    next_id: usize,
+    /// Instruction a funct breakpoint stopped before, so resuming issues it.
+    held: Option<usize>,
+}
+
+impl Debugger {
+    pub fn new(npu: Npu, program: Program) -> Self {
+        Self {
+            npu,
+            program,
+            next: 0,
+            breakpoints: Vec::new(),
+            next_id: 1,
+            held: None,
+        }
+    }
+
+    pub fn npu(&self) -> &Npu {
+        &self.npu
+    }
+
+    pub fn finished(&self) -> bool {
+        self.next >= self.program.insts.len()
+    }
+
+    pub fn add_breakpoint(&mut self, bp: Breakpoint) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.breakpoints.push((id, bp));
+        id
+    }
+
+    pub fn remove_breakpoint(&mut self, id: usize) -> bool {
+        let before = self.breakpoints.len();
+        self.breakpoints.retain(|(i, _)| *i != id);
+        self.breakpoints.len() != before
+    }
+
+    /// Execute up to `limit` instructions (all if `None`), stopping early at
+    /// a breakpoint. Returns a line saying where execution stopped.
+    pub fn run(&mut self, limit: Option<usize>) -> String {
+        let mut executed = 0;
+        loop {
+            if self.finished() {
+                return format!("program finished at cycle {}", self.npu.total_latency());
+            }
+            if limit.is_some_and(|l| executed >= l) {
+                return format!("stopped before {}", self.location(self.next));
+            }
+            if self.held.take() != Some(self.next) {
+                let funct = self.program.insts[self.next].funct;
+                let hit = self.breakpoints.iter().find(|(_, bp)| *bp == Breakpoint::Funct(funct));
+                if let Some((id, bp)) = hit {
+                    self.held = Some(self.next);
+                    return format!("breakpoint {id} ({bp}) before {}", self.location(self.next));
+                }
+            }
+            executed += 1;
+            let hit = self.exec_one();
+            let watched = self.npu.take_watch_hits();
+            if !watched.is_empty() {
+                let hits: Vec<String> = watched.iter().map(ToString::to_string).collect();
+                return format!("{}\nafter {}", hits.join("\n"), self.location(self.next - 1));
+            }
+            if let Some((id, bp)) = hit {
+                return format!("breakpoint {id} ({bp}) after {}", self.location(self.next - 1));
+            }
+        }
+    }
+
+    /// Execute the next instruction and return the first post-issue
+    /// breakpoint it hit.
+    fn exec_one(&mut self) -> Option<(usize, Breakpoint)> {
+        let watched: Vec<(usize, Breakpoint, Option<Vec<u8>>)> = self
+            .breakpoints
+            .iter()
+            .filter_map(|(id, bp)| match *bp {
+                Breakpoint::BankWrite { vbank, offset, len } => {
+                    Some((*id, bp.clone(), self.bank_range(vbank, offset, len)))
+                }
+                _ => None,
+            })
+            .collect();
+
+        let start = self.npu.total_latency();
+        let inst = &self.program.insts[self.next];
+        self.npu.exec(inst.funct, inst.xs1, inst.xs2, 0);
+        self.next += 1;
+        let end = self.npu.total_latency();
+
+        for (id, bp, before) in watched {
+            if let Breakpoint::BankWrite { vbank, offset, len } = bp {
+                if before.is_some() && self.bank_range(vbank, offset, len) != before {
+                    return Some((id, bp));
+                }
+            }
+        }
+        self.breakpoints
+            .iter()
+            .find(|(_, bp)| match *bp {
+                Breakpoint::Retire(n) => self.npu.instruction_count() == n,
+                Breakpoint::Cycle(c) => start < c && c <= end,
+                _ => false,
+            })
+            .cloned()
+    }
+
+    fn bank_range(&self, vbank: u32, offset: usize, len: usize) -> Option<Vec<u8>> {
+        self.npu.bank(vbank)?.get(offset..offset + len).map(<[u8]>::to_vec)
+    }
+
+    fn location(&self, index: usize) -> String {
+        let inst = &self.program.insts[index];
+        let label = inst.label.as_deref().map(|l| format!(" ({l})")).unwrap_or_default();
+        // Raw `inst` lines are shown decoded.
+        let text = match inst.mnemonic.as_str() {
+            "inst" => base_isa().disassemble(inst.funct, inst.xs1, inst.xs2),
+            m => m.to_string(),
+        };
+        format!("#{index} line {}{label}: {text}", inst.line)
+    }
+
+    /// Run one command line and return what to print.
+    pub fn command(&mut self, line: &str) -> Result<String, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let num = |i: usize| -> Result<u64, String> {
+            let w = words
+                .get(i)
+                .ok_or_else(|| format!("`{}` needs more operands", words[0]))?;
+            parse_value(w).ok_or_else(|| format!("not a number: {w}"))
+        };
+        let opt = |i: usize, default: u64| if words.len() > i { num(i) } else { Ok(default) };
+        match words.as_slice() {
+            [] => Ok(String::new()),
+            ["step" | "s", ..] => Ok(self.run(Some(opt(1, 1)? as usize))),
+            ["continue" | "c"] => Ok(self.run(None)),
+            ["break" | "b", kind, ..] => {
+                let bp = match *kind {
+                    "funct" => {
+                        let w = words.get(2).ok_or("`break funct` needs a funct or mnemonic")?;
+                        let funct = funct_of(w).or_else(|| parse_value(w).map(|v| v as u32));
+                        Breakpoint::Funct(funct.ok_or_else(|| format!("unknown instruction: {w}"))?)
+                    }
+                    "retire" => Breakpoint::Retire(num(2)?),
+                    "bank" => Breakpoint::BankWrite {
+                        vbank: num(2)? as u32,
+                        offset: num(3)? as usize,
+                        len: num(4)? as usize,
+                    },
+                    "cycle" => Breakpoint::Cycle(num(2)?),
+                    other => return Err(format!("unknown breakpoint kind: {other}")),
+                };
+                let text = bp.to_string();
+                Ok(format!("breakpoint {}: {text}", self.add_breakpoint(bp)))
+            }
+            ["delete" | "d", _] => {
+                let id = num(1)? as usize;
+                match self.remove_breakpoint(id) {
+                    true => Ok(format!("deleted breakpoint {id}")),
+                    false => Err(format!("no breakpoint {id}")),
+                }
+            }
+            ["watch" | "w", kind, ..] => {
+                let w = match *kind {
+                    "dram" => Watchpoint::Dram {
+                        addr: num(2)?,
+                        len: num(3)?,
+                    },
+                    "bank" => Watchpoint::Bank {
+                        vbank: num(2)? as u32,
+                        offset: num(3)? as usize,
+                        len: num(4)? as usize,
+                    },
+                    other => return Err(format!("unknown watchpoint kind: {other}")),
+                };
+                Ok(format!("watchpoint {}: {w}", self.npu.add_watchpoint(w)))
+            }
+            ["unwatch", _] => {
+                let id = num(1)? as usize;
+                match self.npu.remove_watchpoint(id) {
+                    true => Ok(format!("deleted watchpoint {id}")),
+                    false => Err(format!("no watchpoint {id}")),
+                }
+            }
+            ["watchpoints"] => Ok(self
+                .npu
+                .watchpoints()
+                .iter()
+                .map(|(id, w)| format!("{id:>3}  {w}\n"))
+                .collect::<String>()
+                .trim_end()
+                .to_string()),
+            ["breakpoints"] => Ok(self
+                .breakpoints
+                .iter()
+                .map(|(id, bp)| format!("{id:>3}  {bp}\n"))
+                .collect::<String>()
+                .trim_end()
+                .to_string()),
+            ["bank", ..] => {
+                let vbank = num(1)? as u32;
+                let (offset, len) = (opt(2, 0)? as usize, opt(3, DEFAULT_DUMP as u64)? as usize);
+                let bytes = self.npu.bank_range(vbank, offset, len)?;
+                Ok(hex_dump(offset as u64, bytes))
+            }
+            ["dram", ..] => {
+                let addr = num(1)?;
+                let len = opt(2, DEFAULT_DUMP as u64)? as usize;
+                Ok(hex_dump(addr, &self.npu.read_dram(addr, len)))
+            }
+            ["dump", path, vbanks @ ..] if !vbanks.is_empty() => {
+                let vbanks = vbanks
+                    .iter()
+                    .map(|w| {
+                        parse_value(w)
+                            .map(|v| v as u32)
+                            .ok_or_else(|| format!("not a number: {w}"))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.npu.dump_banks(&vbanks)?.save(Path::new(path))?;
+                Ok(format!("dumped {} bank(s) to {path}", vbanks.len()))
+            }
+            ["bmt"] => Ok(self
+                .npu
+                .bmt()
+                .iter()
+                .enumerate()
+                .filter_map(|(p, e)| e.map(|(v, g)| format!("pbank {p:>4} -> bank{v} group {g}\n")))
+                .collect::<String>()
+                .trim_end()
+                .to_string()),
+            ["info"] => {
+                let next = match self.finished() {
+                    true => "end of program".to_string(),
+                    false => self.location(self.next),
+                };
+                Ok(format!(
+                    "cycle {}, {} instructions retired, next: {next}",
+                    self.npu.total_latency(),
+                    self.npu.instruction_count()
+                ))
+            }
+            [other, ..] => Err(format!("unknown command: {other}")),
+        }
+    }
+}
+
+/// 16 bytes per line, each line prefixed with the address of its first byte.
+pub(crate) fn hex_dump(base: u64, bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for (i, chunk) in bytes.chunks(16).enumerate() {
+        let _ = write!(out, "{:#010x}:", base + 16 * i as u64);
+        for b in chunk {
+            let _ = write!(out, " {b:02x}");
+        }
+        out.push('\n');
+    }
+    out.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::DRAM_BASE;
+
+    #[test]
+    fn breakpoints_stop_on_funct_bank_writes_and_retire() {
+        let program = Program::parse(
+            "mset bank=1\n\
+             mset bank=2\n\
+             mvin bank=2 addr=0x80001000 rows=1\n\
+             mvin bank=1 addr=0x80001000 rows=1\n\
+             mvout bank=1 addr=0x80002000 rows=1\n",
+        )
+        .unwrap();
+        let mut npu = Npu::new(1 << 20);
+        npu.write_dram(DRAM_BASE + 0x1000, &[0xab; 16]);
+        let mut dbg = Debugger::new(npu, program);
+
+        assert_eq!(
+            dbg.command("break bank 1 0 16").unwrap(),
+            "breakpoint 1: bank 1 [0x0, 0x10)"
+        );
+        assert_eq!(dbg.command("break funct mvout").unwrap(), "breakpoint 2: funct 16");
+        dbg.command("break retire 2").unwrap();
+
+        assert_eq!(
+            dbg.command("c").unwrap(),
+            "breakpoint 3 (retire 2) after #1 line 2: mset"
+        );
+        // The mvin into bank 2 does not touch the watched range.
+        assert_eq!(
+            dbg.command("c").unwrap(),
+            "breakpoint 1 (bank 1 [0x0, 0x10)) after #3 line 4: mvin"
+        );
+        assert!(dbg.command("bank 1 0 16").unwrap().ends_with(&" ab".repeat(16)));
+        assert_eq!(
+            dbg.command("c").unwrap(),
+            "breakpoint 2 (funct 16) before #4 line 5: mvout"
+        );
+        // Resuming issues the instruction the funct breakpoint held.
+        assert!(dbg.command("s").unwrap().starts_with("program finished"));
+        assert_eq!(dbg.npu().read_dram(DRAM_BASE + 0x2000, 16), [0xab; 16]);
+
+        assert_eq!(dbg.command("delete 7").unwrap_err(), "no breakpoint 7");
+        assert_eq!(dbg.command("bank 9").unwrap_err(), "bank 9 is not mapped");
+    }
+
+    #[test]
+    fn watchpoints_name_the_instruction_that_touched_the_range() {
+        let program = Program::parse(
+            "mset bank=1\n\
+             mvin bank=1 addr=0x80001000 rows=2 stride=4\n\
+             mvout bank=1 addr=0x80002000 rows=2\n",
+        )
+        .unwrap();
+        let mut npu = Npu::new(1 << 20);
+        npu.write_dram(DRAM_BASE + 0x1040, &[7; 16]);
+        let mut dbg = Debugger::new(npu, program);
+
+        assert_eq!(
+            dbg.command("watch dram 0x80001040 16").unwrap(),
+            "watchpoint 1: dram [0x80001040, 0x80001050)"
+        );
+        dbg.command("watch bank 1 0x10 16").unwrap();
+        dbg.command("watch dram 0x80002010 4").unwrap();
+        // The second mvin row comes from 4 lines further on.
+        assert_eq!(
+            dbg.command("c").unwrap(),
+            "watchpoint 1 (dram [0x80001040, 0x80001050)): inst #2 mvin read 0x80001040 at cycle 3\n\
+             watchpoint 2 (bank 1 [0x10, 0x20)): inst #2 mvin wrote 0x10 at cycle 3\n\
+             after #1 line 2: mvin"
+        );
+        assert_eq!(
+            dbg.command("c").unwrap(),
+            "watchpoint 3 (dram [0x80002010, 0x80002014)): inst #3 mvout wrote 0x80002010 at cycle 5\n\
+             after #2 line 3: mvout"
+        );
+        assert_eq!(dbg.command("unwatch 2").unwrap(), "deleted watchpoint 2");
+        assert_eq!(dbg.command("watchpoints").unwrap().lines().count(), 2);
+        assert_eq!(dbg.command("unwatch 2").unwrap_err(), "no watchpoint 2");
+    }
+}
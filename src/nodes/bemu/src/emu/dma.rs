@@ -0,0 +1,257 @@
+//===- dma.rs - DMA alignment policy and statistics ------------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// The DMA engine moves 16-byte beats. A row whose DRAM address is not beat
+// aligned straddles two beats, so the hardware issues both and stitches the
+// row together: one extra beat per row. Row strides are whole beats, so only
+// the base address decides alignment.
+//
+// By default BEMU charges that split-beat penalty. With `MisalignedDma::Fault`
+// a misaligned transfer aborts the run instead, for workloads that must never
+// take the slow path.
+//
+// `qos_set` caps the rows per cycle available on the DRAM side or the bank
+// side to emulate co-running interference. A transfer that would move rows
+// faster than the tighter of the two caps stalls for the difference; the stall
+// is added to the cycle count after the instruction executes. DRAM row-buffer
+// timing (dram.rs) and the memory-side cache (cache.rs), when configured, are
+// charged through the same stall.
+//
+// `acc_cfg` sets the fp32 load and store paths of Gemmini's fp32
+// configuration: mvin multiplies the values it loads into an fp32 bank by
+// one scale, and mvout multiplies an fp32 accumulator by another and can
+// clamp negatives to zero on the way out.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::cache::Cache;
+use crate::dram::DramModel;
+
+pub const DMA_BEAT_BYTES: u64 = 16;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MisalignedDma {
+    #[default]
+    Penalty,
+    Fault,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DmaStats {
+    pub transfers: u64,
+    pub misaligned: u64,
+    /// Extra beats charged for misaligned rows.
+    pub penalty_cycles: u64,
+    /// Extra cycles spent waiting for QoS tokens.
+    pub throttle_cycles: u64,
+    /// DMA rows that found their DRAM row open / had to open it.
+    pub row_hits: u64,
+    pub row_misses: u64,
+    /// Extra cycles charged by the DRAM timing model.
+    pub dram_cycles: u64,
+}
+
+impl fmt::Display for DmaStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} transfers, {} misaligned (+{} cycles), QoS throttled +{} cycles, \
+             DRAM +{} cycles ({} row hits, {} misses)",
+            self.transfers,
+            self.misaligned,
+            self.penalty_cycles,
+            self.throttle_cycles,
+            self.dram_cycles,
+            self.row_hits,
+            self.row_misses
+        )
+    }
+}
+
+/// Cycles a transfer of `rows` rows starting at `addr` loses to split beats.
+#[inline]
+pub fn split_beat_penalty(addr: u64, rows: u64) -> u64 {
+    if addr.is_multiple_of(DMA_BEAT_BYTES) {
+        0
+    } else {
+        rows
+    }
+}
+
+/// Which side of a transfer a QoS cap applies to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QosPort {
+    Dram,
+    Bank,
+}
+
+/// At most `tokens` rows every `period` cycles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Throttle {
+    pub tokens: u64,
+    pub period: u64,
+}
+
+impl Throttle {
+    /// Cycles `rows` rows take under the cap beyond the unthrottled one per
+    /// cycle.
+    pub fn stall(self, rows: u64) -> u64 {
+        (rows * self.period).div_ceil(self.tokens).saturating_sub(rows)
+    }
+}
+
+/// Scales (as f32 bits) and activation of the fp32 mvin and mvout paths.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fp32Paths {
+    pub mvin_scale: u32,
+    pub mvout_scale: u32,
+    pub relu: bool,
+}
+
+impl Default for Fp32Paths {
+    fn default() -> Self {
+        Self {
+            mvin_scale: 1f32.to_bits(),
+            mvout_scale: 1f32.to_bits(),
+            relu: false,
+        }
+    }
+}
+
+impl Fp32Paths {
+    /// Scale the f32 elements mvin loads into an fp32 bank, in place.
+    pub(crate) fn load(&self, data: &mut [u8]) {
+        let scale = f32::from_bits(self.mvin_scale);
+        map_f32(data, |v| v * scale);
+    }
+
+    /// Scale, then rectify if asked, the f32 elements mvout reads out of an
+    /// fp32 accumulator, in place.
+    pub(crate) fn store(&self, data: &mut [u8]) {
+        let scale = f32::from_bits(self.mvout_scale);
+        map_f32(data, |v| match v * scale {
+            v if self.relu && v < 0.0 => 0.0,
+            v => v,
+        });
+    }
+}
+
+fn map_f32(data: &mut [u8], f: impl Fn(f32) -> f32) {
+    for word in data.chunks_exact_mut(4) {
+        let v = f(f32::from_le_bytes([word[0], word[1], word[2], word[3]]));
+        word.copy_from_slice(&v.to_le_bytes());
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Dma {
+    pub(crate) policy: MisalignedDma,
+    pub(crate) fp32: Fp32Paths,
+    pub(crate) stats: DmaStats,
+    pub(crate) dram_cap: Option<Throttle>,
+    pub(crate) bank_cap: Option<Throttle>,
+    pub(crate) dram: Option<DramModel>,
+    pub(crate) cache: Option<Cache>,
+    /// Stall of the executing instruction, not yet charged.
+    pub(crate) stall: u64,
+    /// DRAM rows (addr, len, write) the executing instruction touched,
+    /// while a recording or a watchpoint needs them.
+    pub(crate) log: Option<Vec<(u64, u64, bool)>>,
+}
+
+impl Dma {
+    /// Account one DMA instruction; aborts on misalignment under `Fault`.
+    /// `via_bank` says whether the rows also go through a bank port.
+    pub(crate) fn transfer(&mut self, op: &str, addr: u64, rows: u64, via_bank: bool) {
+        self.stats.transfers += 1;
+        if let Some(model) = &mut self.dram {
+            model.begin();
+        }
+        if let Some(cache) = &mut self.cache {
+            cache.begin();
+        }
+        let dram = self.dram_cap.map_or(0, |t| t.stall(rows));
+        let bank = self.bank_cap.filter(|_| via_bank).map_or(0, |t| t.stall(rows));
+        let stall = dram.max(bank);
+        self.stall += stall;
+        self.stats.throttle_cycles += stall;
+
+        let penalty = split_beat_penalty(addr, rows);
+        if penalty == 0 {
+            return;
+        }
+        if self.policy == MisalignedDma::Fault {
+            panic!("{op}: DRAM address 0x{addr:x} is not {DMA_BEAT_BYTES}-byte aligned");
+        }
+        self.stats.misaligned += 1;
+        self.stats.penalty_cycles += penalty;
+    }
+
+    /// Cap `port` for subsequent transfers; `None` removes the cap.
+    pub(crate) fn set_cap(&mut self, port: QosPort, cap: Option<Throttle>) {
+        match port {
+            QosPort::Dram => self.dram_cap = cap,
+            QosPort::Bank => self.bank_cap = cap,
+        }
+    }
+
+    /// Account one `len`-byte DRAM row of the current transfer; `write` says
+    /// whether it is stored to DRAM.
+    pub(crate) fn dram_access(&mut self, addr: u64, len: u64, write: bool) {
+        if let Some(log) = &mut self.log {
+            log.push((addr, len, write));
+        }
+        match &mut self.cache {
+            Some(cache) => {
+                let (cycles, fills) = cache.access(addr, len, write);
+                let line = cache.config().line_bytes;
+                self.stall += cycles;
+                for fill in fills {
+                    self.dram_row(fill, line);
+                }
+            }
+            None => self.dram_row(addr, len),
+        }
+    }
+
+    /// Charge the DRAM timing model for one access.
+    fn dram_row(&mut self, addr: u64, len: u64) {
+        let Some(model) = &mut self.dram else {
+            return;
+        };
+        let (cycles, hit) = model.access(addr, len);
+        if hit {
+            self.stats.row_hits += 1;
+        } else {
+            self.stats.row_misses += 1;
+        }
+        self.stats.dram_cycles += cycles;
+        self.stall += cycles;
+    }
+
+    /// Charge `cycles` of transfer time that is only known once the
+    /// instruction runs, such as the data beats of a descriptor list.
+    pub(crate) fn charge(&mut self, cycles: u64) {
+        self.stall += cycles;
+    }
+
+    pub(crate) fn take_stall(&mut self) -> u64 {
+        std::mem::take(&mut self.stall)
+    }
+}
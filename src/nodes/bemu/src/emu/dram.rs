@@ -0,0 +1,162 @@
+//===- dram.rs - DRAM row-buffer timing model ------------------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// Without a timing model DRAM answers every DMA row in the beat that asks
+// for it. With one, each DRAM bank keeps one open row:
+//
+//   - the first access of a transfer waits tCAS,
+//   - an access to a closed bank opens the row first (+tRCD),
+//   - an access to another row of an open bank closes it first (+tRP +tRCD),
+//   - hits to the open row stream behind the first access at no extra cost,
+//   - a row of N bytes takes ceil(N / bytes_per_cycle) cycles instead of one.
+//
+// Addresses interleave across banks every `row_bytes`. The extra cycles are
+// added to the instruction's latency the same way QoS stalls are.
+//
+// By default the DMA engine waits for each row before requesting the next.
+// With a `window` of W, it issues one request per cycle and keeps up to W in
+// flight, so row opens in different DRAM banks overlap:
+//
+//   - a request issues one cycle after the previous one, or when the oldest
+//     of the W in flight completes,
+//   - it starts once its DRAM bank has finished opening the previous row,
+//   - its data follows tCAS (first request only) and the row open, and the
+//     data bus carries one row at a time.
+//
+// The transfer is charged for the cycles by which its last completion exceeds
+// one cycle per row. A window of 1 gives the same cycles as the default.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// DRAM timing parameters, in accelerator cycles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DramTiming {
+    pub t_cas: u64,
+    pub t_rcd: u64,
+    pub t_rp: u64,
+    /// Bytes per DRAM row (page).
+    pub row_bytes: u64,
+    /// DRAM banks that can each hold a row open.
+    pub banks: u64,
+    /// Peak bandwidth; 0 means one DMA row per cycle.
+    #[serde(default)]
+    pub bytes_per_cycle: u64,
+    /// DMA requests in flight; 0 or 1 waits for each row in turn.
+    #[serde(default)]
+    pub window: u64,
+}
+
+impl DramTiming {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.row_bytes == 0 || self.banks == 0 {
+            return Err("dram timing: row_bytes and banks must be > 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DramModel {
+    pub(crate) timing: DramTiming,
+    pub(crate) open_rows: Vec<Option<u64>>,
+    first: bool,
+    /// Pipeline state of the current transfer, in cycles from its start.
+    issue: u64,
+    in_flight: VecDeque<u64>,
+    bank_ready: Vec<u64>,
+    bus_ready: u64,
+    done: u64,
+    rows: u64,
+    charged: u64,
+}
+
+impl DramModel {
+    pub fn new(timing: DramTiming) -> Self {
+        Self {
+            timing,
+            open_rows: vec![None; timing.banks as usize],
+            first: true,
+            issue: 0,
+            in_flight: VecDeque::new(),
+            bank_ready: vec![0; timing.banks as usize],
+            bus_ready: 0,
+            done: 0,
+            rows: 0,
+            charged: 0,
+        }
+    }
+
+    /// Close every row, as after power-up.
+    pub fn reset(&mut self) {
+        self.open_rows.fill(None);
+        self.begin();
+    }
+
+    /// The next access starts a new transfer.
+    pub(crate) fn begin(&mut self) {
+        self.first = true;
+        self.issue = 0;
+        self.in_flight.clear();
+        self.bank_ready.fill(0);
+        self.bus_ready = 0;
+        self.done = 0;
+        self.rows = 0;
+        self.charged = 0;
+    }
+
+    /// Extra cycles a `len`-byte row at `addr` costs, and whether it hit the
+    /// open row.
+    pub(crate) fn access(&mut self, addr: u64, len: u64) -> (u64, bool) {
+        let t = self.timing;
+        let page = addr / t.row_bytes;
+        let bank = (page % t.banks) as usize;
+        let row = page / t.banks;
+        let (open, hit) = match self.open_rows[bank] {
+            Some(open) if open == row => (0, true),
+            Some(_) => (t.t_rp + t.t_rcd, false),
+            None => (t.t_rcd, false),
+        };
+        self.open_rows[bank] = Some(row);
+        let cas = if std::mem::take(&mut self.first) { t.t_cas } else { 0 };
+        let beats = if t.bytes_per_cycle > 0 {
+            len.div_ceil(t.bytes_per_cycle).max(1)
+        } else {
+            1
+        };
+
+        let window = t.window.max(1) as usize;
+        let mut issue = self.issue;
+        if self.in_flight.len() == window {
+            issue = issue.max(self.in_flight.pop_front().unwrap_or(0));
+        }
+        let start = issue.max(self.bank_ready[bank]);
+        self.bank_ready[bank] = start + open;
+        let complete = (start + open + cas).max(self.bus_ready) + beats;
+        self.bus_ready = complete;
+        self.in_flight.push_back(complete);
+        self.issue = issue + 1;
+
+        self.done = self.done.max(complete);
+        self.rows += 1;
+        let cycles = self.done.saturating_sub(self.rows + self.charged);
+        self.charged += cycles;
+        (cycles, hit)
+    }
+}
@@ -0,0 +1,170 @@
+//===- energy.rs - Per-event energy estimate -------------------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// Prices the events perf.rs counts with a technology cost table, in
+// picojoules:
+//
+//   mac         one multiply-accumulate
+//   sram_read   one 16-byte bank line read
+//   sram_write  one 16-byte bank line written
+//   dram_read   one 16-byte DRAM beat read
+//   dram_write  one 16-byte DRAM beat written
+//   bus         one 16-byte beat crossing the DMA bus, either way
+//   static      leakage per cycle
+//
+// The defaults are rough 45 nm figures; replace them with numbers for the
+// process being studied. Dynamic energy is attributed to the instruction
+// that caused it; leakage only appears in the total.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::perf::PerfCounters;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct EnergyTable {
+    pub mac: f64,
+    pub sram_read: f64,
+    pub sram_write: f64,
+    pub dram_read: f64,
+    pub dram_write: f64,
+    pub bus: f64,
+    #[serde(rename = "static")]
+    pub leakage: f64,
+}
+
+impl Default for EnergyTable {
+    fn default() -> Self {
+        Self {
+            mac: 0.25,
+            sram_read: 5.0,
+            sram_write: 6.0,
+            dram_read: 2560.0,
+            dram_write: 2560.0,
+            bus: 16.0,
+            leakage: 0.0,
+        }
+    }
+}
+
+impl EnergyTable {
+    pub fn validate(&self) -> Result<(), String> {
+        let costs = [
+            ("mac", self.mac),
+            ("sram_read", self.sram_read),
+            ("sram_write", self.sram_write),
+            ("dram_read", self.dram_read),
+            ("dram_write", self.dram_write),
+            ("bus", self.bus),
+            ("static", self.leakage),
+        ];
+        match costs.iter().find(|(_, pj)| !(pj.is_finite() && *pj >= 0.0)) {
+            Some((name, pj)) => Err(format!("energy: {name} must be >= 0 pJ, got {pj}")),
+            None => Ok(()),
+        }
+    }
+
+    /// Dynamic energy of the events counted in `c`.
+    fn price(&self, c: Events) -> EnergyBreakdown {
+        let lines = |bytes: u64| bytes as f64 / 16.0;
+        EnergyBreakdown {
+            mac: self.mac * c.macs as f64,
+            sram: self.sram_read * lines(c.bank_read_bytes) + self.sram_write * lines(c.bank_write_bytes),
+            dram: self.dram_read * lines(c.dram_read_bytes) + self.dram_write * lines(c.dram_write_bytes),
+            bus: self.bus * lines(c.dram_read_bytes + c.dram_write_bytes),
+            leakage: 0.0,
+        }
+    }
+}
+
+/// Energy by event class, in picojoules.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct EnergyBreakdown {
+    pub mac: f64,
+    pub sram: f64,
+    pub dram: f64,
+    pub bus: f64,
+    #[serde(rename = "static")]
+    pub leakage: f64,
+}
+
+impl EnergyBreakdown {
+    pub fn total(&self) -> f64 {
+        self.mac + self.sram + self.dram + self.bus + self.leakage
+    }
+}
+
+/// The counters of `PerfCounters` that cost energy.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Events {
+    macs: u64,
+    bank_read_bytes: u64,
+    bank_write_bytes: u64,
+    dram_read_bytes: u64,
+    dram_write_bytes: u64,
+}
+
+impl Events {
+    pub(crate) fn of(c: &PerfCounters) -> Self {
+        Self {
+            macs: c.macs,
+            bank_read_bytes: c.bank_read_bytes,
+            bank_write_bytes: c.bank_write_bytes,
+            dram_read_bytes: c.dram_read_bytes,
+            dram_write_bytes: c.dram_write_bytes,
+        }
+    }
+}
+
+/// The cost table and the dynamic energy of each funct so far.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Energy {
+    pub(crate) table: EnergyTable,
+    pub(crate) per_funct: BTreeMap<u32, f64>,
+}
+
+impl Energy {
+    pub(crate) fn new(table: EnergyTable) -> Self {
+        Self {
+            table,
+            per_funct: BTreeMap::new(),
+        }
+    }
+
+    /// Charge `funct` the events counted since `before`.
+    pub(crate) fn charge(&mut self, funct: u32, before: Events, after: &PerfCounters) {
+        let after = Events::of(after);
+        let delta = Events {
+            macs: after.macs - before.macs,
+            bank_read_bytes: after.bank_read_bytes - before.bank_read_bytes,
+            bank_write_bytes: after.bank_write_bytes - before.bank_write_bytes,
+            dram_read_bytes: after.dram_read_bytes - before.dram_read_bytes,
+            dram_write_bytes: after.dram_write_bytes - before.dram_write_bytes,
+        };
+        *self.per_funct.entry(funct).or_default() += self.table.price(delta).total();
+    }
+
+    /// Energy of the whole run so far.
+    pub(crate) fn breakdown(&self, c: &PerfCounters, cycles: u64) -> EnergyBreakdown {
+        EnergyBreakdown {
+            leakage: self.table.leakage * cycles as f64,
+            ..self.table.price(Events::of(c))
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//===- error.rs - Typed errors for bank and DMA accesses -------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// An instruction that names a bad operand aborts the model, as the hardware
+// would. Hosts that drive the model through `Npu::exec_rocc` or the bank
+// accessors get these errors instead, before anything has run, so they can
+// tell an unmapped bank from a misaligned transfer without parsing a
+// message.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use std::fmt;
+
+use crate::dma::DMA_BEAT_BYTES;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NpuError {
+    /// The vbank is not allocated, or not mapped to a physical bank.
+    UnmappedBank { vbank: u32 },
+    /// Bytes [offset, offset + len) run past the end of a `size`-byte bank.
+    OutOfBounds {
+        vbank: u32,
+        offset: usize,
+        len: usize,
+        size: usize,
+    },
+    /// A DMA address off the beat boundary under `MisalignedDma::Fault`.
+    Misaligned { addr: u64 },
+    /// `funct` writes a bank it also reads, which it does not support.
+    BankConflict { vbank: u32, funct: u32 },
+    /// The issuing tenant does not own the vbank or a physical bank behind it.
+    CrossTenant { vbank: u32, tenant: String },
+    /// Not a RoCC custom instruction word.
+    InvalidInsn { insn: u32 },
+    /// No built-in instruction or registered extension implements `funct`.
+    UnknownFunct { funct: u32 },
+}
+
+impl fmt::Display for NpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NpuError::UnmappedBank { vbank } => write!(f, "bank {vbank} is not mapped"),
+            NpuError::OutOfBounds {
+                vbank,
+                offset,
+                len,
+                size,
+            } => write!(
+                f,
+                "[0x{offset:x}, 0x{:x}) is outside bank {vbank} ({size} bytes)",
+                offset.saturating_add(*len)
+            ),
+            NpuError::Misaligned { addr } => {
+                write!(f, "DRAM address 0x{addr:x} is not {DMA_BEAT_BYTES}-byte aligned")
+            }
+            NpuError::BankConflict { vbank, funct } => {
+                write!(f, "funct {funct} writes bank {vbank}, which it also reads")
+            }
+            NpuError::CrossTenant { vbank, tenant } => {
+                write!(f, "bank {vbank} is outside tenant {tenant}'s partition")
+            }
+            NpuError::InvalidInsn { insn } => write!(f, "not a RoCC custom instruction: 0x{insn:08x}"),
+            NpuError::UnknownFunct { funct } => write!(f, "unknown funct7: {funct}"),
+        }
+    }
+}
+
+impl std::error::Error for NpuError {}
+
+impl From<NpuError> for String {
+    fn from(e: NpuError) -> Self {
+        e.to_string()
+    }
+}
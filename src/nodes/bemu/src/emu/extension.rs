@@ -0,0 +1,137 @@
+//===- extension.rs - Out-of-tree accelerator units ------------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// The built-in instruction set is fixed when bemu is compiled (see
+// BEBOP_BEMU_CHIP_INST). An `Extension` adds functs at run time instead, so a
+// crate that depends on bemu can model its own unit next to the Buckyball
+// instructions without patching this crate:
+//
+//   npu.register_extension(Box::new(MyUnit::new()))?;
+//
+// Npu hands functs no built-in instruction claims to the extension that
+// registered them. The extension gets the whole Npu, so it reads and writes
+// DRAM and banks through the same public accessors a host tool would.
+// Latency, instruction counts and per-funct perf counters are charged as for
+// built-ins; coverage, bank traces and checkpoints do not see extension
+// state. NpuSim treats extension functs as barriers.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use crate::npu::Npu;
+
+pub trait Extension: Send {
+    /// Short name for reports, e.g. "softmax".
+    fn name(&self) -> &str;
+
+    /// Functs this extension executes. They must not overlap the built-in
+    /// instructions or another registered extension.
+    fn functs(&self) -> Vec<u32>;
+
+    /// Execute one instruction and return the value written to rd.
+    fn execute(&mut self, npu: &mut Npu, funct: u32, xs1: u64, xs2: u64) -> u64;
+
+    /// Cycles from issue to completion.
+    fn latency(&self, _funct: u32, _xs1: u64, _xs2: u64) -> u64 {
+        1
+    }
+
+    /// Drop internal state; called from [`Npu::reset`].
+    fn reset(&mut self) {}
+
+    /// Named counters for reports.
+    fn stats(&self) -> Vec<(String, u64)> {
+        Vec::new()
+    }
+}
+
+/// Registered extensions, searched by funct.
+#[derive(Default)]
+pub(crate) struct Extensions {
+    units: Vec<Box<dyn Extension>>,
+}
+
+impl Extensions {
+    pub(crate) fn register(&mut self, ext: Box<dyn Extension>) -> Result<(), String> {
+        for funct in ext.functs() {
+            if funct >= 128 {
+                return Err(format!(
+                    "extension {}: funct {funct} does not fit in funct7",
+                    ext.name()
+                ));
+            }
+            if crate::inst::decode::FUNCTS.contains(&funct) {
+                return Err(format!(
+                    "extension {}: funct {funct} is a built-in instruction",
+                    ext.name()
+                ));
+            }
+            if let Some(other) = self.find(funct) {
+                return Err(format!(
+                    "extension {}: funct {funct} is already taken by {}",
+                    ext.name(),
+                    self.units[other].name()
+                ));
+            }
+        }
+        self.units.push(ext);
+        Ok(())
+    }
+
+    /// Index of the extension that executes `funct`.
+    pub(crate) fn find(&self, funct: u32) -> Option<usize> {
+        self.units.iter().position(|u| u.functs().contains(&funct))
+    }
+
+    pub(crate) fn get(&self, i: usize) -> &dyn Extension {
+        self.units[i].as_ref()
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &dyn Extension> {
+        self.units.iter().map(|u| u.as_ref())
+    }
+
+    pub(crate) fn reset(&mut self) {
+        for u in &mut self.units {
+            u.reset();
+        }
+    }
+
+    /// Take extension `i` out while it runs against the Npu that owns it.
+    pub(crate) fn take(&mut self, i: usize) -> Box<dyn Extension> {
+        std::mem::replace(&mut self.units[i], Box::new(Vacant))
+    }
+
+    pub(crate) fn put_back(&mut self, i: usize, ext: Box<dyn Extension>) {
+        self.units[i] = ext;
+    }
+}
+
+/// Placeholder for an extension that is executing.
+struct Vacant;
+
+impl Extension for Vacant {
+    fn name(&self) -> &str {
+        "(executing)"
+    }
+
+    fn functs(&self) -> Vec<u32> {
+        Vec::new()
+    }
+
+    fn execute(&mut self, _npu: &mut Npu, funct: u32, _xs1: u64, _xs2: u64) -> u64 {
+        panic!("extension re-entered with funct {funct}")
+    }
+}
@@ -0,0 +1,459 @@
+//===- fault.rs - Bit-flip fault injection in banks and DRAM ---------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// Flips bits for resilience studies, at two places:
+//
+//   bank SRAM   an upset flips a stored bit. It stays until an instruction
+//               reads the bank (the check below) or the line is rewritten.
+//   DRAM reads  a mvin beat arrives with a bit flipped; DRAM itself keeps the
+//               right value.
+//
+// Upsets strike at `bank_rate` per million cycles anywhere in the physical
+// banks, and each 16-byte mvin beat is hit with probability `dram_rate`.
+// `[[target]]` entries place single flips at a cycle instead. Faults apply at
+// instruction boundaries, so one due mid-instruction lands before the next.
+//
+// Every 16-byte line carries the configured protection code, checked when the
+// line is read:
+//
+//   none    every flip goes unnoticed
+//   parity  an odd number of flipped bits is detected
+//   secded  one flip is corrected, two are detected, more go unnoticed
+//
+// Corrected flips are repaired in place. Detected and unnoticed ones keep the
+// wrong data, so the golden check (golden.rs) can show what they broke.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::fill::Fill;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protection {
+    #[default]
+    None,
+    Parity,
+    Secded,
+}
+
+/// One bit flip at a fixed cycle: in physical bank `bank` at byte `offset`,
+/// or in the mvin beat that reads DRAM address `addr`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FaultTarget {
+    pub cycle: u64,
+    #[serde(default)]
+    pub bank: Option<usize>,
+    #[serde(default)]
+    pub offset: usize,
+    #[serde(default)]
+    pub addr: Option<u64>,
+    /// Bit within the byte, 0..8.
+    #[serde(default)]
+    pub bit: u8,
+}
+
+/// A `--faults` file:
+///
+/// ```toml
+/// seed = 1
+/// bank_rate = 2.0      # upsets per million cycles
+/// dram_rate = 1e-4     # per 16-byte mvin beat
+/// protection = "secded"
+///
+/// [[target]]
+/// cycle = 100
+/// bank = 3
+/// offset = 0x40
+/// bit = 5
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct FaultConfig {
+    pub seed: u64,
+    pub bank_rate: f64,
+    pub dram_rate: f64,
+    pub protection: Protection,
+    #[serde(rename = "target")]
+    pub targets: Vec<FaultTarget>,
+}
+
+impl FaultConfig {
+    pub fn validate(&self, num_banks: usize, bank_bytes: usize) -> Result<(), String> {
+        if !(self.bank_rate >= 0.0 && self.bank_rate.is_finite()) {
+            return Err(format!("faults: bank_rate must be >= 0, got {}", self.bank_rate));
+        }
+        if !(0.0..=1.0).contains(&self.dram_rate) {
+            return Err(format!("faults: dram_rate must be in 0..=1, got {}", self.dram_rate));
+        }
+        for t in &self.targets {
+            match (t.bank, t.addr) {
+                (Some(b), None) if b >= num_banks => {
+                    return Err(format!("faults: bank {b} does not exist, there are {num_banks}"));
+                }
+                (Some(_), None) if t.offset >= bank_bytes => {
+                    return Err(format!(
+                        "faults: offset 0x{:x} is past the {bank_bytes}-byte bank",
+                        t.offset
+                    ));
+                }
+                (Some(_), None) | (None, Some(_)) => {}
+                _ => return Err(format!("faults: target at cycle {} needs one of bank or addr", t.cycle)),
+            }
+            if t.bit >= 8 {
+                return Err(format!("faults: bit must be 0..8, got {}", t.bit));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FaultOutcome {
+    Corrected,
+    Detected,
+    Undetected,
+}
+
+impl FaultOutcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FaultOutcome::Corrected => "corrected",
+            FaultOutcome::Detected => "detected",
+            FaultOutcome::Undetected => "undetected",
+        }
+    }
+}
+
+/// A line whose flipped bits were checked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FaultEvent {
+    /// Cycle of the check.
+    pub cycle: u64,
+    /// e.g. "pbank3+0x40" or "DRAM[0x80001000]".
+    pub location: String,
+    pub bits: u32,
+    pub outcome: FaultOutcome,
+}
+
+impl fmt::Display for FaultEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "cycle {}: {} bit(s) flipped at {}, {}",
+            self.cycle,
+            self.bits,
+            self.location,
+            self.outcome.as_str()
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FaultStats {
+    /// Bits flipped.
+    pub injected: u64,
+    pub corrected: u64,
+    pub detected: u64,
+    pub undetected: u64,
+    /// Bank lines rewritten before their flips were read.
+    pub masked: u64,
+}
+
+impl fmt::Display for FaultStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} bits flipped; lines read: {} corrected, {} detected, {} undetected; {} overwritten",
+            self.injected, self.corrected, self.detected, self.undetected, self.masked
+        )
+    }
+}
+
+/// Flipped bits of one bank line, and what it held right after.
+struct Upset {
+    mask: [u8; 16],
+    data: [u8; 16],
+}
+
+pub(crate) struct Faults {
+    config: FaultConfig,
+    rng: Fill,
+    /// Cycle of the next random bank upset.
+    next_upset: f64,
+    /// Bank targets not yet applied, latest first.
+    bank_targets: Vec<FaultTarget>,
+    /// DRAM targets whose beat has not been read yet.
+    dram_targets: Vec<FaultTarget>,
+    upsets: BTreeMap<(usize, usize), Upset>,
+    stats: FaultStats,
+    events: Vec<FaultEvent>,
+}
+
+impl Faults {
+    pub(crate) fn new(config: FaultConfig) -> Self {
+        let mut faults = Self {
+            rng: Fill::random(config.seed),
+            config,
+            next_upset: 0.0,
+            bank_targets: Vec::new(),
+            dram_targets: Vec::new(),
+            upsets: BTreeMap::new(),
+            stats: FaultStats::default(),
+            events: Vec::new(),
+        };
+        faults.reset();
+        faults
+    }
+
+    pub(crate) fn stats(&self) -> FaultStats {
+        self.stats
+    }
+
+    pub(crate) fn events(&self) -> &[FaultEvent] {
+        &self.events
+    }
+
+    /// Forget flips and restart the random stream and the targets.
+    pub(crate) fn reset(&mut self) {
+        self.rng.rewind();
+        let (mut bank, dram): (Vec<_>, Vec<_>) = self.config.targets.iter().copied().partition(|t| t.bank.is_some());
+        bank.sort_by_key(|t| std::cmp::Reverse(t.cycle));
+        (self.bank_targets, self.dram_targets) = (bank, dram);
+        self.upsets.clear();
+        self.stats = FaultStats::default();
+        self.events.clear();
+        self.next_upset = self.interval();
+    }
+
+    fn uniform(&mut self) -> f64 {
+        let mut x = [0u8; 8];
+        self.rng.fill(&mut x);
+        (u64::from_le_bytes(x) >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        ((self.uniform() * n as f64) as usize).min(n - 1)
+    }
+
+    /// Cycles to the next random upset; never when `bank_rate` is 0.
+    fn interval(&mut self) -> f64 {
+        if self.config.bank_rate == 0.0 {
+            return f64::INFINITY;
+        }
+        -(1.0 - self.uniform()).ln() * 1e6 / self.config.bank_rate
+    }
+
+    fn flip(&mut self, banks: &mut [Vec<u8>], p: usize, offset: usize, bit: u8) {
+        let line = offset / 16;
+        let at = line * 16;
+        let upset = self.upsets.entry((p, line)).or_insert_with(|| Upset {
+            mask: [0; 16],
+            data: [0; 16],
+        });
+        if upset.mask != [0; 16] && banks[p][at..at + 16] != upset.data {
+            // The earlier flips were overwritten.
+            upset.mask = [0; 16];
+            self.stats.masked += 1;
+        }
+        banks[p][offset] ^= 1 << bit;
+        upset.mask[offset % 16] ^= 1 << bit;
+        upset.data.copy_from_slice(&banks[p][at..at + 16]);
+        self.stats.injected += 1;
+    }
+
+    /// Apply the bank upsets due by `cycle`. Returns the banks hit.
+    pub(crate) fn strike(&mut self, banks: &mut [Vec<u8>], cycle: u64) -> Vec<usize> {
+        let mut hit = Vec::new();
+        while self.bank_targets.last().is_some_and(|t| t.cycle <= cycle) {
+            let t = self.bank_targets.pop().expect("checked above");
+            let p = t.bank.expect("partitioned on bank");
+            // The banks may have been resized since the target was checked.
+            if banks.get(p).is_some_and(|b| t.offset < b.len()) {
+                self.flip(banks, p, t.offset, t.bit);
+                hit.push(p);
+            }
+        }
+        while self.next_upset <= cycle as f64 && !banks.is_empty() {
+            let p = self.below(banks.len());
+            let offset = self.below(banks[p].len());
+            let bit = self.below(8) as u8;
+            self.flip(banks, p, offset, bit);
+            hit.push(p);
+            self.next_upset += self.interval();
+        }
+        hit
+    }
+
+    fn classify(&self, bits: u32) -> FaultOutcome {
+        match (self.config.protection, bits) {
+            (Protection::Parity, b) if b % 2 == 1 => FaultOutcome::Detected,
+            (Protection::Secded, 1) => FaultOutcome::Corrected,
+            (Protection::Secded, 2) => FaultOutcome::Detected,
+            _ => FaultOutcome::Undetected,
+        }
+    }
+
+    fn count(&mut self, cycle: u64, location: String, bits: u32, outcome: FaultOutcome) {
+        match outcome {
+            FaultOutcome::Corrected => self.stats.corrected += 1,
+            FaultOutcome::Detected => self.stats.detected += 1,
+            FaultOutcome::Undetected => self.stats.undetected += 1,
+        }
+        self.events.push(FaultEvent {
+            cycle,
+            location,
+            bits,
+            outcome,
+        });
+    }
+
+    /// Check the flipped lines of physical bank `p`, which is about to be
+    /// read, repairing the ones the code corrects.
+    pub(crate) fn check(&mut self, banks: &mut [Vec<u8>], p: usize, cycle: u64) {
+        let lines: Vec<usize> = self
+            .upsets
+            .range((p, 0)..=(p, usize::MAX))
+            .map(|(&(_, l), _)| l)
+            .collect();
+        for line in lines {
+            let upset = self.upsets.remove(&(p, line)).expect("listed above");
+            let at = line * 16;
+            if banks[p][at..at + 16] != upset.data {
+                self.stats.masked += 1;
+                continue;
+            }
+            let bits = upset.mask.iter().map(|b| b.count_ones()).sum();
+            if bits == 0 {
+                continue;
+            }
+            let outcome = self.classify(bits);
+            if outcome == FaultOutcome::Corrected {
+                for (b, m) in banks[p][at..at + 16].iter_mut().zip(upset.mask) {
+                    *b ^= m;
+                }
+            }
+            self.count(cycle, format!("pbank{p}+0x{at:x}"), bits, outcome);
+        }
+    }
+
+    /// Corrupt the beats of a mvin that just moved `lines` (DRAM address,
+    /// bank offset) into physical bank `p`.
+    pub(crate) fn mvin(&mut self, banks: &mut [Vec<u8>], p: usize, lines: &[(u64, usize)], cycle: u64) {
+        for &(addr, off) in lines {
+            let mut flips = Vec::new();
+            while let Some(i) = self
+                .dram_targets
+                .iter()
+                .position(|t| t.cycle <= cycle && t.addr.is_some_and(|a| a / 16 == addr / 16))
+            {
+                let t = self.dram_targets.remove(i);
+                flips.push((t.addr.expect("matched above") as usize % 16, t.bit));
+            }
+            if self.config.dram_rate > 0.0 && self.uniform() < self.config.dram_rate {
+                flips.push((self.below(16), self.below(8) as u8));
+            }
+            if flips.is_empty() {
+                continue;
+            }
+            let mut mask = [0u8; 16];
+            for (byte, bit) in flips {
+                mask[byte] ^= 1 << bit;
+            }
+            let bits: u32 = mask.iter().map(|b| b.count_ones()).sum();
+            if bits == 0 {
+                continue;
+            }
+            self.stats.injected += bits as u64;
+            let outcome = self.classify(bits);
+            if outcome != FaultOutcome::Corrected {
+                for (b, m) in banks[p][off..off + 16].iter_mut().zip(mask) {
+                    *b ^= m;
+                }
+            }
+            self.count(cycle, format!("DRAM[0x{addr:x}]"), bits, outcome);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::DRAM_BASE;
+    use crate::npu::Npu;
+
+    /// mvin two rows of 0x11 into bank1 and mvout them to DRAM_BASE + 0x100.
+    fn run(protection: Protection, targets: Vec<FaultTarget>) -> Npu {
+        let mut npu = Npu::new(1 << 20);
+        npu.enable_golden_check();
+        let config = FaultConfig {
+            protection,
+            targets,
+            ..FaultConfig::default()
+        };
+        npu.set_faults(Some(config)).unwrap();
+        npu.write_dram(DRAM_BASE, &[0x11; 32]);
+        npu.exec(32, 1, (1 << 5) | (1 << 10), 0);
+        npu.exec(33, 1 | (2 << 30), DRAM_BASE | (1 << 39), 0);
+        npu.exec(16, 1 | (2 << 30), (DRAM_BASE + 0x100) | (1 << 39), 0);
+        npu
+    }
+
+    fn dram(cycle: u64, addr: u64, bit: u8) -> FaultTarget {
+        FaultTarget {
+            cycle,
+            bank: None,
+            offset: 0,
+            addr: Some(addr),
+            bit,
+        }
+    }
+
+    #[test]
+    fn secded_corrects_one_flip_and_detects_two() {
+        let npu = run(Protection::Secded, vec![dram(0, DRAM_BASE + 3, 0)]);
+        assert_eq!(npu.fault_events()[0].outcome, FaultOutcome::Corrected);
+        assert_eq!(npu.read_dram(DRAM_BASE + 0x100, 32), [0x11; 32]);
+
+        let npu = run(
+            Protection::Secded,
+            vec![dram(0, DRAM_BASE + 0x13, 0), dram(0, DRAM_BASE + 0x1f, 4)],
+        );
+        assert_eq!(npu.fault_events()[0].outcome, FaultOutcome::Detected);
+        assert_eq!(npu.fault_events()[0].location, "DRAM[0x80000010]");
+        assert_eq!(npu.golden_mismatches().len(), 1, "the mvin check sees the bad beat");
+    }
+
+    #[test]
+    fn parity_detects_a_bank_upset_when_the_bank_is_read() {
+        let upset = FaultTarget {
+            cycle: 2,
+            bank: Some(0),
+            offset: 0x10,
+            addr: None,
+            bit: 7,
+        };
+        let npu = run(Protection::Parity, vec![upset]);
+        let stats = npu.fault_stats().unwrap();
+        assert_eq!((stats.injected, stats.detected), (1, 1));
+        assert_eq!(npu.fault_events()[0].location, "pbank0+0x10");
+        assert_eq!(npu.read_dram(DRAM_BASE + 0x110, 1), [0x91]);
+    }
+}
@@ -0,0 +1,78 @@
+//===- fill.rs - Initial contents of DRAM and banks ------------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// Real SRAM and DRAM come up holding garbage, but BEMU zeroes them, so a
+// workload that reads a bank before writing it passes here and fails on
+// silicon. With a seed, DRAM, banks at reset and banks at mset alloc are
+// filled from a splitmix64 stream instead. The stream restarts on reset so a
+// seed reproduces the same contents on every run.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+#[derive(Clone, Debug, Default)]
+pub struct Fill {
+    seed: Option<u64>,
+    pub(crate) state: u64,
+}
+
+impl Fill {
+    pub fn random(seed: u64) -> Self {
+        Self {
+            seed: Some(seed),
+            state: seed,
+        }
+    }
+
+    /// A stream from `seed` that has already advanced to `state`.
+    pub fn resume(seed: u64, state: u64) -> Self {
+        Self {
+            seed: Some(seed),
+            state,
+        }
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// Restart the stream from the seed.
+    pub fn rewind(&mut self) {
+        self.state = self.seed.unwrap_or(0);
+    }
+
+    /// Overwrite `buf` with zeros, or with the next bytes of the stream.
+    pub fn fill(&mut self, buf: &mut [u8]) {
+        if self.seed.is_none() {
+            buf.fill(0);
+            return;
+        }
+        let mut chunks = buf.chunks_exact_mut(8);
+        for chunk in &mut chunks {
+            chunk.copy_from_slice(&self.next().to_le_bytes());
+        }
+        let tail = chunks.into_remainder();
+        let len = tail.len();
+        tail.copy_from_slice(&self.next().to_le_bytes()[..len]);
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
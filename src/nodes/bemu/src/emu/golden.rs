@@ -0,0 +1,566 @@
+//===- golden.rs - Golden-model co-simulation checker ----------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// Checks the data instructions move and compute against bebop_golden while
+// the model runs, and records each disagreement with the instruction that
+// made it:
+//
+//   mvin    every bank row equals the DRAM row it was loaded from
+//   matmul  C equals the golden matmul of the A and B the bank held before
+//   conv    likewise with the golden conv2d
+//   mvout   every DRAM row written equals the golden result the bank should
+//           hold, or the bank itself when no matmul or conv produced it
+//
+// A golden result stays attached to its bank only while the bank is
+// untouched; any other write (a later mvin, bank_mut, a remap) changes the
+// bank hash and drops it. Only single-group banks are checked, mvin into
+// accumulator banks is not, and matmuls with i32 A or B are skipped because
+// f64 cannot hold their sums exactly.
+//
+// A matmul on float banks (numfmt.rs) is checked against the same f32 sum
+// in K order, rounded into C's format. Such a matmul, and an mcopy into or
+// out of a float bank, also adds to a per-format FormatError: how far the
+// rounded values landed from the f64 matmul or the source element.
+//
+// bfp8 is not checked byte for byte. A matmul on bfp8 banks and an
+// mvin_bfp instead add to the bfp8 FormatError: how far the values they
+// left in the bank are from an f64 matmul of the operands, or from the fp32
+// values loaded.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use bebop_bank_hash::bank_hash;
+use bebop_golden::{conv2d, matmul_acc, Conv2dParams};
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::bank::{BankConfig, ElemWidth};
+use crate::inst::decode::{rs1_b0, rs1_b1, rs1_b2, rs1_iter, tile_lines, xs2_mem_stride, INSTRUCTIONS};
+use crate::inst::f16_mvout::Mvout;
+use crate::inst::f33_mvin::Mvin;
+use crate::inst::f37_mcopy::Mcopy;
+use crate::inst::f41_mvin_bfp::MvinBfp;
+use crate::inst::f48_matmul::Matmul;
+use crate::inst::f49_conv::{Conv, ConvShape};
+use crate::inst::instruction::Instruction;
+use crate::npu::Npu;
+use crate::numfmt::{Bfp8, FormatError, NumFormat};
+
+/// One executed instruction: its position in the run, funct and pc.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Provenance {
+    pub id: u64,
+    pub funct: u32,
+    pub pc: u64,
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = INSTRUCTIONS
+            .iter()
+            .find(|(funct, _)| *funct == self.funct)
+            .map_or("?", |(_, n)| n);
+        write!(f, "{name} #{} (pc 0x{:x})", self.id, self.pc)
+    }
+}
+
+/// An instruction whose result disagreed with the golden model.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GoldenMismatch {
+    pub at: Provenance,
+    /// First differing byte, e.g. "bank3+0x40" or "DRAM[0x80001000]".
+    pub location: String,
+    pub got: u8,
+    pub want: u8,
+    /// Differing bytes in the whole result.
+    pub bytes: usize,
+    /// The matmul or conv whose golden result an mvout was checked against.
+    pub producer: Option<Provenance>,
+}
+
+impl fmt::Display for GoldenMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} = 0x{:02x}, golden 0x{:02x} ({} bytes differ)",
+            self.at, self.location, self.got, self.want, self.bytes
+        )?;
+        if let Some(p) = self.producer {
+            write!(f, "; data computed by {p}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Golden contents of a physical bank from offset 0.
+#[derive(Clone)]
+struct Expected {
+    bytes: Vec<u8>,
+    producer: Provenance,
+    /// Bank hash right after the producer ran.
+    hash: u64,
+}
+
+#[derive(Default)]
+pub(crate) struct Golden {
+    /// pbank and golden result of the matmul or conv about to run.
+    pending: Option<(usize, Vec<u8>)>,
+    /// vbank and f64 result of the bfp8 matmul about to run.
+    pending_bfp: Option<(u64, Vec<f64>)>,
+    expected: BTreeMap<usize, Expected>,
+    mismatches: Vec<GoldenMismatch>,
+    format_errors: BTreeMap<&'static str, FormatError>,
+}
+
+/// Physical bank and config of `vbank` if it is a mapped single-group bank.
+fn operand(npu: &Npu, vbank: u64) -> Option<(usize, BankConfig)> {
+    let cfg = *npu.bank_cfgs.get(vbank as usize)?;
+    if !cfg.allocated || cfg.cols > 1 {
+        return None;
+    }
+    Some((npu.bank_map.resolve(vbank as u32)?, cfg))
+}
+
+fn load(bank: &[u8], width: ElemWidth, len: usize) -> Vec<f64> {
+    (0..len).map(|i| width.load(bank, i) as f64).collect()
+}
+
+fn store(width: ElemWidth, values: &[f64]) -> Vec<u8> {
+    let mut bytes = vec![0; values.len() * width.bytes()];
+    for (i, &v) in values.iter().enumerate() {
+        width.store(&mut bytes, i, v as i64);
+    }
+    bytes
+}
+
+impl Golden {
+    pub(crate) fn mismatches(&self) -> &[GoldenMismatch] {
+        &self.mismatches
+    }
+
+    pub(crate) fn format_errors(&self) -> &BTreeMap<&'static str, FormatError> {
+        &self.format_errors
+    }
+
+    pub(crate) fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Physical banks whose golden result is still attached.
+    pub(crate) fn intact(&self, banks: &[Vec<u8>]) -> Vec<usize> {
+        self.expected
+            .iter()
+            .filter(|(&p, e)| e.hash == bank_hash(&banks[p]))
+            .map(|(&p, _)| p)
+            .collect()
+    }
+
+    /// Keep the golden result of `p` attached across a fault injected into
+    /// it, so mvout reports what the fault broke.
+    pub(crate) fn rebase(&mut self, banks: &[Vec<u8>], p: usize) {
+        if let Some(e) = self.expected.get_mut(&p) {
+            e.hash = bank_hash(&banks[p]);
+        }
+    }
+
+    /// Attach the golden result of `from` to `to` as well.
+    pub(crate) fn copy(&mut self, from: usize, to: usize) {
+        match self.expected.get(&from).cloned() {
+            Some(e) => self.expected.insert(to, e),
+            None => self.expected.remove(&to),
+        };
+    }
+
+    /// Compute the golden result of a matmul or conv before it runs.
+    pub(crate) fn before(&mut self, npu: &Npu, funct: u32, xs1: u64, xs2: u64) {
+        self.pending = match funct {
+            Matmul::FUNCT if is_bfp_matmul(npu, xs1) => {
+                self.pending_bfp = bfp_matmul_exact(npu, xs1, xs2);
+                None
+            }
+            Matmul::FUNCT => matmul_expect(npu, xs1, xs2, &mut self.format_errors),
+            Conv::FUNCT => conv_expect(npu, xs1, xs2),
+            Mcopy::FUNCT => {
+                mcopy_errors(npu, xs1, &mut self.format_errors);
+                None
+            }
+            _ => None,
+        };
+    }
+
+    /// Check the result of the instruction that just ran.
+    pub(crate) fn after(&mut self, npu: &Npu, at: Provenance, xs1: u64, xs2: u64) {
+        match at.funct {
+            Matmul::FUNCT if self.pending_bfp.is_some() => {
+                let (vbank, exact) = self.pending_bfp.take().unwrap();
+                let got = bfp_values(npu, vbank, exact.len());
+                let error = self.format_errors.entry(Bfp8::NAME).or_default();
+                for (want, got) in exact.iter().zip(got) {
+                    error.record(*want as f32, got);
+                }
+            }
+            MvinBfp::FUNCT => {
+                let vbank = rs1_b0(xs1);
+                let lines = rs1_iter(xs1) as usize;
+                let (addr, stride) = xs2_mem_stride(xs2);
+                let got = bfp_values(npu, vbank, lines * Bfp8::BLOCK);
+                if got.len() < lines * Bfp8::BLOCK {
+                    return;
+                }
+                let error = self.format_errors.entry(Bfp8::NAME).or_default();
+                for line in 0..lines {
+                    let dram = npu.read_dram(addr + line as u64 * 64 * stride, 64);
+                    for (i, w) in dram.chunks_exact(4).enumerate() {
+                        let want = f32::from_le_bytes([w[0], w[1], w[2], w[3]]);
+                        error.record(want, got[line * Bfp8::BLOCK + i]);
+                    }
+                }
+            }
+            Matmul::FUNCT | Conv::FUNCT => {
+                let Some((p, want)) = self.pending.take() else {
+                    return;
+                };
+                let bank = &npu.banks[p];
+                let vbank = rs1_b2(xs1);
+                self.compare(at, &bank[..want.len()], &want, |i| format!("bank{vbank}+0x{i:x}"), None);
+                let hash = bank_hash(bank);
+                self.expected.insert(
+                    p,
+                    Expected {
+                        bytes: want,
+                        producer: at,
+                        hash,
+                    },
+                );
+            }
+            Mvin::FUNCT => {
+                let vbank = rs1_b0(xs1);
+                let Some((p, cfg)) = operand(npu, vbank) else {
+                    return;
+                };
+                if cfg.accumulator {
+                    return;
+                }
+                let lines: Vec<(u64, usize)> = tile_lines(xs1, xs2).collect();
+                let bank: Vec<u8> = lines
+                    .iter()
+                    .flat_map(|&(_, off)| npu.banks[p][off..off + 16].to_vec())
+                    .collect();
+                let mut dram: Vec<u8> = lines.iter().flat_map(|&(addr, _)| npu.read_dram(addr, 16)).collect();
+                if cfg.format == NumFormat::Fp32 {
+                    npu.dma.fp32.load(&mut dram);
+                }
+                self.compare(
+                    at,
+                    &bank,
+                    &dram,
+                    |i| format!("bank{vbank}+0x{:x}", lines[i / 16].1 + i % 16),
+                    None,
+                );
+            }
+            Mvout::FUNCT => {
+                let vbank = rs1_b0(xs1);
+                let Some((p, cfg)) = operand(npu, vbank) else {
+                    return;
+                };
+                let lines: Vec<(u64, usize)> = tile_lines(xs1, xs2).collect();
+                let bank = &npu.banks[p];
+                let expected = self.expected.get(&p).filter(|e| e.hash == bank_hash(bank));
+                let mut want: Vec<u8> = lines
+                    .iter()
+                    .flat_map(|&(_, off)| {
+                        (off..off + 16).map(|b| match expected {
+                            Some(e) if b < e.bytes.len() => e.bytes[b],
+                            _ => bank[b],
+                        })
+                    })
+                    .collect();
+                if cfg.accumulator && cfg.format == NumFormat::Fp32 {
+                    npu.dma.fp32.store(&mut want);
+                }
+                let dram: Vec<u8> = lines.iter().flat_map(|&(addr, _)| npu.read_dram(addr, 16)).collect();
+                let producer = expected.map(|e| e.producer);
+                let location = |i: usize| format!("DRAM[0x{:x}]", lines[i / 16].0 + (i % 16) as u64);
+                self.compare(at, &dram, &want, location, producer);
+            }
+            _ => {}
+        }
+    }
+
+    fn compare(
+        &mut self,
+        at: Provenance,
+        got: &[u8],
+        want: &[u8],
+        location: impl Fn(usize) -> String,
+        producer: Option<Provenance>,
+    ) {
+        let mut differ = (0..want.len()).filter(|&i| got[i] != want[i]);
+        let Some(first) = differ.next() else {
+            return;
+        };
+        self.mismatches.push(GoldenMismatch {
+            at,
+            location: location(first),
+            got: got[first],
+            want: want[first],
+            bytes: 1 + differ.count(),
+            producer,
+        });
+    }
+}
+
+fn matmul_expect(
+    npu: &Npu,
+    xs1: u64,
+    xs2: u64,
+    errors: &mut BTreeMap<&'static str, FormatError>,
+) -> Option<(usize, Vec<u8>)> {
+    let (pa, a) = operand(npu, rs1_b0(xs1))?;
+    let (pb, b) = operand(npu, rs1_b1(xs1))?;
+    let (pc, c) = operand(npu, rs1_b2(xs1))?;
+    let m = rs1_iter(xs1) as usize;
+    let (k, n) = Matmul::dims(xs2, a.width, b.width);
+    let bank_size = npu.geometry.bank_bytes();
+    if m * k * a.width.bytes() > bank_size || k * n * b.width.bytes() > bank_size || m * n * c.width.bytes() > bank_size
+    {
+        return None;
+    }
+    if a.format.is_float() || b.format.is_float() || c.format.is_float() {
+        return float_matmul_expect(npu, (pa, a), (pb, b), (pc, c), (m, k, n), xs2, errors);
+    }
+    if a.width == ElemWidth::I32 || b.width == ElemWidth::I32 {
+        return None;
+    }
+    let mut out = if xs2 & 1 == 1 || c.accumulator {
+        load(&npu.banks[pc], c.width, m * n)
+    } else {
+        vec![0.0; m * n]
+    };
+    let a_mat = load(&npu.banks[pa], a.width, m * k);
+    let b_mat = load(&npu.banks[pb], b.width, k * n);
+    matmul_acc(&mut out, &a_mat, &b_mat, m, k, n);
+    Some((pc, store(c.width, &out)))
+}
+
+fn float_matmul_expect(
+    npu: &Npu,
+    (pa, a): (usize, BankConfig),
+    (pb, b): (usize, BankConfig),
+    (pc, c): (usize, BankConfig),
+    (m, k, n): (usize, usize, usize),
+    xs2: u64,
+    errors: &mut BTreeMap<&'static str, FormatError>,
+) -> Option<(usize, Vec<u8>)> {
+    if !c.format.is_float() {
+        return None;
+    }
+    let floats = |p: usize, cfg: BankConfig, len: usize| -> Vec<f32> {
+        (0..len).map(|i| cfg.format.load(cfg.width, &npu.banks[p], i)).collect()
+    };
+    let (a_mat, b_mat) = (floats(pa, a, m * k), floats(pb, b, k * n));
+    let init = if xs2 & 1 == 1 || c.accumulator {
+        floats(pc, c, m * n)
+    } else {
+        vec![0.0; m * n]
+    };
+    let mut exact: Vec<f64> = init.iter().map(|&v| v as f64).collect();
+    let wide = |v: &[f32]| v.iter().map(|&x| x as f64).collect::<Vec<f64>>();
+    matmul_acc(&mut exact, &wide(&a_mat), &wide(&b_mat), m, k, n);
+    let mut bytes = vec![0; m * n * c.width.bytes()];
+    let error = errors.entry(c.format.name()).or_default();
+    for i in 0..m {
+        for j in 0..n {
+            let sum = (0..k).fold(init[i * n + j], |acc, p| acc + a_mat[i * k + p] * b_mat[p * n + j]);
+            c.format.store(c.width, &mut bytes, i * n + j, sum);
+            error.record(exact[i * n + j] as f32, c.format.load(c.width, &bytes, i * n + j));
+        }
+    }
+    Some((pc, bytes))
+}
+
+fn is_bfp_matmul(npu: &Npu, xs1: u64) -> bool {
+    [rs1_b0(xs1), rs1_b1(xs1), rs1_b2(xs1)].iter().any(|&v| {
+        npu.bank_cfgs
+            .get(v as usize)
+            .is_some_and(|c| c.format == NumFormat::Bfp8)
+    })
+}
+
+/// The first `len` elements of `vbank` as values: bfp8 mantissas scaled by
+/// their line exponents, or elements of its float format.
+fn bfp_values(npu: &Npu, vbank: u64, len: usize) -> Vec<f32> {
+    let Some((p, cfg)) = operand(npu, vbank) else {
+        return Vec::new();
+    };
+    let bank = &npu.banks[p];
+    if cfg.format == NumFormat::Bfp8 {
+        let exps = &npu.bfp_exps[vbank as usize];
+        (0..len)
+            .map(|i| Bfp8::decode(bank[i] as i8, exps[i / Bfp8::BLOCK]))
+            .collect()
+    } else {
+        (0..len).map(|i| cfg.format.load(cfg.width, bank, i)).collect()
+    }
+}
+
+/// C vbank and the f64 result a matmul on bfp8 A and B should approximate.
+fn bfp_matmul_exact(npu: &Npu, xs1: u64, xs2: u64) -> Option<(u64, Vec<f64>)> {
+    let (a, b, c) = (rs1_b0(xs1), rs1_b1(xs1), rs1_b2(xs1));
+    let (_, a_cfg) = operand(npu, a)?;
+    let (_, b_cfg) = operand(npu, b)?;
+    let (_, c_cfg) = operand(npu, c)?;
+    let (m, k, n) = (rs1_iter(xs1) as usize, Bfp8::BLOCK, Bfp8::BLOCK);
+    if Matmul::dims(xs2, a_cfg.width, b_cfg.width) != (k, n) {
+        return None;
+    }
+    if a_cfg.format != NumFormat::Bfp8 || b_cfg.format != NumFormat::Bfp8 || !c_cfg.format.is_float() {
+        return None;
+    }
+    if m * 16 > npu.geometry.bank_bytes() || m * n * c_cfg.width.bytes() > npu.geometry.bank_bytes() {
+        return None;
+    }
+    let wide = |v: Vec<f32>| v.into_iter().map(f64::from).collect::<Vec<f64>>();
+    let mut out = if xs2 & 1 == 1 {
+        wide(bfp_values(npu, c, m * n))
+    } else {
+        vec![0.0; m * n]
+    };
+    matmul_acc(
+        &mut out,
+        &wide(bfp_values(npu, a, m * k)),
+        &wide(bfp_values(npu, b, k * n)),
+        m,
+        k,
+        n,
+    );
+    Some((c, out))
+}
+
+/// Rounding error of an mcopy that converts into or out of a float format.
+fn mcopy_errors(npu: &Npu, xs1: u64, errors: &mut BTreeMap<&'static str, FormatError>) {
+    let (Some((ps, src)), Some((_, dst))) = (operand(npu, rs1_b0(xs1)), operand(npu, rs1_b1(xs1))) else {
+        return;
+    };
+    if !src.format.is_float() && !dst.format.is_float() || [src.format, dst.format].contains(&NumFormat::Bfp8) {
+        return;
+    }
+    let elems = rs1_iter(xs1) as usize * 16 / src.width.bytes();
+    let bank_size = npu.geometry.bank_bytes();
+    if elems * src.width.bytes() > bank_size || elems * dst.width.bytes() > bank_size {
+        return;
+    }
+    let error = errors.entry(dst.format.name()).or_default();
+    let mut word = [0; 4];
+    for i in 0..elems {
+        let v = src.format.load(src.width, &npu.banks[ps], i);
+        dst.format.store(dst.width, &mut word, 0, v);
+        error.record(v, dst.format.load(dst.width, &word, 0));
+    }
+}
+
+fn conv_expect(npu: &Npu, xs1: u64, xs2: u64) -> Option<(usize, Vec<u8>)> {
+    let (pi, i) = operand(npu, rs1_b0(xs1))?;
+    let (pw, w) = operand(npu, rs1_b1(xs1))?;
+    let (po, o) = operand(npu, rs1_b2(xs1))?;
+    let s = ConvShape::decode(xs2);
+    let (out_h, out_w) = s.out_dims();
+    if s.in_ch == 0 || s.out_ch == 0 || s.kernel_h == 0 || s.kernel_w == 0 || out_h == 0 || out_w == 0 {
+        return None;
+    }
+    // Input dilation and rot180 are applied to the operands up front.
+    let (dil_h, dil_w) = s.dilated_in();
+    let p = Conv2dParams {
+        stride: s.stride,
+        padding: s.padding,
+        dilation: s.dilation,
+        ..Conv2dParams::new(s.in_ch, s.out_ch, dil_h, dil_w, s.kernel_h, s.kernel_w)
+    };
+    let (in_len, w_len, out_len) = (
+        s.in_ch * s.in_h * s.in_w,
+        s.out_ch * s.in_ch * s.kernel_h * s.kernel_w,
+        s.out_ch * out_h * out_w,
+    );
+    let bank_size = npu.geometry.bank_bytes();
+    if in_len * i.width.bytes() > bank_size
+        || w_len * w.width.bytes() > bank_size
+        || out_len * o.width.bytes() > bank_size
+    {
+        return None;
+    }
+    let input = load(&npu.banks[pi], i.width, in_len);
+    let mut dilated = vec![0.0; s.in_ch * dil_h * dil_w];
+    for (n, v) in input.iter().enumerate() {
+        let (c, y, x) = (n / (s.in_h * s.in_w), n / s.in_w % s.in_h, n % s.in_w);
+        dilated[(c * dil_h + y * s.input_dilation) * dil_w + x * s.input_dilation] = *v;
+    }
+    let mut weight = load(&npu.banks[pw], w.width, w_len);
+    if s.rot180 {
+        for kernel in weight.chunks_mut(s.kernel_h * s.kernel_w) {
+            kernel.reverse();
+        }
+    }
+    let mut out = conv2d(&dilated, &weight, &p);
+    if o.accumulator {
+        for (v, prev) in out.iter_mut().zip(load(&npu.banks[po], o.width, out_len)) {
+            *v += prev;
+        }
+    }
+    Some((po, store(o.width, &out)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bank::DRAM_BASE;
+    use crate::Npu;
+
+    #[test]
+    fn mvout_of_a_wrong_result_names_its_producer() {
+        let mut npu = Npu::new(1 << 20);
+        npu.enable_golden_check();
+        let a: Vec<u8> = (0..64).map(|i| (i * 7 % 11) as u8).collect();
+        let b: Vec<u8> = (0..256).map(|i| (i * 5 % 9) as u8).collect();
+        npu.write_dram(DRAM_BASE, &a);
+        npu.write_dram(DRAM_BASE + 0x1000, &b);
+        npu.exec(32, 1, (1 << 5) | (1 << 10), 0);
+        npu.exec(32, 2, (1 << 5) | (1 << 10), 0);
+        npu.exec(32, 3, (1 << 5) | (1 << 10) | (2 << 11), 0);
+        npu.exec(33, 1 | (4 << 30), DRAM_BASE | (1 << 39), 0x100);
+        npu.exec(33, 2 | (16 << 30), (DRAM_BASE + 0x1000) | (1 << 39), 0x104);
+        npu.exec(48, 1 | (2 << 10) | (3 << 20) | (4 << 30), 0, 0x108);
+        npu.exec(16, 3 | (16 << 30), (DRAM_BASE + 0x2000) | (1 << 39), 0x10c);
+        assert!(npu.golden_mismatches().is_empty(), "{:?}", npu.golden_mismatches());
+
+        // Stand in for a matmul that computed one element wrong: the bank
+        // keeps the model's value, the golden record the right one.
+        let golden = npu.golden.as_mut().unwrap();
+        golden.expected.values_mut().next().unwrap().bytes[4] ^= 1;
+        npu.exec(16, 3 | (16 << 30), (DRAM_BASE + 0x3000) | (1 << 39), 0x110);
+        let [m] = npu.golden_mismatches() else {
+            panic!("{:?}", npu.golden_mismatches());
+        };
+        assert_eq!((m.at.id, m.at.pc, m.bytes), (8, 0x110, 1));
+        assert_eq!(m.location, format!("DRAM[0x{:x}]", DRAM_BASE + 0x3004));
+        let producer = m.producer.unwrap();
+        assert_eq!((producer.funct, producer.pc), (48, 0x108));
+        assert!(m.to_string().contains("data computed by matmul #6 (pc 0x108)"), "{m}");
+
+        // Another write to the bank drops the golden record.
+        npu.exec(33, 3 | (16 << 30), (DRAM_BASE + 0x1000) | (1 << 39), 0x114);
+        npu.exec(16, 3 | (16 << 30), (DRAM_BASE + 0x3000) | (1 << 39), 0x118);
+        assert_eq!(npu.golden_mismatches().len(), 1);
+    }
+}
@@ -1,4 +1,17 @@
 //===- 00_fence.rs - FENCE instruction -------------------------------------===//
+//
+// When FENCE returns:
+//  - every older instruction has retired,
+//  - every mvout write is visible in DRAM (`Npu::read_dram`, Spike loads),
+//  - banks are quiescent: no mvin/mset/compute is still writing them.
+//
+// BEMU runs each instruction to completion inside `Npu::exec`, so all three
+// already hold at issue; FENCE only costs its one cycle. A model that lets
+// DMA or compute run ahead of issue must drain it here to keep this contract.
+// NpuSim does: FENCE issues only once every unit is idle, and nothing queued
+// behind it issues before it retires.
+//
+//===-----------------------------------------------------------------===//-----===//
 
 use super::instruction::{ExecContext, Instruction};
 
@@ -6,6 +19,7 @@ pub struct Fence;
 
 impl Instruction for Fence {
     const FUNCT: u32 = 0;
+    const NAME: &'static str = "fence";
 
     fn exec(_xs1: u64, _xs2: u64, _ctx: &mut ExecContext) -> u64 {
         0
@@ -6,6 +6,7 @@ pub struct Barrier;
 
 impl Instruction for Barrier {
     const FUNCT: u32 = 1;
+    const NAME: &'static str = "barrier";
 
     fn exec(_xs1: u64, _xs2: u64, _ctx: &mut ExecContext) -> u64 {
         0
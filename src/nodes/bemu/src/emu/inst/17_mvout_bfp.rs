@@ -0,0 +1,77 @@
+//===- 17_mvout_bfp.rs - MVOUT_BFP instruction (bfp8 bank to fp32 DRAM) ----===//
+//
+// Stores a bfp8 bank to DRAM as fp32: each bank line of i8 mantissas is
+// scaled by its shared exponent into 16 fp32 values (numfmt.rs).
+//
+// rs1[9:0]:    vbank (BANK0), a single-group bfp8 bank
+// rs1[63:30]:  bank lines (BB_ITER), 64 bytes of fp32 each
+// rs2[38:0]:   DRAM address
+// rs2[57:39]:  DRAM pitch between lines, in 64-byte units
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use super::super::bank::{mem_write_from, BankConfig};
+use super::decode::{pbank, rs1_b0, rs1_iter, xs2_mem_stride};
+use super::instruction::{ExecContext, Instruction, Shared, Unit};
+use crate::dma::split_beat_penalty;
+use crate::numfmt::{Bfp8, NumFormat};
+
+pub struct MvoutBfp;
+
+impl Instruction for MvoutBfp {
+    const FUNCT: u32 = 17;
+    const NAME: &'static str = "mvout_bfp";
+    const READS_BANK: bool = true;
+    const UNIT: Unit = Unit::Dma;
+    const SHARED: &'static [Shared] = &[Shared::Dram];
+
+    fn exec(xs1: u64, xs2: u64, ctx: &mut ExecContext) -> u64 {
+        let bank_id = rs1_b0(xs1);
+        let lines = rs1_iter(xs1) as usize;
+        let (mem_addr, stride) = xs2_mem_stride(xs2);
+
+        if crate::trace::rtrace(Self::NAME) {
+            eprintln!("[RTRACE] mvout_bfp: bank{bank_id} lines={lines} -> DRAM[0x{mem_addr:x}] stride={stride}");
+        }
+
+        if bank_id >= ctx.bank_count() as u64 {
+            panic!("mvout_bfp: invalid bank_id {bank_id}");
+        }
+        let cfg = ctx.cfgs[bank_id as usize];
+        if !cfg.allocated || cfg.cols > 1 || cfg.format != NumFormat::Bfp8 {
+            panic!("mvout_bfp: bank{bank_id} is not an allocated single-group bfp8 bank");
+        }
+        if stride == 0 {
+            panic!("mvout_bfp: stride must be > 0");
+        }
+        if lines * 16 > ctx.bank_size() {
+            panic!("mvout_bfp: {lines} lines do not fit bank{bank_id}");
+        }
+
+        ctx.dma.transfer(Self::NAME, mem_addr, lines as u64 * 4, true);
+        let p = pbank(ctx.bank_map, bank_id);
+        for line in 0..lines {
+            let exp = ctx.bfp_exps[bank_id as usize][line];
+            let data: Vec<u8> = ctx.banks[p][line * 16..line * 16 + 16]
+                .iter()
+                .flat_map(|&m| Bfp8::decode(m as i8, exp).to_le_bytes())
+                .collect();
+            let addr = mem_addr + (line as u64) * 64 * stride;
+            ctx.dma.dram_access(addr, 64, true);
+            mem_write_from(ctx.memory, ctx.addr_map, addr, &data);
+        }
+        ctx.perf.bank_read_bytes += lines as u64 * 16;
+        ctx.perf.dram_write_bytes += lines as u64 * 64;
+        0
+    }
+
+    fn latency(xs1: u64, xs2: u64) -> u64 {
+        // One 16-byte beat per cycle, four per line.
+        let beats = rs1_iter(xs1) * 4;
+        beats.max(1) + split_beat_penalty(xs2_mem_stride(xs2).0, beats)
+    }
+
+    fn bank_extents(xs1: u64, _xs2: u64, _cfgs: &[BankConfig]) -> Vec<(u64, usize, usize)> {
+        vec![(rs1_b0(xs1), 0, rs1_iter(xs1) as usize * 16)]
+    }
+}
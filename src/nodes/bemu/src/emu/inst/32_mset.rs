@@ -1,26 +1,32 @@
 //===- 32_mset.rs - MSET instruction (bank allocation) ---------------------===//
 
-use super::super::bank::{BankConfig, BANK_NUM};
-use super::decode::{rs1_b0, xs2_mset};
+use super::super::bank::{BankConfig, ElemWidth};
+use super::decode::{rs1_b0, xs2_mset, xs2_mset_acc, xs2_mset_format, xs2_mset_width};
 use super::instruction::{ExecContext, Instruction};
+use crate::numfmt::NumFormat;
+use crate::warnings::WarningKind;
 
 pub struct Mset;
 
 impl Instruction for Mset {
     const FUNCT: u32 = 32;
+    const NAME: &'static str = "mset";
 
     fn exec(xs1: u64, xs2: u64, ctx: &mut ExecContext) -> u64 {
         let bank_id = rs1_b0(xs1);
         let (rows, col, alloc) = xs2_mset(xs2);
+        let width_code = xs2_mset_width(xs2);
+        let accumulator = xs2_mset_acc(xs2);
+        let format_code = xs2_mset_format(xs2);
 
-        if std::env::var("BEMU_RTRACE").is_ok() {
+        if crate::trace::rtrace(Self::NAME) {
             eprintln!(
-                "[RTRACE] mset: bank{} rows={} cols={} alloc={}",
-                bank_id, rows, col, alloc
+                "[RTRACE] mset: bank{} rows={} cols={} alloc={} width={} acc={} format={}",
+                bank_id, rows, col, alloc, width_code, accumulator, format_code
             );
         }
 
-        if bank_id >= BANK_NUM as u64 {
+        if bank_id >= ctx.bank_count() as u64 {
             panic!("mset: invalid bank_id {bank_id}");
         }
 
@@ -29,6 +35,35 @@ impl Instruction for Mset {
         let groups = col.max(1);
 
         if alloc == 1 {
+            let format = NumFormat::from_code(format_code)
+                .unwrap_or_else(|| panic!("mset: bank{bank_id} invalid number format code {format_code}"));
+            let width = ElemWidth::from_code(width_code)
+                .unwrap_or_else(|| panic!("mset: bank{bank_id} invalid element width code {width_code}"));
+            // A float format sets the width; the width field may only agree.
+            let width = match format.width() {
+                Some(w) if width_code == 0 || w == width => w,
+                Some(w) => panic!(
+                    "mset: bank{bank_id} {} elements are {} bits, not i{}",
+                    format.name(),
+                    w.bits(),
+                    width.bits()
+                ),
+                None => width,
+            };
+            if accumulator && format.is_float() && format != NumFormat::Fp32 {
+                panic!(
+                    "mset: accumulator bank{bank_id} must be i32 or fp32, got {}",
+                    format.name()
+                );
+            }
+            if accumulator && width != ElemWidth::I32 {
+                panic!("mset: accumulator bank{bank_id} must be i32, got i{}", width.bits());
+            }
+            if ctx.cfgs[i].allocated {
+                ctx.warnings.record(WarningKind::MsetRealloc, || {
+                    format!("bank{bank_id} reallocated while live, contents dropped")
+                });
+            }
             ctx.bank_map.delete_vbank(v);
             for group in 0..groups {
                 let p = ctx
@@ -36,18 +71,29 @@ impl Instruction for Mset {
                     .first_free_pbank()
                     .unwrap_or_else(|| panic!("mset: no free physical bank"));
                 ctx.bank_map.bind_group(p, v, group as u32);
-                ctx.banks[p].fill(0);
+                if accumulator {
+                    // Accumulators start from zero so the first write is a plain store.
+                    ctx.banks[p].fill(0);
+                } else {
+                    ctx.fill.fill(&mut ctx.banks[p]);
+                }
             }
+            ctx.bfp_exps[i].fill(0);
             ctx.cfgs[i] = BankConfig {
                 allocated: true,
                 cols: col,
+                width,
+                accumulator,
+                format,
             };
         } else {
+            if !ctx.cfgs[i].allocated {
+                ctx.warnings.record(WarningKind::MsetFreeUnallocated, || {
+                    format!("bank{bank_id} freed but never allocated")
+                });
+            }
             ctx.bank_map.delete_vbank(v);
-            ctx.cfgs[i] = BankConfig {
-                allocated: false,
-                cols: 0,
-            };
+            ctx.cfgs[i] = BankConfig::default();
         }
         0
     }
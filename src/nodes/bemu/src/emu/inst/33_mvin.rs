@@ -1,20 +1,43 @@
 //===- 33_mvin.rs - MVIN instruction (memory to bank) ----------------------===//
+//
+// Copies rows of 16-byte lines from DRAM into a bank. Accumulator banks add
+// what is loaded instead, and fp32 banks take each value times the mvin
+// scale of the last acc_cfg.
+//
+// rs1[9:0]:    vbank (BANK0)
+// rs1[19:10]:  lines per row (0 reads as 1); single-group banks only
+// rs1[29:20]:  bank row pitch in lines (0 packs rows); single-group banks only
+// rs1[63:30]:  rows (BB_ITER)
+// rs2[38:0]:   DRAM address
+// rs2[57:39]:  DRAM row pitch in lines
+//
+//===-----------------------------------------------------------------===//-----===//
 
-use super::super::bank::{mem_read, BANK_NUM, BANK_SIZE, MATRIX_SIZE};
-use super::decode::{pbank, pbank_group, rs1_b0, rs1_iter, xs2_mem_stride};
-use super::instruction::{ExecContext, Instruction};
+use super::super::bank::{accumulate_into, mem_read_into, BankConfig, MATRIX_SIZE};
+use super::decode::{
+    pbank, pbank_group, rs1_b0, rs1_is_tile, rs1_iter, rs1_tile, tile_bytes, tile_lines, xs2_mem_stride,
+};
+use super::instruction::{ExecContext, Instruction, Shared, Unit};
+use crate::dma::split_beat_penalty;
+use crate::numfmt::NumFormat;
+use crate::warnings::WarningKind;
 
 pub struct Mvin;
 
 impl Instruction for Mvin {
     const FUNCT: u32 = 33;
+    const NAME: &'static str = "mvin";
+    const WRITES_BANK: bool = true;
+    const UNIT: Unit = Unit::Dma;
+    const SHARED: &'static [Shared] = &[Shared::Dram];
 
     fn exec(xs1: u64, xs2: u64, ctx: &mut ExecContext) -> u64 {
+        let bank_size = ctx.bank_size();
         let bank_id = rs1_b0(xs1);
         let depth = rs1_iter(xs1);
         let (mem_addr, stride) = xs2_mem_stride(xs2);
 
-        let rtrace = std::env::var("BEMU_RTRACE").is_ok();
+        let rtrace = crate::trace::rtrace(Self::NAME);
         if rtrace {
             eprintln!(
                 "[RTRACE] mvin: DRAM[0x{:x}] stride={} -> bank{} depth={}",
@@ -22,7 +45,7 @@ impl Instruction for Mvin {
             );
         }
 
-        if bank_id >= BANK_NUM as u64 {
+        if bank_id >= ctx.bank_count() as u64 {
             panic!("mvin: invalid bank_id {bank_id}");
         }
 
@@ -41,6 +64,19 @@ impl Instruction for Mvin {
 
         let cols = ctx.cfgs[bi].cols;
         let groups = cols.max(1) as usize;
+        let matrix_mode_acc = groups == 1 && cols == 4 && depth <= MATRIX_SIZE as u64;
+        if rs1_is_tile(xs1) && (groups > 1 || matrix_mode_acc) {
+            panic!("mvin: 2-D fields need a single-group bank, bank{bank_id} has cols={cols}");
+        }
+        let (len, _) = rs1_tile(xs1);
+
+        ctx.dma.transfer("mvin", mem_addr, depth * len, true);
+        // Accumulator banks add each element into the bank instead, and fp32
+        // banks take the values times the acc_cfg mvin scale.
+        let cfg = ctx.cfgs[bi];
+        let accumulate = cfg.accumulator;
+        let fp32 = cfg.format == NumFormat::Fp32;
+        let mut saturated = 0;
 
         if groups > 1 {
             if rtrace {
@@ -60,14 +96,23 @@ impl Instruction for Mvin {
                 for group in 0..groups {
                     let p = pbank_group(ctx.bank_map, bank_id, group as u64);
                     let bank_offset = row * 16;
-                    if bank_offset + 16 > BANK_SIZE {
+                    if bank_offset + 16 > bank_size {
                         panic!("mvin: bank range: bank_offset={bank_offset} line_bytes=16 depth={depth}");
                     }
                     let addr = mem_addr + row as u64 * groups as u64 * 16 * stride + group as u64 * 16;
+                    ctx.dma.dram_access(addr, 16, false);
+                    ctx.perf.dma_in(16);
                     let mut data = [0u8; 16];
-                    for j in 0..16 {
-                        data[j] = mem_read(ctx.memory, addr + j as u64);
-                        ctx.banks[p][bank_offset + j] = data[j];
+                    mem_read_into(ctx.memory, ctx.addr_map, addr, &mut data);
+                    let mut line = data;
+                    if fp32 {
+                        ctx.dma.fp32.load(&mut line);
+                    }
+                    if accumulate {
+                        saturated += accumulate_into(&cfg, &mut ctx.banks[p], bank_offset, &line);
+                        ctx.perf.bank_read_bytes += 16;
+                    } else {
+                        ctx.banks[p][bank_offset..bank_offset + 16].copy_from_slice(&line);
                     }
                     crate::trace::mtrace(crate::trace::MTraceEvent {
                         is_write: false,
@@ -81,32 +126,45 @@ impl Instruction for Mvin {
             }
         } else {
             let p = pbank(ctx.bank_map, bank_id);
-            let matrix_mode_acc = cols == 4 && depth <= MATRIX_SIZE as u64;
             let line_bytes = if matrix_mode_acc { 64usize } else { 16usize };
+            let lines: Vec<(u64, usize)> = if matrix_mode_acc {
+                (0..depth)
+                    .map(|i| (mem_addr + i * 64 * stride, i as usize * 64))
+                    .collect()
+            } else {
+                tile_lines(xs1, xs2).collect()
+            };
 
             if rtrace {
-                let bytes = depth * line_bytes as u64;
-                let end = if depth == 0 {
-                    mem_addr
-                } else {
-                    mem_addr + (depth - 1) * line_bytes as u64 * stride + line_bytes as u64
-                };
+                let bytes = lines.len() * line_bytes;
+                let end = lines
+                    .iter()
+                    .map(|&(a, _)| a + line_bytes as u64)
+                    .max()
+                    .unwrap_or(mem_addr);
                 eprintln!(
                     "[RTRACE] mvin-range: bank{} cols={} groups={} line_bytes={} rows={} bytes={} DRAM[0x{:x}..0x{:x})",
                     bank_id, cols, groups, line_bytes, depth, bytes, mem_addr, end
                 );
             }
 
-            for i in 0..depth {
-                let addr = mem_addr + i * line_bytes as u64 * stride;
-                let bank_offset = (i as usize) * line_bytes;
-                if bank_offset + line_bytes > BANK_SIZE {
+            for (addr, bank_offset) in lines {
+                if bank_offset + line_bytes > bank_size {
                     panic!("mvin: bank range: bank_offset={bank_offset} line_bytes={line_bytes} depth={depth}");
                 }
+                ctx.dma.dram_access(addr, line_bytes as u64, false);
+                ctx.perf.dma_in(line_bytes as u64);
                 let mut data = vec![0u8; line_bytes];
-                for j in 0..line_bytes {
-                    data[j] = mem_read(ctx.memory, addr + j as u64);
-                    ctx.banks[p][bank_offset + j] = data[j];
+                mem_read_into(ctx.memory, ctx.addr_map, addr, &mut data);
+                let mut line = data.clone();
+                if fp32 {
+                    ctx.dma.fp32.load(&mut line);
+                }
+                if accumulate {
+                    saturated += accumulate_into(&cfg, &mut ctx.banks[p], bank_offset, &line);
+                    ctx.perf.bank_read_bytes += line_bytes as u64;
+                } else {
+                    ctx.banks[p][bank_offset..bank_offset + line_bytes].copy_from_slice(&line);
                 }
                 crate::trace::mtrace(crate::trace::MTraceEvent {
                     is_write: false,
@@ -118,10 +176,26 @@ impl Instruction for Mvin {
                 });
             }
         }
+
+        if saturated > 0 {
+            ctx.warnings.record(WarningKind::MvinSaturated, || {
+                format!("accumulator bank{bank_id}: {saturated} sums saturated")
+            });
+        }
         0
     }
 
-    fn latency(xs1: u64, _xs2: u64) -> u64 {
-        rs1_iter(xs1).max(1)
+    fn latency(xs1: u64, xs2: u64) -> u64 {
+        let lines = rs1_iter(xs1) * rs1_tile(xs1).0;
+        lines.max(1) + split_beat_penalty(xs2_mem_stride(xs2).0, lines)
+    }
+
+    fn bank_extents(xs1: u64, _xs2: u64, cfgs: &[BankConfig]) -> Vec<(u64, usize, usize)> {
+        let cols = cfgs.get(rs1_b0(xs1) as usize).copied().unwrap_or_default().cols;
+        let bytes = match cols {
+            0 | 1 => tile_bytes(xs1),
+            _ => rs1_iter(xs1) as usize * 16,
+        };
+        vec![(rs1_b0(xs1), 0, bytes)]
     }
 }
@@ -10,18 +10,20 @@
 
 use super::decode::rs1_b0;
 use super::instruction::{ExecContext, Instruction, MmioRegion};
+use crate::warnings::WarningKind;
 
 pub struct MmioSet;
 
 impl Instruction for MmioSet {
     const FUNCT: u32 = 34;
+    const NAME: &'static str = "mmio_set";
 
     fn exec(xs1: u64, xs2: u64, ctx: &mut ExecContext) -> u64 {
         let main_bank = rs1_b0(xs1) as usize;
         let mmio_addr = (xs2 & 0xFFFF) as u16;
         let size_rows = ((xs2 >> 16) & 0xFF) as u8;
 
-        if std::env::var("BEMU_RTRACE").is_ok() {
+        if crate::trace::rtrace(Self::NAME) {
             eprintln!(
                 "[RTRACE] mmio_set: main_bank={} mmio_addr=0x{:x} size_rows={}",
                 main_bank, mmio_addr, size_rows
@@ -32,6 +34,13 @@ impl Instruction for MmioSet {
             panic!("mmio_set: invalid main_bank {}", main_bank);
         }
 
+        let region_end = mmio_addr as usize + size_rows as usize * 1024;
+        if region_end > 16384 {
+            ctx.warnings.record(WarningKind::MmioRegionOverflow, || {
+                format!("bank{main_bank} region 0x{mmio_addr:x}..0x{region_end:x} exceeds MMIO SRAM (0x4000)")
+            });
+        }
+
         if size_rows == 0 {
             ctx.mmio_region_table[main_bank].valid = false;
         } else {
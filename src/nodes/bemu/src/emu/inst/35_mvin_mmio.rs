@@ -12,13 +12,18 @@
 //
 //===-----------------------------------------------------------------===//-----===//
 
-use super::super::bank::mem_read;
-use super::instruction::{ExecContext, Instruction};
+use super::super::bank::mem_read_into;
+use super::instruction::{ExecContext, Instruction, Shared, Unit};
+use crate::dma::split_beat_penalty;
+use crate::warnings::WarningKind;
 
 pub struct MvinMmio;
 
 impl Instruction for MvinMmio {
     const FUNCT: u32 = 35;
+    const NAME: &'static str = "mvin_mmio";
+    const UNIT: Unit = Unit::Dma;
+    const SHARED: &'static [Shared] = &[Shared::Dram, Shared::Mmio];
 
     fn exec(xs1: u64, xs2: u64, ctx: &mut ExecContext) -> u64 {
         let dram_addr = xs2 & 0x7F_FFFF_FFFF; // bits [38:0]
@@ -26,14 +31,29 @@ impl Instruction for MvinMmio {
         let col = ((xs2 >> 56) & 0xFF) as u8; // bits [63:56]
         let row = ((xs1 >> 30) & 0x3_FFFF_FFFF) as u32; // bits [63:30], 34-bit
 
-        if std::env::var("BEMU_RTRACE").is_ok() {
+        if crate::trace::rtrace(Self::NAME) {
             eprintln!(
                 "[RTRACE] mvin_mmio: DRAM[0x{:x}] -> MMIO[0x{:x}] row={} col={}",
                 dram_addr, mmio_addr, row, col
             );
         }
 
+        if row > 0 {
+            ctx.dma.transfer("mvin_mmio", dram_addr, row as u64, false);
+        }
+
         let bytes_per_row = 16usize;
+        if col as usize > bytes_per_row {
+            ctx.warnings.record(WarningKind::MvinMmioColClamped, || {
+                format!("col={col} clamped to {bytes_per_row} (DRAM[0x{dram_addr:x}])")
+            });
+        }
+        if row == 0 || col == 0 {
+            ctx.warnings.record(WarningKind::MvinMmioEmpty, || {
+                format!("row={row} col={col}: nothing loaded (DRAM[0x{dram_addr:x}])")
+            });
+        }
+
         for r in 0..row as usize {
             let src_addr = dram_addr + (r * bytes_per_row) as u64;
             let dst_offset = mmio_addr as usize + r * bytes_per_row;
@@ -42,12 +62,18 @@ impl Instruction for MvinMmio {
                 panic!("mvin_mmio: MMIO address out of range");
             }
 
+            ctx.dma.dram_access(src_addr, bytes_per_row as u64, false);
+            ctx.perf.dram_read_bytes += bytes_per_row as u64;
             let bank_idx = dst_offset / 1024;
             let bank_offset = dst_offset % 1024;
 
-            for b in 0..(col as usize).min(bytes_per_row) {
-                ctx.mmio_banks[bank_idx][bank_offset + b] = mem_read(ctx.memory, src_addr + b as u64);
-            }
+            let n = (col as usize).min(bytes_per_row);
+            mem_read_into(
+                ctx.memory,
+                ctx.addr_map,
+                src_addr,
+                &mut ctx.mmio_banks[bank_idx][bank_offset..bank_offset + n],
+            );
             // Zero-pad remaining bytes
             for b in (col as usize)..bytes_per_row {
                 ctx.mmio_banks[bank_idx][bank_offset + b] = 0;
@@ -59,6 +85,6 @@ impl Instruction for MvinMmio {
     fn latency(_xs1: u64, _xs2: u64) -> u64 {
         // row count is in xs1[63:30], not xs2
         let row = (_xs1 >> 30) & 0x3_FFFF_FFFF;
-        row.max(1)
+        row.max(1) + split_beat_penalty(_xs2 & 0x7F_FFFF_FFFF, row)
     }
 }
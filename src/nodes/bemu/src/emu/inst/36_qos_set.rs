@@ -0,0 +1,38 @@
+//===- 36_qos_set.rs - QOS_SET instruction (bandwidth cap) -----------------===//
+//
+// Caps the rows per cycle that subsequent DMA instructions may move through
+// one side of the transfer, to emulate another agent sharing the bandwidth.
+//
+// rs1[0]:      port (0 = DRAM side, 1 = bank side)
+// rs2[15:0]:   tokens (rows allowed per period; 0 removes the cap)
+// rs2[31:16]:  period (cycles; 0 is treated as 1)
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use super::instruction::{ExecContext, Instruction};
+use crate::dma::{QosPort, Throttle};
+
+pub struct QosSet;
+
+impl Instruction for QosSet {
+    const FUNCT: u32 = 36;
+    const NAME: &'static str = "qos_set";
+
+    fn exec(xs1: u64, xs2: u64, ctx: &mut ExecContext) -> u64 {
+        let port = if xs1 & 1 == 0 { QosPort::Dram } else { QosPort::Bank };
+        let tokens = xs2 & 0xFFFF;
+        let period = ((xs2 >> 16) & 0xFFFF).max(1);
+
+        if crate::trace::rtrace(Self::NAME) {
+            eprintln!("[RTRACE] qos_set: port={port:?} tokens={tokens} period={period}");
+        }
+
+        let cap = (tokens != 0).then_some(Throttle { tokens, period });
+        ctx.dma.set_cap(port, cap);
+        0
+    }
+
+    fn latency(_xs1: u64, _xs2: u64) -> u64 {
+        1
+    }
+}
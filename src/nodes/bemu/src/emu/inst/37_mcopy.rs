@@ -0,0 +1,124 @@
+//===- 37_mcopy.rs - MCOPY instruction (bank to bank) ----------------------===//
+//
+// Copies elements from one bank to another, converting between the element
+// widths each bank declared at mset. Widening sign-extends; narrowing
+// saturates and reports the saturated elements as a warning. Both banks are
+// treated as flat element arrays starting at offset 0.
+//
+// If either bank holds a float format (numfmt.rs), elements go through f32:
+// the source is decoded and each value is rounded into the destination's
+// format, saturating if that is an integer width.
+//
+// rs1[9:0]:    src vbank (BANK0)
+// rs1[19:10]:  dst vbank (BANK1)
+// rs1[63:30]:  rows (BB_ITER, 16-byte source rows to copy)
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use super::super::bank::BankConfig;
+use super::decode::{pbank, rs1_b0, rs1_b1, rs1_iter};
+use super::instruction::{ExecContext, Instruction, Unit};
+use crate::numfmt::NumFormat;
+use crate::warnings::WarningKind;
+
+pub struct Mcopy;
+
+impl Instruction for Mcopy {
+    const FUNCT: u32 = 37;
+    const NAME: &'static str = "mcopy";
+    const READS_BANK: bool = true;
+    const WRITES_BANK: bool = true;
+    const UNIT: Unit = Unit::Ball;
+
+    fn exec(xs1: u64, _xs2: u64, ctx: &mut ExecContext) -> u64 {
+        let src = rs1_b0(xs1);
+        let dst = rs1_b1(xs1);
+        let rows = rs1_iter(xs1);
+
+        for bank_id in [src, dst] {
+            if bank_id >= ctx.bank_count() as u64 {
+                panic!("mcopy: invalid bank_id {bank_id}");
+            }
+            let cfg = ctx.cfgs[bank_id as usize];
+            if !cfg.allocated {
+                panic!("mcopy: bank {bank_id} not allocated");
+            }
+            if cfg.cols > 1 {
+                panic!(
+                    "mcopy: bank {bank_id} spans {} groups; only single-group banks",
+                    cfg.cols
+                );
+            }
+        }
+
+        let src_w = ctx.cfgs[src as usize].width;
+        let dst_w = ctx.cfgs[dst as usize].width;
+        let elems = rows as usize * 16 / src_w.bytes();
+
+        if crate::trace::rtrace(Self::NAME) {
+            eprintln!(
+                "[RTRACE] mcopy: bank{src} (i{}) -> bank{dst} (i{}) rows={rows} elems={elems}",
+                src_w.bits(),
+                dst_w.bits()
+            );
+        }
+
+        if rows as usize * 16 > ctx.bank_size() || elems * dst_w.bytes() > ctx.bank_size() {
+            panic!(
+                "mcopy: {elems} elements do not fit bank{src} (i{}) -> bank{dst} (i{})",
+                src_w.bits(),
+                dst_w.bits()
+            );
+        }
+
+        // Read everything first: src and dst may be the same bank.
+        let sp = pbank(ctx.bank_map, src);
+        let dp = pbank(ctx.bank_map, dst);
+        let (src_f, dst_f) = (ctx.cfgs[src as usize].format, ctx.cfgs[dst as usize].format);
+        if src_f == NumFormat::Bfp8 || dst_f == NumFormat::Bfp8 {
+            panic!(
+                "mcopy: bfp8 bank{} moves through mvin_bfp and mvout_bfp",
+                if src_f == NumFormat::Bfp8 { src } else { dst }
+            );
+        }
+        let saturated = if src_f.is_float() || dst_f.is_float() {
+            let values: Vec<f32> = (0..elems).map(|i| src_f.load(src_w, &ctx.banks[sp], i)).collect();
+            values
+                .iter()
+                .enumerate()
+                .filter(|&(i, &v)| dst_f.store(dst_w, &mut ctx.banks[dp], i, v))
+                .count()
+        } else {
+            let values: Vec<i32> = (0..elems).map(|i| src_w.load(&ctx.banks[sp], i)).collect();
+            values
+                .iter()
+                .enumerate()
+                .filter(|&(i, &v)| dst_w.store(&mut ctx.banks[dp], i, v as i64))
+                .count()
+        };
+        ctx.perf.bank_read_bytes += rows * 16;
+        ctx.perf.bank_write_bytes += (elems * dst_w.bytes()) as u64;
+
+        if saturated > 0 {
+            ctx.warnings.record(WarningKind::McopySaturated, || {
+                format!(
+                    "bank{src} (i{}) -> bank{dst} (i{}): {saturated} of {elems} elements saturated",
+                    src_w.bits(),
+                    dst_w.bits()
+                )
+            });
+        }
+        0
+    }
+
+    fn latency(xs1: u64, _xs2: u64) -> u64 {
+        rs1_iter(xs1).max(1)
+    }
+
+    fn bank_extents(xs1: u64, _xs2: u64, cfgs: &[BankConfig]) -> Vec<(u64, usize, usize)> {
+        let width = |bank: u64| cfgs.get(bank as usize).copied().unwrap_or_default().width;
+        let (src, dst, rows) = (rs1_b0(xs1), rs1_b1(xs1), rs1_iter(xs1) as usize);
+        let elems = rows * 16 / width(src).bytes();
+        vec![(src, 0, rows * 16), (dst, 0, elems * width(dst).bytes())]
+    }
+}
@@ -0,0 +1,86 @@
+//===- 38_bmt.rs - BMT instruction (bank mapping table) --------------------===//
+//
+// Programs or queries the virtual-to-physical bank mapping table that mset
+// fills with the first free physical banks.
+//
+// Remap moves every group of an allocated vbank onto chosen physical banks.
+// Group g goes to pbank first + g (block) or (first + g * step) % num_banks
+// (cyclic). Only the table changes: the vbank then reads whatever the new
+// physical banks hold. A target bound to another vbank is an error.
+//
+// Query returns the pbank of one group in rd, or all ones if unmapped.
+//
+// rs1[9:0]:    vbank
+// rs1[19:10]:  first pbank (remap) or group (query)
+// rs2[0]:      op (0 = remap, 1 = query)
+// rs2[1]:      interleave (0 = block, 1 = cyclic)
+// rs2[15:8]:   cyclic step (0 is treated as 1)
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use super::decode::{rs1_b0, rs1_b1};
+use super::instruction::{ExecContext, Instruction};
+
+pub struct Bmt;
+
+impl Instruction for Bmt {
+    const FUNCT: u32 = 38;
+    const NAME: &'static str = "bmt";
+
+    fn exec(xs1: u64, xs2: u64, ctx: &mut ExecContext) -> u64 {
+        let vbank = rs1_b0(xs1);
+        let arg = rs1_b1(xs1);
+        let query = xs2 & 1 == 1;
+        let cyclic = (xs2 >> 1) & 1 == 1;
+        let step = ((xs2 >> 8) & 0xFF).max(1) as usize;
+
+        if crate::trace::rtrace(Self::NAME) {
+            eprintln!("[RTRACE] bmt: bank{vbank} arg={arg} query={query} cyclic={cyclic} step={step}");
+        }
+
+        if vbank >= ctx.bank_count() as u64 {
+            panic!("bmt: invalid bank_id {vbank}");
+        }
+        let v = vbank as u32;
+
+        if query {
+            return ctx.bank_map.resolve_group(v, arg as u32).map_or(u64::MAX, |p| p as u64);
+        }
+
+        let cfg = ctx.cfgs[vbank as usize];
+        if !cfg.allocated {
+            panic!("bmt: bank {vbank} not allocated");
+        }
+        let num_banks = ctx.bank_count();
+        let first = arg as usize;
+        let groups = cfg.cols.max(1) as usize;
+        let targets: Vec<usize> = (0..groups)
+            .map(|g| match cyclic {
+                true => (first + g * step) % num_banks,
+                false => first + g,
+            })
+            .collect();
+        for (g, &p) in targets.iter().enumerate() {
+            if p >= num_banks {
+                panic!("bmt: bank{vbank} group {g} maps past the last pbank ({p} >= {num_banks})");
+            }
+            if targets[..g].contains(&p) {
+                panic!("bmt: bank{vbank} maps two groups to pbank {p}");
+            }
+            let slot = &ctx.bank_map.slots[p];
+            if slot.valid && slot.vbank_id != v {
+                panic!("bmt: pbank {p} is bound to bank{}", slot.vbank_id);
+            }
+        }
+
+        ctx.bank_map.delete_vbank(v);
+        for (g, &p) in targets.iter().enumerate() {
+            ctx.bank_map.bind_group(p, v, g as u32);
+        }
+        0
+    }
+
+    fn latency(_xs1: u64, _xs2: u64) -> u64 {
+        1
+    }
+}
@@ -0,0 +1,134 @@
+//===- 39_dma_sg.rs - DMA_SG instruction (scatter-gather DMA) --------------===//
+//
+// Walks a list of descriptors in DRAM and moves each one's bytes between DRAM
+// and a bank, so sparse or blocked tensors move in one instruction. Each
+// descriptor is 16 bytes, little-endian:
+//
+//   [63:0]    DRAM address
+//   [95:64]   byte offset in the bank; the vbank's groups follow each other,
+//             so offset / bank size picks the group
+//   [127:96]  length in bytes; must not cross into the next group
+//
+// Loads into an accumulator bank add their elements into it, i32 or f32, as
+// mvin does, but without mvin's fp32 scale.
+//
+// Fetching a descriptor takes one cycle. Its data then takes one cycle per
+// 16-byte beat, and a misaligned DRAM address costs one more beat per beat as
+// for mvin rows. Those cycles depend on the list, so they are charged after
+// the instruction executes.
+//
+// rs1[9:0]:    vbank (BANK0)
+// rs1[10]:     direction (0 = DRAM to bank, 1 = bank to DRAM)
+// rs1[63:30]:  descriptor count (BB_ITER)
+// rs2[38:0]:   DRAM address of the descriptor list
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use super::super::bank::{accumulate_into, mem_read_into, mem_write_from};
+use super::decode::{pbank_group, rs1_b0, rs1_iter, xs2_mem_stride};
+use super::instruction::{ExecContext, Instruction, Shared, Unit};
+use crate::dma::{split_beat_penalty, DMA_BEAT_BYTES};
+use crate::warnings::WarningKind;
+
+/// Bytes per descriptor.
+pub const DESCRIPTOR_BYTES: u64 = 16;
+
+pub struct DmaSg;
+
+impl Instruction for DmaSg {
+    const FUNCT: u32 = 39;
+    const NAME: &'static str = "dma_sg";
+    const UNIT: Unit = Unit::Dma;
+    const SHARED: &'static [Shared] = &[Shared::Dram];
+    // Either direction, so both bank access latencies apply.
+    const READS_BANK: bool = true;
+    const WRITES_BANK: bool = true;
+
+    fn exec(xs1: u64, xs2: u64, ctx: &mut ExecContext) -> u64 {
+        let bank_id = rs1_b0(xs1);
+        let store = (xs1 >> 10) & 1 == 1;
+        let count = rs1_iter(xs1);
+        let list = xs2_mem_stride(xs2).0;
+        let op = if store { "dma_sg store" } else { "dma_sg load" };
+
+        if crate::trace::rtrace(Self::NAME) {
+            eprintln!("[RTRACE] {op}: bank{bank_id} {count} descriptors at DRAM[0x{list:x}]");
+        }
+
+        if bank_id >= ctx.bank_count() as u64 {
+            panic!("dma_sg: invalid bank_id {bank_id}");
+        }
+        let cfg = ctx.cfgs[bank_id as usize];
+        if !cfg.allocated {
+            panic!("dma_sg: bank {bank_id} not allocated");
+        }
+        let bank_size = ctx.bank_size();
+        let accumulate = cfg.accumulator && !store;
+        let mut saturated = 0;
+
+        for d in 0..count {
+            let at = list + d * DESCRIPTOR_BYTES;
+            ctx.dma.dram_access(at, DESCRIPTOR_BYTES, false);
+            ctx.perf.dram_read_bytes += DESCRIPTOR_BYTES;
+            let mut desc = [0u8; DESCRIPTOR_BYTES as usize];
+            mem_read_into(ctx.memory, ctx.addr_map, at, &mut desc);
+            let addr = u64::from_le_bytes(desc[0..8].try_into().unwrap());
+            let offset = u32::from_le_bytes(desc[8..12].try_into().unwrap()) as usize;
+            let len = u32::from_le_bytes(desc[12..16].try_into().unwrap()) as usize;
+            if len == 0 {
+                continue;
+            }
+
+            let (group, start) = (offset / bank_size, offset % bank_size);
+            if group >= cfg.cols.max(1) as usize || start + len > bank_size {
+                panic!("dma_sg: descriptor {d} [0x{offset:x}, +{len}) is outside bank{bank_id} or crosses a group");
+            }
+            if accumulate && !(start.is_multiple_of(4) && len.is_multiple_of(4)) {
+                panic!("dma_sg: descriptor {d} is not i32-aligned for accumulator bank{bank_id}");
+            }
+            let p = pbank_group(ctx.bank_map, bank_id, group as u64);
+
+            let beats = (len as u64).div_ceil(DMA_BEAT_BYTES);
+            ctx.dma.transfer(op, addr, beats, true);
+            ctx.dma.charge(beats + split_beat_penalty(addr, beats));
+            ctx.dma.dram_access(addr, len as u64, store);
+
+            let data: Vec<u8> = if store {
+                ctx.perf.dma_out(len as u64);
+                let data = ctx.banks[p][start..start + len].to_vec();
+                mem_write_from(ctx.memory, ctx.addr_map, addr, &data);
+                data
+            } else {
+                ctx.perf.dma_in(len as u64);
+                let mut data = vec![0u8; len];
+                mem_read_into(ctx.memory, ctx.addr_map, addr, &mut data);
+                if accumulate {
+                    saturated += accumulate_into(&cfg, &mut ctx.banks[p], start, &data);
+                    ctx.perf.bank_read_bytes += len as u64;
+                } else {
+                    ctx.banks[p][start..start + len].copy_from_slice(&data);
+                }
+                data
+            };
+            crate::trace::mtrace(crate::trace::MTraceEvent {
+                is_write: store,
+                addr,
+                data,
+                vbank_id: bank_id as u32,
+                pbank_id: p as u32,
+                group_id: group as u32,
+            });
+        }
+
+        if saturated > 0 {
+            ctx.warnings.record(WarningKind::MvinSaturated, || {
+                format!("accumulator bank{bank_id}: {saturated} sums saturated by dma_sg")
+            });
+        }
+        0
+    }
+
+    fn latency(xs1: u64, _xs2: u64) -> u64 {
+        rs1_iter(xs1).max(1)
+    }
+}
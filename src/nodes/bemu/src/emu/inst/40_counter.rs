@@ -0,0 +1,40 @@
+//===- 40_counter.rs - COUNTER instruction (read a performance counter) ----===//
+//
+// Returns one performance counter in rd, so bare-metal workloads can measure
+// themselves. Values include this instruction's own cycle and count. The
+// counter ids are `perf::COUNTERS`:
+//
+//   0 cycles            4 dram_write_bytes   8 compute_cycles
+//   1 instructions      5 bank_read_bytes    9 compute_ops
+//   2 macs              6 bank_write_bytes  10 dma_transfers
+//   3 dram_read_bytes   7 dma_stall_cycles  11 bank_conflict_cycles
+//
+// rs1[7:0]:    counter id
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use super::instruction::{ExecContext, Instruction};
+
+pub struct Counter;
+
+impl Instruction for Counter {
+    const FUNCT: u32 = 40;
+    const NAME: &'static str = "counter";
+
+    fn exec(xs1: u64, _xs2: u64, ctx: &mut ExecContext) -> u64 {
+        let id = (xs1 & 0xFF) as usize;
+        let value = ctx
+            .perf
+            .read(id, ctx.cycle, ctx.instructions, &ctx.dma.stats)
+            .unwrap_or_else(|| panic!("counter: invalid counter id {id}"));
+
+        if crate::trace::rtrace(Self::NAME) {
+            eprintln!("[RTRACE] counter: {} = {value}", crate::perf::COUNTERS[id]);
+        }
+        value
+    }
+
+    fn latency(_xs1: u64, _xs2: u64) -> u64 {
+        1
+    }
+}
@@ -0,0 +1,83 @@
+//===- 41_mvin_bfp.rs - MVIN_BFP instruction (fp32 DRAM to bfp8 bank) ------===//
+//
+// Loads fp32 values from DRAM into a bfp8 bank. Each 16 values become one
+// bank line of i8 mantissas, and the line's shared exponent goes to the
+// exponent table beside the banks (numfmt.rs).
+//
+// rs1[9:0]:    vbank (BANK0), a single-group bfp8 bank
+// rs1[63:30]:  bank lines (BB_ITER), 64 bytes of fp32 each
+// rs2[38:0]:   DRAM address
+// rs2[57:39]:  DRAM pitch between lines, in 64-byte units
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use super::super::bank::{mem_read_into, BankConfig};
+use super::decode::{pbank, rs1_b0, rs1_iter, xs2_mem_stride};
+use super::instruction::{ExecContext, Instruction, Shared, Unit};
+use crate::dma::split_beat_penalty;
+use crate::numfmt::{Bfp8, NumFormat};
+
+pub struct MvinBfp;
+
+impl Instruction for MvinBfp {
+    const FUNCT: u32 = 41;
+    const NAME: &'static str = "mvin_bfp";
+    const WRITES_BANK: bool = true;
+    const UNIT: Unit = Unit::Dma;
+    const SHARED: &'static [Shared] = &[Shared::Dram];
+
+    fn exec(xs1: u64, xs2: u64, ctx: &mut ExecContext) -> u64 {
+        let bank_id = rs1_b0(xs1);
+        let lines = rs1_iter(xs1) as usize;
+        let (mem_addr, stride) = xs2_mem_stride(xs2);
+
+        if crate::trace::rtrace(Self::NAME) {
+            eprintln!("[RTRACE] mvin_bfp: DRAM[0x{mem_addr:x}] stride={stride} -> bank{bank_id} lines={lines}");
+        }
+
+        if bank_id >= ctx.bank_count() as u64 {
+            panic!("mvin_bfp: invalid bank_id {bank_id}");
+        }
+        let cfg = ctx.cfgs[bank_id as usize];
+        if !cfg.allocated || cfg.cols > 1 || cfg.format != NumFormat::Bfp8 {
+            panic!("mvin_bfp: bank{bank_id} is not an allocated single-group bfp8 bank");
+        }
+        if stride == 0 {
+            panic!("mvin_bfp: stride must be > 0");
+        }
+        if lines * 16 > ctx.bank_size() {
+            panic!("mvin_bfp: {lines} lines do not fit bank{bank_id}");
+        }
+
+        ctx.dma.transfer(Self::NAME, mem_addr, lines as u64 * 4, true);
+        let p = pbank(ctx.bank_map, bank_id);
+        for line in 0..lines {
+            let addr = mem_addr + (line as u64) * 64 * stride;
+            let mut data = [0u8; 64];
+            ctx.dma.dram_access(addr, 64, false);
+            mem_read_into(ctx.memory, ctx.addr_map, addr, &mut data);
+            let values: Vec<f32> = data
+                .chunks_exact(4)
+                .map(|w| f32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+                .collect();
+            let (exp, mantissas) = Bfp8::encode_block(&values);
+            ctx.bfp_exps[bank_id as usize][line] = exp;
+            for (b, m) in ctx.banks[p][line * 16..line * 16 + 16].iter_mut().zip(mantissas) {
+                *b = m as u8;
+            }
+        }
+        ctx.perf.dram_read_bytes += lines as u64 * 64;
+        ctx.perf.bank_write_bytes += lines as u64 * 16;
+        0
+    }
+
+    fn latency(xs1: u64, xs2: u64) -> u64 {
+        // One 16-byte beat per cycle, four per line.
+        let beats = rs1_iter(xs1) * 4;
+        beats.max(1) + split_beat_penalty(xs2_mem_stride(xs2).0, beats)
+    }
+
+    fn bank_extents(xs1: u64, _xs2: u64, _cfgs: &[BankConfig]) -> Vec<(u64, usize, usize)> {
+        vec![(rs1_b0(xs1), 0, rs1_iter(xs1) as usize * 16)]
+    }
+}
@@ -0,0 +1,50 @@
+//===- 42_acc_cfg.rs - ACC_CFG instruction (fp32 load/store paths) ---------===//
+//
+// Configures the fp32 paths of Gemmini's fp32 configuration (ElemT = AccT =
+// f32). Later mvins into fp32 banks multiply what they load by the mvin
+// scale; later mvouts of fp32 accumulator banks multiply by the mvout scale
+// and, with relu set, clamp negatives to zero. Integer banks are unaffected.
+//
+// rs1[0]:      relu on mvout (0 = none, 1 = relu)
+// rs1[63:32]:  mvin scale, f32 bits (0 reads as 1.0)
+// rs2[31:0]:   mvout scale, f32 bits (0 reads as 1.0)
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use super::instruction::{ExecContext, Instruction};
+use crate::dma::Fp32Paths;
+
+pub struct AccCfg;
+
+impl Instruction for AccCfg {
+    const FUNCT: u32 = 42;
+    const NAME: &'static str = "acc_cfg";
+
+    fn exec(xs1: u64, xs2: u64, ctx: &mut ExecContext) -> u64 {
+        let scale = |bits: u64| match bits as u32 {
+            0 => 1f32.to_bits(),
+            b => b,
+        };
+        let cfg = Fp32Paths {
+            mvin_scale: scale(xs1 >> 32),
+            mvout_scale: scale(xs2 & 0xFFFF_FFFF),
+            relu: xs1 & 1 == 1,
+        };
+
+        if crate::trace::rtrace(Self::NAME) {
+            eprintln!(
+                "[RTRACE] acc_cfg: mvin_scale={} mvout_scale={} relu={}",
+                f32::from_bits(cfg.mvin_scale),
+                f32::from_bits(cfg.mvout_scale),
+                cfg.relu
+            );
+        }
+
+        ctx.dma.fp32 = cfg;
+        0
+    }
+
+    fn latency(_xs1: u64, _xs2: u64) -> u64 {
+        1
+    }
+}
@@ -0,0 +1,259 @@
+//===- 48_matmul.rs - MATMUL instruction (integer matrix multiply) ---------===//
+//
+// C = A * B (or C += A * B) on banks, with element types taken from the
+// widths the banks declared at mset. Products accumulate in 64 bits and are
+// stored with saturation to C's width, so an i8 x i8 -> i32 bank set models
+// the quantized inference path.
+//
+// If any bank holds a float format (numfmt.rs), the operands are decoded to
+// f32, products accumulate in f32 in K order, and each result is rounded
+// into C's format, which must then be a float format too.
+//
+// bfp8 A and B follow block-floating-point rules instead. Each line of A and
+// of B shares one exponent, so a row of C sums mantissa products whose
+// exponents differ only by B's line exponents. The products are aligned to
+// the largest exponent by arithmetic right shifts, dropping the bits below
+// it, and summed exactly. A bfp8 C then renormalizes each row into one
+// block; any other float C rounds each sum into its format.
+//
+// A holds M rows of K elements and B holds K rows of N elements, each row
+// one or more 16-byte bank lines (K = lines * 16 / A bytes, N = lines * 16 /
+// B bytes). C is M x N elements stored row-major from offset 0, as mcopy
+// lays out elements.
+//
+// rs1[9:0]:    A vbank (BANK0)
+// rs1[19:10]:  B vbank (BANK1)
+// rs1[29:20]:  C vbank (BANK2)
+// rs1[63:30]:  M (BB_ITER, rows of A)
+// rs2[0]:      accumulate into C instead of overwriting it (always on when C
+//              is an accumulator bank)
+// rs2[15:8]:   lines per row of A (0 reads as 1)
+// rs2[23:16]:  lines per row of B (0 reads as 1)
+//
+// K and N are logical sizes, independent of the hardware. On an R x C
+// systolic array, K is split into tiles of R and N into tiles of C. Each
+// tile streams the M rows of A through the array once, and partial sums of
+// the K tiles accumulate in C, so a binary written for one array runs
+// unchanged on a smaller one and only the cycle count changes.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use super::super::bank::{ArrayGeometry, BankConfig, ElemWidth};
+use super::decode::{pbank, rs1_b0, rs1_b1, rs1_b2, rs1_iter};
+use super::instruction::{ExecContext, Instruction, Unit};
+use crate::numfmt::{Bfp8, NumFormat};
+use crate::warnings::WarningKind;
+
+pub struct Matmul;
+
+impl Matmul {
+    /// (K, N) of a matmul on A and B elements of these widths.
+    pub fn dims(xs2: u64, a: ElemWidth, b: ElemWidth) -> (usize, usize) {
+        let lines = |shift: u32| ((xs2 >> shift) & 0xff).max(1) as usize;
+        (lines(8) * 16 / a.bytes(), lines(16) * 16 / b.bytes())
+    }
+}
+
+impl Instruction for Matmul {
+    const FUNCT: u32 = 48;
+    const NAME: &'static str = "matmul";
+    const READS_BANK: bool = true;
+    const WRITES_BANK: bool = true;
+    const UNIT: Unit = Unit::Ball;
+
+    fn exec(xs1: u64, xs2: u64, ctx: &mut ExecContext) -> u64 {
+        let (a, b, c) = (rs1_b0(xs1), rs1_b1(xs1), rs1_b2(xs1));
+        let m = rs1_iter(xs1) as usize;
+        let accumulate = xs2 & 1 == 1;
+
+        for bank_id in [a, b, c] {
+            if bank_id >= ctx.bank_count() as u64 {
+                panic!("matmul: invalid bank_id {bank_id}");
+            }
+            let cfg = ctx.cfgs[bank_id as usize];
+            if !cfg.allocated {
+                panic!("matmul: bank {bank_id} not allocated");
+            }
+            if cfg.cols > 1 {
+                panic!(
+                    "matmul: bank {bank_id} spans {} groups; only single-group banks",
+                    cfg.cols
+                );
+            }
+        }
+        if c == a || c == b {
+            panic!("matmul: C bank {c} aliases an input");
+        }
+        let accumulate = accumulate || ctx.cfgs[c as usize].accumulator;
+
+        let (aw, bw, cw) = (
+            ctx.cfgs[a as usize].width,
+            ctx.cfgs[b as usize].width,
+            ctx.cfgs[c as usize].width,
+        );
+        let (k, n) = Self::dims(xs2, aw, bw);
+
+        if crate::trace::rtrace(Self::NAME) {
+            eprintln!(
+                "[RTRACE] matmul: bank{a} (i{}) x bank{b} (i{}) -> bank{c} (i{}) m={m} k={k} n={n} acc={accumulate}",
+                aw.bits(),
+                bw.bits(),
+                cw.bits()
+            );
+        }
+
+        if m * k * aw.bytes() > ctx.bank_size() || k * n * bw.bytes() > ctx.bank_size() {
+            panic!("matmul: {m}x{k} A or {k}x{n} B does not fit its bank");
+        }
+        if m * n * cw.bytes() > ctx.bank_size() {
+            panic!("matmul: {m}x{n} result does not fit bank{c} (i{})", cw.bits());
+        }
+
+        let (pa, pb, pc) = (pbank(ctx.bank_map, a), pbank(ctx.bank_map, b), pbank(ctx.bank_map, c));
+        let [fa, fb, fc] = [a, b, c].map(|bank| ctx.cfgs[bank as usize].format);
+        let mut saturated = 0;
+        if [fa, fb, fc].contains(&NumFormat::Bfp8) {
+            if (k, n) != (Bfp8::BLOCK, Bfp8::BLOCK) {
+                panic!("matmul: bfp8 operands take one line per row, got k={k} n={n}");
+            }
+            if fa != NumFormat::Bfp8 || fb != NumFormat::Bfp8 || !fc.is_float() {
+                panic!(
+                    "matmul: bfp8 needs bfp8 A and B and a float C, got {} x {} -> {}",
+                    fa.name(),
+                    fb.name(),
+                    fc.name()
+                );
+            }
+            bfp_matmul(ctx, [(a, pa), (b, pb), (c, pc)], m, accumulate);
+        } else if fa.is_float() || fb.is_float() || fc.is_float() {
+            if !fc.is_float() {
+                panic!(
+                    "matmul: C bank{c} holds integers but A or B holds {}",
+                    if fa.is_float() { fa.name() } else { fb.name() }
+                );
+            }
+            for i in 0..m {
+                for j in 0..n {
+                    let mut acc = if accumulate {
+                        fc.load(cw, &ctx.banks[pc], i * n + j)
+                    } else {
+                        0.0
+                    };
+                    for p in 0..k {
+                        acc += fa.load(aw, &ctx.banks[pa], i * k + p) * fb.load(bw, &ctx.banks[pb], p * n + j);
+                    }
+                    fc.store(cw, &mut ctx.banks[pc], i * n + j, acc);
+                }
+            }
+        } else {
+            for i in 0..m {
+                for j in 0..n {
+                    let mut acc: i64 = if accumulate {
+                        cw.load(&ctx.banks[pc], i * n + j) as i64
+                    } else {
+                        0
+                    };
+                    for p in 0..k {
+                        let x = aw.load(&ctx.banks[pa], i * k + p) as i64;
+                        let y = bw.load(&ctx.banks[pb], p * n + j) as i64;
+                        acc += x * y;
+                    }
+                    if cw.store(&mut ctx.banks[pc], i * n + j, acc) {
+                        saturated += 1;
+                    }
+                }
+            }
+        }
+
+        let c_bytes = (m * n * cw.bytes()) as u64;
+        ctx.perf.macs += (m * n * k) as u64;
+        ctx.perf.bank_read_bytes +=
+            (m * k * aw.bytes() + k * n * bw.bytes()) as u64 + if accumulate { c_bytes } else { 0 };
+        ctx.perf.bank_write_bytes += c_bytes;
+
+        if saturated > 0 {
+            ctx.warnings.record(WarningKind::MatmulSaturated, || {
+                format!("bank{c} (i{}): {saturated} of {} results saturated", cw.bits(), m * n)
+            });
+        }
+        0
+    }
+
+    fn latency(xs1: u64, xs2: u64) -> u64 {
+        Self::array_latency(xs1, xs2, ArrayGeometry::default(), &[])
+    }
+
+    fn array_latency(xs1: u64, xs2: u64, array: ArrayGeometry, cfgs: &[BankConfig]) -> u64 {
+        let width = |bank: u64| cfgs.get(bank as usize).copied().unwrap_or_default().width;
+        let (k, n) = Self::dims(xs2, width(rs1_b0(xs1)), width(rs1_b1(xs1)));
+        let passes = (k.div_ceil(array.rows) * n.div_ceil(array.cols)) as u64;
+        // Per pass, one row of A per cycle plus the array's fill and drain.
+        passes * (rs1_iter(xs1).max(1) + array.rows as u64)
+    }
+
+    fn bank_extents(xs1: u64, xs2: u64, cfgs: &[BankConfig]) -> Vec<(u64, usize, usize)> {
+        let (a, b, c) = (rs1_b0(xs1), rs1_b1(xs1), rs1_b2(xs1));
+        let width = |bank: u64| cfgs.get(bank as usize).copied().unwrap_or_default().width;
+        let (k, n) = Self::dims(xs2, width(a), width(b));
+        let m = rs1_iter(xs1) as usize;
+        vec![
+            (a, 0, m * k * width(a).bytes()),
+            (b, 0, k * n * width(b).bytes()),
+            (c, 0, m * n * width(c).bytes()),
+        ]
+    }
+}
+
+/// C = A * B on bfp8 A and B, 16 x 16 mantissas per line, as (vbank, pbank)
+/// operands.
+fn bfp_matmul(ctx: &mut ExecContext, [(a, pa), (b, pb), (c, pc)]: [(u64, usize); 3], m: usize, accumulate: bool) {
+    let (k, n) = (Bfp8::BLOCK, Bfp8::BLOCK);
+    let fc = ctx.cfgs[c as usize].format;
+    let cw = ctx.cfgs[c as usize].width;
+    let b_exps: Vec<i32> = (0..k).map(|p| ctx.bfp_exps[b as usize][p] as i32).collect();
+    for i in 0..m {
+        let a_exp = ctx.bfp_exps[a as usize][i] as i32;
+        // (mantissa product, exponent) of every term of row i.
+        let mut dots: Vec<Vec<(i64, i32)>> = (0..n)
+            .map(|j| {
+                (0..k)
+                    .map(|p| {
+                        let x = ctx.banks[pa][i * k + p] as i8 as i64;
+                        let y = ctx.banks[pb][p * n + j] as i8 as i64;
+                        (x * y, a_exp + b_exps[p])
+                    })
+                    .collect()
+            })
+            .collect();
+        if accumulate && fc == NumFormat::Bfp8 {
+            let c_exp = ctx.bfp_exps[c as usize][i] as i32;
+            for (j, terms) in dots.iter_mut().enumerate() {
+                terms.push((ctx.banks[pc][i * n + j] as i8 as i64, c_exp));
+            }
+        }
+        let top = dots.iter().flatten().map(|&(_, e)| e).max().unwrap_or(0);
+        let sums: Vec<f32> = dots
+            .iter()
+            .map(|terms| {
+                let sum: i64 = terms.iter().map(|&(t, e)| t >> (top - e).min(63)).sum();
+                (sum as f64 * 2f64.powi(top)) as f32
+            })
+            .collect();
+        if fc == NumFormat::Bfp8 {
+            let (exp, mantissas) = Bfp8::encode_block(&sums);
+            ctx.bfp_exps[c as usize][i] = exp;
+            for (byte, mantissa) in ctx.banks[pc][i * n..i * n + n].iter_mut().zip(mantissas) {
+                *byte = mantissa as u8;
+            }
+        } else {
+            for (j, sum) in sums.into_iter().enumerate() {
+                let prior = if accumulate {
+                    fc.load(cw, &ctx.banks[pc], i * n + j)
+                } else {
+                    0.0
+                };
+                fc.store(cw, &mut ctx.banks[pc], i * n + j, prior + sum);
+            }
+        }
+    }
+}
@@ -0,0 +1,253 @@
+//===- 49_conv.rs - CONV instruction (direct 2-D convolution) --------------===//
+//
+// Sliding-window convolution over bank data, without lowering to matmul. The
+// input is a CHW feature map and the weights are OIHW, each stored flat from
+// offset 0 in the element width its bank declared at mset. The output is CHW
+// in the output bank's width; sums accumulate in 64 bits and saturate.
+// Padding reads as zero. An accumulator output bank adds the results to what
+// it already holds.
+//
+// Kernel dilation spaces the kernel taps that many pixels apart. Input
+// dilation spreads the input pixels that far apart with zeros between them,
+// before padding. rot180 flips each kernel upside down and left to right.
+// Input dilation plus rot180 with padding k-1-p turns the instruction into
+// the transposed convolution of stride d and padding p, channels permitting.
+//
+// rs1[9:0]:    input vbank (BANK0)
+// rs1[19:10]:  weight vbank (BANK1)
+// rs1[29:20]:  output vbank (BANK2)
+// rs2[7:0]:    input channels
+// rs2[15:8]:   output channels
+// rs2[25:16]:  input height
+// rs2[35:26]:  input width
+// rs2[39:36]:  kernel height
+// rs2[43:40]:  kernel width
+// rs2[47:44]:  stride (0 reads as 1)
+// rs2[51:48]:  padding
+// rs2[55:52]:  kernel dilation (0 reads as 1)
+// rs2[59:56]:  input dilation (0 reads as 1)
+// rs2[60]:     rot180
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use super::super::bank::{ArrayGeometry, BankConfig};
+use super::decode::{pbank, rs1_b0, rs1_b1, rs1_b2};
+use super::instruction::{ExecContext, Instruction, Unit};
+use crate::warnings::WarningKind;
+
+/// Convolution shape decoded from rs2.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConvShape {
+    pub in_ch: usize,
+    pub out_ch: usize,
+    pub in_h: usize,
+    pub in_w: usize,
+    pub kernel_h: usize,
+    pub kernel_w: usize,
+    pub stride: usize,
+    pub padding: usize,
+    pub dilation: usize,
+    pub input_dilation: usize,
+    pub rot180: bool,
+}
+
+impl ConvShape {
+    pub fn decode(xs2: u64) -> Self {
+        let field = |lo: u32, bits: u32| ((xs2 >> lo) & ((1 << bits) - 1)) as usize;
+        Self {
+            in_ch: field(0, 8),
+            out_ch: field(8, 8),
+            in_h: field(16, 10),
+            in_w: field(26, 10),
+            kernel_h: field(36, 4),
+            kernel_w: field(40, 4),
+            stride: field(44, 4).max(1),
+            padding: field(48, 4),
+            dilation: field(52, 4).max(1),
+            input_dilation: field(56, 4).max(1),
+            rot180: field(60, 1) == 1,
+        }
+    }
+
+    /// Input (height, width) after input dilation, before padding.
+    pub fn dilated_in(&self) -> (usize, usize) {
+        let dilate = |n: usize| n.saturating_sub(1) * self.input_dilation + 1;
+        (dilate(self.in_h), dilate(self.in_w))
+    }
+
+    /// Output (height, width); zero if the kernel does not fit.
+    pub fn out_dims(&self) -> (usize, usize) {
+        let out = |n: usize, k: usize| match (n + 2 * self.padding).checked_sub(self.dilation * k.saturating_sub(1) + 1)
+        {
+            Some(span) => span / self.stride + 1,
+            None => 0,
+        };
+        let (in_h, in_w) = self.dilated_in();
+        (out(in_h, self.kernel_h), out(in_w, self.kernel_w))
+    }
+
+    fn macs(&self) -> u64 {
+        let (oh, ow) = self.out_dims();
+        (self.out_ch * oh * ow * self.in_ch * self.kernel_h * self.kernel_w) as u64
+    }
+}
+
+pub struct Conv;
+
+impl Instruction for Conv {
+    const FUNCT: u32 = 49;
+    const NAME: &'static str = "conv";
+    const READS_BANK: bool = true;
+    const WRITES_BANK: bool = true;
+    const UNIT: Unit = Unit::Ball;
+
+    fn exec(xs1: u64, xs2: u64, ctx: &mut ExecContext) -> u64 {
+        let (input, weight, output) = (rs1_b0(xs1), rs1_b1(xs1), rs1_b2(xs1));
+        let s = ConvShape::decode(xs2);
+
+        for bank_id in [input, weight, output] {
+            if bank_id >= ctx.bank_count() as u64 {
+                panic!("conv: invalid bank_id {bank_id}");
+            }
+            let cfg = ctx.cfgs[bank_id as usize];
+            if !cfg.allocated {
+                panic!("conv: bank {bank_id} not allocated");
+            }
+            if cfg.cols > 1 {
+                panic!(
+                    "conv: bank {bank_id} spans {} groups; only single-group banks",
+                    cfg.cols
+                );
+            }
+        }
+        if output == input || output == weight {
+            panic!("conv: output bank {output} aliases an input");
+        }
+        if s.in_ch == 0 || s.out_ch == 0 || s.kernel_h == 0 || s.kernel_w == 0 {
+            panic!("conv: channels and kernel size must be > 0, got {s:?}");
+        }
+        let (out_h, out_w) = s.out_dims();
+        let (dil_h, dil_w) = s.dilated_in();
+        if out_h == 0 || out_w == 0 {
+            panic!(
+                "conv: {}x{} kernel does not fit a padded {}x{} input",
+                s.kernel_h, s.kernel_w, s.in_h, s.in_w
+            );
+        }
+
+        let (iw, ww, ow) = (
+            ctx.cfgs[input as usize].width,
+            ctx.cfgs[weight as usize].width,
+            ctx.cfgs[output as usize].width,
+        );
+        for (bank, width, len) in [
+            (input, iw, s.in_ch * s.in_h * s.in_w),
+            (weight, ww, s.out_ch * s.in_ch * s.kernel_h * s.kernel_w),
+            (output, ow, s.out_ch * out_h * out_w),
+        ] {
+            if len * width.bytes() > ctx.bank_size() {
+                panic!("conv: {len} i{} elements do not fit bank{bank}", width.bits());
+            }
+        }
+
+        if crate::trace::rtrace(Self::NAME) {
+            eprintln!("[RTRACE] conv: bank{input} * bank{weight} -> bank{output} {s:?} out={out_h}x{out_w}");
+        }
+
+        let (pi, pw, po) = (
+            pbank(ctx.bank_map, input),
+            pbank(ctx.bank_map, weight),
+            pbank(ctx.bank_map, output),
+        );
+        let accumulate = ctx.cfgs[output as usize].accumulator;
+        let mut saturated = 0;
+        for oc in 0..s.out_ch {
+            for oy in 0..out_h {
+                for ox in 0..out_w {
+                    let out = (oc * out_h + oy) * out_w + ox;
+                    let mut acc: i64 = if accumulate {
+                        ow.load(&ctx.banks[po], out) as i64
+                    } else {
+                        0
+                    };
+                    for ic in 0..s.in_ch {
+                        for ky in 0..s.kernel_h {
+                            // Position in the dilated input; the zeros input
+                            // dilation inserts read like padding.
+                            let Some(y) = (oy * s.stride + ky * s.dilation)
+                                .checked_sub(s.padding)
+                                .filter(|&y| y < dil_h && y % s.input_dilation == 0)
+                            else {
+                                continue;
+                            };
+                            for kx in 0..s.kernel_w {
+                                let Some(x) = (ox * s.stride + kx * s.dilation)
+                                    .checked_sub(s.padding)
+                                    .filter(|&x| x < dil_w && x % s.input_dilation == 0)
+                                else {
+                                    continue;
+                                };
+                                let (y, x) = (y / s.input_dilation, x / s.input_dilation);
+                                let (ty, tx) = match s.rot180 {
+                                    true => (s.kernel_h - 1 - ky, s.kernel_w - 1 - kx),
+                                    false => (ky, kx),
+                                };
+                                let a = iw.load(&ctx.banks[pi], (ic * s.in_h + y) * s.in_w + x) as i64;
+                                let k = ww.load(
+                                    &ctx.banks[pw],
+                                    ((oc * s.in_ch + ic) * s.kernel_h + ty) * s.kernel_w + tx,
+                                ) as i64;
+                                acc += a * k;
+                            }
+                        }
+                    }
+                    if ow.store(&mut ctx.banks[po], out, acc) {
+                        saturated += 1;
+                    }
+                }
+            }
+        }
+
+        ctx.perf.macs += s.macs();
+        ctx.perf.bank_read_bytes += ((s.in_ch * s.in_h * s.in_w) * iw.bytes()
+            + (s.out_ch * s.in_ch * s.kernel_h * s.kernel_w) * ww.bytes()) as u64;
+        let out_bytes = (s.out_ch * out_h * out_w * ow.bytes()) as u64;
+        if accumulate {
+            ctx.perf.bank_read_bytes += out_bytes;
+        }
+        ctx.perf.bank_write_bytes += out_bytes;
+
+        if saturated > 0 {
+            ctx.warnings.record(WarningKind::ConvSaturated, || {
+                format!(
+                    "bank{output} (i{}): {saturated} of {} outputs saturated",
+                    ow.bits(),
+                    s.out_ch * out_h * out_w
+                )
+            });
+        }
+        0
+    }
+
+    fn latency(xs1: u64, xs2: u64) -> u64 {
+        Self::array_latency(xs1, xs2, ArrayGeometry::default(), &[])
+    }
+
+    fn array_latency(_xs1: u64, xs2: u64, array: ArrayGeometry, _cfgs: &[BankConfig]) -> u64 {
+        // Every PE busy each cycle, plus the array's fill and drain.
+        let macs = ConvShape::decode(xs2).macs();
+        macs.div_ceil(array.peak_macs()).max(1) + array.rows as u64
+    }
+
+    fn bank_extents(xs1: u64, xs2: u64, cfgs: &[BankConfig]) -> Vec<(u64, usize, usize)> {
+        let (input, weight, output) = (rs1_b0(xs1), rs1_b1(xs1), rs1_b2(xs1));
+        let bytes = |bank: u64| cfgs.get(bank as usize).copied().unwrap_or_default().width.bytes();
+        let s = ConvShape::decode(xs2);
+        let (out_h, out_w) = s.out_dims();
+        vec![
+            (input, 0, s.in_ch * s.in_h * s.in_w * bytes(input)),
+            (weight, 0, s.out_ch * s.in_ch * s.kernel_h * s.kernel_w * bytes(weight)),
+            (output, 0, s.out_ch * out_h * out_w * bytes(output)),
+        ]
+    }
+}
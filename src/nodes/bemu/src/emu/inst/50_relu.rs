@@ -0,0 +1,89 @@
+//===- 50_relu.rs - RELU instruction (clamp negatives to zero) -------------===//
+//
+// dst = max(src, 0) elementwise, in the element width both banks declared
+// at mset. src and dst may be the same bank, which is how a layer's result
+// is rectified in place before mvout. Both banks are treated as flat element
+// arrays starting at offset 0, as mcopy lays them out.
+//
+// rs1[9:0]:    src vbank (BANK0)
+// rs1[19:10]:  dst vbank (BANK1)
+// rs1[63:30]:  rows (BB_ITER, 16-byte rows to rectify)
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use super::super::bank::BankConfig;
+use super::decode::{pbank, rs1_b0, rs1_b1, rs1_iter};
+use super::instruction::{ExecContext, Instruction, Unit};
+
+pub struct Relu;
+
+impl Instruction for Relu {
+    const FUNCT: u32 = 50;
+    const NAME: &'static str = "relu";
+    const READS_BANK: bool = true;
+    const WRITES_BANK: bool = true;
+    const UNIT: Unit = Unit::Ball;
+
+    fn exec(xs1: u64, _xs2: u64, ctx: &mut ExecContext) -> u64 {
+        let src = rs1_b0(xs1);
+        let dst = rs1_b1(xs1);
+        let rows = rs1_iter(xs1);
+
+        for bank_id in [src, dst] {
+            if bank_id >= ctx.bank_count() as u64 {
+                panic!("relu: invalid bank_id {bank_id}");
+            }
+            let cfg = ctx.cfgs[bank_id as usize];
+            if !cfg.allocated {
+                panic!("relu: bank {bank_id} not allocated");
+            }
+            if cfg.cols > 1 {
+                panic!(
+                    "relu: bank {bank_id} spans {} groups; only single-group banks",
+                    cfg.cols
+                );
+            }
+        }
+
+        let width = ctx.cfgs[src as usize].width;
+        let dst_w = ctx.cfgs[dst as usize].width;
+        if width != dst_w {
+            panic!(
+                "relu: bank{src} is i{} but bank{dst} is i{}; use mcopy to convert",
+                width.bits(),
+                dst_w.bits()
+            );
+        }
+        let elems = rows as usize * 16 / width.bytes();
+
+        if crate::trace::rtrace(Self::NAME) {
+            eprintln!(
+                "[RTRACE] relu: bank{src} -> bank{dst} (i{}) rows={rows} elems={elems}",
+                width.bits()
+            );
+        }
+
+        if rows as usize * 16 > ctx.bank_size() {
+            panic!("relu: {rows} rows do not fit bank{src}");
+        }
+
+        let sp = pbank(ctx.bank_map, src);
+        let values: Vec<i32> = (0..elems).map(|i| width.load(&ctx.banks[sp], i).max(0)).collect();
+        let dp = pbank(ctx.bank_map, dst);
+        for (i, v) in values.into_iter().enumerate() {
+            width.store(&mut ctx.banks[dp], i, v as i64);
+        }
+        ctx.perf.bank_read_bytes += rows * 16;
+        ctx.perf.bank_write_bytes += rows * 16;
+        0
+    }
+
+    fn latency(xs1: u64, _xs2: u64) -> u64 {
+        rs1_iter(xs1).max(1)
+    }
+
+    fn bank_extents(xs1: u64, _xs2: u64, _cfgs: &[BankConfig]) -> Vec<(u64, usize, usize)> {
+        let bytes = rs1_iter(xs1) as usize * 16;
+        vec![(rs1_b0(xs1), 0, bytes), (rs1_b1(xs1), 0, bytes)]
+    }
+}
@@ -0,0 +1,108 @@
+//===- 51_transpose.rs - TRANSPOSE instruction (bank tile transpose) -------===//
+//
+// Transposes one tile, e.g. to turn a row-major weight matrix into the B
+// operand matmul expects. src holds R rows of C elements, one 16-byte bank
+// row each (C = 16 / element bytes, R <= C). dst receives C rows of R
+// elements, one bank row each, with the rest of every row zeroed. Both
+// banks must declare the same element width, and they must differ.
+//
+// rs1[9:0]:    src vbank (BANK0)
+// rs1[19:10]:  dst vbank (BANK1)
+// rs1[63:30]:  R (BB_ITER, rows of src)
+//
+// The tile streams through the array like a matmul pass: one src row in per
+// cycle, then C cycles to drain the columns.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use super::super::bank::{ArrayGeometry, BankConfig};
+use super::decode::{pbank, rs1_b0, rs1_b1, rs1_iter};
+use super::instruction::{ExecContext, Instruction, Unit};
+
+pub struct Transpose;
+
+impl Instruction for Transpose {
+    const FUNCT: u32 = 51;
+    const NAME: &'static str = "transpose";
+    const READS_BANK: bool = true;
+    const WRITES_BANK: bool = true;
+    const UNIT: Unit = Unit::Ball;
+
+    fn exec(xs1: u64, _xs2: u64, ctx: &mut ExecContext) -> u64 {
+        let src = rs1_b0(xs1);
+        let dst = rs1_b1(xs1);
+        let rows = rs1_iter(xs1) as usize;
+
+        for bank_id in [src, dst] {
+            if bank_id >= ctx.bank_count() as u64 {
+                panic!("transpose: invalid bank_id {bank_id}");
+            }
+            let cfg = ctx.cfgs[bank_id as usize];
+            if !cfg.allocated {
+                panic!("transpose: bank {bank_id} not allocated");
+            }
+            if cfg.cols > 1 {
+                panic!(
+                    "transpose: bank {bank_id} spans {} groups; only single-group banks",
+                    cfg.cols
+                );
+            }
+        }
+        if src == dst {
+            panic!("transpose: dst bank {dst} aliases src");
+        }
+
+        let width = ctx.cfgs[src as usize].width;
+        let dst_w = ctx.cfgs[dst as usize].width;
+        if width != dst_w {
+            panic!(
+                "transpose: bank{src} is i{} but bank{dst} is i{}; use mcopy to convert",
+                width.bits(),
+                dst_w.bits()
+            );
+        }
+        let cols = 16 / width.bytes();
+        if rows == 0 || rows > cols {
+            panic!("transpose: rows must be 1..={cols} for i{}, got {rows}", width.bits());
+        }
+        if cols * 16 > ctx.bank_size() {
+            panic!("transpose: a {cols}-row tile does not fit bank{dst}");
+        }
+
+        if crate::trace::rtrace(Self::NAME) {
+            eprintln!(
+                "[RTRACE] transpose: bank{src} -> bank{dst} (i{}) {rows}x{cols}",
+                width.bits()
+            );
+        }
+
+        let (sp, dp) = (pbank(ctx.bank_map, src), pbank(ctx.bank_map, dst));
+        ctx.banks[dp][..cols * 16].fill(0);
+        for i in 0..rows {
+            for j in 0..cols {
+                let v = width.load(&ctx.banks[sp], i * cols + j);
+                width.store(&mut ctx.banks[dp], j * cols + i, v as i64);
+            }
+        }
+        ctx.perf.bank_read_bytes += (rows * 16) as u64;
+        ctx.perf.bank_write_bytes += (cols * 16) as u64;
+        0
+    }
+
+    fn latency(xs1: u64, xs2: u64) -> u64 {
+        Self::array_latency(xs1, xs2, ArrayGeometry::default(), &[])
+    }
+
+    fn array_latency(xs1: u64, _xs2: u64, _array: ArrayGeometry, cfgs: &[BankConfig]) -> u64 {
+        let width = cfgs.get(rs1_b0(xs1) as usize).copied().unwrap_or_default().width;
+        rs1_iter(xs1).max(1) + (16 / width.bytes()) as u64
+    }
+
+    fn bank_extents(xs1: u64, _xs2: u64, cfgs: &[BankConfig]) -> Vec<(u64, usize, usize)> {
+        let width = cfgs.get(rs1_b0(xs1) as usize).copied().unwrap_or_default().width;
+        vec![
+            (rs1_b0(xs1), 0, rs1_iter(xs1) as usize * 16),
+            (rs1_b1(xs1), 0, 16 / width.bytes() * 16),
+        ]
+    }
+}
@@ -0,0 +1,46 @@
+//===- 52_loop_ab.rs - LOOP_AB instruction (loop_ws operands) --------------===//
+//
+// Sets the A and B operands of the next loop_ws: where each matrix lives in
+// DRAM and which bank it is staged through. The banks must already be
+// allocated when loop_ws runs.
+//
+// rs1[38:0]:   A DRAM address (M x K i8, row-major)
+// rs1[48:39]:  A vbank
+// rs2[38:0]:   B DRAM address (K x N i8, row-major)
+// rs2[48:39]:  B vbank
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use super::instruction::{ExecContext, Instruction, LoopRegs};
+
+pub struct LoopAb;
+
+const ADDR_MASK: u64 = (1 << 39) - 1;
+
+impl Instruction for LoopAb {
+    const FUNCT: u32 = 52;
+    const NAME: &'static str = "loop_ab";
+
+    fn exec(xs1: u64, xs2: u64, ctx: &mut ExecContext) -> u64 {
+        let regs = LoopRegs {
+            a_addr: xs1 & ADDR_MASK,
+            a_bank: (xs1 >> 39) & 0x3ff,
+            b_addr: xs2 & ADDR_MASK,
+            b_bank: (xs2 >> 39) & 0x3ff,
+        };
+
+        if crate::trace::rtrace(Self::NAME) {
+            eprintln!(
+                "[RTRACE] loop_ab: A 0x{:x} via bank{}, B 0x{:x} via bank{}",
+                regs.a_addr, regs.a_bank, regs.b_addr, regs.b_bank
+            );
+        }
+
+        *ctx.loop_regs = regs;
+        0
+    }
+
+    fn latency(_xs1: u64, _xs2: u64) -> u64 {
+        1
+    }
+}
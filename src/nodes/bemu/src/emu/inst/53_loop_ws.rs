@@ -0,0 +1,109 @@
+//===- 53_loop_ws.rs - LOOP_WS instruction (tiled matmul) ------------------===//
+//
+// C = A * B for whole DRAM matrices in one instruction, like Gemmini's
+// loop_ws. The accelerator expands it into the mvin, matmul and mvout
+// sequence a host would otherwise issue: for every block of rows that fits
+// the banks and every 16-column tile of C, it loads each 16-wide slice of A
+// and 16-row slice of B, accumulates them into C, and writes the C tile back
+// row-major with one 2-D mvout. The A and B operands come from the last
+// loop_ab.
+//
+// rs1[38:0]:   C DRAM address (M x N i32, row-major)
+// rs1[48:39]:  C vbank (i32)
+// rs2[15:0]:   M
+// rs2[31:16]:  K (multiple of 16)
+// rs2[47:32]:  N (multiple of 16)
+//
+// The A and B banks must be i8. The expansion runs through the same decode
+// and issue path as host instructions: Npu executes the micro-ops one by
+// one, and NpuSim queues them ahead of younger work, which may overlap them
+// under its scoreboard.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use super::super::bank::{BankConfig, ElemWidth};
+use super::f16_mvout::Mvout;
+use super::f33_mvin::Mvin;
+use super::f48_matmul::Matmul;
+use super::instruction::{ExecContext, Instruction, LoopRegs};
+
+pub struct LoopWs;
+
+/// (M, K, N) of a loop_ws.
+pub fn dims(xs2: u64) -> (usize, usize, usize) {
+    let field = |shift: u32| ((xs2 >> shift) & 0xffff) as usize;
+    (field(0), field(16), field(32))
+}
+
+/// The micro-ops (funct, rs1, rs2) a loop_ws expands to, given the last
+/// loop_ab, the bank configs and the bank size in bytes.
+pub fn expand(
+    regs: &LoopRegs,
+    xs1: u64,
+    xs2: u64,
+    cfgs: &[BankConfig],
+    bank_bytes: usize,
+) -> Result<Vec<(u32, u64, u64)>, String> {
+    let (c_addr, c_bank) = (xs1 & ((1 << 39) - 1), (xs1 >> 39) & 0x3ff);
+    let (m, k, n) = dims(xs2);
+    if m == 0 || k == 0 || n == 0 {
+        return Err(format!("dimensions must be > 0, got m={m} k={k} n={n}"));
+    }
+    if !k.is_multiple_of(16) || !n.is_multiple_of(16) {
+        return Err(format!("k and n must be multiples of 16, got k={k} n={n}"));
+    }
+    for (role, bank, width) in [
+        ("A", regs.a_bank, ElemWidth::I8),
+        ("B", regs.b_bank, ElemWidth::I8),
+        ("C", c_bank, ElemWidth::I32),
+    ] {
+        let cfg = cfgs.get(bank as usize).copied().unwrap_or_default();
+        if !cfg.allocated || cfg.cols > 1 || cfg.width != width {
+            return Err(format!(
+                "{role} bank {bank} must be an allocated single-group i{} bank",
+                width.bits()
+            ));
+        }
+    }
+    // One i32 C row is four lines; A rows are one line each.
+    let m_tile = m.min(bank_bytes / 64);
+    if m_tile == 0 {
+        return Err(format!("a {bank_bytes}-byte bank cannot hold one C row"));
+    }
+    let (k_lines, n_lines) = ((k / 16) as u64, (n / 16) as u64);
+    let mut ops = Vec::new();
+    for m0 in (0..m).step_by(m_tile) {
+        let rows = (m_tile.min(m - m0) as u64) << 30;
+        for nt in 0..n / 16 {
+            for kt in 0..k / 16 {
+                let a = regs.a_addr + (m0 * k + kt * 16) as u64;
+                let b = regs.b_addr + (kt * 16 * n + nt * 16) as u64;
+                ops.push((Mvin::FUNCT, regs.a_bank | rows, a | (k_lines << 39)));
+                ops.push((Mvin::FUNCT, regs.b_bank | (16 << 30), b | (n_lines << 39)));
+                ops.push((
+                    Matmul::FUNCT,
+                    regs.a_bank | (regs.b_bank << 10) | (c_bank << 20) | rows,
+                    u64::from(kt > 0),
+                ));
+            }
+            let c = c_addr + ((m0 * n + nt * 16) * 4) as u64;
+            ops.push((Mvout::FUNCT, c_bank | (4 << 10) | rows, c | ((n as u64 / 4) << 39)));
+        }
+    }
+    Ok(ops)
+}
+
+impl Instruction for LoopWs {
+    const FUNCT: u32 = 53;
+    const NAME: &'static str = "loop_ws";
+    const READS_BANK: bool = true;
+    const WRITES_BANK: bool = true;
+
+    fn exec(_xs1: u64, _xs2: u64, _ctx: &mut ExecContext) -> u64 {
+        unreachable!("loop_ws is expanded into micro-ops before it executes")
+    }
+
+    fn latency(_xs1: u64, _xs2: u64) -> u64 {
+        1
+    }
+}
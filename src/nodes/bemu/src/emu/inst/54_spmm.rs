@@ -0,0 +1,167 @@
+//===- 54_spmm.rs - SPMM instruction (sparse x dense matrix multiply) ------===//
+//
+// C = A * B (or C += A * B) like matmul, with A stored in its bank as CSR
+// instead of dense rows. B and C are laid out as for matmul, and A's rows
+// are still K = 16 / A bytes elements wide. The A bank holds, from offset 0:
+//
+//   row pointers     M + 1 u16, little-endian; row i's nonzeros are entries
+//                    [ptr[i], ptr[i + 1]) of the two arrays below
+//   column indices   NNZ u16, each below K
+//   values           NNZ elements of A's width, from the next 16-byte
+//                    boundary
+//
+// rs1[9:0]:    A vbank (BANK0, CSR)
+// rs1[19:10]:  B vbank (BANK1)
+// rs1[29:20]:  C vbank (BANK2)
+// rs1[63:30]:  M (BB_ITER, rows of A)
+// rs2[0]:      accumulate into C instead of overwriting it (always on when C
+//              is an accumulator bank)
+// rs2[63:32]:  NNZ, which must equal ptr[M]
+//
+// The array consumes one nonzero per PE row per cycle, so each tile of C
+// columns takes ceil(NNZ / R) cycles plus the fill and drain. When K equals
+// R, full density costs what the dense matmul does. NNZ is an operand so the
+// latency is known at issue.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use super::super::bank::{ArrayGeometry, BankConfig};
+use super::decode::{pbank, rs1_b0, rs1_b1, rs1_b2, rs1_iter};
+use super::instruction::{ExecContext, Instruction, Unit};
+use crate::warnings::WarningKind;
+
+pub struct Spmm;
+
+impl Spmm {
+    /// Byte offset of the values in a CSR bank.
+    pub fn values_offset(m: usize, nnz: usize) -> usize {
+        (2 * (m + 1 + nnz)).next_multiple_of(16)
+    }
+}
+
+impl Instruction for Spmm {
+    const FUNCT: u32 = 54;
+    const NAME: &'static str = "spmm";
+    const READS_BANK: bool = true;
+    const WRITES_BANK: bool = true;
+    const UNIT: Unit = Unit::Ball;
+
+    fn exec(xs1: u64, xs2: u64, ctx: &mut ExecContext) -> u64 {
+        let (a, b, c) = (rs1_b0(xs1), rs1_b1(xs1), rs1_b2(xs1));
+        let m = rs1_iter(xs1) as usize;
+        let accumulate = xs2 & 1 == 1;
+        let nnz = (xs2 >> 32) as usize;
+
+        for bank_id in [a, b, c] {
+            if bank_id >= ctx.bank_count() as u64 {
+                panic!("spmm: invalid bank_id {bank_id}");
+            }
+            let cfg = ctx.cfgs[bank_id as usize];
+            if !cfg.allocated {
+                panic!("spmm: bank {bank_id} not allocated");
+            }
+            if cfg.cols > 1 {
+                panic!(
+                    "spmm: bank {bank_id} spans {} groups; only single-group banks",
+                    cfg.cols
+                );
+            }
+        }
+        if c == a || c == b {
+            panic!("spmm: C bank {c} aliases an input");
+        }
+        let accumulate = accumulate || ctx.cfgs[c as usize].accumulator;
+
+        let (aw, bw, cw) = (
+            ctx.cfgs[a as usize].width,
+            ctx.cfgs[b as usize].width,
+            ctx.cfgs[c as usize].width,
+        );
+        let k = 16 / aw.bytes();
+        let n = 16 / bw.bytes();
+        let values = Self::values_offset(m, nnz);
+
+        if crate::trace::rtrace(Self::NAME) {
+            eprintln!(
+                "[RTRACE] spmm: bank{a} (i{}, csr) x bank{b} (i{}) -> bank{c} (i{}) m={m} k={k} n={n} nnz={nnz} acc={accumulate}",
+                aw.bits(),
+                bw.bits(),
+                cw.bits()
+            );
+        }
+
+        if values + nnz * aw.bytes() > ctx.bank_size() {
+            panic!("spmm: {m} rows with {nnz} nonzeros do not fit bank{a}");
+        }
+        if m * n * cw.bytes() > ctx.bank_size() {
+            panic!("spmm: {m}x{n} result does not fit bank{c} (i{})", cw.bits());
+        }
+
+        let (pa, pb, pc) = (pbank(ctx.bank_map, a), pbank(ctx.bank_map, b), pbank(ctx.bank_map, c));
+        let u16_at = |bank: &[u8], i: usize| u16::from_le_bytes([bank[2 * i], bank[2 * i + 1]]) as usize;
+        let ptr: Vec<usize> = (0..=m).map(|i| u16_at(&ctx.banks[pa], i)).collect();
+        if ptr[0] != 0 || ptr[m] != nnz || ptr.windows(2).any(|w| w[0] > w[1]) {
+            panic!("spmm: bank{a} row pointers {ptr:?} do not describe {nnz} nonzeros");
+        }
+        let cols: Vec<usize> = (0..nnz).map(|p| u16_at(&ctx.banks[pa], m + 1 + p)).collect();
+        if let Some(&col) = cols.iter().find(|&&col| col >= k) {
+            panic!("spmm: bank{a} column index {col} is not below K={k}");
+        }
+
+        let mut saturated = 0;
+        for i in 0..m {
+            for j in 0..n {
+                let mut acc: i64 = if accumulate {
+                    cw.load(&ctx.banks[pc], i * n + j) as i64
+                } else {
+                    0
+                };
+                let row = ptr[i]..ptr[i + 1];
+                for (p, &col) in row.clone().zip(&cols[row]) {
+                    let x = aw.load(&ctx.banks[pa][values..], p) as i64;
+                    let y = bw.load(&ctx.banks[pb], col * n + j) as i64;
+                    acc += x * y;
+                }
+                if cw.store(&mut ctx.banks[pc], i * n + j, acc) {
+                    saturated += 1;
+                }
+            }
+        }
+
+        let c_bytes = (m * n * cw.bytes()) as u64;
+        ctx.perf.macs += (nnz * n) as u64;
+        ctx.perf.bank_read_bytes +=
+            (values + nnz * aw.bytes() + nnz * 16) as u64 + if accumulate { c_bytes } else { 0 };
+        ctx.perf.bank_write_bytes += c_bytes;
+
+        if saturated > 0 {
+            ctx.warnings.record(WarningKind::MatmulSaturated, || {
+                format!("bank{c} (i{}): {saturated} of {} results saturated", cw.bits(), m * n)
+            });
+        }
+        0
+    }
+
+    fn latency(xs1: u64, xs2: u64) -> u64 {
+        Self::array_latency(xs1, xs2, ArrayGeometry::default(), &[])
+    }
+
+    fn array_latency(xs1: u64, xs2: u64, array: ArrayGeometry, cfgs: &[BankConfig]) -> u64 {
+        let width = |bank: u64| cfgs.get(bank as usize).copied().unwrap_or_default().width;
+        let n = 16 / width(rs1_b1(xs1)).bytes();
+        let nnz = xs2 >> 32;
+        n.div_ceil(array.cols) as u64 * (nnz.div_ceil(array.rows as u64) + array.rows as u64)
+    }
+
+    fn bank_extents(xs1: u64, xs2: u64, cfgs: &[BankConfig]) -> Vec<(u64, usize, usize)> {
+        let (a, b, c) = (rs1_b0(xs1), rs1_b1(xs1), rs1_b2(xs1));
+        let width = |bank: u64| cfgs.get(bank as usize).copied().unwrap_or_default().width;
+        let (m, nnz) = (rs1_iter(xs1) as usize, (xs2 >> 32) as usize);
+        let (k, n) = (16 / width(a).bytes(), 16 / width(b).bytes());
+        vec![
+            (a, 0, Self::values_offset(m, nnz) + nnz * width(a).bytes()),
+            (b, 0, k * 16),
+            (c, 0, m * n * width(c).bytes()),
+        ]
+    }
+}
@@ -0,0 +1,158 @@
+//===- 55_im2col.rs - IM2COL instruction (conv patch unrolling) ------------===//
+//
+// Unrolls the input patches of a convolution into a matrix, so the conv can
+// run as a matmul against the weights without the host reshuffling data.
+// The input is a CHW feature map as conv reads it. Each output pixel
+// (oy, ox) becomes patch row m = oy * out_w + ox, and each kernel tap
+// (ic, ky, kx) becomes column (ic * kh + ky) * kw + kx, the order OIHW
+// weights are stored in. Padding and input dilation read as zero, as in
+// conv.
+//
+// The patch matrix is written as matmul A operands: columns are cut into
+// K-tiles of one 16-byte line (16 / element bytes columns), the last tile
+// zero-padded. K-tile t holds the M patch rows at lines [t * M, (t + 1) * M).
+// A patch of at most one line is therefore a single matmul A of M rows.
+// Multiplying it by B = the weights as (ic, ky, kx) x oc gives the conv
+// output in HWC order.
+//
+// rs1[9:0]:    input vbank (BANK0)
+// rs1[19:10]:  patch vbank (BANK1), same element width as the input
+// rs2:         the conv shape fields of 49_conv.rs; output channels are
+//              ignored and rot180 must be clear
+//
+// One patch line is written per cycle.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use super::super::bank::{ArrayGeometry, BankConfig};
+use super::decode::{pbank, rs1_b0, rs1_b1};
+use super::f49_conv::ConvShape;
+use super::instruction::{ExecContext, Instruction, Unit};
+
+pub struct Im2col;
+
+impl Im2col {
+    /// (patch rows, K-tiles) of the patch matrix for `s` at `elem_bytes`.
+    pub fn dims(s: &ConvShape, elem_bytes: usize) -> (usize, usize) {
+        let (out_h, out_w) = s.out_dims();
+        let k = s.in_ch * s.kernel_h * s.kernel_w;
+        (out_h * out_w, k.div_ceil(16 / elem_bytes))
+    }
+}
+
+impl Instruction for Im2col {
+    const FUNCT: u32 = 55;
+    const NAME: &'static str = "im2col";
+    const READS_BANK: bool = true;
+    const WRITES_BANK: bool = true;
+    const UNIT: Unit = Unit::Ball;
+
+    fn exec(xs1: u64, xs2: u64, ctx: &mut ExecContext) -> u64 {
+        let (input, patch) = (rs1_b0(xs1), rs1_b1(xs1));
+        let s = ConvShape::decode(xs2);
+
+        for bank_id in [input, patch] {
+            if bank_id >= ctx.bank_count() as u64 {
+                panic!("im2col: invalid bank_id {bank_id}");
+            }
+            let cfg = ctx.cfgs[bank_id as usize];
+            if !cfg.allocated {
+                panic!("im2col: bank {bank_id} not allocated");
+            }
+            if cfg.cols > 1 {
+                panic!(
+                    "im2col: bank {bank_id} spans {} groups; only single-group banks",
+                    cfg.cols
+                );
+            }
+        }
+        if patch == input {
+            panic!("im2col: patch bank {patch} aliases the input");
+        }
+        let width = ctx.cfgs[input as usize].width;
+        if ctx.cfgs[patch as usize].width != width {
+            panic!("im2col: banks {input} and {patch} have different element widths");
+        }
+        if s.in_ch == 0 || s.kernel_h == 0 || s.kernel_w == 0 {
+            panic!("im2col: channels and kernel size must be > 0, got {s:?}");
+        }
+        if s.rot180 {
+            panic!("im2col: rot180 applies to conv weights; flip the B operand instead");
+        }
+        let (out_h, out_w) = s.out_dims();
+        let (dil_h, dil_w) = s.dilated_in();
+        if out_h == 0 || out_w == 0 {
+            panic!(
+                "im2col: {}x{} kernel does not fit a padded {}x{} input",
+                s.kernel_h, s.kernel_w, s.in_h, s.in_w
+            );
+        }
+        let per_line = 16 / width.bytes();
+        let (m, tiles) = Self::dims(&s, width.bytes());
+        if s.in_ch * s.in_h * s.in_w * width.bytes() > ctx.bank_size() {
+            panic!("im2col: input does not fit bank{input}");
+        }
+        if m * tiles * 16 > ctx.bank_size() {
+            panic!("im2col: {m} patch rows of {tiles} lines do not fit bank{patch}");
+        }
+
+        if crate::trace::rtrace(Self::NAME) {
+            eprintln!("[RTRACE] im2col: bank{input} -> bank{patch} {s:?} rows={m} tiles={tiles}");
+        }
+
+        let (pi, pp) = (pbank(ctx.bank_map, input), pbank(ctx.bank_map, patch));
+        ctx.banks[pp][..m * tiles * 16].fill(0);
+        for oy in 0..out_h {
+            for ox in 0..out_w {
+                let row = oy * out_w + ox;
+                for ic in 0..s.in_ch {
+                    for ky in 0..s.kernel_h {
+                        let Some(y) = (oy * s.stride + ky * s.dilation)
+                            .checked_sub(s.padding)
+                            .filter(|&y| y < dil_h && y % s.input_dilation == 0)
+                        else {
+                            continue;
+                        };
+                        for kx in 0..s.kernel_w {
+                            let Some(x) = (ox * s.stride + kx * s.dilation)
+                                .checked_sub(s.padding)
+                                .filter(|&x| x < dil_w && x % s.input_dilation == 0)
+                            else {
+                                continue;
+                            };
+                            let (y, x) = (y / s.input_dilation, x / s.input_dilation);
+                            let v = width.load(&ctx.banks[pi], (ic * s.in_h + y) * s.in_w + x);
+                            let col = (ic * s.kernel_h + ky) * s.kernel_w + kx;
+                            let at = ((col / per_line) * m + row) * per_line + col % per_line;
+                            width.store(&mut ctx.banks[pp], at, v as i64);
+                        }
+                    }
+                }
+            }
+        }
+
+        ctx.perf.bank_read_bytes += (s.in_ch * s.in_h * s.in_w * width.bytes()) as u64;
+        ctx.perf.bank_write_bytes += (m * tiles * 16) as u64;
+        0
+    }
+
+    fn latency(xs1: u64, xs2: u64) -> u64 {
+        Self::array_latency(xs1, xs2, ArrayGeometry::default(), &[])
+    }
+
+    fn array_latency(xs1: u64, xs2: u64, _array: ArrayGeometry, cfgs: &[BankConfig]) -> u64 {
+        let width = cfgs.get(rs1_b0(xs1) as usize).copied().unwrap_or_default().width;
+        let (m, tiles) = Self::dims(&ConvShape::decode(xs2), width.bytes());
+        (m * tiles).max(1) as u64
+    }
+
+    fn bank_extents(xs1: u64, xs2: u64, cfgs: &[BankConfig]) -> Vec<(u64, usize, usize)> {
+        let width = cfgs.get(rs1_b0(xs1) as usize).copied().unwrap_or_default().width;
+        let s = ConvShape::decode(xs2);
+        let (m, tiles) = Self::dims(&s, width.bytes());
+        vec![
+            (rs1_b0(xs1), 0, s.in_ch * s.in_h * s.in_w * width.bytes()),
+            (rs1_b1(xs1), 0, m * tiles * 16),
+        ]
+    }
+}
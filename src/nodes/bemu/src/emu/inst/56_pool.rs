@@ -0,0 +1,151 @@
+//===- 56_pool.rs - POOL instruction (max / average pooling) ---------------===//
+//
+// Pools every channel of a CHW feature map over a sliding window. The input
+// and output banks share one element width; the output is CHW from offset 0.
+// Max pooling ignores padded positions. Average pooling divides by the
+// window taps inside the input, rounding to nearest with ties upward, so no
+// result can overflow its width.
+//
+// rs1[9:0]:    input vbank (BANK0)
+// rs1[19:10]:  output vbank (BANK1)
+// rs2:         the conv shape fields of 49_conv.rs for channels, height,
+//              width, window, stride and padding; output channels and
+//              dilation are ignored
+// rs2[61]:     average instead of max
+//
+// One output line is produced per window tap, so an output of L lines
+// takes L * kh * kw cycles.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use super::super::bank::{ArrayGeometry, BankConfig};
+use super::decode::{pbank, rs1_b0, rs1_b1};
+use super::f49_conv::ConvShape;
+use super::instruction::{ExecContext, Instruction, Unit};
+
+pub struct Pool;
+
+impl Pool {
+    /// Window geometry of a pool, which has no dilation.
+    pub fn shape(xs2: u64) -> ConvShape {
+        ConvShape {
+            out_ch: 0,
+            dilation: 1,
+            input_dilation: 1,
+            rot180: false,
+            ..ConvShape::decode(xs2)
+        }
+    }
+}
+
+impl Instruction for Pool {
+    const FUNCT: u32 = 56;
+    const NAME: &'static str = "pool";
+    const READS_BANK: bool = true;
+    const WRITES_BANK: bool = true;
+    const UNIT: Unit = Unit::Ball;
+
+    fn exec(xs1: u64, xs2: u64, ctx: &mut ExecContext) -> u64 {
+        let (input, output) = (rs1_b0(xs1), rs1_b1(xs1));
+        let s = Self::shape(xs2);
+        let avg = (xs2 >> 61) & 1 == 1;
+
+        for bank_id in [input, output] {
+            if bank_id >= ctx.bank_count() as u64 {
+                panic!("pool: invalid bank_id {bank_id}");
+            }
+            let cfg = ctx.cfgs[bank_id as usize];
+            if !cfg.allocated {
+                panic!("pool: bank {bank_id} not allocated");
+            }
+            if cfg.cols > 1 {
+                panic!(
+                    "pool: bank {bank_id} spans {} groups; only single-group banks",
+                    cfg.cols
+                );
+            }
+        }
+        if output == input {
+            panic!("pool: output bank {output} aliases the input");
+        }
+        let width = ctx.cfgs[input as usize].width;
+        if ctx.cfgs[output as usize].width != width {
+            panic!("pool: banks {input} and {output} have different element widths");
+        }
+        if s.in_ch == 0 || s.kernel_h == 0 || s.kernel_w == 0 {
+            panic!("pool: channels and window size must be > 0, got {s:?}");
+        }
+        if s.padding >= s.kernel_h || s.padding >= s.kernel_w {
+            panic!("pool: padding {} leaves windows with no input", s.padding);
+        }
+        let (out_h, out_w) = s.out_dims();
+        if out_h == 0 || out_w == 0 {
+            panic!(
+                "pool: {}x{} window does not fit a padded {}x{} input",
+                s.kernel_h, s.kernel_w, s.in_h, s.in_w
+            );
+        }
+        for (bank, len) in [(input, s.in_ch * s.in_h * s.in_w), (output, s.in_ch * out_h * out_w)] {
+            if len * width.bytes() > ctx.bank_size() {
+                panic!("pool: {len} i{} elements do not fit bank{bank}", width.bits());
+            }
+        }
+
+        if crate::trace::rtrace(Self::NAME) {
+            let mode = if avg { "avg" } else { "max" };
+            eprintln!("[RTRACE] pool: {mode} bank{input} -> bank{output} {s:?} out={out_h}x{out_w}");
+        }
+
+        let (pi, po) = (pbank(ctx.bank_map, input), pbank(ctx.bank_map, output));
+        for c in 0..s.in_ch {
+            for oy in 0..out_h {
+                for ox in 0..out_w {
+                    let ys = (oy * s.stride..oy * s.stride + s.kernel_h)
+                        .filter_map(|y| y.checked_sub(s.padding).filter(|&y| y < s.in_h));
+                    let taps: Vec<i64> = ys
+                        .flat_map(|y| {
+                            (ox * s.stride..ox * s.stride + s.kernel_w)
+                                .filter_map(|x| x.checked_sub(s.padding).filter(|&x| x < s.in_w))
+                                .map(move |x| (y, x))
+                        })
+                        .map(|(y, x)| width.load(&ctx.banks[pi], (c * s.in_h + y) * s.in_w + x) as i64)
+                        .collect();
+                    let v = match avg {
+                        true => {
+                            let n = taps.len() as i64;
+                            (2 * taps.iter().sum::<i64>() + n).div_euclid(2 * n)
+                        }
+                        false => taps.iter().copied().max().unwrap_or_default(),
+                    };
+                    width.store(&mut ctx.banks[po], (c * out_h + oy) * out_w + ox, v);
+                }
+            }
+        }
+
+        ctx.perf.bank_read_bytes += (s.in_ch * s.in_h * s.in_w * width.bytes()) as u64;
+        ctx.perf.bank_write_bytes += (s.in_ch * out_h * out_w * width.bytes()) as u64;
+        0
+    }
+
+    fn latency(xs1: u64, xs2: u64) -> u64 {
+        Self::array_latency(xs1, xs2, ArrayGeometry::default(), &[])
+    }
+
+    fn array_latency(xs1: u64, xs2: u64, _array: ArrayGeometry, cfgs: &[BankConfig]) -> u64 {
+        let width = cfgs.get(rs1_b0(xs1) as usize).copied().unwrap_or_default().width;
+        let s = Self::shape(xs2);
+        let (out_h, out_w) = s.out_dims();
+        let lines = (s.in_ch * out_h * out_w * width.bytes()).div_ceil(16);
+        (lines * s.kernel_h * s.kernel_w).max(1) as u64
+    }
+
+    fn bank_extents(xs1: u64, xs2: u64, cfgs: &[BankConfig]) -> Vec<(u64, usize, usize)> {
+        let width = cfgs.get(rs1_b0(xs1) as usize).copied().unwrap_or_default().width;
+        let s = Self::shape(xs2);
+        let (out_h, out_w) = s.out_dims();
+        vec![
+            (rs1_b0(xs1), 0, s.in_ch * s.in_h * s.in_w * width.bytes()),
+            (rs1_b1(xs1), 0, s.in_ch * out_h * out_w * width.bytes()),
+        ]
+    }
+}
@@ -0,0 +1,182 @@
+//===- 57_norm.rs - NORM instruction (layernorm and softmax statistics) ----===//
+//
+// Streams i32 accumulator data through 16 statistics registers, in the
+// multi-pass style of Gemmini's normalizer, so a row longer than one
+// instruction can cover is reduced in pieces under one stat id:
+//
+//   0 reset      clear stat `id`
+//   1 sum        fold the elements into `id`: count, sum, sum of squares, max
+//   2 sum_exp    fold exp((x - max) / 2^in_frac) into `id`; needs max
+//   3 layernorm  write (x - mean) / stddev, scaled by 2^out_frac
+//   4 softmax    write exp((x - max) / 2^in_frac) / sum_exp, scaled by
+//                2^out_frac
+//
+// The elements are `count` i32 values from line `line` of the source bank.
+// layernorm and softmax write the same element positions, from the same
+// line, of the destination bank in its width, rounding to nearest and
+// saturating. A constant input normalizes to zero.
+//
+// rs1[9:0]:    source vbank (BANK0), i32
+// rs1[19:10]:  destination vbank (BANK1)
+// rs1[29:20]:  first line
+// rs1[63:30]:  count (BB_ITER)
+// rs2[3:0]:    stat id
+// rs2[7:4]:    op
+// rs2[15:8]:   in_frac, fraction bits of the input for exp
+// rs2[23:16]:  out_frac, fraction bits of the output
+//
+// A line of four elements streams per cycle; layernorm and softmax add the
+// divider's latency.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use super::super::bank::{BankConfig, ElemWidth};
+use super::decode::{pbank, rs1_b0, rs1_b1, rs1_b2, rs1_iter};
+use super::instruction::{ExecContext, Instruction, NormStat, Shared, Unit};
+use crate::warnings::WarningKind;
+
+/// Cycles the reciprocal square root or division takes after the stream.
+const DIVIDER_CYCLES: u64 = 8;
+
+const OPS: [&str; 5] = ["reset", "sum", "sum_exp", "layernorm", "softmax"];
+
+pub struct Norm;
+
+impl Norm {
+    /// Name of the op in rs2[7:4], if it is one.
+    pub fn op_name(xs2: u64) -> Option<&'static str> {
+        OPS.get(((xs2 >> 4) & 0xf) as usize).copied()
+    }
+}
+
+impl Instruction for Norm {
+    const FUNCT: u32 = 57;
+    const NAME: &'static str = "norm";
+    const READS_BANK: bool = true;
+    const WRITES_BANK: bool = true;
+    const UNIT: Unit = Unit::Ball;
+    const SHARED: &'static [Shared] = &[Shared::NormStats];
+
+    fn exec(xs1: u64, xs2: u64, ctx: &mut ExecContext) -> u64 {
+        let (src, dst, line) = (rs1_b0(xs1), rs1_b1(xs1), rs1_b2(xs1) as usize);
+        let count = rs1_iter(xs1) as usize;
+        let id = (xs2 & 0xf) as usize;
+        let op = Self::op_name(xs2).unwrap_or_else(|| panic!("norm: unknown op {}", (xs2 >> 4) & 0xf));
+        let in_scale = (1u64 << ((xs2 >> 8) & 0xff).min(62)) as f64;
+        let out_scale = (1u64 << ((xs2 >> 16) & 0xff).min(62)) as f64;
+
+        if crate::trace::rtrace(Self::NAME) {
+            eprintln!("[RTRACE] norm: {op} stat {id} bank{src} -> bank{dst} line={line} count={count}");
+        }
+        if op == "reset" {
+            ctx.norm_stats[id] = NormStat::default();
+            return 0;
+        }
+
+        let writes = matches!(op, "layernorm" | "softmax");
+        let banks: &[u64] = if writes { &[src, dst] } else { &[src] };
+        for &bank_id in banks {
+            if bank_id >= ctx.bank_count() as u64 {
+                panic!("norm: invalid bank_id {bank_id}");
+            }
+            let cfg = ctx.cfgs[bank_id as usize];
+            if !cfg.allocated {
+                panic!("norm: bank {bank_id} not allocated");
+            }
+            if cfg.cols > 1 {
+                panic!(
+                    "norm: bank {bank_id} spans {} groups; only single-group banks",
+                    cfg.cols
+                );
+            }
+        }
+        if ctx.cfgs[src as usize].width != ElemWidth::I32 {
+            panic!("norm: source bank {src} is not i32");
+        }
+        let dw = ctx.cfgs[dst as usize].width;
+        if (line * 16 + count * 4).max(line * 16 + count * dw.bytes()) > ctx.bank_size() {
+            panic!("norm: {count} elements from line {line} do not fit the bank");
+        }
+
+        let ps = pbank(ctx.bank_map, src);
+        let xs: Vec<i32> = (0..count)
+            .map(|i| ElemWidth::I32.load(&ctx.banks[ps], line * 4 + i))
+            .collect();
+        ctx.perf.bank_read_bytes += (count * 4) as u64;
+        let stat = &mut ctx.norm_stats[id];
+        let exp = |x: i32, max: i32| ((x as f64 - max as f64) / in_scale).exp();
+        if matches!(op, "sum_exp" | "layernorm" | "softmax") && stat.count == 0 {
+            panic!("norm: {op} on stat {id}, which has no elements; run sum first");
+        }
+        let ys: Vec<f64> = match op {
+            "sum" => {
+                for &x in &xs {
+                    stat.count += 1;
+                    stat.sum += x as i64;
+                    stat.sum_sq += (x as i128) * (x as i128);
+                    stat.max = stat.max.max(x);
+                }
+                return 0;
+            }
+            "sum_exp" => {
+                stat.sum_exp += xs.iter().map(|&x| exp(x, stat.max)).sum::<f64>();
+                return 0;
+            }
+            "layernorm" => {
+                let n = stat.count as f64;
+                let mean = stat.sum as f64 / n;
+                let var = (stat.sum_sq as f64 / n - mean * mean).max(0.0);
+                let inv = if var > 0.0 { out_scale / var.sqrt() } else { 0.0 };
+                xs.iter().map(|&x| (x as f64 - mean) * inv).collect()
+            }
+            _ => {
+                if stat.sum_exp <= 0.0 {
+                    panic!("norm: softmax on stat {id} before sum_exp");
+                }
+                let (max, sum_exp) = (stat.max, stat.sum_exp);
+                xs.iter().map(|&x| exp(x, max) * out_scale / sum_exp).collect()
+            }
+        };
+
+        let pd = pbank(ctx.bank_map, dst);
+        let first = line * (16 / dw.bytes());
+        let mut saturated = 0;
+        for (i, y) in ys.iter().enumerate() {
+            if dw.store(&mut ctx.banks[pd], first + i, y.round() as i64) {
+                saturated += 1;
+            }
+        }
+        ctx.perf.bank_write_bytes += (count * dw.bytes()) as u64;
+        if saturated > 0 {
+            ctx.warnings.record(WarningKind::NormSaturated, || {
+                format!(
+                    "bank{dst} (i{}): {saturated} of {count} {op} outputs saturated",
+                    dw.bits()
+                )
+            });
+        }
+        0
+    }
+
+    fn latency(xs1: u64, xs2: u64) -> u64 {
+        let lines = rs1_iter(xs1).div_ceil(4).max(1);
+        match Self::op_name(xs2) {
+            Some("layernorm" | "softmax") => lines + DIVIDER_CYCLES,
+            Some("sum" | "sum_exp") => lines,
+            _ => 1,
+        }
+    }
+
+    fn bank_extents(xs1: u64, xs2: u64, cfgs: &[BankConfig]) -> Vec<(u64, usize, usize)> {
+        let (src, dst, line) = (rs1_b0(xs1), rs1_b1(xs1), rs1_b2(xs1) as usize);
+        let count = rs1_iter(xs1) as usize;
+        match Self::op_name(xs2) {
+            Some("sum" | "sum_exp") => vec![(src, line * 16, count * 4)],
+            Some("layernorm" | "softmax") => {
+                let dw = cfgs.get(dst as usize).copied().unwrap_or_default().width;
+                vec![(src, line * 16, count * 4), (dst, line * 16, count * dw.bytes())]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
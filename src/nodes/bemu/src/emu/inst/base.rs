@@ -1,9 +1,31 @@
 //===- base.rs - Base BEMU instruction set --------------------------------===//
 
-use super::instruction::{ExecContext, Instruction};
+use super::super::bank::{ArrayGeometry, BankConfig};
+use super::instruction::{ExecContext, Instruction, Shared, Unit};
 
+// Two instructions with the same FUNCT would silently shadow each other in
+// the dispatch match, so registration fails to compile instead.
 macro_rules! register_instructions {
     ($($inst:path),* $(,)?) => {
+        /// funct7 of every registered instruction, in registration order.
+        pub const FUNCTS: &[u32] = &[$(<$inst as Instruction>::FUNCT),*];
+
+        /// (funct7, name) of every registered instruction.
+        pub const INSTRUCTIONS: &[(u32, &str)] = &[$((<$inst as Instruction>::FUNCT, <$inst as Instruction>::NAME)),*];
+
+        const _: () = {
+            let mut i = 0;
+            while i < FUNCTS.len() {
+                assert!(FUNCTS[i] < 128, "register_instructions: FUNCT does not fit in funct7");
+                let mut j = i + 1;
+                while j < FUNCTS.len() {
+                    assert!(FUNCTS[i] != FUNCTS[j], "register_instructions: two instructions share a FUNCT");
+                    j += 1;
+                }
+                i += 1;
+            }
+        };
+
         pub fn execute_known(
             funct: u32,
             xs1: u64,
@@ -20,16 +42,52 @@ macro_rules! register_instructions {
             }
         }
 
-        pub fn cycles_after_issue(funct: u32, xs1: u64, xs2: u64) -> u64 {
+        pub fn cycles_after_issue(funct: u32, xs1: u64, xs2: u64, array: ArrayGeometry, cfgs: &[BankConfig]) -> u64 {
             match funct {
                 $(
                     <$inst as Instruction>::FUNCT => {
-                        <$inst as Instruction>::latency(xs1, xs2)
+                        <$inst as Instruction>::array_latency(xs1, xs2, array, cfgs)
                     }
                 )*
                 _ => 1,
             }
         }
+
+        /// (vbank, byte offset, length) of the bank ranges `funct` touches.
+        pub fn bank_extents(funct: u32, xs1: u64, xs2: u64, cfgs: &[BankConfig]) -> Vec<(u64, usize, usize)> {
+            match funct {
+                $(
+                    <$inst as Instruction>::FUNCT => {
+                        <$inst as Instruction>::bank_extents(xs1, xs2, cfgs)
+                    }
+                )*
+                _ => Vec::new(),
+            }
+        }
+
+        /// (execution unit, shared state) of `funct`, if it is registered.
+        pub fn issue_class(funct: u32) -> Option<(Unit, &'static [Shared])> {
+            match funct {
+                $(
+                    <$inst as Instruction>::FUNCT => {
+                        Some((<$inst as Instruction>::UNIT, <$inst as Instruction>::SHARED))
+                    }
+                )*
+                _ => None,
+            }
+        }
+
+        /// (reads a bank, writes a bank) for `funct`.
+        pub fn bank_access(funct: u32) -> (bool, bool) {
+            match funct {
+                $(
+                    <$inst as Instruction>::FUNCT => {
+                        (<$inst as Instruction>::READS_BANK, <$inst as Instruction>::WRITES_BANK)
+                    }
+                )*
+                _ => (false, false),
+            }
+        }
     };
 }
 
@@ -37,8 +95,26 @@ register_instructions! {
     super::f00_fence::Fence,
     super::f01_barrier::Barrier,
     super::f16_mvout::Mvout,
+    super::f17_mvout_bfp::MvoutBfp,
     super::f32_mset::Mset,
     super::f33_mvin::Mvin,
     super::f34_mmio_set::MmioSet,
     super::f35_mvin_mmio::MvinMmio,
+    super::f36_qos_set::QosSet,
+    super::f37_mcopy::Mcopy,
+    super::f38_bmt::Bmt,
+    super::f39_dma_sg::DmaSg,
+    super::f40_counter::Counter,
+    super::f41_mvin_bfp::MvinBfp,
+    super::f42_acc_cfg::AccCfg,
+    super::f48_matmul::Matmul,
+    super::f49_conv::Conv,
+    super::f50_relu::Relu,
+    super::f51_transpose::Transpose,
+    super::f52_loop_ab::LoopAb,
+    super::f53_loop_ws::LoopWs,
+    super::f54_spmm::Spmm,
+    super::f55_im2col::Im2col,
+    super::f56_pool::Pool,
+    super::f57_norm::Norm,
 }
@@ -3,12 +3,36 @@
 // ISA decode — funct7 and rs1/rs2 fields match `bb-tests/workloads/lib/bbhw/isa/isa.h`
 // (`FIELD`, `BB_BANK0`..`BB_BANK2`, `BB_ITER`).
 //
+// Instructions with fewer than three bank operands may give the spare
+// `BB_BANK1` (rs1[19:10]) and `BB_BANK2` (rs1[29:20]) slots another meaning.
+// Software fills them with the same isa.h macros; only the decode differs:
+//
+//   mvin, mvout  BANK1 = lines per row, BANK2 = bank row pitch (`rs1_tile`)
+//   dma_sg       BANK1 bit 0 = direction
+//   bmt          BANK1 = first pbank or group
+//   norm         BANK2 = first line
+//
+// `bank_operands` (bank/ports.rs) only treats a slot as a vbank where the
+// table above does not claim it.
+//
 //===-----------------------------------------------------------------===//-----===//
 
-use super::super::bank::{BankMap, BANK_NUM};
+use super::super::bank::BankMap;
 
 // Re-export the active chip instruction set.
-pub use super::active_chip::{cycles_after_issue, execute_known};
+pub use super::active_chip::{
+    bank_access, bank_extents, cycles_after_issue, execute_known, issue_class, FUNCTS, INSTRUCTIONS,
+};
+
+/// RoCC custom-0..3 major opcodes (`insn[6:0]`).
+const ROCC_OPCODES: [u32; 4] = [0x0b, 0x2b, 0x5b, 0x7b];
+
+/// Extract funct7 (`insn[31:25]`) from a binary RoCC instruction word, or
+/// `None` if the major opcode is not one of custom-0..3.
+#[inline]
+pub fn rocc_funct(insn: u32) -> Option<u32> {
+    ROCC_OPCODES.contains(&(insn & 0x7f)).then_some(insn >> 25)
+}
 
 #[inline]
 pub fn rs1_b0(xs1: u64) -> u64 {
@@ -38,6 +62,47 @@ pub fn xs2_mem_stride(xs2: u64) -> (u64, u64) {
     (mem, stride)
 }
 
+/// 2-D fields of mvin/mvout, carried in the `BB_BANK1` and `BB_BANK2` slots
+/// (see the header): 16-byte lines per row in rs1[19:10] (0 reads as one)
+/// and the bank row pitch in lines in rs1[29:20] (0 packs rows back to
+/// back). Both zero is the plain one-line-per-row transfer, so code built
+/// before the fields existed keeps its meaning.
+#[inline]
+pub fn rs1_tile(xs1: u64) -> (u64, u64) {
+    let len = rs1_b1(xs1).max(1);
+    let pitch = match rs1_b2(xs1) {
+        0 => len,
+        p => p,
+    };
+    (len, pitch)
+}
+
+/// Whether an mvin/mvout sets either 2-D field.
+#[inline]
+pub fn rs1_is_tile(xs1: u64) -> bool {
+    rs1_b1(xs1) | rs1_b2(xs1) != 0
+}
+
+/// Bank bytes a single-group mvin or mvout spans, through the end of its
+/// last row.
+pub fn tile_bytes(xs1: u64) -> usize {
+    let (len, pitch) = rs1_tile(xs1);
+    match rs1_iter(xs1) {
+        0 => 0,
+        rows => ((rows - 1) * pitch + len) as usize * 16,
+    }
+}
+
+/// (DRAM address, bank byte offset) of every line a single-group mvin or
+/// mvout moves. Row r starts `stride` lines after row r - 1 in DRAM and
+/// `pitch` lines after it in the bank.
+pub fn tile_lines(xs1: u64, xs2: u64) -> impl Iterator<Item = (u64, usize)> {
+    let (mem, stride) = xs2_mem_stride(xs2);
+    let (len, pitch) = rs1_tile(xs1);
+    (0..rs1_iter(xs1))
+        .flat_map(move |r| (0..len).map(move |c| (mem + (r * stride + c) * 16, ((r * pitch + c) * 16) as usize)))
+}
+
 #[inline]
 pub fn xs2_mset(xs2: u64) -> (u64, u64, u64) {
     let row = xs2 & 0x1f;
@@ -46,6 +111,24 @@ pub fn xs2_mset(xs2: u64) -> (u64, u64, u64) {
     (row, col, alloc)
 }
 
+/// Element width code of mset, bits [12:11].
+#[inline]
+pub fn xs2_mset_width(xs2: u64) -> u64 {
+    (xs2 >> 11) & 0x3
+}
+
+/// Accumulator flag of mset, bit [13].
+#[inline]
+pub fn xs2_mset_acc(xs2: u64) -> bool {
+    (xs2 >> 13) & 1 == 1
+}
+
+/// Number format code of mset, bits [16:14].
+#[inline]
+pub fn xs2_mset_format(xs2: u64) -> u64 {
+    (xs2 >> 14) & 0x7
+}
+
 /// the bank field in the instruction is **vbank_id**; parse it to physical slot index before accessing `banks`.
 #[inline]
 pub fn pbank(bm: &BankMap, vbank: u64) -> usize {
@@ -54,9 +137,21 @@ pub fn pbank(bm: &BankMap, vbank: u64) -> usize {
 
 #[inline]
 pub fn pbank_group(bm: &BankMap, vbank: u64, group: u64) -> usize {
-    if vbank >= BANK_NUM as u64 {
-        panic!("pbank: invalid vbank_id {vbank}");
-    }
     bm.resolve_group(vbank as u32, group as u32)
         .unwrap_or_else(|| panic!("pbank: vbank {vbank} group {group} not mapped"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rocc_funct_accepts_custom_opcodes_only() {
+        // custom0 funct7=33 rd=x0 rs1=a0 rs2=a1 xs1 xs2
+        let mvin = (33 << 25) | (11 << 20) | (10 << 15) | (0b011 << 12) | 0x0b;
+        assert_eq!(rocc_funct(mvin), Some(33));
+        assert_eq!(rocc_funct((16 << 25) | 0x7b), Some(16));
+        // addi a0, a0, 1
+        assert_eq!(rocc_funct(0x0015_0513), None);
+    }
+}
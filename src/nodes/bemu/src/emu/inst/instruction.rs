@@ -22,7 +22,11 @@
 //
 //===-----------------------------------------------------------------===//-----===//
 
-use super::super::bank::{BankConfig, BankMap};
+use super::super::bank::{AddrMap, ArrayGeometry, BankConfig, BankMap};
+use crate::dma::Dma;
+use crate::fill::Fill;
+use crate::perf::PerfCounters;
+use crate::warnings::Warnings;
 
 /// MMIO region descriptor
 #[derive(Clone, Copy, Default)]
@@ -32,14 +36,90 @@ pub struct MmioRegion {
     pub size_rows: u8,
 }
 
+/// Operands loop_ab leaves for the next loop_ws
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LoopRegs {
+    pub a_addr: u64,
+    pub a_bank: u64,
+    pub b_addr: u64,
+    pub b_bank: u64,
+}
+
+/// Statistics norm gathers under one stat id
+#[derive(Clone, Copy, Debug)]
+pub struct NormStat {
+    pub count: u64,
+    pub sum: i64,
+    pub sum_sq: i128,
+    pub max: i32,
+    pub sum_exp: f64,
+}
+
+impl Default for NormStat {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            sum: 0,
+            sum_sq: 0,
+            max: i32::MIN,
+            sum_exp: 0.0,
+        }
+    }
+}
+
 /// Execution context passed to all instructions
 pub struct ExecContext<'a> {
     pub memory: &'a mut [u8],
+    pub addr_map: &'a AddrMap,
     pub banks: &'a mut [Vec<u8>],
     pub cfgs: &'a mut [BankConfig],
     pub bank_map: &'a mut BankMap,
+    /// Per vbank: the shared exponent of each line of a bfp8 bank.
+    pub bfp_exps: &'a mut [Vec<i8>],
     pub mmio_banks: &'a mut [[u8; 1024]; 16],
     pub mmio_region_table: &'a mut [MmioRegion; 32],
+    pub warnings: &'a mut Warnings,
+    pub fill: &'a mut Fill,
+    pub dma: &'a mut Dma,
+    pub perf: &'a mut PerfCounters,
+    pub loop_regs: &'a mut LoopRegs,
+    pub norm_stats: &'a mut [NormStat; 16],
+    /// Cycle the instruction completes at, before DMA stalls.
+    pub cycle: u64,
+    /// Instructions executed so far, this one included.
+    pub instructions: u64,
+}
+
+impl ExecContext<'_> {
+    /// Number of physical banks, which is also the number of vbank ids.
+    pub fn bank_count(&self) -> usize {
+        self.banks.len()
+    }
+
+    /// Bytes per bank.
+    pub fn bank_size(&self) -> usize {
+        self.banks.first().map_or(0, Vec::len)
+    }
+}
+
+/// Kind of NpuSim execution unit an instruction issues to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Unit {
+    /// Changes configuration: runs alone, once every unit has drained
+    Serial,
+    /// Moves data between DRAM or MMIO and the banks
+    Dma,
+    /// Computes bank to bank
+    Ball,
+}
+
+/// State other than banks that two overlapping instructions must not share
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shared {
+    Dram,
+    Mmio,
+    /// norm's statistics registers
+    NormStats,
 }
 
 /// Instruction trait - all instructions must implement this
@@ -47,9 +127,37 @@ pub trait Instruction {
     /// Instruction opcode (funct7 field)
     const FUNCT: u32;
 
+    /// Mnemonic, as used in traces and the manifest
+    const NAME: &'static str;
+
+    /// Whether the instruction reads / writes bank data, for the configured
+    /// bank access latencies
+    const READS_BANK: bool = false;
+    const WRITES_BANK: bool = false;
+
+    /// Execution unit the instruction occupies in NpuSim, and the state
+    /// besides its bank operands that it must not share with another
+    /// instruction in flight
+    const UNIT: Unit = Unit::Serial;
+    const SHARED: &'static [Shared] = &[];
+
     /// Execute the instruction, return result value
     fn exec(xs1: u64, xs2: u64, ctx: &mut ExecContext) -> u64;
 
     /// Calculate latency (cycles from issue to complete)
     fn latency(xs1: u64, xs2: u64) -> u64;
+
+    /// Latency on an `array`-shaped systolic array, given the bank configs
+    /// the instruction will run against. Only array instructions override it.
+    fn array_latency(xs1: u64, xs2: u64, _array: ArrayGeometry, _cfgs: &[BankConfig]) -> u64 {
+        Self::latency(xs1, xs2)
+    }
+
+    /// (vbank, byte offset, length) of every bank range the instruction
+    /// touches, given the bank configs it will run against, so a host can
+    /// reject an oversized operand before it runs. Instructions with bank
+    /// operands override it.
+    fn bank_extents(_xs1: u64, _xs2: u64, _cfgs: &[BankConfig]) -> Vec<(u64, usize, usize)> {
+        Vec::new()
+    }
 }
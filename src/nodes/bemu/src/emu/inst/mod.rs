@@ -7,6 +7,8 @@ pub mod f00_fence;
 pub mod f01_barrier;
 #[path = "16_mvout.rs"]
 pub mod f16_mvout;
+#[path = "17_mvout_bfp.rs"]
+pub mod f17_mvout_bfp;
 #[path = "32_mset.rs"]
 pub mod f32_mset;
 #[path = "33_mvin.rs"]
@@ -15,5 +17,39 @@ pub mod f33_mvin;
 pub mod f34_mmio_set;
 #[path = "35_mvin_mmio.rs"]
 pub mod f35_mvin_mmio;
+#[path = "36_qos_set.rs"]
+pub mod f36_qos_set;
+#[path = "37_mcopy.rs"]
+pub mod f37_mcopy;
+#[path = "38_bmt.rs"]
+pub mod f38_bmt;
+#[path = "39_dma_sg.rs"]
+pub mod f39_dma_sg;
+#[path = "40_counter.rs"]
+pub mod f40_counter;
+#[path = "41_mvin_bfp.rs"]
+pub mod f41_mvin_bfp;
+#[path = "42_acc_cfg.rs"]
+pub mod f42_acc_cfg;
+#[path = "48_matmul.rs"]
+pub mod f48_matmul;
+#[path = "49_conv.rs"]
+pub mod f49_conv;
+#[path = "50_relu.rs"]
+pub mod f50_relu;
+#[path = "51_transpose.rs"]
+pub mod f51_transpose;
+#[path = "52_loop_ab.rs"]
+pub mod f52_loop_ab;
+#[path = "53_loop_ws.rs"]
+pub mod f53_loop_ws;
+#[path = "54_spmm.rs"]
+pub mod f54_spmm;
+#[path = "55_im2col.rs"]
+pub mod f55_im2col;
+#[path = "56_pool.rs"]
+pub mod f56_pool;
+#[path = "57_norm.rs"]
+pub mod f57_norm;
 pub mod instruction;
 include!(concat!(env!("OUT_DIR"), "/chip.rs"));
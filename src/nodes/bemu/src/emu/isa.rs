@@ -0,0 +1,491 @@
+//===- isa.rs - Instruction format description ------------------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// The instruction formats programs are written in: each mnemonic's funct and
+// the bit field every named operand occupies in rs1 or rs2. Encoding ORs
+// each operand into its field, so two operands may share a field (bmt's
+// pbank and group). Decoding reads every field back.
+//
+// `Isa::base` describes the instructions in inst/, matching the layouts at
+// the top of each file. An `[[isa.inst]]` table in an `--arch` file adds a
+// mnemonic or replaces one of the same name, so a program can use a new
+// format without rebuilding the assembler:
+//
+//   [[isa.inst]]
+//   name = "mvin"
+//   funct = 33
+//   fields = [
+//     { name = "bank", reg = "rs1", lo = 0, width = 10 },
+//     { name = "rows", reg = "rs1", lo = 30, width = 34, default = 1 },
+//     { name = "addr", reg = "rs2", lo = 0, width = 39 },
+//   ]
+//
+// The description drives the program assembler and `Isa::disassemble`,
+// which prints instructions in itrace, recordings and the debugger as
+// program text; the instruction models in inst/ still decode rs1/rs2
+// themselves.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Reg {
+    Rs1,
+    Rs2,
+}
+
+/// One named operand: bits `[lo, lo + width)` of `reg`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IsaField {
+    pub name: String,
+    pub reg: Reg,
+    pub lo: u32,
+    pub width: u32,
+    /// Value when the operand is left out; required without one.
+    #[serde(default)]
+    pub default: Option<u64>,
+}
+
+impl IsaField {
+    fn max(&self) -> u64 {
+        u64::MAX >> (64 - self.width)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IsaInst {
+    pub name: String,
+    pub funct: u32,
+    #[serde(default)]
+    pub fields: Vec<IsaField>,
+}
+
+impl IsaInst {
+    pub fn field(&self, name: &str) -> Option<&IsaField> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.funct >= 128 {
+            return Err(format!(
+                "isa: {} funct {} does not fit in funct7",
+                self.name, self.funct
+            ));
+        }
+        for (i, f) in self.fields.iter().enumerate() {
+            if f.width == 0 || f.lo + f.width > 64 {
+                return Err(format!(
+                    "isa: {} operand {} [{}, {}) is not inside a 64-bit register",
+                    self.name,
+                    f.name,
+                    f.lo,
+                    f.lo + f.width
+                ));
+            }
+            if self.fields[..i].iter().any(|g| g.name == f.name) {
+                return Err(format!("isa: {} has two operands named {}", self.name, f.name));
+            }
+        }
+        Ok(())
+    }
+
+    /// rs1/rs2 from operand values; operands left out take their defaults.
+    pub fn encode(&self, values: &BTreeMap<&str, u64>) -> Result<(u64, u64), String> {
+        if let Some(name) = values.keys().find(|n| self.field(n).is_none()) {
+            return Err(format!("{} has no operand {name:?}", self.name));
+        }
+        let (mut xs1, mut xs2) = (0, 0);
+        for f in &self.fields {
+            let v = values
+                .get(f.name.as_str())
+                .copied()
+                .or(f.default)
+                .ok_or_else(|| format!("{} needs {}=", self.name, f.name))?;
+            if v > f.max() {
+                return Err(format!(
+                    "{} operand {}={v} does not fit in {} bits",
+                    self.name, f.name, f.width
+                ));
+            }
+            match f.reg {
+                Reg::Rs1 => xs1 |= v << f.lo,
+                Reg::Rs2 => xs2 |= v << f.lo,
+            }
+        }
+        Ok((xs1, xs2))
+    }
+
+    /// Operand values of an encoded instruction, in field order.
+    pub fn decode(&self, xs1: u64, xs2: u64) -> Vec<(&str, u64)> {
+        self.fields
+            .iter()
+            .map(|f| {
+                let reg = match f.reg {
+                    Reg::Rs1 => xs1,
+                    Reg::Rs2 => xs2,
+                };
+                (f.name.as_str(), (reg >> f.lo) & f.max())
+            })
+            .collect()
+    }
+}
+
+/// Every mnemonic programs can use.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Isa {
+    pub insts: Vec<IsaInst>,
+}
+
+/// (name, reg, lo, width, default) of one operand of the base set.
+type BaseField = (&'static str, Reg, u32, u32, Option<u64>);
+
+const BASE: &[(&str, u32, &[BaseField])] = {
+    use Reg::{Rs1, Rs2};
+    const MOVE: &[BaseField] = &[
+        ("bank", Rs1, 0, 10, None),
+        ("addr", Rs2, 0, 39, None),
+        ("rows", Rs1, 30, 34, None),
+        ("stride", Rs2, 39, 19, Some(1)),
+        ("cols", Rs1, 10, 10, Some(0)),
+        ("bank_stride", Rs1, 20, 10, Some(0)),
+    ];
+    const BFP_MOVE: &[BaseField] = &[
+        ("bank", Rs1, 0, 10, None),
+        ("addr", Rs2, 0, 39, None),
+        ("rows", Rs1, 30, 34, None),
+        ("stride", Rs2, 39, 19, Some(1)),
+    ];
+    const UNARY: &[BaseField] = &[
+        ("src", Rs1, 0, 10, None),
+        ("dst", Rs1, 10, 10, None),
+        ("rows", Rs1, 30, 34, None),
+    ];
+    &[
+        ("fence", 0, &[]),
+        ("barrier", 1, &[]),
+        ("mvout", 16, MOVE),
+        ("mvout_bfp", 17, BFP_MOVE),
+        (
+            "mset",
+            32,
+            &[
+                ("bank", Rs1, 0, 10, None),
+                ("rows", Rs2, 0, 5, Some(0)),
+                ("cols", Rs2, 5, 5, Some(1)),
+                ("alloc", Rs2, 10, 1, Some(1)),
+                ("width", Rs2, 11, 2, Some(0)),
+                ("acc", Rs2, 13, 1, Some(0)),
+                ("format", Rs2, 14, 3, Some(0)),
+            ],
+        ),
+        ("mvin", 33, MOVE),
+        (
+            "mmio_set",
+            34,
+            &[
+                ("bank", Rs1, 0, 10, None),
+                ("mmio_addr", Rs2, 0, 16, None),
+                ("size_rows", Rs2, 16, 8, None),
+            ],
+        ),
+        (
+            "mvin_mmio",
+            35,
+            &[
+                ("addr", Rs2, 0, 39, None),
+                ("mmio_addr", Rs2, 39, 17, None),
+                ("rows", Rs1, 30, 34, None),
+                ("col", Rs2, 56, 8, Some(16)),
+            ],
+        ),
+        (
+            "qos_set",
+            36,
+            &[
+                ("port", Rs1, 0, 1, None),
+                ("tokens", Rs2, 0, 16, None),
+                ("period", Rs2, 16, 16, Some(1)),
+            ],
+        ),
+        ("mcopy", 37, UNARY),
+        (
+            "bmt",
+            38,
+            &[
+                ("bank", Rs1, 0, 10, None),
+                ("pbank", Rs1, 10, 10, Some(0)),
+                ("group", Rs1, 10, 10, Some(0)),
+                ("query", Rs2, 0, 1, Some(0)),
+                ("cyclic", Rs2, 1, 1, Some(0)),
+                ("step", Rs2, 8, 8, Some(1)),
+            ],
+        ),
+        (
+            "dma_sg",
+            39,
+            &[
+                ("bank", Rs1, 0, 10, None),
+                ("list", Rs2, 0, 39, None),
+                ("count", Rs1, 30, 34, None),
+                ("store", Rs1, 10, 1, Some(0)),
+            ],
+        ),
+        ("counter", 40, &[("id", Rs1, 0, 8, None)]),
+        ("mvin_bfp", 41, BFP_MOVE),
+        (
+            "acc_cfg",
+            42,
+            &[
+                ("relu", Rs1, 0, 1, Some(0)),
+                ("mvin_scale", Rs1, 32, 32, Some(0)),
+                ("mvout_scale", Rs2, 0, 32, Some(0)),
+            ],
+        ),
+        (
+            "matmul",
+            48,
+            &[
+                ("a", Rs1, 0, 10, None),
+                ("b", Rs1, 10, 10, None),
+                ("c", Rs1, 20, 10, None),
+                ("rows", Rs1, 30, 34, None),
+                ("acc", Rs2, 0, 1, Some(0)),
+                ("k_lines", Rs2, 8, 8, Some(0)),
+                ("n_lines", Rs2, 16, 8, Some(0)),
+            ],
+        ),
+        (
+            "conv",
+            49,
+            &[
+                ("in", Rs1, 0, 10, None),
+                ("weight", Rs1, 10, 10, None),
+                ("out", Rs1, 20, 10, None),
+                ("in_ch", Rs2, 0, 8, None),
+                ("out_ch", Rs2, 8, 8, None),
+                ("height", Rs2, 16, 10, None),
+                ("width", Rs2, 26, 10, None),
+                ("kh", Rs2, 36, 4, None),
+                ("kw", Rs2, 40, 4, None),
+                ("stride", Rs2, 44, 4, Some(1)),
+                ("pad", Rs2, 48, 4, Some(0)),
+                ("dilation", Rs2, 52, 4, Some(1)),
+                ("in_dilation", Rs2, 56, 4, Some(1)),
+                ("rot180", Rs2, 60, 1, Some(0)),
+            ],
+        ),
+        ("relu", 50, UNARY),
+        ("transpose", 51, UNARY),
+        (
+            "loop_ab",
+            52,
+            &[
+                ("a", Rs1, 0, 39, None),
+                ("a_bank", Rs1, 39, 10, None),
+                ("b", Rs2, 0, 39, None),
+                ("b_bank", Rs2, 39, 10, None),
+            ],
+        ),
+        (
+            "loop_ws",
+            53,
+            &[
+                ("c", Rs1, 0, 39, None),
+                ("c_bank", Rs1, 39, 10, None),
+                ("m", Rs2, 0, 16, None),
+                ("k", Rs2, 16, 16, None),
+                ("n", Rs2, 32, 16, None),
+            ],
+        ),
+        (
+            "spmm",
+            54,
+            &[
+                ("a", Rs1, 0, 10, None),
+                ("b", Rs1, 10, 10, None),
+                ("c", Rs1, 20, 10, None),
+                ("rows", Rs1, 30, 34, None),
+                ("acc", Rs2, 0, 1, Some(0)),
+                ("nnz", Rs2, 32, 32, None),
+            ],
+        ),
+        (
+            "im2col",
+            55,
+            &[
+                ("src", Rs1, 0, 10, None),
+                ("dst", Rs1, 10, 10, None),
+                ("in_ch", Rs2, 0, 8, None),
+                ("height", Rs2, 16, 10, None),
+                ("width", Rs2, 26, 10, None),
+                ("kh", Rs2, 36, 4, None),
+                ("kw", Rs2, 40, 4, None),
+                ("stride", Rs2, 44, 4, Some(1)),
+                ("pad", Rs2, 48, 4, Some(0)),
+                ("dilation", Rs2, 52, 4, Some(1)),
+                ("in_dilation", Rs2, 56, 4, Some(1)),
+            ],
+        ),
+        (
+            "pool",
+            56,
+            &[
+                ("src", Rs1, 0, 10, None),
+                ("dst", Rs1, 10, 10, None),
+                ("ch", Rs2, 0, 8, None),
+                ("height", Rs2, 16, 10, None),
+                ("width", Rs2, 26, 10, None),
+                ("kh", Rs2, 36, 4, None),
+                ("kw", Rs2, 40, 4, None),
+                ("stride", Rs2, 44, 4, Some(1)),
+                ("pad", Rs2, 48, 4, Some(0)),
+                ("avg", Rs2, 61, 1, Some(0)),
+            ],
+        ),
+        (
+            "norm",
+            57,
+            &[
+                ("src", Rs1, 0, 10, None),
+                ("dst", Rs1, 10, 10, Some(0)),
+                ("line", Rs1, 20, 10, Some(0)),
+                ("count", Rs1, 30, 34, None),
+                ("id", Rs2, 0, 4, Some(0)),
+                ("op", Rs2, 4, 4, None),
+                ("in_frac", Rs2, 8, 8, Some(0)),
+                ("out_frac", Rs2, 16, 8, Some(0)),
+            ],
+        ),
+    ]
+};
+
+/// `Isa::base`, built once for the trace and the disassembler.
+pub(crate) fn base_isa() -> &'static Isa {
+    static BASE_ISA: OnceLock<Isa> = OnceLock::new();
+    BASE_ISA.get_or_init(Isa::base)
+}
+
+impl Default for Isa {
+    fn default() -> Self {
+        Self::base()
+    }
+}
+
+impl Isa {
+    /// The formats of the instructions in inst/.
+    pub fn base() -> Self {
+        let insts = BASE
+            .iter()
+            .map(|&(name, funct, fields)| IsaInst {
+                name: name.to_string(),
+                funct,
+                fields: fields
+                    .iter()
+                    .map(|&(name, reg, lo, width, default)| IsaField {
+                        name: name.to_string(),
+                        reg,
+                        lo,
+                        width,
+                        default,
+                    })
+                    .collect(),
+            })
+            .collect();
+        Self { insts }
+    }
+
+    /// Add `insts`, each replacing the mnemonic of the same name if there
+    /// is one.
+    pub fn extend(&mut self, insts: Vec<IsaInst>) -> Result<(), String> {
+        for inst in insts {
+            inst.validate()?;
+            match self.insts.iter_mut().find(|i| i.name == inst.name) {
+                Some(old) => *old = inst,
+                None => self.insts.push(inst),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, mnemonic: &str) -> Option<&IsaInst> {
+        self.insts.iter().find(|i| i.name == mnemonic)
+    }
+
+    /// Funct of a mnemonic, e.g. `mvin` -> 33.
+    pub fn funct_of(&self, mnemonic: &str) -> Option<u32> {
+        self.get(mnemonic).map(|i| i.funct)
+    }
+
+    /// Program text for one encoded instruction: its mnemonic and every
+    /// operand, or a raw `inst` line for a funct with no mnemonic.
+    pub fn disassemble(&self, funct: u32, xs1: u64, xs2: u64) -> String {
+        let Some(inst) = self.insts.iter().find(|i| i.funct == funct) else {
+            return format!("inst {funct} 0x{xs1:x} 0x{xs2:x}");
+        };
+        let mut text = inst.name.clone();
+        for (name, value) in inst.decode(xs1, xs2) {
+            if value < 10 {
+                text += &format!(" {name}={value}");
+            } else {
+                text += &format!(" {name}=0x{value:x}");
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program::Program;
+
+    #[test]
+    fn disassembly_assembles_back_to_the_same_words() {
+        let isa = Isa::base();
+        for inst in &isa.insts {
+            inst.validate().unwrap();
+        }
+        let program = Program::parse(
+            "mset bank=1 cols=2 width=i32 acc=1\n\
+             mvin bank=1 addr=0x80001090 rows=4 cols=2 stride=4 bank_stride=3\n\
+             conv in=1 weight=2 out=3 in_ch=8 out_ch=16 height=28 width=28 kh=3 kw=3 pad=1\n\
+             loop_ws c=0x80004000 c_bank=5 m=64 k=32 n=48\n",
+        )
+        .unwrap();
+        for inst in &program.insts {
+            let text = isa.disassemble(inst.funct, inst.xs1, inst.xs2);
+            let again = &Program::parse(&text).unwrap().insts[0];
+            assert_eq!(
+                (again.funct, again.xs1, again.xs2),
+                (inst.funct, inst.xs1, inst.xs2),
+                "{text}"
+            );
+        }
+        assert_eq!(isa.disassemble(120, 1, 2), "inst 120 0x1 0x2");
+
+        let mvin = isa.get("mvin").unwrap();
+        let err = mvin
+            .encode(&BTreeMap::from([("bank", 1024), ("addr", 0), ("rows", 1)]))
+            .unwrap_err();
+        assert_eq!(err, "mvin operand bank=1024 does not fit in 10 bits");
+    }
+}
@@ -0,0 +1,140 @@
+//===- layout.rs - Matrix layouts in guest DRAM ----------------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// Where element (r, c) of a rows x cols matrix sits in DRAM, so hosts and
+// tests can load and read back matrices without flattening them by hand.
+// Tiled stores tiles in row-major order of tiles, each tile row-major and
+// full-sized: edge tiles are zero-padded, so every tile row is the same
+// number of bytes apart, as mvin's stride expects.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use crate::bank::ElemWidth;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+    RowMajor,
+    ColMajor,
+    /// `rows` x `cols` tiles.
+    Tiled {
+        rows: usize,
+        cols: usize,
+    },
+}
+
+impl Layout {
+    /// Element index of (r, c) in a rows x cols matrix.
+    pub fn index(self, r: usize, c: usize, rows: usize, cols: usize) -> usize {
+        match self {
+            Layout::RowMajor => r * cols + c,
+            Layout::ColMajor => c * rows + r,
+            Layout::Tiled { rows: th, cols: tw } => {
+                let tiles_per_row = cols.div_ceil(tw);
+                let tile = (r / th) * tiles_per_row + c / tw;
+                tile * th * tw + (r % th) * tw + c % tw
+            }
+        }
+    }
+
+    /// Elements a rows x cols matrix occupies, padding included.
+    pub fn len(self, rows: usize, cols: usize) -> usize {
+        match self {
+            Layout::RowMajor | Layout::ColMajor => rows * cols,
+            Layout::Tiled { rows: th, cols: tw } => rows.div_ceil(th) * th * cols.div_ceil(tw) * tw,
+        }
+    }
+
+    pub(crate) fn check(self) -> Result<(), String> {
+        match self {
+            Layout::Tiled { rows: 0, .. } | Layout::Tiled { cols: 0, .. } => {
+                Err(format!("{self:?}: tiles must be at least 1x1"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// `data`, row-major rows x cols, as `width` elements in `layout`.
+pub(crate) fn pack(
+    data: &[i32],
+    rows: usize,
+    cols: usize,
+    width: ElemWidth,
+    layout: Layout,
+) -> Result<Vec<u8>, String> {
+    layout.check()?;
+    if data.len() != rows * cols {
+        return Err(format!("{} elements given for a {rows}x{cols} matrix", data.len()));
+    }
+    let mut bytes = vec![0u8; layout.len(rows, cols) * width.bytes()];
+    for r in 0..rows {
+        for c in 0..cols {
+            let v = data[r * cols + c];
+            if width.store(&mut bytes, layout.index(r, c, rows, cols), v as i64) {
+                return Err(format!("element ({r}, {c}) = {v} does not fit i{}", width.bits()));
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+/// The rows x cols matrix `bytes` holds in `layout`, row-major. `layout`
+/// must have passed `check`.
+pub(crate) fn unpack(bytes: &[u8], rows: usize, cols: usize, width: ElemWidth, layout: Layout) -> Vec<i32> {
+    let mut data = Vec::with_capacity(rows * cols);
+    for r in 0..rows {
+        for c in 0..cols {
+            data.push(width.load(bytes, layout.index(r, c, rows, cols)));
+        }
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::DRAM_BASE;
+    use crate::Npu;
+
+    #[test]
+    fn matrices_round_trip_through_each_layout() {
+        let mut npu = Npu::new(1 << 20);
+        let m: Vec<i32> = (0..15).collect(); // 3x5
+        npu.write_dram_matrix(DRAM_BASE, &m, 3, 5, ElemWidth::I8, Layout::ColMajor)
+            .unwrap();
+        assert_eq!(npu.read_dram(DRAM_BASE, 4), [0, 5, 10, 1]);
+
+        let tiled = Layout::Tiled { rows: 2, cols: 4 };
+        npu.write_dram_matrix(DRAM_BASE, &m, 3, 5, ElemWidth::I16, tiled)
+            .unwrap();
+        let tile1: Vec<u8> = [4i16, 0, 0, 0, 9, 0, 0, 0]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        assert_eq!(npu.read_dram(DRAM_BASE + 16, 16), tile1);
+        for layout in [Layout::RowMajor, Layout::ColMajor, tiled] {
+            npu.write_dram_matrix(DRAM_BASE, &m, 3, 5, ElemWidth::I32, layout)
+                .unwrap();
+            assert_eq!(
+                npu.read_dram_matrix(DRAM_BASE, 3, 5, ElemWidth::I32, layout).unwrap(),
+                m
+            );
+        }
+
+        let err = npu.write_dram_matrix(DRAM_BASE, &[300], 1, 1, ElemWidth::I8, Layout::RowMajor);
+        assert_eq!(err.unwrap_err(), "element (0, 0) = 300 does not fit i8");
+    }
+}
@@ -0,0 +1,387 @@
+//===- lower.rs - Layer description to instruction program ----------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// Lowers a small network description to a text program (see program.rs), so
+// the latency of a whole network can be estimated without hand-writing the
+// mvin/matmul/mvout stream:
+//
+//   {"layers": [
+//     {"op": "matmul", "m": 64, "k": 128, "n": 32},
+//     {"op": "conv", "in_ch": 3, "out_ch": 8, "height": 32, "width": 32,
+//      "kernel": 3, "stride": 1, "pad": 1}
+//   ]}
+//
+// Every layer gets its own operands in DRAM, allocated upward from `base`
+// (DRAM_BASE by default) on 4 KiB boundaries and listed at the top of the
+// program. Layers are not chained numerically: requantizing an i32 result
+// to the next layer's i8 input is not modeled, so the program measures
+// cycles, not the network's output.
+//
+// matmul takes i8 A [m][k] and B [k][n] and writes i32 C. k and n must be
+// multiples of 16; the array computes one 16x16 tile of B at a time, K
+// tiles accumulate in the C bank, and M is split so A and C fit a bank. C
+// is written to DRAM blocked by column tile, [n / 16][m][16], since a C row
+// wider than one tile is not contiguous in any single bank.
+//
+// relu is fused into the matmul or conv before it: the result is rectified
+// in the C bank before each mvout, so it costs no DRAM traffic.
+//
+// conv takes i8 input [in_ch][height][width] and weights
+// [out_ch][in_ch][kernel][kernel] and writes i32 output
+// [out_ch][out_h][out_w]. It is one conv instruction, so every tensor has to
+// fit a bank and the shape has to fit the instruction's rs2 fields.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use std::fmt::Write;
+
+use serde::Deserialize;
+
+use crate::bank::{BankGeometry, DRAM_BASE};
+
+/// Virtual banks the lowered program uses.
+const BANK_A: u32 = 1;
+const BANK_B: u32 = 2;
+const BANK_C: u32 = 3;
+
+/// DRAM alignment of each tensor.
+const TENSOR_ALIGN: u64 = 4096;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Network {
+    #[serde(default)]
+    base: Option<u64>,
+    layers: Vec<Layer>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case", deny_unknown_fields)]
+enum Layer {
+    Matmul {
+        m: usize,
+        k: usize,
+        n: usize,
+    },
+    Conv {
+        in_ch: usize,
+        out_ch: usize,
+        height: usize,
+        width: usize,
+        kernel: usize,
+        #[serde(default = "one")]
+        stride: usize,
+        #[serde(default)]
+        pad: usize,
+    },
+    Relu,
+}
+
+fn one() -> usize {
+    1
+}
+
+/// One DRAM operand of the lowered program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoweredTensor {
+    /// `<layer label>.<operand>`, e.g. "layer0_matmul.b".
+    pub name: String,
+    pub addr: u64,
+    pub bytes: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Lowered {
+    /// Program text for [`crate::Program::parse`].
+    pub program: String,
+    pub tensors: Vec<LoweredTensor>,
+}
+
+struct Lowerer {
+    bank_bytes: usize,
+    next_addr: u64,
+    out: Lowered,
+    body: String,
+    /// Label of a relu layer fused into the layer being lowered.
+    relu: Option<String>,
+}
+
+impl Lowerer {
+    fn alloc(&mut self, name: String, bytes: usize) -> u64 {
+        let addr = self.next_addr;
+        self.next_addr = (addr + bytes as u64).next_multiple_of(TENSOR_ALIGN);
+        self.out.tensors.push(LoweredTensor { name, addr, bytes });
+        addr
+    }
+
+    fn line(&mut self, inst: String) {
+        let _ = writeln!(self.body, "  {inst}");
+    }
+
+    /// Write `lines` rows of the C bank to `addr`, rectifying them first
+    /// when a relu follows. The relu gets its own label so the report
+    /// counts its cycles apart from the layer that produced the data.
+    fn store(&mut self, label: &str, addr: u64, lines: usize) {
+        if let Some(relu) = self.relu.clone() {
+            let _ = writeln!(self.body, "{relu}:");
+            self.line(format!("relu src={BANK_C} dst={BANK_C} rows={lines}"));
+            let _ = writeln!(self.body, "{label}:");
+        }
+        self.line(format!("mvout bank={BANK_C} addr=0x{addr:x} rows={lines}"));
+    }
+
+    fn matmul(&mut self, label: &str, m: usize, k: usize, n: usize) -> Result<(), String> {
+        if m == 0 || k == 0 || n == 0 {
+            return Err(format!("{label}: dimensions must be > 0, got m={m} k={k} n={n}"));
+        }
+        if !k.is_multiple_of(16) || !n.is_multiple_of(16) {
+            return Err(format!("{label}: k and n must be multiples of 16, got k={k} n={n}"));
+        }
+        // One i32 C row is four lines; A rows are one line each.
+        let m_tile = m.min(self.bank_bytes / 64);
+        if m_tile == 0 {
+            return Err(format!(
+                "{label}: a {}-byte bank cannot hold one C row",
+                self.bank_bytes
+            ));
+        }
+        let a = self.alloc(format!("{label}.a"), m * k);
+        let b = self.alloc(format!("{label}.b"), k * n);
+        let c = self.alloc(format!("{label}.c"), m * n * 4);
+        let (k_lines, n_lines) = ((k / 16) as u64, (n / 16) as u64);
+
+        self.line(format!("mset bank={BANK_A} width=i8"));
+        self.line(format!("mset bank={BANK_B} width=i8"));
+        self.line(format!("mset bank={BANK_C} width=i32"));
+        for m0 in (0..m).step_by(m_tile) {
+            let rows = m_tile.min(m - m0);
+            for nt in 0..n / 16 {
+                for kt in 0..k / 16 {
+                    let a_addr = a + (m0 * k + kt * 16) as u64;
+                    let b_addr = b + (kt * 16 * n + nt * 16) as u64;
+                    self.line(format!(
+                        "mvin bank={BANK_A} addr=0x{a_addr:x} rows={rows} stride={k_lines}"
+                    ));
+                    self.line(format!("mvin bank={BANK_B} addr=0x{b_addr:x} rows=16 stride={n_lines}"));
+                    self.line(format!(
+                        "matmul a={BANK_A} b={BANK_B} c={BANK_C} rows={rows} acc={}",
+                        u8::from(kt > 0)
+                    ));
+                }
+                let c_addr = c + ((nt * m + m0) * 64) as u64;
+                self.store(label, c_addr, rows * 4);
+            }
+        }
+        self.free();
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn conv(
+        &mut self,
+        label: &str,
+        in_ch: usize,
+        out_ch: usize,
+        height: usize,
+        width: usize,
+        kernel: usize,
+        stride: usize,
+        pad: usize,
+    ) -> Result<(), String> {
+        for (field, value, min, max) in [
+            ("in_ch", in_ch, 1, 255),
+            ("out_ch", out_ch, 1, 255),
+            ("height", height, 1, 1023),
+            ("width", width, 1, 1023),
+            ("kernel", kernel, 1, 15),
+            ("stride", stride, 1, 15),
+            ("pad", pad, 0, 15),
+        ] {
+            if !(min..=max).contains(&value) {
+                return Err(format!("{label}: {field} must be {min}..={max}, got {value}"));
+            }
+        }
+        let out_dim = |n: usize| (n + 2 * pad).checked_sub(kernel).map(|span| span / stride + 1);
+        let (Some(out_h), Some(out_w)) = (out_dim(height), out_dim(width)) else {
+            return Err(format!(
+                "{label}: {kernel}x{kernel} kernel does not fit a padded {height}x{width} input"
+            ));
+        };
+        let input_bytes = in_ch * height * width;
+        let weight_bytes = out_ch * in_ch * kernel * kernel;
+        let output_bytes = out_ch * out_h * out_w * 4;
+        for (operand, bytes) in [
+            ("input", input_bytes),
+            ("weight", weight_bytes),
+            ("output", output_bytes),
+        ] {
+            if bytes > self.bank_bytes {
+                return Err(format!(
+                    "{label}: {operand} is {bytes} bytes, more than a {}-byte bank",
+                    self.bank_bytes
+                ));
+            }
+        }
+        // Round to whole lines; the padding past each tensor is allocated too.
+        let lines = |bytes: usize| bytes.div_ceil(16);
+        let input = self.alloc(format!("{label}.input"), lines(input_bytes) * 16);
+        let weight = self.alloc(format!("{label}.weight"), lines(weight_bytes) * 16);
+        let output = self.alloc(format!("{label}.output"), lines(output_bytes) * 16);
+
+        self.line(format!("mset bank={BANK_A} width=i8"));
+        self.line(format!("mset bank={BANK_B} width=i8"));
+        self.line(format!("mset bank={BANK_C} width=i32"));
+        self.line(format!(
+            "mvin bank={BANK_A} addr=0x{input:x} rows={}",
+            lines(input_bytes)
+        ));
+        self.line(format!(
+            "mvin bank={BANK_B} addr=0x{weight:x} rows={}",
+            lines(weight_bytes)
+        ));
+        self.line(format!(
+            "conv in={BANK_A} weight={BANK_B} out={BANK_C} in_ch={in_ch} out_ch={out_ch} \
+             height={height} width={width} kh={kernel} kw={kernel} stride={stride} pad={pad}"
+        ));
+        self.store(label, output, lines(output_bytes));
+        self.free();
+        Ok(())
+    }
+
+    fn free(&mut self) {
+        for bank in [BANK_A, BANK_B, BANK_C] {
+            self.line(format!("mset bank={bank} alloc=0"));
+        }
+    }
+}
+
+/// Lower a JSON layer description to a program for banks of `geometry`.
+pub fn lower(json: &str, geometry: &BankGeometry) -> Result<Lowered, String> {
+    let network: Network = serde_json::from_str(json).map_err(|e| format!("invalid layer description: {e}"))?;
+    if geometry.num_banks <= BANK_C as usize {
+        return Err(format!(
+            "lowering needs {} banks, the geometry has {}",
+            BANK_C + 1,
+            geometry.num_banks
+        ));
+    }
+    let mut l = Lowerer {
+        bank_bytes: geometry.bank_bytes(),
+        next_addr: network.base.unwrap_or(DRAM_BASE).next_multiple_of(TENSOR_ALIGN),
+        out: Lowered::default(),
+        body: String::new(),
+        relu: None,
+    };
+    for (i, layer) in network.layers.iter().enumerate() {
+        l.relu = matches!(network.layers.get(i + 1), Some(Layer::Relu)).then(|| format!("layer{}_relu", i + 1));
+        match *layer {
+            Layer::Matmul { m, k, n } => {
+                let label = format!("layer{i}_matmul");
+                let _ = writeln!(l.body, "{label}:");
+                l.matmul(&label, m, k, n)?;
+            }
+            Layer::Conv {
+                in_ch,
+                out_ch,
+                height,
+                width,
+                kernel,
+                stride,
+                pad,
+            } => {
+                let label = format!("layer{i}_conv");
+                let _ = writeln!(l.body, "{label}:");
+                l.conv(&label, in_ch, out_ch, height, width, kernel, stride, pad)?;
+            }
+            Layer::Relu => {
+                if !matches!(
+                    i.checked_sub(1).map(|p| &network.layers[p]),
+                    Some(Layer::Matmul { .. } | Layer::Conv { .. })
+                ) {
+                    return Err(format!("layer{i}_relu: relu must follow a matmul or conv"));
+                }
+            }
+        }
+    }
+
+    let mut program = format!("# {} layers lowered to bemu instructions\n", network.layers.len());
+    for t in &l.out.tensors {
+        let _ = writeln!(program, "# {:<24} 0x{:x} {} bytes", t.name, t.addr, t.bytes);
+    }
+    program.push_str(&l.body);
+    l.out.program = program;
+    Ok(l.out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Npu, Program};
+
+    fn bytes(seed: u64, len: usize) -> Vec<u8> {
+        let mut x = seed | 1;
+        (0..len)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                // Small values so no i32 sum saturates.
+                (x % 7) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lowered_network_matches_the_golden_model() {
+        let geometry = BankGeometry {
+            bank_depth: 64,
+            ..BankGeometry::default()
+        };
+        let lowered = lower(
+            r#"{"layers": [
+                {"op": "matmul", "m": 40, "k": 32, "n": 32},
+                {"op": "conv", "in_ch": 2, "out_ch": 3, "height": 6, "width": 6, "kernel": 3, "pad": 1},
+                {"op": "relu"}
+            ]}"#,
+            &geometry,
+        )
+        .unwrap();
+        let program = Program::parse(&lowered.program).unwrap();
+        let mut npu = Npu::new(1 << 20);
+        npu.set_bank_geometry(geometry).unwrap();
+        npu.enable_golden_check();
+        for (i, t) in lowered.tensors.iter().enumerate() {
+            npu.write_dram(t.addr, &bytes(i as u64 + 1, t.bytes));
+        }
+        let report = program.run(&mut npu);
+        assert!(report.cycles > 0);
+        assert_eq!(npu.golden_mismatches(), &[]);
+
+        // 40 rows of A split into bank-sized tiles of 16, 16 and 8.
+        let (m, k, n) = (40, 32, 32);
+        let a = bebop_golden::widen(&npu.read_dram(lowered.tensors[0].addr, m * k));
+        let b = bebop_golden::widen(&npu.read_dram(lowered.tensors[1].addr, k * n));
+        let want = bebop_golden::matmul(&a, &b, m, k, n);
+        let c = npu.read_dram(lowered.tensors[2].addr, m * n * 4);
+        for i in 0..m {
+            for j in 0..n {
+                let at = ((j / 16 * m + i) * 16 + j % 16) * 4;
+                let got = i32::from_le_bytes(c[at..at + 4].try_into().unwrap());
+                assert_eq!(got as f64, want[i * n + j], "C[{i}][{j}]");
+            }
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//===- manifest.rs - Description of the instantiated model -----------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// A manifest states what a run simulated: DRAM and bank geometry, the MMIO
+// SRAM, the DMA beat size and policies, and every registered instruction
+// with its latency. Tools read it instead of hard-coding these numbers, so a
+// parameter change shows up in their output rather than silently skewing it.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use serde::{Deserialize, Serialize};
+
+use crate::bank::{ArrayGeometry, DRAM_BASE, MATRIX_SIZE};
+use crate::cache::CacheConfig;
+use crate::dma::{Throttle, DMA_BEAT_BYTES};
+use crate::dram::DramTiming;
+use crate::inst::decode::INSTRUCTIONS;
+use crate::npu::Npu;
+
+const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    pub model: String,
+    pub dram: DramManifest,
+    pub banks: BankManifest,
+    /// Systolic array matmul and conv are timed on.
+    pub array: ArrayGeometry,
+    pub mmio: MmioManifest,
+    pub dma: DmaManifest,
+    pub instructions: Vec<InstManifest>,
+    pub init_seed: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DramManifest {
+    pub base: u64,
+    pub size: u64,
+    /// `None` when DRAM answers with zero latency.
+    pub timing: Option<DramTiming>,
+    /// Memory-side cache in front of DRAM, if any.
+    #[serde(default)]
+    pub cache: Option<CacheConfig>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BankManifest {
+    pub count: usize,
+    pub lines: usize,
+    pub width_bits: usize,
+    pub bytes: usize,
+    pub matrix_size: usize,
+    /// Extra cycles for instructions that read / write a bank.
+    pub read_latency: u64,
+    pub write_latency: u64,
+    /// Element widths mset accepts, in bits.
+    pub element_bits: Vec<u32>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MmioManifest {
+    pub banks: usize,
+    pub bank_bytes: usize,
+    pub regions: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DmaManifest {
+    pub beat_bytes: u64,
+    /// "penalty" or "fault".
+    pub misaligned: String,
+    /// QoS caps in effect as (tokens, period).
+    pub dram_cap: Option<(u64, u64)>,
+    pub bank_cap: Option<(u64, u64)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstManifest {
+    pub funct: u32,
+    pub name: String,
+    /// Cycles for a one-row, beat-aligned issue.
+    pub unit_latency: u64,
+}
+
+impl Manifest {
+    pub fn capture(npu: &Npu) -> Self {
+        let cap = |t: Option<Throttle>| t.map(|t| (t.tokens, t.period));
+        let geometry = npu.bank_geometry();
+        Self {
+            version: MANIFEST_VERSION,
+            model: "bemu".to_string(),
+            dram: DramManifest {
+                base: DRAM_BASE,
+                size: npu.memory.len() as u64,
+                timing: npu.dma.dram.as_ref().map(|d| d.timing),
+                cache: npu.dma.cache.as_ref().map(|c| c.config()),
+            },
+            banks: BankManifest {
+                count: geometry.num_banks,
+                lines: geometry.bank_depth,
+                width_bits: geometry.word_bits,
+                bytes: geometry.bank_bytes(),
+                matrix_size: MATRIX_SIZE,
+                read_latency: geometry.read_latency,
+                write_latency: geometry.write_latency,
+                element_bits: vec![8, 16, 32],
+            },
+            array: npu.array_geometry(),
+            mmio: MmioManifest {
+                banks: npu.mmio_banks.len(),
+                bank_bytes: npu.mmio_banks[0].len(),
+                regions: npu.mmio_region_table.len(),
+            },
+            dma: DmaManifest {
+                beat_bytes: DMA_BEAT_BYTES,
+                misaligned: format!("{:?}", npu.dma.policy).to_lowercase(),
+                dram_cap: cap(npu.dma.dram_cap),
+                bank_cap: cap(npu.dma.bank_cap),
+            },
+            instructions: INSTRUCTIONS
+                .iter()
+                .map(|&(funct, name)| InstManifest {
+                    funct,
+                    name: name.to_string(),
+                    unit_latency: npu.issue_latency(funct, 1 << 30, 0),
+                })
+                .collect(),
+            init_seed: npu.init_seed(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("manifest serializes")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_reflects_configured_model() {
+        let mut npu = Npu::new(1 << 20);
+        npu.exec(36, 1, 3 | (8 << 16), 0);
+        let m = npu.manifest();
+        assert_eq!(m.dram.size, 1 << 20);
+        assert_eq!(m.dma.bank_cap, Some((3, 8)));
+        assert_eq!(m.dma.misaligned, "penalty");
+        let mvin = m.instructions.iter().find(|i| i.funct == 33).unwrap();
+        assert_eq!((mvin.name.as_str(), mvin.unit_latency), ("mvin", 1));
+
+        let json = m.to_json();
+        assert_eq!(serde_json::from_str::<Manifest>(&json).unwrap(), m);
+    }
+}
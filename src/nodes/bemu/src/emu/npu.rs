@@ -0,0 +1,2178 @@
+//===- npu.rs - BEMU accelerator state -------------------------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// Npu owns everything the Buckyball instructions touch: DRAM, banks, the
+// vbank -> pbank map, MMIO banks and the latency counters.
+//
+// Spike reaches it through the RoCC hooks in native/ffi.rs, but nothing here
+// depends on Spike, so tools (e.g. `bebop script`) can drive it directly.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use bebop_bank_hash::bank_hash;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::bank::{
+    bank_operands, mem_read_into, mem_write_from, Access, AddrMap, ArrayGeometry, BankConfig, BankGeometry, BankMap,
+    BankPorts, ElemWidth,
+};
+use crate::cache::{Cache, CacheConfig, CacheStats};
+use crate::coverage::Coverage;
+use crate::dma::{split_beat_penalty, Dma, DmaStats, MisalignedDma};
+use crate::dram::{DramModel, DramTiming};
+use crate::energy::{Energy, EnergyTable, Events};
+use crate::error::NpuError;
+use crate::extension::{Extension, Extensions};
+use crate::fault::{FaultConfig, FaultEvent, FaultStats, Faults};
+use crate::fill::Fill;
+use crate::golden::{Golden, GoldenMismatch, Provenance};
+use crate::inst;
+use crate::inst::instruction::{Instruction, LoopRegs, MmioRegion, NormStat};
+use crate::layout::{self, Layout};
+use crate::numfmt::FormatError;
+use crate::perf::{PerfCounters, PerfReport};
+use crate::record::Recorder;
+use crate::tenant::{TenantConfig, TenantStats, Tenants};
+use crate::tiletag::TileTags;
+use crate::trace::{with_trace_ptr, TraceConfig, TraceState};
+use crate::warnings::{WarningKind, Warnings};
+use crate::watch::{self, WatchHit, Watches, Watchpoint};
+
+// 1GB Here is important, for baremetal mode, when we set this to 4GB,
+// it will running for a long time.
+pub const DEFAULT_MEM_SIZE: usize = 1 << 30;
+
+/// Bank-writing instructions that may name their source bank as the
+/// destination (mcopy, relu, norm); the others abort on it.
+const IN_PLACE: [u32; 3] = [37, 50, 57];
+
+pub struct Npu {
+    pub(crate) memory: Vec<u8>,
+    pub(crate) addr_map: AddrMap,
+    pub(crate) banks: Vec<Vec<u8>>,
+    pub(crate) bank_cfgs: Vec<BankConfig>,
+    pub(crate) bank_map: BankMap,
+    /// Per vbank: the shared exponent of each line of a bfp8 bank.
+    pub(crate) bfp_exps: Vec<Vec<i8>>,
+    pub(crate) geometry: BankGeometry,
+    pub(crate) array: ArrayGeometry,
+    pub(crate) ports: BankPorts,
+    pub(crate) mmio_banks: [[u8; 1024]; 16],
+    pub(crate) mmio_region_table: [MmioRegion; 32],
+    pub(crate) loop_regs: LoopRegs,
+    pub(crate) norm_stats: [NormStat; 16],
+    pub(crate) total_lat: u64,
+    pub(crate) npu_instruction_id: u64,
+    pub(crate) trace: TraceState,
+    pub(crate) warnings: Warnings,
+    pub(crate) fill: Fill,
+    pub(crate) dma: Dma,
+    pub(crate) coverage: Option<Coverage>,
+    pub(crate) recorder: Option<Recorder>,
+    pub(crate) perf: PerfCounters,
+    pub(crate) extensions: Extensions,
+    pub(crate) golden: Option<Golden>,
+    pub(crate) tile_tags: Option<TileTags>,
+    pub(crate) faults: Option<Faults>,
+    pub(crate) energy: Option<Energy>,
+    pub(crate) tenants: Option<Tenants>,
+    pub(crate) watches: Watches,
+    /// DRAM rows written since the last `take_dram_writes`, when tracked.
+    pub(crate) dram_writes: Option<Vec<(u64, u64)>>,
+}
+
+impl Npu {
+    /// Create an accelerator with `mem_size` bytes of DRAM and tracing disabled.
+    pub fn new(mem_size: usize) -> Self {
+        let geometry = BankGeometry::default();
+        Self {
+            // memory is maintained by bemu not spike
+            memory: vec![0; mem_size],
+            addr_map: AddrMap::default(),
+            banks: vec![vec![0; geometry.bank_bytes()]; geometry.num_banks],
+            bank_cfgs: vec![BankConfig::default(); geometry.num_banks],
+            bank_map: BankMap::new(geometry.num_banks),
+            bfp_exps: vec![vec![0; geometry.bank_depth]; geometry.num_banks],
+            geometry,
+            array: ArrayGeometry::default(),
+            ports: BankPorts::default(),
+            mmio_banks: [[0u8; 1024]; 16],
+            mmio_region_table: [MmioRegion::default(); 32],
+            loop_regs: LoopRegs::default(),
+            norm_stats: [NormStat::default(); 16],
+            total_lat: 0,
+            npu_instruction_id: 0,
+            trace: TraceState::default(),
+            warnings: Warnings::default(),
+            fill: Fill::default(),
+            dma: Dma::default(),
+            coverage: None,
+            recorder: None,
+            perf: PerfCounters::default(),
+            extensions: Extensions::default(),
+            golden: None,
+            tile_tags: None,
+            faults: None,
+            energy: None,
+            tenants: None,
+            watches: Watches::default(),
+            dram_writes: None,
+        }
+    }
+
+    /// Create an accelerator whose traces are written under `log_dir`.
+    pub fn with_trace(mem_size: usize, log_dir: &Path, trace_config: TraceConfig) -> Result<Self, String> {
+        let mut npu = Self::new(mem_size);
+        npu.trace = TraceState::new(log_dir, trace_config).map_err(|e| e.to_string())?;
+        Ok(npu)
+    }
+
+    /// Fill DRAM and banks with seeded pseudo-random bytes instead of zeros,
+    /// including banks refilled by later resets and mset allocations.
+    pub fn randomize(&mut self, seed: u64) {
+        self.fill = Fill::random(seed);
+        self.fill.fill(&mut self.memory);
+        for b in &mut self.banks {
+            self.fill.fill(b);
+        }
+    }
+
+    /// Seed passed to [`Npu::randomize`], if any.
+    pub fn init_seed(&self) -> Option<u64> {
+        self.fill.seed()
+    }
+
+    /// Clear banks, bank mappings, MMIO state, QoS caps, counters, golden
+    /// mismatches, watchpoint hits and extension state. DRAM, coverage,
+    /// registered extensions, watchpoints and a recording in progress are
+    /// kept.
+    pub fn reset(&mut self) {
+        self.fill.rewind();
+        for b in &mut self.banks {
+            self.fill.fill(b);
+        }
+        self.bank_cfgs.fill(BankConfig::default());
+        self.bank_map = BankMap::new(self.banks.len());
+        for exps in &mut self.bfp_exps {
+            exps.fill(0);
+        }
+        for bank in &mut self.mmio_banks {
+            bank.fill(0);
+        }
+        self.mmio_region_table = [MmioRegion::default(); 32];
+        self.loop_regs = LoopRegs::default();
+        self.norm_stats = [NormStat::default(); 16];
+        self.total_lat = 0;
+        self.npu_instruction_id = 0;
+        self.perf = PerfCounters::default();
+        self.warnings.clear();
+        self.watches.hits.clear();
+        if let Some(writes) = &mut self.dram_writes {
+            writes.clear();
+        }
+        self.extensions.reset();
+        if let Some(golden) = &mut self.golden {
+            golden.reset();
+        }
+        if let Some(tags) = &mut self.tile_tags {
+            *tags = TileTags::new(self.banks.len(), self.geometry.bank_depth);
+        }
+        if let Some(faults) = &mut self.faults {
+            faults.reset();
+        }
+        if let Some(energy) = &mut self.energy {
+            energy.per_funct.clear();
+        }
+        if let Some(tenants) = &mut self.tenants {
+            tenants.reset();
+            self.bank_map.pool = Some(tenants.banks());
+        }
+        if let Some(rec) = &mut self.recorder {
+            rec.reset();
+        }
+        // Keep the misalignment policy, DRAM timing and cache shape; QoS caps
+        // are accelerator state.
+        let mut dram = self.dma.dram.take();
+        if let Some(model) = &mut dram {
+            model.reset();
+        }
+        let mut cache = self.dma.cache.take();
+        if let Some(cache) = &mut cache {
+            cache.reset();
+        }
+        self.dma = Dma {
+            policy: self.dma.policy,
+            dram,
+            cache,
+            ..Dma::default()
+        };
+    }
+
+    /// Change the number and size of banks and their access latencies. Banks
+    /// are reallocated, so this resets the accelerator as [`Npu::reset`]
+    /// does.
+    pub fn set_bank_geometry(&mut self, geometry: BankGeometry) -> Result<(), String> {
+        geometry.validate()?;
+        self.tenants = None;
+        self.banks = vec![vec![0; geometry.bank_bytes()]; geometry.num_banks];
+        self.bank_cfgs = vec![BankConfig::default(); geometry.num_banks];
+        self.bfp_exps = vec![vec![0; geometry.bank_depth]; geometry.num_banks];
+        self.geometry = geometry;
+        self.reset();
+        Ok(())
+    }
+
+    pub fn bank_geometry(&self) -> BankGeometry {
+        self.geometry
+    }
+
+    /// Change the systolic array matmul and conv are timed on.
+    pub fn set_array_geometry(&mut self, array: ArrayGeometry) -> Result<(), String> {
+        array.validate()?;
+        self.array = array;
+        Ok(())
+    }
+
+    pub fn array_geometry(&self) -> ArrayGeometry {
+        self.array
+    }
+
+    /// Cycles from issue to completion: the instruction's own latency on the
+    /// configured systolic array, stretched by bank port conflicts, plus the
+    /// configured bank access latencies.
+    pub(crate) fn issue_latency(&self, funct: u32, xs1: u64, xs2: u64) -> u64 {
+        if let Some(i) = self.extension_for(funct) {
+            return self.extensions.get(i).latency(funct, xs1, xs2);
+        }
+        let (reads, writes) = inst::decode::bank_access(funct);
+        let cycles = inst::decode::cycles_after_issue(funct, xs1, xs2, self.array, &self.bank_cfgs);
+        cycles
+            + self.port_conflict_cycles(funct, xs1, cycles)
+            + if reads { self.geometry.read_latency } else { 0 }
+            + if writes { self.geometry.write_latency } else { 0 }
+    }
+
+    /// Limit how many reads and writes each physical bank serves per cycle.
+    pub fn set_bank_ports(&mut self, ports: BankPorts) -> Result<(), String> {
+        ports.validate(self.geometry.num_banks)?;
+        self.ports = ports;
+        Ok(())
+    }
+
+    pub fn bank_ports(&self) -> &BankPorts {
+        &self.ports
+    }
+
+    /// Extra cycles an instruction that streams for `cycles` spends waiting
+    /// for ports of banks its operands share.
+    fn port_conflict_cycles(&self, funct: u32, xs1: u64, cycles: u64) -> u64 {
+        if self.ports.is_ideal() {
+            return 0;
+        }
+        let mut streams: BTreeMap<usize, (u64, u64)> = BTreeMap::new();
+        for (vbank, access) in bank_operands(funct, xs1) {
+            let Some(p) = self.bank_map.resolve(vbank as u32) else {
+                continue;
+            };
+            let s = streams.entry(p).or_default();
+            match access {
+                Access::Read => s.0 += 1,
+                Access::Write => s.1 += 1,
+            }
+        }
+        let beats = streams
+            .iter()
+            .map(|(&p, &(r, w))| self.ports.kind(p).beats(r, w))
+            .max()
+            .unwrap_or(1);
+        (beats - 1) * cycles
+    }
+
+    /// Execute the functs `ext` claims with it from now on. Fails if one of
+    /// them is a built-in instruction or belongs to another extension.
+    pub fn register_extension(&mut self, ext: Box<dyn Extension>) -> Result<(), String> {
+        self.extensions.register(ext)
+    }
+
+    pub fn extensions(&self) -> impl Iterator<Item = &dyn Extension> {
+        self.extensions.iter()
+    }
+
+    /// Index of the extension executing `funct`; built-ins always win.
+    pub(crate) fn extension_for(&self, funct: u32) -> Option<usize> {
+        if inst::decode::FUNCTS.contains(&funct) {
+            return None;
+        }
+        self.extensions.find(funct)
+    }
+
+    fn exec_extension(&mut self, i: usize, funct: u32, xs1: u64, xs2: u64) -> u64 {
+        if let Some(rec) = &mut self.recorder {
+            rec.inst(funct, xs1, xs2, self.total_lat);
+        }
+        let lat = self.issue_latency(funct, xs1, xs2);
+        self.total_lat += lat;
+        self.trace.set_bemu_clk(self.total_lat);
+        self.npu_instruction_id = self.npu_instruction_id.wrapping_add(1);
+        let events = Events::of(&self.perf);
+        let mut ext = self.extensions.take(i);
+        let result = ext.execute(self, funct, xs1, xs2);
+        self.extensions.put_back(i, ext);
+        if let Some(energy) = &mut self.energy {
+            energy.charge(funct, events, &self.perf);
+        }
+        let per_funct = self.perf.per_funct.entry(funct).or_default();
+        per_funct.0 += 1;
+        per_funct.1 += lat;
+        result
+    }
+
+    /// Charge misaligned DMA rows a split-beat penalty (default) or abort.
+    pub fn set_misaligned_dma(&mut self, policy: MisalignedDma) {
+        self.dma.policy = policy;
+    }
+
+    pub fn dma_stats(&self) -> DmaStats {
+        self.dma.stats
+    }
+
+    /// Charge DMA rows DRAM row-buffer and bandwidth timing; `None` restores
+    /// zero-latency DRAM.
+    pub fn set_dram_timing(&mut self, timing: Option<DramTiming>) -> Result<(), String> {
+        if let Some(t) = &timing {
+            t.validate()?;
+        }
+        self.dma.dram = timing.map(DramModel::new);
+        Ok(())
+    }
+
+    /// Put a memory-side cache (see `cache.rs`) between DMA and DRAM; `None`
+    /// removes it. The cache starts empty.
+    pub fn set_cache(&mut self, config: Option<CacheConfig>) -> Result<(), String> {
+        if let Some(c) = &config {
+            c.validate()?;
+        }
+        self.dma.cache = config.map(Cache::new);
+        Ok(())
+    }
+
+    /// Hits and misses of the memory-side cache, when there is one.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.dma.cache.as_ref().map(Cache::stats)
+    }
+
+    /// Start counting ISA coverage bins for every instruction executed.
+    pub fn enable_coverage(&mut self) {
+        self.coverage.get_or_insert_with(Coverage::new);
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Check mvin, matmul, conv and mvout against the golden model from now
+    /// on (see `golden.rs`).
+    pub fn enable_golden_check(&mut self) {
+        self.golden.get_or_insert_with(Golden::default);
+    }
+
+    /// Price MACs, bank lines and DRAM beats with `table` from now on (see
+    /// `energy.rs`); `None` stops estimating energy.
+    pub fn set_energy_table(&mut self, table: Option<EnergyTable>) -> Result<(), String> {
+        if let Some(t) = &table {
+            t.validate()?;
+        }
+        self.energy = table.map(Energy::new);
+        Ok(())
+    }
+
+    /// Flip bank and DRAM bits as `config` says from now on (see
+    /// `fault.rs`); `None` stops. Flips already in the banks stay.
+    pub fn set_faults(&mut self, config: Option<FaultConfig>) -> Result<(), String> {
+        if let Some(config) = &config {
+            config.validate(self.banks.len(), self.banks.first().map_or(0, Vec::len))?;
+        }
+        self.faults = config.map(Faults::new);
+        Ok(())
+    }
+
+    /// Counts of injected faults and their fates since the last reset.
+    pub fn fault_stats(&self) -> Option<FaultStats> {
+        self.faults.as_ref().map(|f| f.stats())
+    }
+
+    /// Every checked line with flipped bits, in order.
+    pub fn fault_events(&self) -> &[FaultEvent] {
+        self.faults.as_ref().map_or(&[], |f| f.events())
+    }
+
+    /// Apply the faults due before an instruction runs, then check the
+    /// flipped lines of the banks it reads.
+    fn inject_faults(&mut self, faults: &mut Faults, funct: u32, xs1: u64) {
+        let intact = self.golden.as_ref().map(|g| g.intact(&self.banks)).unwrap_or_default();
+        for p in faults.strike(&mut self.banks, self.total_lat) {
+            if let Some(golden) = self.golden.as_mut().filter(|_| intact.contains(&p)) {
+                golden.rebase(&self.banks, p);
+            }
+        }
+        for (vbank, access) in bank_operands(funct, xs1) {
+            if access != Access::Read {
+                continue;
+            }
+            for (p, e) in self.bank_map.slots.iter().enumerate() {
+                if e.valid && e.vbank_id as u64 == vbank {
+                    faults.check(&mut self.banks, p, self.total_lat);
+                }
+            }
+        }
+    }
+
+    /// Corrupt the beats the mvin that just ran read from DRAM.
+    fn corrupt_mvin(&mut self, faults: &mut Faults, xs1: u64, xs2: u64) {
+        let vbank = inst::decode::rs1_b0(xs1);
+        if !self
+            .bank_cfgs
+            .get(vbank as usize)
+            .is_some_and(|c| c.allocated && c.cols <= 1)
+        {
+            return;
+        }
+        let Some(p) = self.bank_map.resolve(vbank as u32) else {
+            return;
+        };
+        let lines: Vec<(u64, usize)> = inst::decode::tile_lines(xs1, xs2).collect();
+        faults.mvin(&mut self.banks, p, &lines, self.total_lat);
+    }
+
+    /// Tag bank lines with the type and layout they were written as from now
+    /// on, and warn when matmul or conv reads them as another (see
+    /// `tiletag.rs`).
+    pub fn enable_tile_tags(&mut self) {
+        let (banks, depth) = (self.banks.len(), self.geometry.bank_depth);
+        self.tile_tags.get_or_insert_with(|| TileTags::new(banks, depth));
+    }
+
+    /// Partition the banks between the tenants of `config` from now on (see
+    /// `tenant.rs`), with tenant 0 issuing; `None` shares every bank again.
+    /// A new bank geometry drops the partition.
+    pub fn set_tenants(&mut self, config: Option<TenantConfig>) -> Result<(), String> {
+        if let Some(config) = &config {
+            config.validate(self.banks.len())?;
+        }
+        self.tenants = config.map(Tenants::new);
+        self.bank_map.pool = self.tenants.as_ref().map(Tenants::banks);
+        Ok(())
+    }
+
+    /// Tag the instructions that follow as tenant `id`'s, by its index in
+    /// the partition.
+    pub fn set_tenant(&mut self, id: usize) -> Result<(), String> {
+        let tenants = self.tenants.as_mut().ok_or("no tenant partition is set")?;
+        if id >= tenants.stats.len() {
+            return Err(format!("tenant {id} does not exist, there are {}", tenants.stats.len()));
+        }
+        tenants.current = id;
+        self.bank_map.pool = Some(tenants.banks());
+        Ok(())
+    }
+
+    /// Each tenant's name and what its instructions spent since the last
+    /// reset, in partition order.
+    pub fn tenant_stats(&self) -> Option<Vec<(&str, TenantStats)>> {
+        let tenants = self.tenants.as_ref()?;
+        Some(
+            tenants
+                .config
+                .tenants
+                .iter()
+                .zip(&tenants.stats)
+                .map(|(t, s)| (t.name.as_str(), *s))
+                .collect(),
+        )
+    }
+
+    /// Err if the issuing tenant does not own a bank the instruction names,
+    /// by vbank id or by a physical bank behind one of its groups.
+    fn check_tenant(&self, funct: u32, xs1: u64) -> Result<(), NpuError> {
+        let Some(tenants) = &self.tenants else {
+            return Ok(());
+        };
+        let banks = tenants.banks();
+        let mut vbanks: Vec<u64> = bank_operands(funct, xs1).into_iter().map(|(v, _)| v).collect();
+        if matches!(funct, 32 | 38) {
+            vbanks.push(inst::decode::rs1_b0(xs1));
+        }
+        for vbank in vbanks {
+            let vbank = vbank as u32;
+            let mapped_outside = self
+                .bank_map
+                .slots
+                .iter()
+                .enumerate()
+                .any(|(p, e)| e.valid && e.vbank_id == vbank && !banks.contains(&p));
+            if !banks.contains(&(vbank as usize)) || mapped_outside {
+                return Err(NpuError::CrossTenant {
+                    vbank,
+                    tenant: tenants.name().to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Results that disagreed with the golden model since the last reset.
+    pub fn golden_mismatches(&self) -> &[GoldenMismatch] {
+        self.golden.as_ref().map_or(&[], |g| g.mismatches())
+    }
+
+    /// Rounding error per float format of the matmuls and mcopies checked
+    /// since the last reset, keyed by format name.
+    pub fn format_errors(&self) -> BTreeMap<&'static str, FormatError> {
+        self.golden
+            .as_ref()
+            .map(|g| g.format_errors().clone())
+            .unwrap_or_default()
+    }
+
+    /// Record every instruction executed from now on to `path` (see
+    /// `Recording`), replacing any recording in progress.
+    pub fn record_trace(&mut self, path: &Path) -> Result<(), String> {
+        self.recorder = Some(Recorder::create(path)?);
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) {
+        self.recorder = None;
+    }
+
+    /// Execute one RoCC instruction and return the value written to rd.
+    pub fn exec(&mut self, funct: u32, xs1: u64, xs2: u64, pc: u64) -> u64 {
+        self.warnings.set_cycle(self.total_lat);
+        if let Some(i) = self.extension_for(funct) {
+            return self.exec_extension(i, funct, xs1, xs2);
+        }
+        if funct == inst::f53_loop_ws::LoopWs::FUNCT {
+            for (funct, xs1, xs2) in self.expand_loop_ws(xs1, xs2) {
+                self.exec(funct, xs1, xs2, pc);
+            }
+            return 0;
+        }
+        if let Err(e) = self.check_tenant(funct, xs1) {
+            if let Some(tenants) = &mut self.tenants {
+                tenants.stats[tenants.current].violations += 1;
+            }
+            self.warnings.record(WarningKind::CrossTenant, || e.to_string());
+            return 0;
+        }
+        let spent_before = (self.total_lat, self.perf.dram_read_bytes, self.perf.dram_write_bytes);
+        if let Some(coverage) = &mut self.coverage {
+            coverage.record(funct, xs1, xs2, &self.bank_cfgs);
+        }
+        self.with_taken(
+            |npu| &mut npu.faults,
+            |npu, faults| npu.inject_faults(faults, funct, xs1),
+        );
+        self.with_taken(|npu| &mut npu.golden, |npu, golden| golden.before(npu, funct, xs1, xs2));
+        if let Some(tags) = &self.tile_tags {
+            for detail in tags.check(self, funct, xs1, xs2) {
+                self.warnings.record(WarningKind::TileTagMismatch, || detail);
+            }
+        }
+        if let Some(rec) = &mut self.recorder {
+            rec.inst(funct, xs1, xs2, self.total_lat);
+        }
+        let logged = self.recorder.is_some() || !self.watches.points.is_empty() || self.dram_writes.is_some();
+        self.dma.log = logged.then(Vec::new);
+        let watched = self.watched_banks();
+        let events = Events::of(&self.perf);
+        let lat = self.issue_latency(funct, xs1, xs2);
+        let streaming = inst::decode::cycles_after_issue(funct, xs1, xs2, self.array, &self.bank_cfgs);
+        self.perf.bank_conflict_cycles += self.port_conflict_cycles(funct, xs1, streaming);
+        self.total_lat += lat;
+        self.trace.set_bemu_clk(self.total_lat);
+        self.npu_instruction_id = self.npu_instruction_id.wrapping_add(1);
+        let instruction_id = self.npu_instruction_id;
+        let trace = &mut self.trace as *mut TraceState;
+        let btrace = self.trace.btrace_enabled();
+
+        unsafe {
+            with_trace_ptr(trace, || {
+                crate::trace::itrace(crate::trace::ITraceEvent {
+                    funct,
+                    pc,
+                    rs1: xs1,
+                    rs2: xs2,
+                });
+            })
+        };
+
+        let Npu {
+            memory,
+            addr_map,
+            banks,
+            bank_cfgs,
+            bank_map,
+            bfp_exps,
+            mmio_banks,
+            mmio_region_table,
+            loop_regs,
+            norm_stats,
+            warnings,
+            fill,
+            dma,
+            perf,
+            total_lat,
+            npu_instruction_id,
+            ..
+        } = self;
+
+        let before_hashes: Vec<u64> = if btrace {
+            banks.iter().map(|bank| bank_hash(bank)).collect()
+        } else {
+            Vec::new()
+        };
+
+        let result = unsafe {
+            with_trace_ptr(trace, || {
+                let mut ctx = inst::instruction::ExecContext {
+                    memory,
+                    addr_map,
+                    banks,
+                    cfgs: bank_cfgs,
+                    bank_map,
+                    bfp_exps,
+                    mmio_banks,
+                    mmio_region_table,
+                    warnings,
+                    fill,
+                    dma,
+                    perf,
+                    loop_regs,
+                    norm_stats,
+                    cycle: *total_lat,
+                    instructions: *npu_instruction_id,
+                };
+
+                inst::decode::execute_known(funct, xs1, xs2, &mut ctx).unwrap_or_else(|| {
+                    panic!(
+                        "unknown funct7: {} (instruction_id={} pc=0x{:x} xs1=0x{:x} xs2=0x{:x})",
+                        funct, instruction_id, pc, xs1, xs2
+                    )
+                })
+            })
+        };
+
+        // QoS stalls depend on the transfer, so they are known only now.
+        let stall = self.dma.take_stall();
+        self.total_lat += stall;
+        let per_funct = self.perf.per_funct.entry(funct).or_default();
+        per_funct.0 += 1;
+        per_funct.1 += lat + stall;
+        if let Some(energy) = &mut self.energy {
+            energy.charge(funct, events, &self.perf);
+        }
+
+        if let (Some(rec), Some(rows)) = (&mut self.recorder, &self.dma.log) {
+            // mvout writes the rows it touches; everything else read them. A
+            // dma_sg store also logs the rows it wrote, and replay writing
+            // those back first is harmless.
+            if funct != inst::f16_mvout::Mvout::FUNCT {
+                for &(addr, len, _) in rows {
+                    let mut bytes = vec![0u8; len as usize];
+                    mem_read_into(memory, addr_map, addr, &mut bytes);
+                    rec.dram(addr, &bytes);
+                }
+            }
+        }
+
+        if btrace {
+            let op_type = format!("funct7_{}", funct);
+            unsafe {
+                with_trace_ptr(trace, || {
+                    for (bank_id, bank) in banks.iter().enumerate() {
+                        let hash = bank_hash(bank);
+                        if before_hashes[bank_id] != hash {
+                            crate::trace::bemu_bank_hash(instruction_id, bank_id as u32, funct, &op_type, hash, pc);
+                        }
+                    }
+                })
+            };
+        }
+
+        if funct == inst::f33_mvin::Mvin::FUNCT {
+            self.with_taken(|npu| &mut npu.faults, |npu, faults| npu.corrupt_mvin(faults, xs1, xs2));
+        }
+        let at = Provenance {
+            id: instruction_id,
+            funct,
+            pc,
+        };
+        self.with_taken(|npu| &mut npu.golden, |npu, golden| golden.after(npu, at, xs1, xs2));
+        self.with_taken(|npu| &mut npu.tile_tags, |npu, tags| tags.after(npu, funct, xs1, xs2));
+        let rows = self.dma.log.take().unwrap_or_default();
+        if !self.watches.points.is_empty() {
+            self.check_watchpoints(funct, &watched, &rows);
+        }
+        if let Some(writes) = &mut self.dram_writes {
+            writes.extend(rows.iter().filter(|r| r.2).map(|&(addr, len, _)| (addr, len)));
+        }
+        if let Some(tenants) = &mut self.tenants {
+            let stats = &mut tenants.stats[tenants.current];
+            stats.instructions += 1;
+            stats.cycles += self.total_lat - spent_before.0;
+            stats.dram_read_bytes += self.perf.dram_read_bytes - spent_before.1;
+            stats.dram_write_bytes += self.perf.dram_write_bytes - spent_before.2;
+        }
+
+        result
+    }
+
+    /// Run `hook` on the debug state in `slot` with it moved out of the NPU,
+    /// putting it back even if the hook panics and the caller recovers with
+    /// `catch_unwind`.
+    fn with_taken<T>(&mut self, slot: fn(&mut Npu) -> &mut Option<T>, hook: impl FnOnce(&mut Npu, &mut T)) {
+        let Some(mut state) = slot(self).take() else {
+            return;
+        };
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| hook(self, &mut state)));
+        *slot(self) = Some(state);
+        if let Err(panic) = result {
+            std::panic::resume_unwind(panic);
+        }
+    }
+
+    /// Contents of every bank watchpoint's range, in watchpoint order.
+    fn watched_banks(&self) -> Vec<Option<Vec<u8>>> {
+        self.watches
+            .points
+            .iter()
+            .map(|(_, w)| match *w {
+                Watchpoint::Bank { vbank, offset, len } => {
+                    self.bank(vbank)?.get(offset..offset + len).map(<[u8]>::to_vec)
+                }
+                Watchpoint::Dram { .. } => None,
+            })
+            .collect()
+    }
+
+    /// Record a hit for each watchpoint the instruction just executed
+    /// touched: a logged DMA row overlapping a DRAM range, or a changed
+    /// byte in a bank range.
+    fn check_watchpoints(&mut self, funct: u32, before: &[Option<Vec<u8>>], rows: &[(u64, u64, bool)]) {
+        let after = self.watched_banks();
+        let mut hits = Vec::new();
+        for (k, &(id, watchpoint)) in self.watches.points.iter().enumerate() {
+            let touched = match watchpoint {
+                Watchpoint::Dram { addr, len } => rows
+                    .iter()
+                    .find_map(|&(a, l, write)| watch::overlap(a, l, addr, len).map(|at| (at, write))),
+                Watchpoint::Bank { offset, .. } => match (&before[k], &after[k]) {
+                    (Some(b), Some(a)) => b
+                        .iter()
+                        .zip(a)
+                        .position(|(x, y)| x != y)
+                        .map(|i| ((offset + i) as u64, true)),
+                    _ => None,
+                },
+            };
+            if let Some((at, write)) = touched {
+                hits.push(WatchHit {
+                    id,
+                    watchpoint,
+                    inst: self.npu_instruction_id,
+                    funct,
+                    cycle: self.total_lat,
+                    write,
+                    at,
+                });
+            }
+        }
+        self.watches.hits.extend(hits);
+    }
+
+    /// Log every instruction that touches `watchpoint` (see `watch.rs`);
+    /// returns its id.
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> usize {
+        self.watches.add(watchpoint)
+    }
+
+    pub fn remove_watchpoint(&mut self, id: usize) -> bool {
+        self.watches.remove(id)
+    }
+
+    /// (id, watchpoint) in the order they were added.
+    pub fn watchpoints(&self) -> &[(usize, Watchpoint)] {
+        &self.watches.points
+    }
+
+    /// Hits recorded since the last call, oldest first.
+    pub fn take_watch_hits(&mut self) -> Vec<WatchHit> {
+        std::mem::take(&mut self.watches.hits)
+    }
+
+    /// Start logging the DRAM rows DMA writes, for `take_dram_writes`.
+    pub(crate) fn track_dram_writes(&mut self) {
+        self.dram_writes.get_or_insert_with(Vec::new);
+    }
+
+    /// `(addr, len)` of every DRAM row written since the last call.
+    pub(crate) fn take_dram_writes(&mut self) -> Vec<(u64, u64)> {
+        self.dram_writes.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// The micro-ops of a loop_ws (see `53_loop_ws.rs`), counted as one
+    /// loop_ws; aborts like any instruction on invalid operands.
+    pub(crate) fn expand_loop_ws(&mut self, xs1: u64, xs2: u64) -> Vec<(u32, u64, u64)> {
+        let funct = inst::f53_loop_ws::LoopWs::FUNCT;
+        if let Some(coverage) = &mut self.coverage {
+            coverage.record(funct, xs1, xs2, &self.bank_cfgs);
+        }
+        let bank_bytes = self.banks.first().map_or(0, Vec::len);
+        let ops = inst::f53_loop_ws::expand(&self.loop_regs, xs1, xs2, &self.bank_cfgs, bank_bytes)
+            .unwrap_or_else(|e| panic!("loop_ws: {e}"));
+        self.perf.per_funct.entry(funct).or_default().0 += 1;
+        ops
+    }
+
+    /// Execute a binary-encoded RoCC instruction word (e.g. from a `.insn`
+    /// directive or a disassembled kernel) with its source register values.
+    /// Bank and DMA operands are checked first (see `check_operands`), so
+    /// those mistakes come back as errors instead of aborting the model.
+    pub fn exec_rocc(&mut self, insn: u32, xs1: u64, xs2: u64, pc: u64) -> Result<u64, NpuError> {
+        let funct = inst::decode::rocc_funct(insn).ok_or(NpuError::InvalidInsn { insn })?;
+        self.check_operands(funct, xs1, xs2)?;
+        Ok(self.exec(funct, xs1, xs2, pc))
+    }
+
+    /// The mistakes `exec` would abort on that can be seen without running
+    /// the instruction: a funct no instruction or extension implements,
+    /// banks outside the issuing tenant's partition (checked before the
+    /// others, so a tenant cannot probe another's banks), unallocated banks,
+    /// a bank written and read by an instruction that cannot work in place,
+    /// bank ranges past the end of a bank, and a misaligned DMA under
+    /// `MisalignedDma::Fault`. Anything else still aborts in `exec`.
+    pub fn check_operands(&self, funct: u32, xs1: u64, xs2: u64) -> Result<(), NpuError> {
+        if !inst::decode::FUNCTS.contains(&funct) && self.extensions.find(funct).is_none() {
+            return Err(NpuError::UnknownFunct { funct });
+        }
+        self.check_tenant(funct, xs1)?;
+        let operands = bank_operands(funct, xs1);
+        for &(vbank, access) in &operands {
+            let vbank = vbank as u32;
+            if !self.bank_cfgs.get(vbank as usize).is_some_and(|c| c.allocated) {
+                return Err(NpuError::UnmappedBank { vbank });
+            }
+            let read_too = operands.iter().any(|&(v, a)| v as u32 == vbank && a == Access::Read);
+            if access == Access::Write && read_too && !IN_PLACE.contains(&funct) {
+                return Err(NpuError::BankConflict { vbank, funct });
+            }
+        }
+        let size = self.banks.first().map_or(0, Vec::len);
+        for (vbank, offset, len) in inst::decode::bank_extents(funct, xs1, xs2, &self.bank_cfgs) {
+            if offset.saturating_add(len) > size {
+                let vbank = vbank as u32;
+                return Err(NpuError::OutOfBounds {
+                    vbank,
+                    offset,
+                    len,
+                    size,
+                });
+            }
+        }
+        let fault = self.dma.policy == MisalignedDma::Fault;
+        let misaligned = |addr: u64| match fault && split_beat_penalty(addr, 1) > 0 {
+            true => Err(NpuError::Misaligned { addr }),
+            false => Ok(()),
+        };
+        match funct {
+            inst::f16_mvout::Mvout::FUNCT
+            | inst::f17_mvout_bfp::MvoutBfp::FUNCT
+            | inst::f33_mvin::Mvin::FUNCT
+            | inst::f41_mvin_bfp::MvinBfp::FUNCT => misaligned(inst::decode::xs2_mem_stride(xs2).0)?,
+            inst::f35_mvin_mmio::MvinMmio::FUNCT if xs1 >> 30 > 0 => misaligned(xs2 & 0x7F_FFFF_FFFF)?,
+            inst::f39_dma_sg::DmaSg::FUNCT => self.check_dma_sg(xs1, xs2, size, misaligned)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Check every descriptor of a dma_sg: its bank range against the bank
+    /// and its DRAM address against `misaligned`.
+    fn check_dma_sg(
+        &self,
+        xs1: u64,
+        xs2: u64,
+        size: usize,
+        misaligned: impl Fn(u64) -> Result<(), NpuError>,
+    ) -> Result<(), NpuError> {
+        use inst::f39_dma_sg::DESCRIPTOR_BYTES;
+        let vbank = inst::decode::rs1_b0(xs1) as u32;
+        let groups = self.bank_cfgs[vbank as usize].cols.max(1) as usize;
+        let list = inst::decode::xs2_mem_stride(xs2).0;
+        for d in 0..inst::decode::rs1_iter(xs1) {
+            let desc = self.read_dram(list + d * DESCRIPTOR_BYTES, DESCRIPTOR_BYTES as usize);
+            let addr = u64::from_le_bytes(desc[0..8].try_into().unwrap());
+            let offset = u32::from_le_bytes(desc[8..12].try_into().unwrap()) as usize;
+            let len = u32::from_le_bytes(desc[12..16].try_into().unwrap()) as usize;
+            if len == 0 {
+                continue;
+            }
+            // A descriptor stays inside one group of the bank.
+            if offset / size.max(1) >= groups || offset % size.max(1) + len > size {
+                return Err(NpuError::OutOfBounds {
+                    vbank,
+                    offset,
+                    len,
+                    size: groups * size,
+                });
+            }
+            misaligned(addr)?;
+        }
+        Ok(())
+    }
+
+    /// Run a text instruction program (see `Program`) from `path`.
+    pub fn run_program(&mut self, path: &Path) -> Result<crate::ProgramReport, String> {
+        Ok(crate::Program::load(path)?.run(self))
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        &mut self.memory
+    }
+
+    /// Exchange guest DRAM with `other`, so several models can take turns on
+    /// one DRAM image while keeping their own banks.
+    pub fn swap_dram(&mut self, other: &mut Npu) {
+        std::mem::swap(&mut self.memory, &mut other.memory);
+        std::mem::swap(&mut self.addr_map, &mut other.addr_map);
+    }
+
+    /// Read `len` bytes of guest DRAM starting at `addr`.
+    pub fn read_dram(&self, addr: u64, len: usize) -> Vec<u8> {
+        let mut bytes = vec![0u8; len];
+        mem_read_into(&self.memory, &self.addr_map, addr, &mut bytes);
+        bytes
+    }
+
+    /// Write `bytes` into guest DRAM starting at `addr`.
+    pub fn write_dram(&mut self, addr: u64, bytes: &[u8]) {
+        mem_write_from(&mut self.memory, &self.addr_map, addr, bytes);
+    }
+
+    /// Write the row-major rows x cols matrix `data` into guest DRAM at
+    /// `addr` as `width` elements laid out as `layout`. Fails if `data` has
+    /// the wrong length or an element does not fit `width`.
+    pub fn write_dram_matrix(
+        &mut self,
+        addr: u64,
+        data: &[i32],
+        rows: usize,
+        cols: usize,
+        width: ElemWidth,
+        layout: Layout,
+    ) -> Result<(), String> {
+        let bytes = layout::pack(data, rows, cols, width, layout)?;
+        self.write_dram(addr, &bytes);
+        Ok(())
+    }
+
+    /// Read back a rows x cols matrix of `width` elements laid out as
+    /// `layout` at `addr`, row-major.
+    pub fn read_dram_matrix(
+        &self,
+        addr: u64,
+        rows: usize,
+        cols: usize,
+        width: ElemWidth,
+        layout: Layout,
+    ) -> Result<Vec<i32>, String> {
+        layout.check()?;
+        let bytes = self.read_dram(addr, layout.len(rows, cols) * width.bytes());
+        Ok(layout::unpack(&bytes, rows, cols, width, layout))
+    }
+
+    /// Physical bank backing group 0 of `vbank`, if the bank is mapped.
+    pub fn bank(&self, vbank: u32) -> Option<&[u8]> {
+        self.bank_map.resolve(vbank).map(|p| self.banks[p].as_slice())
+    }
+
+    pub fn bank_mut(&mut self, vbank: u32) -> Option<&mut [u8]> {
+        let p = self.bank_map.resolve(vbank)?;
+        if let Some(tags) = &mut self.tile_tags {
+            tags.clear(p);
+        }
+        Some(self.banks[p].as_mut_slice())
+    }
+
+    /// Copy physical bank `from` into `to` with the golden result and tile
+    /// tags attached to it, for NpuSim's bank renaming.
+    pub(crate) fn copy_pbank(&mut self, from: usize, to: usize) {
+        let data = self.banks[from].clone();
+        self.banks[to] = data;
+        if let Some(golden) = &mut self.golden {
+            golden.copy(from, to);
+        }
+        if let Some(tags) = &mut self.tile_tags {
+            tags.copy(from, to);
+        }
+    }
+
+    /// Bytes [offset, offset + len) of `vbank`.
+    pub fn bank_range(&self, vbank: u32, offset: usize, len: usize) -> Result<&[u8], NpuError> {
+        let bank = self.bank(vbank).ok_or(NpuError::UnmappedBank { vbank })?;
+        let size = bank.len();
+        offset
+            .checked_add(len)
+            .and_then(|end| bank.get(offset..end))
+            .ok_or(NpuError::OutOfBounds {
+                vbank,
+                offset,
+                len,
+                size,
+            })
+    }
+
+    /// Copy `data` into `vbank` from byte `offset`.
+    pub fn write_bank_range(&mut self, vbank: u32, offset: usize, data: &[u8]) -> Result<(), NpuError> {
+        let bank = self.bank_mut(vbank).ok_or(NpuError::UnmappedBank { vbank })?;
+        let (size, len) = (bank.len(), data.len());
+        let dst = offset
+            .checked_add(len)
+            .and_then(|end| bank.get_mut(offset..end))
+            .ok_or(NpuError::OutOfBounds {
+                vbank,
+                offset,
+                len,
+                size,
+            })?;
+        dst.copy_from_slice(data);
+        Ok(())
+    }
+
+    /// The bank mapping table: per physical bank, the (vbank, group) bound
+    /// to it.
+    pub fn bmt(&self) -> Vec<Option<(u32, u32)>> {
+        self.bank_map
+            .slots
+            .iter()
+            .map(|e| e.valid.then_some((e.vbank_id, e.group_id)))
+            .collect()
+    }
+
+    /// Combined hash of every physical bank, for comparing end states.
+    pub fn bank_checksum(&self) -> u64 {
+        self.banks
+            .iter()
+            .fold(0, |acc, bank| acc.rotate_left(5) ^ bank_hash(bank))
+    }
+
+    /// Hash of all of guest DRAM.
+    pub fn dram_checksum(&self) -> u64 {
+        bank_hash(&self.memory)
+    }
+
+    pub fn total_latency(&self) -> u64 {
+        self.total_lat
+    }
+
+    pub fn instruction_count(&self) -> u64 {
+        self.npu_instruction_id
+    }
+
+    /// Utilization counters and roofline summary since the last reset.
+    pub fn perf_report(&self) -> PerfReport {
+        PerfReport::capture(self)
+    }
+
+    /// Full contents of `vbanks`, for `bebop bank-diff`.
+    pub fn dump_banks(&self, vbanks: &[u32]) -> Result<crate::BankDump, String> {
+        crate::BankDump::capture(self, vbanks)
+    }
+
+    /// Architectural state for `bebop snapshot-diff`.
+    pub fn snapshot(&self) -> crate::Snapshot {
+        crate::Snapshot::capture(self)
+    }
+
+    /// Write the full model state to `path` so a later run can resume from it.
+    pub fn save_checkpoint(&self, path: &Path) -> Result<(), String> {
+        crate::Checkpoint::capture(self).save(path)
+    }
+
+    /// Replace the model state with the checkpoint at `path`.
+    pub fn load_checkpoint(&mut self, path: &Path) -> Result<(), String> {
+        crate::Checkpoint::load(path)?.restore(self)
+    }
+
+    /// Geometry, policies and instruction set of this model.
+    pub fn manifest(&self) -> crate::Manifest {
+        crate::Manifest::capture(self)
+    }
+
+    /// Dubious-but-legal behaviour seen since the last reset.
+    pub fn warnings(&self) -> &Warnings {
+        &self.warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::{ElemWidth, DRAM_BASE};
+
+    #[test]
+    fn mvout_is_visible_in_dram_after_fence() {
+        let mut npu = Npu::new(1 << 20);
+        let src = DRAM_BASE + 0x1000;
+        let dst = DRAM_BASE + 0x2000;
+        let line: Vec<u8> = (1..=16).collect();
+        let one_row = 1 << 30;
+        let stride_one = 1 << 39;
+
+        npu.exec(32, 1, (1 << 5) | (1 << 10), 0); // mset bank 1, cols=1, alloc
+        npu.write_dram(src, &line);
+        npu.exec(33, 1 | one_row, src | stride_one, 0); // mvin
+        npu.exec(16, 1 | one_row, dst | stride_one, 0); // mvout
+        npu.exec(0, 0, 0, 0); // fence
+
+        assert_eq!(npu.read_dram(dst, 16), line);
+        assert_eq!(npu.bank(1).map(|b| &b[..16]), Some(line.as_slice()));
+        assert_eq!(npu.instruction_count(), 4);
+        assert_eq!(npu.total_latency(), 4);
+    }
+
+    #[test]
+    fn warnings_aggregate_count_and_cycles() {
+        let mut npu = Npu::new(1 << 20);
+        let alloc = (1 << 5) | (1 << 10);
+        npu.exec(32, 1, alloc, 0);
+        npu.exec(32, 1, alloc, 0); // realloc at cycle 1
+        npu.exec(32, 2, 0, 0); // free of a never-allocated bank
+        npu.exec(32, 1, alloc, 0); // realloc at cycle 3
+
+        let stats: Vec<_> = npu
+            .warnings()
+            .iter()
+            .map(|(k, s)| (k, s.count, s.first_cycle, s.last_cycle))
+            .collect();
+        assert_eq!(
+            stats,
+            [
+                (crate::WarningKind::MsetRealloc, 2, 1, 3),
+                (crate::WarningKind::MsetFreeUnallocated, 1, 2, 2)
+            ]
+        );
+        npu.reset();
+        assert!(npu.warnings().is_empty());
+    }
+
+    #[test]
+    fn misaligned_dma_pays_one_beat_per_row() {
+        let mut npu = Npu::new(1 << 20);
+        npu.exec(32, 1, (1 << 5) | (1 << 10), 0);
+        npu.exec(33, 1 | (4 << 30), (DRAM_BASE + 0x100) | (1 << 39), 0);
+        npu.exec(33, 1 | (4 << 30), (DRAM_BASE + 0x104) | (1 << 39), 0);
+        assert_eq!(npu.total_latency(), 1 + 4 + 8);
+        assert_eq!(
+            npu.dma_stats(),
+            DmaStats {
+                transfers: 2,
+                misaligned: 1,
+                penalty_cycles: 4,
+                ..DmaStats::default()
+            }
+        );
+    }
+
+    #[test]
+    fn mcopy_converts_between_element_widths() {
+        let mut npu = Npu::new(1 << 20);
+        npu.exec(32, 1, (1 << 5) | (1 << 10) | (2 << 11), 0); // bank1: i32
+        npu.exec(32, 2, (1 << 5) | (1 << 10), 0); // bank2: i8
+        npu.exec(32, 3, (1 << 5) | (1 << 10) | (1 << 11), 0); // bank3: i16
+        let words: Vec<u8> = [-3i32, 127, 128, -200].iter().flat_map(|v| v.to_le_bytes()).collect();
+        npu.bank_mut(1).unwrap()[..16].copy_from_slice(&words);
+
+        npu.exec(37, 1 | (2 << 10) | (1 << 30), 0, 0); // i32 -> i8, one row
+        assert_eq!(npu.bank(2).unwrap()[..4], [0xfd, 127, 127, 0x80]);
+        let stats: Vec<_> = npu.warnings().iter().map(|(k, s)| (k, s.count)).collect();
+        assert_eq!(stats, [(crate::WarningKind::McopySaturated, 1)]);
+
+        npu.exec(37, 2 | (3 << 10) | (1 << 30), 0, 0); // i8 -> i16 sign-extends
+        assert_eq!(npu.bank(3).unwrap()[..8], [0xfd, 0xff, 127, 0, 127, 0, 0x80, 0xff]);
+    }
+
+    #[test]
+    fn relu_and_transpose_rearrange_an_i32_tile() {
+        let mut npu = Npu::new(1 << 20);
+        npu.exec(32, 1, (1 << 5) | (1 << 10) | (2 << 11), 0); // bank1: i32
+        npu.exec(32, 2, (1 << 5) | (1 << 10) | (2 << 11), 0); // bank2: i32
+        let words: Vec<u8> = [1i32, -2, 3, -4, -5, 6, -7, 8]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        npu.bank_mut(1).unwrap()[..32].copy_from_slice(&words);
+
+        npu.exec(50, 1 | (1 << 10) | (2 << 30), 0, 0); // relu in place, two rows
+        npu.exec(51, 1 | (2 << 10) | (2 << 30), 0, 0); // 2x4 -> 4x2
+        let got: Vec<i32> = npu.bank(2).unwrap()[..64]
+            .chunks(4)
+            .map(|w| i32::from_le_bytes(w.try_into().unwrap()))
+            .collect();
+        assert_eq!(got, [1, 0, 0, 0, 0, 6, 0, 0, 3, 0, 0, 0, 0, 8, 0, 0]);
+    }
+
+    #[test]
+    fn counters_track_compute_dma_and_bank_conflicts() {
+        let mut npu = Npu::new(1 << 20);
+        npu.set_bank_ports(crate::BankPorts {
+            default: crate::PortKind::Single,
+            ..Default::default()
+        })
+        .unwrap();
+        for bank in 1..=3 {
+            npu.exec(32, bank, (1 << 5) | (1 << 10), 0);
+        }
+        npu.exec(33, 1 | (4 << 30), DRAM_BASE | (1 << 39), 0);
+        npu.exec(48, 1 | (2 << 10) | (3 << 20) | (4 << 30), 0, 0);
+        npu.exec(48, 1 | (2 << 10) | (3 << 20) | (4 << 30), 1, 0);
+        npu.exec(50, 3 | (3 << 10) | (4 << 30), 0, 0); // in place: 4 extra cycles
+        let read = |npu: &mut Npu, id: u64| npu.exec(40, id, 0, 0);
+        assert_eq!(read(&mut npu, 9), 2, "compute_ops");
+        assert_eq!(read(&mut npu, 10), 1, "dma_transfers");
+        assert_eq!(read(&mut npu, 11), 4, "bank_conflict_cycles");
+    }
+
+    #[test]
+    fn accumulator_bank_adds_writes() {
+        let mut npu = Npu::new(1 << 20);
+        let src = DRAM_BASE + 0x1000;
+        let words: Vec<u8> = [5i32, -7, i32::MAX, 1].iter().flat_map(|v| v.to_le_bytes()).collect();
+        npu.write_dram(src, &words);
+        npu.exec(32, 1, (1 << 5) | (1 << 10) | (2 << 11) | (1 << 13), 0); // bank1: i32 accumulator
+        npu.exec(33, 1 | (1 << 30), src | (1 << 39), 0);
+        npu.exec(33, 1 | (1 << 30), src | (1 << 39), 0);
+
+        let sums: Vec<u8> = [10i32, -14, i32::MAX, 2].iter().flat_map(|v| v.to_le_bytes()).collect();
+        assert_eq!(npu.bank(1).map(|b| &b[..16]), Some(sums.as_slice()));
+        let warnings: Vec<_> = npu.warnings().iter().map(|(k, s)| (k, s.count)).collect();
+        assert_eq!(warnings, [(crate::WarningKind::MvinSaturated, 1)]);
+    }
+
+    #[test]
+    fn bmt_remaps_and_queries_groups() {
+        let mut npu = Npu::new(1 << 20);
+        npu.exec(32, 1, (2 << 5) | (1 << 10), 0); // bank1: two groups
+        assert_eq!(npu.exec(38, 1 | (1 << 10), 1, 0), 1);
+
+        npu.bank_mut(1).unwrap()[0] = 7;
+        npu.exec(38, 1 | (5 << 10), (1 << 1) | (30 << 8), 0); // cyclic, step 30 of 32
+        assert_eq!(npu.exec(38, 1 | (1 << 10), 1, 0), 3);
+        assert_eq!(npu.exec(38, 2, 1, 0), u64::MAX);
+        let bound: Vec<_> = npu
+            .bmt()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(p, e)| Some((p, e?)))
+            .collect();
+        assert_eq!(bound, [(3, (1, 1)), (5, (1, 0))]);
+        // Only the table moved; pbank 5 never held bank1's data.
+        assert_eq!(npu.bank(1).unwrap()[0], 0);
+    }
+
+    #[test]
+    fn smaller_array_tiles_matmul_into_more_passes() {
+        let matmul = |array: Option<ArrayGeometry>, b_width: u64| {
+            let mut npu = Npu::new(1 << 20);
+            if let Some(array) = array {
+                npu.set_array_geometry(array).unwrap();
+            }
+            npu.exec(32, 1, (1 << 5) | (1 << 10), 0);
+            npu.exec(32, 2, (1 << 5) | (1 << 10) | (b_width << 11), 0);
+            npu.exec(32, 3, (1 << 5) | (1 << 10) | (2 << 11), 0);
+            npu.bank_mut(1).unwrap()[..16].fill(1);
+            npu.bank_mut(2).unwrap().fill(1);
+            let start = npu.total_latency();
+            npu.exec(48, 1 | (2 << 10) | (3 << 20) | (4 << 30), 0, 0);
+            let sum = ElemWidth::I32.load(npu.bank(3).unwrap(), 0);
+            (npu.total_latency() - start, sum, npu.perf_report().peak_macs_per_cycle)
+        };
+        assert_eq!(matmul(None, 0), (4 + 16, 16, 256));
+        // K = 16 and N = 16 on 8x4 PEs: 2 x 4 tiles of 4 rows plus an 8-cycle fill.
+        let small = ArrayGeometry { rows: 8, cols: 4 };
+        assert_eq!(matmul(Some(small), 0), (8 * (4 + 8), 16, 32));
+        // i32 B has N = 4, so only K needs tiling.
+        assert_eq!(matmul(Some(small), 2).0, 2 * (4 + 8));
+        assert!(Npu::new(1 << 20)
+            .set_array_geometry(ArrayGeometry { rows: 0, cols: 4 })
+            .is_err());
+    }
+
+    #[test]
+    fn wide_matmul_runs_unchanged_on_any_array() {
+        // K = 64 and N = 32: A rows span 4 lines and B rows 2.
+        let (m, k, n) = (8usize, 64usize, 32usize);
+        let a: Vec<i8> = (0..m * k).map(|i| (i * 37 % 255) as u8 as i8).collect();
+        let b: Vec<i8> = (0..k * n).map(|i| (i * 91 % 255) as u8 as i8).collect();
+        let run = |array: ArrayGeometry| {
+            let mut npu = Npu::new(1 << 20);
+            npu.set_array_geometry(array).unwrap();
+            npu.enable_golden_check();
+            npu.exec(32, 1, (1 << 5) | (1 << 10), 0);
+            npu.exec(32, 2, (1 << 5) | (1 << 10), 0);
+            npu.exec(32, 3, (1 << 5) | (1 << 10) | (2 << 11), 0);
+            npu.bank_mut(1).unwrap()[..a.len()].copy_from_slice(&a.iter().map(|&v| v as u8).collect::<Vec<_>>());
+            npu.bank_mut(2).unwrap()[..b.len()].copy_from_slice(&b.iter().map(|&v| v as u8).collect::<Vec<_>>());
+            let start = npu.total_latency();
+            npu.exec(
+                48,
+                1 | (2 << 10) | (3 << 20) | ((m as u64) << 30),
+                (4 << 8) | (2 << 16),
+                0,
+            );
+            assert!(npu.golden_mismatches().is_empty());
+            let c: Vec<f64> = npu.bank(3).unwrap()[..m * n * 4]
+                .chunks(4)
+                .map(|w| i32::from_le_bytes(w.try_into().unwrap()) as f64)
+                .collect();
+            (npu.total_latency() - start, c)
+        };
+        let (full, c) = run(ArrayGeometry::default());
+        assert_eq!(
+            c,
+            bebop_golden::matmul(&bebop_golden::widen(&a), &bebop_golden::widen(&b), m, k, n)
+        );
+        // 4 K tiles x 2 N tiles, each streaming 8 rows plus a 16-cycle fill.
+        assert_eq!(full, 8 * (8 + 16));
+        let (small, c_small) = run(ArrayGeometry { rows: 8, cols: 8 });
+        assert_eq!(c_small, c);
+        assert_eq!(small, 8 * 4 * (8 + 8));
+    }
+
+    #[test]
+    fn dma_sg_walks_descriptors_both_ways() {
+        let mut npu = Npu::new(1 << 20);
+        let list = DRAM_BASE + 0x100;
+        let desc = |addr: u64, offset: u32, len: u32| {
+            [addr.to_le_bytes().as_slice(), &offset.to_le_bytes(), &len.to_le_bytes()].concat()
+        };
+        npu.write_dram(DRAM_BASE + 0x1000, &[1; 8]);
+        npu.write_dram(DRAM_BASE + 0x2004, &[2; 20]);
+        npu.write_dram(
+            list,
+            &[desc(DRAM_BASE + 0x1000, 4, 8), desc(DRAM_BASE + 0x2004, 32, 20)].concat(),
+        );
+        npu.exec(32, 1, (1 << 5) | (1 << 10), 0);
+        let start = npu.total_latency();
+        npu.exec(39, 1 | (2 << 30), list, 0);
+        // Two descriptor fetches, then 1 + 2 beats; the second is misaligned.
+        assert_eq!(npu.total_latency() - start, 2 + 3 + 2);
+        let bank = npu.bank(1).unwrap();
+        assert_eq!(
+            (&bank[..4], &bank[4..12], &bank[32..52]),
+            (&[0; 4][..], &[1; 8][..], &[2; 20][..])
+        );
+
+        let out = DRAM_BASE + 0x3000;
+        npu.write_dram(list, &desc(out, 32, 4));
+        npu.exec(39, 1 | (1 << 10) | (1 << 30), list, 0);
+        assert_eq!(npu.read_dram(out, 5), [2, 2, 2, 2, 0]);
+    }
+
+    #[test]
+    fn int8_matmul_matches_golden() {
+        let mut npu = Npu::new(1 << 20);
+        npu.exec(32, 1, (1 << 5) | (1 << 10), 0); // A: i8, 4x16
+        npu.exec(32, 2, (1 << 5) | (1 << 10), 0); // B: i8, 16x16
+        npu.exec(32, 3, (1 << 5) | (1 << 10) | (2 << 11), 0); // C: i32
+        let a: Vec<i8> = (0..4 * 16).map(|i| (i * 37 % 255 - 127) as i8).collect();
+        let b: Vec<i8> = (0..16 * 16).map(|i| (i * 91 % 255 - 127) as i8).collect();
+        npu.bank_mut(1).unwrap()[..a.len()].copy_from_slice(&a.iter().map(|&v| v as u8).collect::<Vec<_>>());
+        npu.bank_mut(2).unwrap()[..b.len()].copy_from_slice(&b.iter().map(|&v| v as u8).collect::<Vec<_>>());
+
+        npu.exec(48, 1 | (2 << 10) | (3 << 20) | (4 << 30), 0, 0);
+        let c: Vec<f64> = npu.bank(3).unwrap()[..4 * 16 * 4]
+            .chunks(4)
+            .map(|w| i32::from_le_bytes(w.try_into().unwrap()) as f64)
+            .collect();
+        let want = bebop_golden::matmul(&bebop_golden::widen(&a), &bebop_golden::widen(&b), 4, 16, 16);
+        assert_eq!(c, want);
+        assert!(npu.warnings().is_empty());
+
+        // Accumulating a second time doubles C.
+        npu.exec(48, 1 | (2 << 10) | (3 << 20) | (4 << 30), 1, 0);
+        assert_eq!(
+            i32::from_le_bytes(npu.bank(3).unwrap()[..4].try_into().unwrap()) as f64,
+            2.0 * want[0]
+        );
+    }
+
+    #[test]
+    fn float_matmul_rounds_into_c_and_reports_the_error() {
+        use crate::numfmt::{Bf16, NumberFormat, Posit8};
+        let mut npu = Npu::new(1 << 20);
+        npu.enable_golden_check();
+        npu.exec(32, 1, (1 << 5) | (1 << 10) | (4 << 14), 0); // A: posit8, 4x16
+        npu.exec(32, 2, (1 << 5) | (1 << 10) | (4 << 14), 0); // B: posit8, 16x16
+        npu.exec(32, 3, (1 << 5) | (1 << 10) | (2 << 14), 0); // C: bf16
+        npu.exec(32, 4, (1 << 5) | (1 << 10) | (1 << 14), 0); // fp32
+        let a: Vec<f32> = (0..4 * 16).map(|i| (i % 7) as f32 * 0.375 - 1.0).collect();
+        let b: Vec<f32> = (0..16 * 16).map(|i| (i % 5) as f32 * 1.25 - 2.5).collect();
+        let a_bytes: Vec<u8> = a.iter().map(|&v| Posit8::encode(v) as u8).collect();
+        let b_bytes: Vec<u8> = b.iter().map(|&v| Posit8::encode(v) as u8).collect();
+        npu.bank_mut(1).unwrap()[..64].copy_from_slice(&a_bytes);
+        npu.bank_mut(2).unwrap()[..256].copy_from_slice(&b_bytes);
+
+        npu.exec(48, 1 | (2 << 10) | (3 << 20) | (4 << 30), 0, 0);
+        let c = npu.bank(3).unwrap();
+        for (i, j) in [(0, 0), (1, 5), (3, 15)] {
+            let want: f32 = (0..16)
+                .map(|p| Posit8::decode(a_bytes[i * 16 + p] as u32) * Posit8::decode(b_bytes[p * 16 + j] as u32))
+                .sum();
+            let got = Bf16::decode(u16::from_le_bytes([c[(i * 16 + j) * 2], c[(i * 16 + j) * 2 + 1]]) as u32);
+            assert_eq!(got, Bf16::decode(Bf16::encode(want)));
+        }
+        assert!(npu.golden_mismatches().is_empty(), "{:?}", npu.golden_mismatches());
+        let bf16 = npu.format_errors()["bf16"];
+        assert_eq!(bf16.values, 64);
+        assert!(bf16.max_rel <= 1.0 / 256.0, "{bf16}");
+
+        // Widening to fp32 is exact; the error is recorded all the same.
+        npu.exec(37, 3 | (4 << 10) | (8 << 30), 0, 0);
+        assert_eq!(npu.format_errors()["fp32"].max_abs, 0.0);
+    }
+
+    #[test]
+    fn bfp8_matmul_aligns_blocks_to_the_largest_exponent() {
+        let fp32 = |values: &[f32]| values.iter().flat_map(|v| v.to_le_bytes()).collect::<Vec<u8>>();
+        let mut npu = Npu::new(1 << 20);
+        npu.enable_golden_check();
+        let mut a = vec![0.0; 4 * 16];
+        a[..2].copy_from_slice(&[1.0, 1.0]);
+        let mut b = vec![0.0; 16 * 16];
+        b[0] = 64.0;
+        b[16] = 2f32.powi(-10);
+        b[32..34].copy_from_slice(&[100.0, 1.3]);
+        npu.write_dram(DRAM_BASE, &fp32(&a));
+        npu.write_dram(DRAM_BASE + 0x1000, &fp32(&b));
+        for bank in [1, 2, 4] {
+            npu.exec(32, bank, (1 << 5) | (1 << 10) | (5 << 14), 0); // bfp8
+        }
+        npu.exec(32, 3, (1 << 5) | (1 << 10) | (1 << 14), 0); // fp32
+        npu.exec(41, 1 | (4 << 30), DRAM_BASE | (1 << 39), 0);
+        npu.exec(41, 2 | (16 << 30), (DRAM_BASE + 0x1000) | (1 << 39), 0);
+        // 1.3 shares its line with 100, whose exponent leaves it 1.
+        let loaded = npu.format_errors()["bfp8"];
+        assert_eq!(loaded.values, 20 * 16);
+        assert!((loaded.max_abs - 0.3).abs() < 1e-6, "{loaded}");
+
+        // 1 * 2^-10 is shifted out when aligned to 1 * 64.
+        npu.exec(48, 1 | (2 << 10) | (3 << 20) | (4 << 30), 0, 0);
+        let c = f32::from_le_bytes(npu.bank(3).unwrap()[..4].try_into().unwrap());
+        assert_eq!(c, 64.0);
+        let errors = npu.format_errors()["bfp8"];
+        assert_eq!(errors.values, loaded.values + 64);
+        assert_eq!(errors.sum_abs - loaded.sum_abs, 2f64.powi(-10));
+
+        // A bfp8 C renormalizes each row into one block.
+        npu.exec(48, 1 | (2 << 10) | (4 << 20) | (4 << 30), 0, 0);
+        assert_eq!(npu.bfp_exps[4][0], 0);
+        npu.exec(17, 4 | (4 << 30), (DRAM_BASE + 0x2000) | (1 << 39), 0);
+        assert_eq!(npu.read_dram(DRAM_BASE + 0x2000, 8), fp32(&[64.0, 0.0]));
+        assert!(npu.golden_mismatches().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "holds integers")]
+    fn float_matmul_needs_a_float_c() {
+        let mut npu = Npu::new(1 << 20);
+        npu.exec(32, 1, (1 << 5) | (1 << 10) | (2 << 14), 0); // bf16
+        npu.exec(32, 2, (1 << 5) | (1 << 10) | (2 << 14), 0); // bf16
+        npu.exec(32, 3, (1 << 5) | (1 << 10) | (2 << 11), 0); // i32
+        npu.exec(48, 1 | (2 << 10) | (3 << 20) | (4 << 30), 0, 0);
+    }
+
+    #[test]
+    fn tile_tags_catch_stale_types_and_row_layouts() {
+        let mismatches = |npu: &Npu| -> Vec<String> {
+            npu.warnings()
+                .iter()
+                .filter(|(k, _)| *k == crate::WarningKind::TileTagMismatch)
+                .map(|(_, s)| s.first.clone())
+                .collect()
+        };
+        let mut npu = Npu::new(1 << 20);
+        npu.enable_tile_tags();
+        npu.exec(32, 1, (1 << 5) | (1 << 10), 0); // A: i8, pbank 0
+        npu.exec(32, 2, (1 << 5) | (1 << 10), 0); // B: i8, pbank 1
+        npu.exec(32, 3, (1 << 5) | (1 << 10) | (2 << 11), 0); // C: i32, pbank 2
+        npu.exec(32, 5, (1 << 5) | (1 << 10) | (2 << 11), 0); // i32, pbank 3
+        npu.exec(33, 5 | (4 << 30), DRAM_BASE | (1 << 39), 0);
+        npu.exec(32, 5, 0, 0); // free; pbank 3 keeps the i32 rows
+        npu.exec(33, 2 | (16 << 30), DRAM_BASE | (1 << 39), 0);
+        npu.exec(48, 1 | (2 << 10) | (3 << 20) | (4 << 30), 0, 0);
+        assert!(mismatches(&npu).is_empty());
+
+        npu.exec(38, 1 | (3 << 10), 0, 0); // A now reads pbank 3
+        npu.exec(48, 1 | (2 << 10) | (3 << 20) | (4 << 30), 0, 0);
+        assert_eq!(
+            mismatches(&npu),
+            ["matmul A bank1 line 0: written as i32 in 1-line rows, read as i8 in 1-line rows (4 lines differ)"]
+        );
+
+        // A written as 4-line rows but read with k_lines = 2.
+        let mut npu = Npu::new(1 << 20);
+        npu.enable_tile_tags();
+        npu.exec(32, 1, (1 << 5) | (1 << 10), 0);
+        npu.exec(32, 2, (1 << 5) | (1 << 10), 0);
+        npu.exec(32, 3, (1 << 5) | (1 << 10) | (2 << 11), 0);
+        npu.exec(33, 1 | (4 << 10) | (2 << 30), DRAM_BASE | (4 << 39), 0);
+        npu.exec(48, 1 | (2 << 10) | (3 << 20) | (4 << 30), 2 << 8, 0);
+        assert_eq!(
+            mismatches(&npu),
+            ["matmul A bank1 line 0: written as i8 in 4-line rows, read as i8 in 2-line rows (8 lines differ)"]
+        );
+    }
+
+    #[test]
+    fn csr_spmm_matches_the_dense_matmul_in_fewer_cycles() {
+        let mut npu = Npu::new(1 << 20);
+        for bank in [1, 2, 4] {
+            npu.exec(32, bank, (1 << 5) | (1 << 10), 0); // A, B, CSR A: i8
+        }
+        for bank in [3, 5] {
+            npu.exec(32, bank, (1 << 5) | (1 << 10) | (2 << 11), 0); // C: i32
+        }
+        // 64 rows with one nonzero each, on the diagonal of each 16-row block.
+        let m = 64;
+        let mut a = vec![0u8; m * 16];
+        let (mut ptr, mut cols, mut values) = (vec![0u16], Vec::new(), Vec::new());
+        for i in 0..m {
+            a[i * 16 + i % 16] = i as u8 + 1;
+            cols.push((i % 16) as u16);
+            values.push(i as u8 + 1);
+            ptr.push(cols.len() as u16);
+        }
+        let b: Vec<u8> = (0..16 * 16).map(|i| (i * 91 % 255) as u8).collect();
+        let mut csr: Vec<u8> = ptr.iter().chain(&cols).flat_map(|v| v.to_le_bytes()).collect();
+        csr.resize(crate::inst::f54_spmm::Spmm::values_offset(m, m), 0);
+        csr.extend(&values);
+        npu.bank_mut(1).unwrap()[..a.len()].copy_from_slice(&a);
+        npu.bank_mut(2).unwrap()[..b.len()].copy_from_slice(&b);
+        npu.bank_mut(4).unwrap()[..csr.len()].copy_from_slice(&csr);
+
+        let start = npu.total_latency();
+        npu.exec(48, 1 | (2 << 10) | (3 << 20) | ((m as u64) << 30), 0, 0);
+        let dense = npu.total_latency() - start;
+        npu.exec(54, 4 | (2 << 10) | (5 << 20) | ((m as u64) << 30), (m as u64) << 32, 0);
+        let sparse = npu.total_latency() - start - dense;
+        assert_eq!(npu.bank(5).unwrap()[..m * 64], npu.bank(3).unwrap()[..m * 64]);
+        assert_eq!((dense, sparse), (80, 20));
+    }
+
+    #[test]
+    fn loop_ws_tiles_a_dram_matmul() {
+        let (m, k, n) = (20usize, 32usize, 48usize);
+        let (a_addr, b_addr, c_addr) = (DRAM_BASE, DRAM_BASE + 0x1000, DRAM_BASE + 0x2000);
+        let a: Vec<i8> = (0..m * k).map(|i| (i * 37 % 255) as u8 as i8).collect();
+        let b: Vec<i8> = (0..k * n).map(|i| (i * 91 % 255) as u8 as i8).collect();
+        let mut npu = Npu::new(1 << 20);
+        npu.enable_golden_check();
+        npu.write_dram(a_addr, &a.iter().map(|&v| v as u8).collect::<Vec<_>>());
+        npu.write_dram(b_addr, &b.iter().map(|&v| v as u8).collect::<Vec<_>>());
+        npu.exec(32, 1, (1 << 5) | (1 << 10), 0); // A: i8
+        npu.exec(32, 2, (1 << 5) | (1 << 10), 0); // B: i8
+        npu.exec(32, 3, (1 << 5) | (1 << 10) | (2 << 11), 0); // C: i32
+
+        npu.exec(52, a_addr | (1 << 39), b_addr | (2 << 39), 0); // loop_ab
+        npu.exec(
+            53,
+            c_addr | (3 << 39),
+            m as u64 | ((k as u64) << 16) | ((n as u64) << 32),
+            0,
+        );
+        npu.exec(0, 0, 0, 0); // fence
+
+        let c: Vec<f64> = npu
+            .read_dram(c_addr, m * n * 4)
+            .chunks(4)
+            .map(|w| i32::from_le_bytes(w.try_into().unwrap()) as f64)
+            .collect();
+        let want = bebop_golden::matmul(&bebop_golden::widen(&a), &bebop_golden::widen(&b), m, k, n);
+        assert_eq!(c, want);
+        assert!(npu.golden_mismatches().is_empty());
+        // One matmul per (N tile, K tile), one mvout per N tile.
+        let count = |name: &str| {
+            npu.perf_report()
+                .per_inst
+                .iter()
+                .find(|p| p.name == name)
+                .map_or(0, |p| p.count)
+        };
+        assert_eq!((count("loop_ws"), count("matmul"), count("mvout")), (1, 6, 3));
+    }
+
+    #[test]
+    fn conv_matches_golden_with_stride_and_padding() {
+        use bebop_golden::{conv2d, widen, Conv2dParams};
+
+        let mut npu = Npu::new(1 << 20);
+        npu.enable_golden_check();
+        npu.exec(32, 1, (1 << 5) | (1 << 10), 0); // input: i8
+        npu.exec(32, 2, (1 << 5) | (1 << 10), 0); // weights: i8
+        npu.exec(32, 3, (1 << 5) | (1 << 10) | (2 << 11), 0); // output: i32
+        let p = Conv2dParams {
+            stride: 2,
+            padding: 1,
+            ..Conv2dParams::new(2, 3, 7, 6, 3, 2)
+        };
+        let input: Vec<i8> = (0..2 * 7 * 6).map(|i| (i * 29 % 61 - 30) as i8).collect();
+        let weight: Vec<i8> = (0..3 * 2 * 3 * 2).map(|i| (i * 7 % 13 - 6) as i8).collect();
+        npu.bank_mut(1).unwrap()[..input.len()].copy_from_slice(&input.iter().map(|&v| v as u8).collect::<Vec<_>>());
+        npu.bank_mut(2).unwrap()[..weight.len()].copy_from_slice(&weight.iter().map(|&v| v as u8).collect::<Vec<_>>());
+
+        let xs2 = 2 | (3 << 8) | (7 << 16) | (6 << 26) | (3 << 36) | (2 << 40) | (2 << 44) | (1 << 48);
+        npu.exec(49, 1 | (2 << 10) | (3 << 20), xs2, 0);
+        let want = conv2d(&widen(&input), &widen(&weight), &p);
+        let got: Vec<f64> = npu.bank(3).unwrap()[..want.len() * 4]
+            .chunks(4)
+            .map(|w| i32::from_le_bytes(w.try_into().unwrap()) as f64)
+            .collect();
+        assert_eq!(got, want);
+        assert!(npu.golden_mismatches().is_empty());
+    }
+
+    #[test]
+    fn conv_dilation_and_rot180_match_golden() {
+        use bebop_golden::{conv2d, widen, Conv2dParams};
+
+        let mut npu = Npu::new(1 << 20);
+        npu.enable_golden_check();
+        npu.exec(32, 1, (1 << 5) | (1 << 10), 0); // input: i8
+        npu.exec(32, 2, (1 << 5) | (1 << 10), 0); // weights: i8
+        npu.exec(32, 3, (1 << 5) | (1 << 10) | (2 << 11), 0); // output: i32
+        let input: Vec<i8> = (0..2 * 5 * 4).map(|i| (i * 29 % 61 - 30) as i8).collect();
+        let weight: Vec<i8> = (0..2 * 2 * 3 * 3).map(|i| (i * 7 % 13 - 6) as i8).collect();
+        npu.bank_mut(1).unwrap()[..input.len()].copy_from_slice(&input.iter().map(|&v| v as u8).collect::<Vec<_>>());
+        npu.bank_mut(2).unwrap()[..weight.len()].copy_from_slice(&weight.iter().map(|&v| v as u8).collect::<Vec<_>>());
+        let conv = |npu: &mut Npu, xs2: u64, len: usize| {
+            npu.exec(49, 1 | (2 << 10) | (3 << 20), xs2, 0);
+            npu.bank(3).unwrap()[..len * 4]
+                .chunks(4)
+                .map(|w| i32::from_le_bytes(w.try_into().unwrap()) as f64)
+                .collect::<Vec<_>>()
+        };
+        let shape = |in_ch: u64, out_ch: u64| in_ch | (out_ch << 8) | (5 << 16) | (4 << 26) | (3 << 36) | (3 << 40);
+
+        // Kernel dilation 2 with padding 2.
+        let p = Conv2dParams {
+            padding: 2,
+            dilation: 2,
+            ..Conv2dParams::new(2, 2, 5, 4, 3, 3)
+        };
+        let want = conv2d(&widen(&input), &widen(&weight), &p);
+        assert_eq!(conv(&mut npu, shape(2, 2) | (2 << 48) | (2 << 52), want.len()), want);
+
+        // Input dilation 2, rot180 and padding k-1-p is the transposed conv of
+        // stride 2 and padding p; with one channel each the layouts agree.
+        let p = Conv2dParams {
+            stride: 2,
+            padding: 1,
+            transposed: true,
+            ..Conv2dParams::new(1, 1, 5, 4, 3, 3)
+        };
+        let want = conv2d(&widen(&input[..20]), &widen(&weight[..9]), &p);
+        let xs2 = shape(1, 1) | (1 << 48) | (2 << 56) | (1 << 60);
+        assert_eq!(conv(&mut npu, xs2, want.len()), want);
+        assert!(npu.golden_mismatches().is_empty());
+    }
+
+    #[test]
+    fn im2col_then_matmul_computes_the_conv() {
+        let mut npu = Npu::new(1 << 20);
+        for bank in [1, 2, 4, 5] {
+            npu.exec(32, bank, (1 << 5) | (1 << 10), 0); // input, weights, patches, B: i8
+        }
+        for bank in [3, 6] {
+            npu.exec(32, bank, (1 << 5) | (1 << 10) | (2 << 11), 0); // conv and matmul C: i32
+        }
+        let input: Vec<u8> = (0..6 * 6).map(|i| (i * 29 % 61) as u8).collect();
+        let weight: Vec<u8> = (0..4 * 9).map(|i| (i * 7 % 13) as u8).collect();
+        // B row (ky, kx), column oc.
+        let mut b = vec![0u8; 16 * 16];
+        for oc in 0..4 {
+            for tap in 0..9 {
+                b[tap * 16 + oc] = weight[oc * 9 + tap];
+            }
+        }
+        npu.bank_mut(1).unwrap()[..input.len()].copy_from_slice(&input);
+        npu.bank_mut(2).unwrap()[..weight.len()].copy_from_slice(&weight);
+        npu.bank_mut(5).unwrap()[..b.len()].copy_from_slice(&b);
+
+        // One channel, 6x6, 3x3 kernel, padding 1: 36 output pixels.
+        let shape = 1 | (6 << 16) | (6 << 26) | (3 << 36) | (3 << 40) | (1 << 48);
+        npu.exec(49, 1 | (2 << 10) | (3 << 20), shape | (4 << 8), 0);
+        let start = npu.total_latency();
+        npu.exec(55, 1 | (4 << 10), shape, 0);
+        assert_eq!(npu.total_latency() - start, 36);
+        npu.exec(48, 4 | (5 << 10) | (6 << 20) | (36 << 30), 0, 0);
+
+        let word = |bank: &[u8], i: usize| i32::from_le_bytes(bank[4 * i..4 * i + 4].try_into().unwrap());
+        let (conv, matmul) = (npu.bank(3).unwrap(), npu.bank(6).unwrap());
+        for oc in 0..4 {
+            for m in 0..36 {
+                assert_eq!(
+                    word(matmul, m * 16 + oc),
+                    word(conv, oc * 36 + m),
+                    "pixel {m} channel {oc}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn pool_takes_the_max_or_rounded_mean_of_each_window() {
+        let mut npu = Npu::new(1 << 20);
+        npu.exec(32, 1, (1 << 5) | (1 << 10), 0);
+        npu.exec(32, 2, (1 << 5) | (1 << 10), 0);
+        #[rustfmt::skip]
+        let input: [i8; 16] = [
+            1, 2, -3, -4,
+            5, 6, -7, -9,
+            0, 0, 9, 9,
+            0, 1, 9, 8,
+        ];
+        npu.bank_mut(1).unwrap()[..16].copy_from_slice(&input.map(|v| v as u8));
+        let pooled = |npu: &mut Npu, xs2: u64, len: usize| {
+            npu.exec(56, 1 | (2 << 10), xs2, 0);
+            npu.bank(2).unwrap()[..len].iter().map(|&v| v as i8).collect::<Vec<_>>()
+        };
+
+        // One channel, 4x4, 2x2 window, stride 2.
+        let shape = 1 | (4 << 16) | (4 << 26) | (2 << 36) | (2 << 40) | (2 << 44);
+        assert_eq!(pooled(&mut npu, shape, 4), [6, -3, 1, 9]);
+        let start = npu.total_latency();
+        assert_eq!(pooled(&mut npu, shape | (1 << 61), 4), [4, -6, 0, 9]);
+        assert_eq!(npu.total_latency() - start, 4);
+
+        // 3x3 max with stride 1 and padding 1 keeps the 4x4 shape.
+        let shape = 1 | (4 << 16) | (4 << 26) | (3 << 36) | (3 << 40) | (1 << 44) | (1 << 48);
+        let out = pooled(&mut npu, shape, 16);
+        assert_eq!(out[..4], [6, 6, 6, -3]);
+    }
+
+    #[test]
+    fn norm_layernorms_and_softmaxes_through_a_stat() {
+        let mut npu = Npu::new(1 << 20);
+        npu.exec(32, 1, (1 << 5) | (1 << 10) | (2 << 11), 0); // bank1: i32
+        npu.exec(32, 2, (1 << 5) | (1 << 10), 0); // bank2: i8
+        let input: Vec<u8> = [10i32, 20, 30, 40].iter().flat_map(|v| v.to_le_bytes()).collect();
+        npu.bank_mut(1).unwrap()[..16].copy_from_slice(&input);
+        let norm = |npu: &mut Npu, op: u64, frac: u64| {
+            npu.exec(57, 1 | (2 << 10) | (4 << 30), 3 | (op << 4) | frac, 0);
+            npu.bank(2).unwrap()[..4].iter().map(|&v| v as i8).collect::<Vec<_>>()
+        };
+
+        norm(&mut npu, 0, 0);
+        norm(&mut npu, 1, 0);
+        let start = npu.total_latency();
+        assert_eq!(norm(&mut npu, 3, 4 << 16), [-21, -7, 7, 21]);
+        assert_eq!(npu.total_latency() - start, 1 + 8);
+
+        norm(&mut npu, 2, 3 << 8);
+        assert_eq!(norm(&mut npu, 4, (3 << 8) | (7 << 16)), [2, 8, 26, 92]);
+        assert!(npu.warnings().is_empty());
+        assert_eq!(norm(&mut npu, 4, (3 << 8) | (8 << 16))[3], 127);
+        let warnings: Vec<_> = npu.warnings().iter().map(|(k, s)| (k, s.count)).collect();
+        assert_eq!(warnings, [(crate::WarningKind::NormSaturated, 1)]);
+    }
+
+    #[test]
+    fn qos_cap_stalls_later_transfers() {
+        let mut npu = Npu::new(1 << 20);
+        npu.exec(32, 1, (1 << 5) | (1 << 10), 0);
+        npu.exec(36, 0, 1 | (4 << 16), 0); // DRAM side: 1 row every 4 cycles
+        npu.exec(33, 1 | (4 << 30), DRAM_BASE | (1 << 39), 0);
+        assert_eq!(npu.total_latency(), 1 + 1 + 16);
+        assert_eq!(npu.dma_stats().throttle_cycles, 12);
+
+        npu.exec(36, 1, 2 | (1 << 16), 0); // bank side: 2 rows per cycle, no limit
+        npu.exec(36, 0, 0, 0); // uncap DRAM side
+        npu.exec(16, 1 | (4 << 30), DRAM_BASE | (1 << 39), 0);
+        assert_eq!(npu.total_latency(), 18 + 2 + 4);
+    }
+
+    #[test]
+    fn dram_timing_charges_row_misses() {
+        let mut npu = Npu::new(1 << 20);
+        npu.set_dram_timing(Some(DramTiming {
+            t_cas: 10,
+            t_rcd: 5,
+            t_rp: 3,
+            row_bytes: 1024,
+            banks: 2,
+            bytes_per_cycle: 0,
+            window: 0,
+        }))
+        .unwrap();
+        npu.exec(32, 1, (1 << 5) | (1 << 10), 0);
+        // Four rows 512 bytes apart: bank 0 row 0, hit, bank 1 row 0, hit.
+        npu.exec(33, 1 | (4 << 30), DRAM_BASE | (32 << 39), 0);
+        assert_eq!(npu.total_latency(), 1 + 4 + 10 + 5 + 5);
+        // Rows 2048 bytes apart all land in bank 0: the first reuses the open
+        // row 0, the second closes it.
+        npu.exec(33, 1 | (2 << 30), DRAM_BASE | (128 << 39), 0);
+        assert_eq!(npu.total_latency(), 25 + 2 + 10 + 8);
+        let stats = npu.dma_stats();
+        assert_eq!((stats.row_hits, stats.row_misses, stats.dram_cycles), (3, 3, 38));
+    }
+
+    #[test]
+    fn cache_hits_make_a_reloaded_tile_cheaper() {
+        let mut npu = Npu::new(1 << 20);
+        npu.set_cache(Some(CacheConfig {
+            size: 4096,
+            ways: 4,
+            line_bytes: 64,
+            mshrs: 2,
+            hit_latency: 2,
+            miss_latency: 30,
+        }))
+        .unwrap();
+        npu.exec(32, 1, (1 << 5) | (1 << 10), 0);
+        // Four contiguous 16-byte rows share one line: one miss, three hits.
+        npu.exec(33, 1 | (4 << 30), DRAM_BASE | (1 << 39), 0);
+        assert_eq!(npu.total_latency(), 1 + 4 + 2 + 30);
+        // The same tile again only pays the lookup.
+        npu.exec(33, 1 | (4 << 30), DRAM_BASE | (1 << 39), 0);
+        assert_eq!(npu.total_latency(), 37 + 4 + 2);
+        let stats = npu.cache_stats().unwrap();
+        assert_eq!((stats.hits, stats.misses, stats.cycles), (7, 1, 34));
+
+        npu.reset();
+        assert_eq!(npu.cache_stats(), Some(CacheStats::default()));
+    }
+
+    #[test]
+    fn dram_window_overlaps_row_opens() {
+        let mvin = |window: u64| {
+            let mut npu = Npu::new(1 << 20);
+            npu.set_dram_timing(Some(DramTiming {
+                t_cas: 10,
+                t_rcd: 5,
+                t_rp: 3,
+                row_bytes: 1024,
+                banks: 2,
+                bytes_per_cycle: 0,
+                window,
+            }))
+            .unwrap();
+            npu.exec(32, 1, (1 << 5) | (1 << 10), 0);
+            let start = npu.total_latency();
+            // One row per 1024 bytes: bank 0, bank 1, then each one's next row.
+            npu.exec(33, 1 | (4 << 30), DRAM_BASE | (64 << 39), 0);
+            npu.total_latency() - start
+        };
+        assert_eq!(mvin(0), 4 + (5 + 10) + 5 + 8 + 8);
+        assert_eq!(mvin(1), mvin(0));
+        // The bank 1 open and both reopens hide behind the first tCAS; the
+        // data bus then returns one row per cycle.
+        assert_eq!(mvin(4), 5 + 10 + 4);
+    }
+
+    #[test]
+    fn bank_geometry_sets_bank_size_and_access_latency() {
+        let mut npu = Npu::new(1 << 20);
+        let geometry = BankGeometry {
+            num_banks: 4,
+            bank_depth: 8,
+            read_latency: 2,
+            write_latency: 3,
+            ..BankGeometry::default()
+        };
+        npu.set_bank_geometry(geometry).unwrap();
+        npu.exec(32, 3, (1 << 5) | (1 << 10), 0);
+        assert_eq!(npu.bank(3).unwrap().len(), 8 * 16);
+        npu.exec(33, 3 | (4 << 30), DRAM_BASE | (1 << 39), 0);
+        npu.exec(16, 3 | (4 << 30), DRAM_BASE | (1 << 39), 0);
+        assert_eq!(npu.total_latency(), 1 + (4 + 3) + (4 + 2));
+        assert_eq!(npu.manifest().banks.count, 4);
+
+        let err = npu.set_bank_geometry(BankGeometry {
+            word_bits: 256,
+            ..geometry
+        });
+        assert_eq!(err.unwrap_err(), "bank geometry: word_bits must be 128, got 256");
+    }
+
+    #[test]
+    fn vbanks_past_32_work_with_more_banks() {
+        let mut npu = Npu::new(1 << 20);
+        npu.set_bank_geometry(BankGeometry {
+            num_banks: 64,
+            ..BankGeometry::default()
+        })
+        .unwrap();
+        npu.write_dram(DRAM_BASE, &[7; 64]);
+        npu.exec(32, 40, (1 << 5) | (1 << 10), 0);
+        npu.exec(33, 40 | (4 << 30), DRAM_BASE | (1 << 39), 0);
+        assert_eq!(npu.bank(40).unwrap()[..64], [7; 64]);
+    }
+
+    #[test]
+    #[should_panic(expected = "not 16-byte aligned")]
+    fn misaligned_dma_faults_when_configured() {
+        let mut npu = Npu::new(1 << 20);
+        npu.set_misaligned_dma(MisalignedDma::Fault);
+        npu.exec(32, 1, (1 << 5) | (1 << 10), 0);
+        npu.exec(16, 1 | (1 << 30), (DRAM_BASE + 0x8) | (1 << 39), 0);
+    }
+
+    #[test]
+    fn exec_rocc_reports_bad_operands_without_running() {
+        let mut npu = Npu::new(1 << 20);
+        let rocc = |funct: u32| (funct << 25) | (11 << 20) | (10 << 15) | (0b011 << 12) | 0x0b;
+        npu.set_misaligned_dma(MisalignedDma::Fault);
+        npu.exec(32, 1, (1 << 5) | (1 << 10), 0);
+
+        let mvin = |npu: &mut Npu, bank: u64, addr: u64| npu.exec_rocc(rocc(33), bank | (1 << 30), addr | (1 << 39), 0);
+        assert_eq!(mvin(&mut npu, 2, DRAM_BASE), Err(NpuError::UnmappedBank { vbank: 2 }));
+        assert_eq!(
+            mvin(&mut npu, 1, DRAM_BASE + 8),
+            Err(NpuError::Misaligned { addr: DRAM_BASE + 8 })
+        );
+        assert_eq!(
+            npu.exec_rocc(rocc(51), 1 | (1 << 10), 0, 0),
+            Err(NpuError::BankConflict { vbank: 1, funct: 51 })
+        );
+        assert_eq!(npu.exec_rocc(0x13, 0, 0, 0), Err(NpuError::InvalidInsn { insn: 0x13 }));
+        assert_eq!(
+            npu.exec_rocc(rocc(127), 0, 0, 0),
+            Err(NpuError::UnknownFunct { funct: 127 })
+        );
+        assert_eq!(npu.instruction_count(), 1);
+        assert!(mvin(&mut npu, 1, DRAM_BASE).is_ok());
+
+        let size = npu.bank(1).unwrap().len();
+        assert_eq!(
+            npu.write_bank_range(1, size - 1, &[0, 0]),
+            Err(NpuError::OutOfBounds {
+                vbank: 1,
+                offset: size - 1,
+                len: 2,
+                size
+            })
+        );
+    }
+
+    #[test]
+    fn exec_rocc_rejects_bank_ranges_and_misaligned_dma() {
+        let mut npu = Npu::new(1 << 20);
+        let rocc = |funct: u32| (funct << 25) | (11 << 20) | (10 << 15) | (0b011 << 12) | 0x0b;
+        npu.set_misaligned_dma(MisalignedDma::Fault);
+        npu.exec(32, 1, (1 << 5) | (1 << 10), 0);
+        npu.exec(32, 2, (1 << 5) | (1 << 10) | (5 << 14), 0);
+        let size = npu.bank(1).unwrap().len();
+        let lines = (size / 16) as u64;
+
+        // One row more than the bank holds, which used to abort in mvin.
+        assert_eq!(
+            npu.exec_rocc(rocc(33), 1 | ((lines + 1) << 30), DRAM_BASE | (1 << 39), 0),
+            Err(NpuError::OutOfBounds {
+                vbank: 1,
+                offset: 0,
+                len: size + 16,
+                size
+            })
+        );
+        assert_eq!(
+            npu.exec_rocc(rocc(16), 1 | ((lines + 1) << 30), DRAM_BASE | (1 << 39), 0),
+            Err(NpuError::OutOfBounds {
+                vbank: 1,
+                offset: 0,
+                len: size + 16,
+                size
+            })
+        );
+        assert!(npu
+            .exec_rocc(rocc(33), 1 | (lines << 30), DRAM_BASE | (1 << 39), 0)
+            .is_ok());
+
+        for (funct, xs1) in [(41, 2 | (1 << 30)), (17, 2 | (1 << 30)), (35, 1 << 30)] {
+            assert_eq!(
+                npu.exec_rocc(rocc(funct), xs1, (DRAM_BASE + 8) | (1 << 39), 0),
+                Err(NpuError::Misaligned { addr: DRAM_BASE + 8 }),
+                "funct {funct}"
+            );
+        }
+
+        // dma_sg descriptors: (DRAM address, bank offset, length).
+        let list = DRAM_BASE + 0x1000;
+        let desc = |addr: u64, offset: u32, len: u32| {
+            [addr.to_le_bytes().as_slice(), &offset.to_le_bytes(), &len.to_le_bytes()].concat()
+        };
+        npu.write_dram(list, &desc(DRAM_BASE, size as u32 - 8, 16));
+        assert_eq!(
+            npu.exec_rocc(rocc(39), 1 | (1 << 30), list, 0),
+            Err(NpuError::OutOfBounds {
+                vbank: 1,
+                offset: size - 8,
+                len: 16,
+                size
+            })
+        );
+        npu.write_dram(list, &desc(DRAM_BASE + 4, 0, 16));
+        assert_eq!(
+            npu.exec_rocc(rocc(39), 1 | (1 << 30), list, 0),
+            Err(NpuError::Misaligned { addr: DRAM_BASE + 4 })
+        );
+        assert_eq!(npu.instruction_count(), 3);
+    }
+
+    #[test]
+    fn debug_state_survives_a_panicking_hook() {
+        let mut npu = Npu::new(1 << 20);
+        npu.enable_golden_check();
+        npu.enable_tile_tags();
+        let caught = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            npu.with_taken(|npu| &mut npu.golden, |_, _| panic!("golden hook"));
+        }));
+        assert!(caught.is_err());
+        let caught = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            npu.with_taken(|npu| &mut npu.tile_tags, |_, _| panic!("tag hook"));
+        }));
+        assert!(caught.is_err());
+        assert!(npu.golden.is_some());
+        assert!(npu.tile_tags.is_some());
+    }
+
+    #[test]
+    fn fp32_accumulator_scales_loads_and_rectifies_stores() {
+        let f32s = |v: &[f32]| v.iter().flat_map(|x| x.to_le_bytes()).collect::<Vec<u8>>();
+        let mut npu = Npu::new(1 << 20);
+        npu.enable_golden_check();
+        npu.write_dram(DRAM_BASE + 0x1000, &f32s(&[1.0, 2.0, 3.0, 4.0]));
+        let b = [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, -1.0],
+        ];
+        npu.write_dram(DRAM_BASE + 0x2000, &f32s(b.as_flattened()));
+        npu.write_dram(DRAM_BASE + 0x3000, &f32s(&[1.0; 4]));
+        let program = crate::Program::parse(
+            "mset bank=1 format=fp32\n\
+             mset bank=2 format=fp32\n\
+             mset bank=3 format=fp32 acc=1\n\
+             acc_cfg relu=1 mvin_scale=0x40000000 mvout_scale=0x3f000000\n\
+             mvin bank=1 addr=0x80001000 rows=1\n\
+             mvin bank=2 addr=0x80002000 rows=4\n\
+             matmul a=1 b=2 c=3 rows=1\n\
+             mvin bank=3 addr=0x80003000 rows=1\n\
+             mvout bank=3 addr=0x80004000 rows=1\n",
+        )
+        .unwrap();
+        program.run(&mut npu);
+
+        // Loads are doubled, so C = 2A * 2B' plus 2 from the second mvin.
+        assert_eq!(npu.bank(3).unwrap()[..16], f32s(&[6.0, 10.0, 14.0, -14.0]));
+        assert_eq!(npu.read_dram(DRAM_BASE + 0x4000, 16), f32s(&[3.0, 5.0, 7.0, 0.0]));
+        assert_eq!(npu.golden_mismatches(), &[]);
+    }
+
+    #[test]
+    fn random_init_reproduces_from_seed() {
+        let alloc_and_read = |npu: &mut Npu| {
+            npu.reset();
+            npu.exec(32, 3, (1 << 5) | (1 << 10), 0);
+            npu.bank(3).unwrap()[..64].to_vec()
+        };
+        let mut a = Npu::new(1 << 16);
+        let mut b = Npu::new(1 << 16);
+        a.randomize(42);
+        b.randomize(42);
+        assert_eq!(a.memory(), b.memory());
+        assert!(a.memory().iter().any(|&x| x != 0));
+
+        let first = alloc_and_read(&mut a);
+        assert!(first.iter().any(|&x| x != 0), "allocated bank should hold garbage");
+        assert_eq!(alloc_and_read(&mut a), first);
+        assert_eq!(alloc_and_read(&mut b), first);
+        assert_eq!(a.init_seed(), Some(42));
+    }
+
+    /// Sums bank `xs1`'s first `xs2` bytes; counts its calls.
+    struct BankSum {
+        calls: u64,
+    }
+
+    impl Extension for BankSum {
+        fn name(&self) -> &str {
+            "banksum"
+        }
+
+        fn functs(&self) -> Vec<u32> {
+            vec![64]
+        }
+
+        fn execute(&mut self, npu: &mut Npu, _funct: u32, xs1: u64, xs2: u64) -> u64 {
+            self.calls += 1;
+            let bank = npu.bank(xs1 as u32).unwrap();
+            bank[..xs2 as usize].iter().map(|&b| b as u64).sum()
+        }
+
+        fn latency(&self, _funct: u32, _xs1: u64, xs2: u64) -> u64 {
+            xs2.div_ceil(16)
+        }
+
+        fn reset(&mut self) {
+            self.calls = 0;
+        }
+
+        fn stats(&self) -> Vec<(String, u64)> {
+            vec![("calls".into(), self.calls)]
+        }
+    }
+
+    #[test]
+    fn extension_executes_unclaimed_functs() {
+        let mut npu = Npu::new(1 << 20);
+        npu.register_extension(Box::new(BankSum { calls: 0 })).unwrap();
+        let err = npu.register_extension(Box::new(BankSum { calls: 0 })).unwrap_err();
+        assert!(err.contains("already taken by banksum"), "{err}");
+
+        npu.write_dram(DRAM_BASE, &[3; 16]);
+        npu.exec(32, 1, (1 << 5) | (1 << 10), 0);
+        npu.exec(33, 1 | (1 << 30), DRAM_BASE | (1 << 39), 0);
+        let before = npu.total_latency();
+        assert_eq!(npu.exec(64, 1, 32, 0), 3 * 16);
+        assert_eq!(npu.total_latency() - before, 2);
+
+        let ext = npu.extensions().next().unwrap();
+        assert_eq!(ext.stats(), vec![("calls".to_string(), 1)]);
+        let perf = npu.perf_report();
+        let row = perf.per_inst.iter().find(|i| i.funct == 64).unwrap();
+        assert_eq!((row.name.as_str(), row.count, row.cycles), ("banksum", 1, 2));
+
+        npu.reset();
+        assert_eq!(npu.extensions().next().unwrap().stats()[0].1, 0);
+    }
+}
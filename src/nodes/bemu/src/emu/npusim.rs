@@ -0,0 +1,1456 @@
+//===- npusim.rs - Cycle-stepped embedding API -----------------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// NpuSim drives an Npu from another program one cycle at a time, with no
+// Spike hart, sockets or trace files:
+//
+//   let mut sim = NpuSim::new(NpuSimConfig::default());
+//   sim.push_inst(32, 1, (1 << 5) | (1 << 10))?;
+//   sim.tick(10);
+//   let bank = sim.read_bank(1);
+//
+// Pushed instructions wait in an issue queue and run on one or more
+// execution units. An instruction holds a unit for its latency, and its
+// effects become visible when it retires. Instructions retiring in the same
+// cycle take effect in issue order.
+//
+// The units are a registry of `ExecUnit`s, each accepting some kinds of
+// work. By default every unit takes anything; with `dma_units` set, that
+// many extra units take only DMA instructions and the others everything
+// else. Each instruction declares its unit kind and the shared state it uses
+// (`Instruction::UNIT` and `SHARED`), so a new instruction takes part in
+// issue without changes here.
+//
+// With several units, instructions that touch the same bank, or that share
+// DRAM, MMIO or norm's statistics, never overlap. Configuration instructions
+// (mset, mmio_set, qos_set, bmt, acc_cfg, fence, barrier) and extension functs wait
+// for every unit to drain, and nothing behind them issues until they retire. A loop_ws is
+// decoded into its mvin, matmul and mvout micro-ops when it reaches the
+// issue stage; they take its place in the queue and issue like any other
+// instruction, so younger work on other banks overlaps them.
+//
+// The arbitration policy picks what issues next:
+//
+//   RoundRobin  in order from the queue head; each instruction goes to the
+//               next free unit after the one used last. A hazard at the head
+//               stalls everything behind it.
+//   Scoreboard  any queued instruction whose resources are free and that
+//               does not conflict with an older queued one, to the
+//               lowest-numbered free unit. Independent work overtakes a
+//               stalled head.
+//
+// At most `issue_width` instructions issue per cycle (one per free unit by
+// default). Cycles in which queued work does not issue are counted by cause:
+// every unit busy, the issue width used up, or a hazard. Hazard stalls are
+// further split into read-port stalls and configuration drains.
+//
+// With a reorder buffer of `rob_depth` entries, every issued instruction
+// holds an entry until it retires, and instructions retire in issue order: one
+// that finishes early keeps its unit free but its effects and its entry wait
+// for every older one. Issue stalls while the buffer is full.
+//
+// With `out_of_order` set, a write to a single-group bank is renamed at issue
+// to a free physical bank, so it no longer waits for older instructions
+// still reading the bank; they keep reading the physical banks they were
+// given at issue. The renamed write starts from a copy of the bank's prior
+// contents, since instructions write part of a bank or add into it, so it
+// still waits for older writes. When it retires, the vbank maps to the new
+// physical bank. Every issued instruction is then independent of the
+// unretired ones, so with a reorder buffer instructions retire as soon as
+// they finish. Leaving the flag off gives the in-order pipeline to compare
+// against.
+//
+// `step_cycle` advances exactly one cycle and lists what happened in it:
+// issues, loop_ws expansions, completions, retires and the stall cause, if
+// any. The cycle debugger (cycle_debugger.rs) prints them.
+//
+// `write_vcd` dumps the queue and the units every cycle as a waveform
+// (vcd.rs), and `write_occupancy` sums occupancy and stall causes into a CSV
+// every N cycles (occupancy.rs). With `timeline` set, `write_timeline`
+// exports when each instruction was queued, issued, completed and retired as
+// a Chrome trace (timeline.rs).
+//
+// `reset` empties the pipeline and resets the model so one simulator runs
+// program after program; `reconfigure` does the same into a new pipeline
+// shape.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::path::Path;
+
+use crate::bank::{bank_operands, Access, ElemWidth};
+use crate::checkpoint::{Checkpoint, InFlightState, SimState};
+use crate::inst::decode::issue_class;
+use crate::inst::f53_loop_ws::LoopWs;
+use crate::inst::instruction::{Instruction, Shared, Unit};
+use crate::isa::base_isa;
+use crate::layout::Layout;
+use crate::npu::Npu;
+use crate::numfmt::NumFormat;
+use crate::occupancy::{CycleSample, OccupancyWriter};
+use crate::timeline::{write_chrome_trace, TimelineEntry};
+use crate::vcd::VcdWriter;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Arbitration {
+    #[default]
+    RoundRobin,
+    Scoreboard,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct NpuSimConfig {
+    /// Guest DRAM size in bytes.
+    pub mem_size: usize,
+    /// Instructions that may wait behind the ones executing.
+    pub queue_depth: usize,
+    /// Execution units instructions are issued to.
+    pub units: usize,
+    /// Units that only take DMA instructions, besides `units`; 0 to issue
+    /// DMA instructions to `units` too.
+    pub dma_units: usize,
+    /// Instructions issued per cycle at most; 0 for one per free unit.
+    pub issue_width: usize,
+    /// Reorder buffer entries; 0 for none, so instructions retire as soon
+    /// as they finish.
+    pub rob_depth: usize,
+    pub arbitration: Arbitration,
+    /// Rename written banks and retire out of order (see the module
+    /// comment); false keeps the in-order pipeline.
+    pub out_of_order: bool,
+    /// Record each instruction's queue, issue, complete and retire cycles
+    /// for `write_timeline`.
+    pub timeline: bool,
+}
+
+impl Default for NpuSimConfig {
+    fn default() -> Self {
+        Self {
+            // Small enough to create many simulators in one process.
+            mem_size: 64 << 20,
+            queue_depth: 64,
+            units: 1,
+            dma_units: 0,
+            issue_width: 0,
+            rob_depth: 0,
+            arbitration: Arbitration::RoundRobin,
+            out_of_order: false,
+            timeline: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NpuSimStats {
+    pub cycle: u64,
+    pub pushed: u64,
+    pub issued: u64,
+    pub retired: u64,
+    /// Cycles in which at least one execution unit held an instruction.
+    pub busy_cycles: u64,
+    /// Cycles in which a unit was free and instructions were queued, but
+    /// none could issue.
+    pub hazard_stalls: u64,
+    /// Hazard stalls in which an instruction waited only for a read port
+    /// of a bank another unit was reading.
+    pub port_stalls: u64,
+    /// Hazard stalls in which a configuration instruction at the queue head
+    /// waited for the units to drain.
+    pub drain_stalls: u64,
+    /// Cycles in which instructions were queued but every unit that could
+    /// take one was busy.
+    pub unit_stalls: u64,
+    /// Cycles in which an instruction could have issued to a free unit but
+    /// the issue width was used up.
+    pub width_stalls: u64,
+    /// Cycles in which an instruction could have issued to a free unit but
+    /// the reorder buffer was full.
+    pub rob_stalls: u64,
+    /// Micro-ops loop_ws instructions expanded into.
+    pub expanded: u64,
+    /// Writes renamed to a free physical bank.
+    pub renamed: u64,
+    pub queued: usize,
+}
+
+/// Why queued work did not issue in a cycle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StallCause {
+    /// Every unit that could take the work was busy.
+    Units,
+    /// The issue width was used up.
+    Width,
+    /// The reorder buffer was full.
+    Rob,
+    /// An instruction waited only for a bank read port.
+    Ports,
+    /// A configuration instruction waited for the units to drain.
+    Drain,
+    /// Any other conflict with older work.
+    Hazard,
+}
+
+/// One thing that happened during a cycle, from `NpuSim::step_cycle`.
+/// `seq` numbers instructions in issue order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SimEvent {
+    Issue {
+        seq: u64,
+        unit: usize,
+        funct: u32,
+        xs1: u64,
+        xs2: u64,
+        latency: u64,
+    },
+    /// A loop_ws was decoded into `ops` micro-ops at the queue head.
+    Expand {
+        ops: usize,
+    },
+    /// Finished executing; with a reorder buffer it may retire later.
+    Complete {
+        seq: u64,
+        unit: usize,
+    },
+    /// Took effect on the banks and DRAM.
+    Retire {
+        seq: u64,
+        funct: u32,
+        xs1: u64,
+        xs2: u64,
+    },
+    Stall(StallCause),
+}
+
+impl fmt::Display for SimEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            SimEvent::Issue {
+                seq,
+                unit,
+                funct,
+                xs1,
+                xs2,
+                latency,
+            } => write!(
+                f,
+                "issue    #{seq} unit {unit}: {} ({latency} cycles)",
+                base_isa().disassemble(funct, xs1, xs2)
+            ),
+            SimEvent::Expand { ops } => write!(f, "expand   loop_ws into {ops} micro-ops"),
+            SimEvent::Complete { seq, unit } => write!(f, "complete #{seq} unit {unit}"),
+            SimEvent::Retire { seq, funct, xs1, xs2 } => {
+                write!(f, "retire   #{seq}: {}", base_isa().disassemble(funct, xs1, xs2))
+            }
+            SimEvent::Stall(cause) => write!(f, "stall    {cause:?}"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Inst {
+    funct: u32,
+    xs1: u64,
+    xs2: u64,
+    /// Cycle it entered the issue queue.
+    queued: u64,
+}
+
+/// Something two overlapping instructions must not both use. Two reads of
+/// the same bank only conflict when its ports cannot serve both.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Resource {
+    Bank(u64, Access),
+    Shared(Shared),
+}
+
+impl Inst {
+    /// Kind of unit the instruction issues to; extension functs run alone.
+    fn unit(&self) -> Unit {
+        issue_class(self.funct).map_or(Unit::Serial, |(unit, _)| unit)
+    }
+
+    /// What the instruction touches, or `None` if it must run alone.
+    fn resources(&self) -> Option<Vec<Resource>> {
+        let (unit, shared) = issue_class(self.funct)?;
+        if unit == Unit::Serial {
+            return None;
+        }
+        let banks = bank_operands(self.funct, self.xs1)
+            .into_iter()
+            .map(|(bank, access)| Resource::Bank(bank, access));
+        Some(banks.chain(shared.iter().map(|&s| Resource::Shared(s))).collect())
+    }
+
+    /// Whether `younger` must wait for `self`. A renamed write does not
+    /// wait for reads of its bank.
+    fn conflicts(&self, younger: &Inst, renamed: bool) -> bool {
+        match (self.resources(), younger.resources()) {
+            (Some(a), Some(b)) => a.iter().any(|r| {
+                b.iter().any(|s| match (r, s) {
+                    (Resource::Bank(_, Access::Read), Resource::Bank(_, Access::Write)) if renamed => false,
+                    (Resource::Bank(x, a), Resource::Bank(y, b)) => {
+                        x == y && (*a == Access::Write || *b == Access::Write)
+                    }
+                    _ => r == s,
+                })
+            }),
+            _ => true,
+        }
+    }
+
+    /// Reads of each vbank.
+    fn bank_reads(&self) -> impl Iterator<Item = u64> + '_ {
+        bank_operands(self.funct, self.xs1)
+            .into_iter()
+            .filter(|&(_, access)| access == Access::Read)
+            .map(|(bank, _)| bank)
+    }
+
+    /// The vbank it writes, if any.
+    fn bank_write(&self) -> Option<u64> {
+        bank_operands(self.funct, self.xs1)
+            .into_iter()
+            .find(|&(_, access)| access == Access::Write)
+            .map(|(bank, _)| bank)
+    }
+}
+
+/// Physical banks an instruction was given at issue when banks are renamed.
+#[derive(Clone, Copy, Debug, Default)]
+struct Renamed {
+    /// (vbank, pbank) of each single-group bank read.
+    reads: [Option<(u64, usize)>; 2],
+    /// (vbank, prior pbank, renamed pbank) of the write.
+    write: Option<(u64, usize, usize)>,
+}
+
+impl Renamed {
+    fn pbanks(&self) -> impl Iterator<Item = usize> + '_ {
+        let reads = self.reads.iter().flatten().map(|&(_, p)| p);
+        reads.chain(self.write.into_iter().flat_map(|(_, from, to)| [from, to]))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct InFlight {
+    inst: Inst,
+    done_at: u64,
+    /// Issue order, so same-cycle retires apply in program order.
+    seq: u64,
+    renamed: Renamed,
+}
+
+/// One execution unit: the kinds of instruction it takes and the one it
+/// holds, if any.
+#[derive(Clone, Copy, Debug)]
+struct ExecUnit {
+    accepts: &'static [Unit],
+    slot: Option<InFlight>,
+}
+
+impl ExecUnit {
+    const ANY: &'static [Unit] = &[Unit::Serial, Unit::Dma, Unit::Ball];
+    const COMPUTE: &'static [Unit] = &[Unit::Serial, Unit::Ball];
+    const DMA: &'static [Unit] = &[Unit::Dma];
+
+    /// Whether `inst` could issue to this unit now.
+    fn can_accept(&self, inst: &Inst) -> bool {
+        self.slot.is_none() && self.accepts.contains(&inst.unit())
+    }
+
+    fn issue(&mut self, flight: InFlight) {
+        debug_assert!(self.can_accept(&flight.inst));
+        self.slot = Some(flight);
+    }
+}
+
+pub struct NpuSim {
+    npu: Npu,
+    config: NpuSimConfig,
+    queue: VecDeque<Inst>,
+    units: Vec<ExecUnit>,
+    /// Finished instructions waiting for older ones to retire, oldest first.
+    rob: VecDeque<InFlight>,
+    /// Cycles spent at each reorder buffer occupancy.
+    rob_occupancy: Vec<u64>,
+    unit_busy: Vec<u64>,
+    /// Unit the next round-robin search starts at.
+    next_unit: usize,
+    stats: NpuSimStats,
+    vcd: Option<VcdWriter>,
+    occupancy: Option<OccupancyWriter>,
+    /// Recorded instructions by issue order, when `config.timeline` is set.
+    timeline: BTreeMap<u64, TimelineEntry>,
+    /// Events of the cycle being stepped, inside `step_cycle` only.
+    events: Option<Vec<SimEvent>>,
+}
+
+impl NpuSim {
+    /// `config.units` is at least 1; the `dma_units` follow them.
+    pub fn new(config: NpuSimConfig) -> Self {
+        let mut sim = Self {
+            npu: Npu::new(config.mem_size),
+            config,
+            queue: VecDeque::with_capacity(config.queue_depth),
+            units: Vec::new(),
+            rob: VecDeque::new(),
+            rob_occupancy: Vec::new(),
+            unit_busy: Vec::new(),
+            next_unit: 0,
+            stats: NpuSimStats::default(),
+            vcd: None,
+            occupancy: None,
+            timeline: BTreeMap::new(),
+            events: None,
+        };
+        sim.reshape(config);
+        sim
+    }
+
+    /// Take `config`'s pipeline shape with everything empty and every
+    /// counter at zero.
+    fn reshape(&mut self, config: NpuSimConfig) {
+        let units = config.units.max(1);
+        self.config = NpuSimConfig { units, ..config };
+        self.queue.clear();
+        let general = match config.dma_units {
+            0 => ExecUnit::ANY,
+            _ => ExecUnit::COMPUTE,
+        };
+        self.units = std::iter::repeat_n(general, units)
+            .chain(std::iter::repeat_n(ExecUnit::DMA, config.dma_units))
+            .map(|accepts| ExecUnit { accepts, slot: None })
+            .collect();
+        self.rob.clear();
+        self.rob_occupancy = vec![0; config.rob_depth + usize::from(config.rob_depth > 0)];
+        self.unit_busy = vec![0; self.units.len()];
+        self.next_unit = 0;
+        self.stats = NpuSimStats::default();
+        self.vcd = None;
+        self.occupancy = None;
+        self.timeline.clear();
+    }
+
+    /// Return to where `new` left off, so one simulator can run program
+    /// after program: the queue, units and reorder buffer are emptied, the
+    /// counters and timeline cleared, and the model reset as by
+    /// `Npu::reset`, which keeps DRAM and the attached models. A waveform
+    /// being written ends, as the cycle count starts over.
+    pub fn reset(&mut self) {
+        self.npu.reset();
+        self.reshape(self.config);
+    }
+
+    /// Reset (see `reset`) into a different pipeline: units and DMA units,
+    /// issue width, queue and reorder buffer depth, arbitration and timeline
+    /// recording. Guest DRAM cannot be resized in place.
+    pub fn reconfigure(&mut self, config: NpuSimConfig) -> Result<(), String> {
+        if config.mem_size != self.config.mem_size {
+            return Err(format!(
+                "mem_size {} differs from the {} bytes of guest DRAM; build a new NpuSim",
+                config.mem_size, self.config.mem_size
+            ));
+        }
+        self.npu.reset();
+        self.reshape(config);
+        Ok(())
+    }
+
+    pub fn config(&self) -> NpuSimConfig {
+        self.config
+    }
+
+    /// Queue one instruction. Fails when the issue queue is full; tick and
+    /// push again.
+    pub fn push_inst(&mut self, funct: u32, xs1: u64, xs2: u64) -> Result<(), String> {
+        if self.queue.len() >= self.config.queue_depth {
+            return Err(format!("issue queue full ({} entries)", self.config.queue_depth));
+        }
+        self.queue.push_back(Inst {
+            funct,
+            xs1,
+            xs2,
+            queued: self.stats.cycle,
+        });
+        self.stats.pushed += 1;
+        Ok(())
+    }
+
+    /// Advance `n` cycles and return how many instructions retired.
+    pub fn tick(&mut self, n: u64) -> u64 {
+        let retired = self.stats.retired;
+        for _ in 0..n {
+            self.step();
+        }
+        self.stats.retired - retired
+    }
+
+    /// Advance one cycle and return what happened in it, in order.
+    pub fn step_cycle(&mut self) -> Vec<SimEvent> {
+        self.events = Some(Vec::new());
+        self.step();
+        self.events.take().unwrap_or_default()
+    }
+
+    /// Tick until every pushed instruction has retired; returns the cycles
+    /// taken.
+    pub fn run_until_idle(&mut self) -> u64 {
+        let start = self.stats.cycle;
+        while !self.is_idle() {
+            self.step();
+        }
+        self.stats.cycle - start
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.units.iter().all(|u| u.slot.is_none()) && self.queue.is_empty() && self.rob.is_empty()
+    }
+
+    /// Bank contents as of the last retired instruction.
+    pub fn read_bank(&self, vbank: u32) -> Option<&[u8]> {
+        self.npu.bank(vbank)
+    }
+
+    pub fn stats(&self) -> NpuSimStats {
+        NpuSimStats {
+            queued: self.queue.len(),
+            ..self.stats
+        }
+    }
+
+    /// Cycles each execution unit held an instruction, the DMA units last.
+    pub fn unit_busy_cycles(&self) -> &[u64] {
+        &self.unit_busy
+    }
+
+    /// Cycles spent with each number of reorder buffer entries in use,
+    /// indexed by occupancy; empty without a reorder buffer.
+    pub fn rob_occupancy(&self) -> &[u64] {
+        &self.rob_occupancy
+    }
+
+    /// Reorder buffer entries in use: issued instructions not yet retired.
+    fn rob_len(&self) -> usize {
+        self.in_flight().count() + self.rob.len()
+    }
+
+    /// Instructions held by the execution units.
+    fn in_flight(&self) -> impl Iterator<Item = &InFlight> {
+        self.units.iter().filter_map(|u| u.slot.as_ref())
+    }
+
+    /// Issued instructions that have not retired.
+    fn unretired(&self) -> impl Iterator<Item = &InFlight> {
+        self.in_flight().chain(&self.rob)
+    }
+
+    /// The underlying model, e.g. to load DRAM before pushing instructions.
+    pub fn npu(&self) -> &Npu {
+        &self.npu
+    }
+
+    pub fn npu_mut(&mut self) -> &mut Npu {
+        &mut self.npu
+    }
+
+    /// `Npu::write_dram_matrix` on the underlying model.
+    pub fn write_dram_matrix(
+        &mut self,
+        addr: u64,
+        data: &[i32],
+        rows: usize,
+        cols: usize,
+        width: ElemWidth,
+        layout: Layout,
+    ) -> Result<(), String> {
+        self.npu.write_dram_matrix(addr, data, rows, cols, width, layout)
+    }
+
+    /// `Npu::read_dram_matrix` on the underlying model.
+    pub fn read_dram_matrix(
+        &self,
+        addr: u64,
+        rows: usize,
+        cols: usize,
+        width: ElemWidth,
+        layout: Layout,
+    ) -> Result<Vec<i32>, String> {
+        self.npu.read_dram_matrix(addr, rows, cols, width, layout)
+    }
+
+    /// Write the issue queue and every unit's state to a VCD waveform at
+    /// `path`, one sample per cycle from now on.
+    pub fn write_vcd(&mut self, path: &Path) -> Result<(), String> {
+        self.vcd = Some(VcdWriter::create(path, self.units.len(), self.config.rob_depth > 0)?);
+        Ok(())
+    }
+
+    /// Close the waveform started by `write_vcd`.
+    pub fn stop_vcd(&mut self) {
+        self.vcd = None;
+    }
+
+    /// Write queue, reorder buffer and unit occupancy with per-cause stall
+    /// counts to a CSV at `path`, one row per `every` cycles from now on.
+    pub fn write_occupancy(&mut self, path: &Path, every: u64) -> Result<(), String> {
+        self.occupancy = Some(OccupancyWriter::create(path, every, self.stats.cycle)?);
+        Ok(())
+    }
+
+    /// Close the CSV started by `write_occupancy`, writing any partial
+    /// interval.
+    pub fn stop_occupancy(&mut self) {
+        self.occupancy = None;
+    }
+
+    /// Instructions recorded so far in issue order; empty unless
+    /// `NpuSimConfig::timeline` is set. Ones still in flight have
+    /// `completed` and `retired` of 0.
+    pub fn timeline(&self) -> Vec<TimelineEntry> {
+        self.timeline.values().copied().collect()
+    }
+
+    /// Write the retired instructions of the timeline to `path` as Chrome
+    /// trace-event JSON, for chrome://tracing or Perfetto.
+    pub fn write_timeline(&self, path: &Path) -> Result<(), String> {
+        if !self.config.timeline {
+            return Err("timeline recording is off; set NpuSimConfig::timeline".to_string());
+        }
+        let retired: Vec<TimelineEntry> = self.timeline.values().filter(|e| e.retired > 0).copied().collect();
+        write_chrome_trace(path, &retired, self.units.len())
+    }
+
+    /// Model state plus the queued and in-flight instructions.
+    pub fn checkpoint(&self) -> Checkpoint {
+        let raw = |i: &Inst| (i.funct, i.xs1, i.xs2);
+        let state = |f: &InFlight| InFlightState {
+            inst: raw(&f.inst),
+            done_at: f.done_at,
+            seq: f.seq,
+            reads: f.renamed.reads.iter().flatten().copied().collect(),
+            write: f.renamed.write,
+        };
+        Checkpoint {
+            sim: Some(SimState {
+                cycle: self.stats.cycle,
+                pushed: self.stats.pushed,
+                issued: self.stats.issued,
+                retired: self.stats.retired,
+                busy_cycles: self.stats.busy_cycles,
+                hazard_stalls: self.stats.hazard_stalls,
+                port_stalls: self.stats.port_stalls,
+                drain_stalls: self.stats.drain_stalls,
+                unit_stalls: self.stats.unit_stalls,
+                width_stalls: self.stats.width_stalls,
+                rob_stalls: self.stats.rob_stalls,
+                expanded: self.stats.expanded,
+                renamed: self.stats.renamed,
+                queue: self.queue.iter().map(raw).collect(),
+                units: self.units.iter().map(|u| u.slot.as_ref().map(state)).collect(),
+                rob: self.rob.iter().map(state).collect(),
+                rob_occupancy: self.rob_occupancy.clone(),
+                unit_busy: self.unit_busy.clone(),
+                next_unit: self.next_unit,
+            }),
+            ..Checkpoint::capture(&self.npu)
+        }
+    }
+
+    /// Resume from `ckpt`. A checkpoint taken from a bare Npu restores the
+    /// model with an empty queue and the cycle counter at zero.
+    pub fn restore(&mut self, ckpt: &Checkpoint) -> Result<(), String> {
+        let mut sim = ckpt.sim.clone().unwrap_or_default();
+        if sim.queue.len() > self.config.queue_depth {
+            return Err(format!(
+                "checkpoint has {} queued instructions, queue depth is {}",
+                sim.queue.len(),
+                self.config.queue_depth
+            ));
+        }
+        if ckpt.sim.is_none() {
+            sim.units = vec![None; self.units.len()];
+            sim.unit_busy = vec![0; self.units.len()];
+        }
+        if sim.rob_occupancy.is_empty() {
+            sim.rob_occupancy = vec![0; self.rob_occupancy.len()];
+        }
+        if sim.rob_occupancy.len() != self.rob_occupancy.len() {
+            return Err(format!(
+                "checkpoint has a {}-entry reorder buffer, simulator has {}",
+                sim.rob_occupancy.len().saturating_sub(1),
+                self.config.rob_depth
+            ));
+        }
+        if sim.units.len() != self.units.len() || sim.unit_busy.len() != self.units.len() {
+            return Err(format!(
+                "checkpoint has {} execution units, simulator has {}",
+                sim.units.len(),
+                self.units.len()
+            ));
+        }
+        ckpt.restore(&mut self.npu)?;
+        let inst = |(funct, xs1, xs2)| Inst {
+            funct,
+            xs1,
+            xs2,
+            queued: sim.cycle,
+        };
+        let flight = |f: InFlightState| {
+            let mut renamed = Renamed {
+                write: f.write,
+                ..Renamed::default()
+            };
+            for (slot, read) in renamed.reads.iter_mut().zip(f.reads) {
+                *slot = Some(read);
+            }
+            InFlight {
+                inst: inst(f.inst),
+                done_at: f.done_at,
+                seq: f.seq,
+                renamed,
+            }
+        };
+        self.queue = sim.queue.into_iter().map(inst).collect();
+        for (unit, slot) in self.units.iter_mut().zip(sim.units) {
+            unit.slot = slot.map(flight);
+        }
+        self.rob = sim.rob.into_iter().map(flight).collect();
+        self.rob_occupancy = sim.rob_occupancy;
+        self.unit_busy = sim.unit_busy;
+        self.next_unit = sim.next_unit % self.units.len();
+        self.stats = NpuSimStats {
+            cycle: sim.cycle,
+            pushed: sim.pushed,
+            issued: sim.issued,
+            retired: sim.retired,
+            busy_cycles: sim.busy_cycles,
+            hazard_stalls: sim.hazard_stalls,
+            port_stalls: sim.port_stalls,
+            drain_stalls: sim.drain_stalls,
+            unit_stalls: sim.unit_stalls,
+            width_stalls: sim.width_stalls,
+            rob_stalls: sim.rob_stalls,
+            expanded: sim.expanded,
+            renamed: sim.renamed,
+            queued: 0,
+        };
+        Ok(())
+    }
+
+    pub fn save_checkpoint(&self, path: &Path) -> Result<(), String> {
+        self.checkpoint().save(path)
+    }
+
+    pub fn load_checkpoint(&mut self, path: &Path) -> Result<(), String> {
+        self.restore(&Checkpoint::load(path)?)
+    }
+
+    /// Index into the queue of the next instruction that may issue, if any.
+    /// Without `ports`, bank read ports are assumed unlimited; without
+    /// `units`, some unit is assumed free to take it.
+    fn pick(&self, ports: bool, units: bool) -> Option<usize> {
+        let in_flight: Vec<&Inst> = self.in_flight().map(|f| &f.inst).collect();
+        // Finished instructions in the reorder buffer have not taken effect.
+        let unretired: Vec<&Inst> = self.unretired().map(|f| &f.inst).collect();
+        let ready = |i: usize| {
+            let inst = &self.queue[i];
+            if units && self.free_unit(inst).is_none() {
+                return false;
+            }
+            if inst.resources().is_none() {
+                return i == 0 && unretired.is_empty();
+            }
+            // Queued instructions are not renamed yet, so a write still
+            // waits for older queued reads.
+            let renamed = self.rename_target(inst).is_some();
+            !unretired.iter().any(|f| f.conflicts(inst, renamed))
+                && !self.queue.range(..i).any(|q| q.conflicts(inst, false))
+                && (!ports || self.read_ports_free(inst, &in_flight))
+        };
+        match self.config.arbitration {
+            Arbitration::RoundRobin => (!self.queue.is_empty() && ready(0)).then_some(0),
+            Arbitration::Scoreboard => (0..self.queue.len()).find(|&i| ready(i)),
+        }
+    }
+
+    /// Whether every bank `inst` reads has read ports left for it next to
+    /// the in-flight instructions reading the same bank.
+    fn read_ports_free(&self, inst: &Inst, in_flight: &[&Inst]) -> bool {
+        let ports = self.npu.bank_ports();
+        if ports.is_ideal() {
+            return true;
+        }
+        inst.bank_reads().all(|bank| {
+            let others = in_flight
+                .iter()
+                .flat_map(|f| f.bank_reads())
+                .filter(|&b| b == bank)
+                .count();
+            let Some(p) = self.npu.bank_map.resolve(bank as u32) else {
+                return true;
+            };
+            let own = inst.bank_reads().filter(|&b| b == bank).count();
+            others == 0 || ports.kind(p).beats((others + own) as u64, 0) == 1
+        })
+    }
+
+    /// Free physical bank `inst`'s write would be renamed to: one no vbank
+    /// maps and no unretired instruction uses. None without `out_of_order`,
+    /// and for multi-group banks and bfp8 banks, whose block exponents belong
+    /// to the vbank.
+    fn rename_target(&self, inst: &Inst) -> Option<usize> {
+        if !self.config.out_of_order {
+            return None;
+        }
+        let vbank = inst.bank_write()?;
+        let cfg = self.npu.bank_cfgs.get(vbank as usize)?;
+        if !cfg.allocated || cfg.cols > 1 || cfg.format == NumFormat::Bfp8 {
+            return None;
+        }
+        self.npu.bank_map.resolve(vbank as u32)?;
+        let held: Vec<usize> = self.unretired().flat_map(|f| f.renamed.pbanks()).collect();
+        let slots = &self.npu.bank_map.slots;
+        (0..slots.len()).find(|&p| !slots[p].valid && !held.contains(&p))
+    }
+
+    /// Physical banks `inst` uses from issue until it retires.
+    fn rename(&mut self, inst: &Inst) -> Renamed {
+        let mut renamed = Renamed::default();
+        if !self.config.out_of_order {
+            return renamed;
+        }
+        let map = &self.npu.bank_map;
+        let reads = inst.bank_reads().filter_map(|vbank| {
+            let cfg = self.npu.bank_cfgs.get(vbank as usize)?;
+            (cfg.cols <= 1).then_some((vbank, map.resolve(vbank as u32)?))
+        });
+        for (slot, read) in renamed.reads.iter_mut().zip(reads) {
+            *slot = Some(read);
+        }
+        if let Some(to) = self.rename_target(inst) {
+            let vbank = inst.bank_write().expect("renamed instructions write a bank");
+            let from = map.resolve(vbank as u32).expect("renamed banks are mapped");
+            renamed.write = Some((vbank, from, to));
+            self.stats.renamed += 1;
+        }
+        renamed
+    }
+
+    /// Apply a retiring instruction to the model. Its reads see the physical
+    /// banks they were given at issue; a renamed write goes to a copy of the
+    /// bank's prior contents, which the vbank then maps to.
+    fn commit(&mut self, flight: &InFlight) {
+        let Inst { funct, xs1, xs2, .. } = flight.inst;
+        let Renamed { reads, write } = flight.renamed;
+        if reads.iter().all(Option::is_none) && write.is_none() {
+            self.npu.exec(funct, xs1, xs2, 0);
+            return;
+        }
+        let map = self.npu.bank_map.slots.clone();
+        if let Some((_, from, to)) = write {
+            self.npu.copy_pbank(from, to);
+        }
+        let banks = reads
+            .into_iter()
+            .flatten()
+            .chain(write.map(|(vbank, _, to)| (vbank, to)));
+        for (vbank, p) in banks {
+            self.npu.bank_map.delete_vbank(vbank as u32);
+            self.npu.bank_map.bind_group(p, vbank as u32, 0);
+        }
+        self.npu.exec(funct, xs1, xs2, 0);
+        self.npu.bank_map.slots = map;
+        if let Some((vbank, _, to)) = write {
+            self.npu.bank_map.delete_vbank(vbank as u32);
+            self.npu.bank_map.bind_group(to, vbank as u32, 0);
+        }
+    }
+
+    fn note(&mut self, event: SimEvent) {
+        if let Some(events) = &mut self.events {
+            events.push(event);
+        }
+    }
+
+    /// Free unit `inst` would go to, if any.
+    fn free_unit(&self, inst: &Inst) -> Option<usize> {
+        let n = self.units.len();
+        match self.config.arbitration {
+            Arbitration::RoundRobin => (0..n)
+                .map(|k| (self.next_unit + k) % n)
+                .find(|&u| self.units[u].can_accept(inst)),
+            Arbitration::Scoreboard => self.units.iter().position(|u| u.can_accept(inst)),
+        }
+    }
+
+    fn step(&mut self) {
+        let issued = self.stats.issued;
+        let retired = self.stats.retired;
+        let mut stalled = false;
+        let mut stall = None;
+        let mut slots = match self.config.issue_width {
+            0 => usize::MAX,
+            width => width,
+        };
+        loop {
+            if self.queue.is_empty() {
+                break;
+            }
+            let Some(i) = self.pick(true, true) else {
+                if self.units.iter().all(|u| u.slot.is_some()) || self.pick(true, false).is_some() {
+                    self.stats.unit_stalls += 1;
+                    stall = Some(StallCause::Units);
+                    break;
+                }
+                stalled = true;
+                stall = Some(if self.pick(false, false).is_some() {
+                    self.stats.port_stalls += 1;
+                    StallCause::Ports
+                } else if self.queue[0].resources().is_none() {
+                    self.stats.drain_stalls += 1;
+                    StallCause::Drain
+                } else {
+                    StallCause::Hazard
+                });
+                break;
+            };
+            let unit = self.free_unit(&self.queue[i]).expect("picked with a free unit");
+            if slots == 0 {
+                self.stats.width_stalls += 1;
+                stall = Some(StallCause::Width);
+                break;
+            }
+            if self.config.rob_depth > 0 && self.rob_len() >= self.config.rob_depth {
+                self.stats.rob_stalls += 1;
+                stall = Some(StallCause::Rob);
+                break;
+            }
+            let inst = self.queue.remove(i).expect("picked from the queue");
+            if inst.funct == LoopWs::FUNCT {
+                // Decode into micro-ops ahead of everything younger; they
+                // issue like pushed instructions.
+                let ops = self.npu.expand_loop_ws(inst.xs1, inst.xs2);
+                self.stats.expanded += ops.len() as u64;
+                self.note(SimEvent::Expand { ops: ops.len() });
+                for (funct, xs1, xs2) in ops.into_iter().rev() {
+                    self.queue.push_front(Inst {
+                        funct,
+                        xs1,
+                        xs2,
+                        queued: inst.queued,
+                    });
+                }
+                continue;
+            }
+            let lat = self.npu.issue_latency(inst.funct, inst.xs1, inst.xs2);
+            if self.config.timeline {
+                self.timeline.insert(
+                    self.stats.issued,
+                    TimelineEntry {
+                        seq: self.stats.issued,
+                        funct: inst.funct,
+                        xs1: inst.xs1,
+                        xs2: inst.xs2,
+                        unit,
+                        queued: inst.queued,
+                        issued: self.stats.cycle,
+                        completed: 0,
+                        retired: 0,
+                    },
+                );
+            }
+            let renamed = self.rename(&inst);
+            self.units[unit].issue(InFlight {
+                inst,
+                done_at: self.stats.cycle + lat,
+                seq: self.stats.issued,
+                renamed,
+            });
+            self.note(SimEvent::Issue {
+                seq: self.stats.issued,
+                unit,
+                funct: inst.funct,
+                xs1: inst.xs1,
+                xs2: inst.xs2,
+                latency: lat,
+            });
+            self.next_unit = (unit + 1) % self.units.len();
+            self.stats.issued += 1;
+            slots -= 1;
+        }
+        if stalled {
+            self.stats.hazard_stalls += 1;
+        }
+        if let Some(cause) = stall {
+            self.note(SimEvent::Stall(cause));
+        }
+        let rob_len = self.rob_len();
+        if let Some(cycles) = self.rob_occupancy.get_mut(rob_len) {
+            *cycles += 1;
+        }
+
+        // Unit state is sampled while the instructions are held, before the
+        // ones finishing this cycle retire.
+        let mut sample = Vec::new();
+        if self.vcd.is_some() {
+            sample = vec![self.queue.len() as u64, self.stats.issued - issued, 0, stalled as u64];
+            if self.config.rob_depth > 0 {
+                sample.push(rob_len as u64);
+            }
+            for unit in &self.units {
+                sample.extend(match unit.slot {
+                    Some(f) => [1, f.inst.funct as u64, f.done_at - self.stats.cycle],
+                    None => [0, 0, 0],
+                });
+            }
+        }
+        let held = (self.queue.len(), rob_len, self.in_flight().count());
+
+        self.stats.cycle += 1;
+        let mut busy = false;
+        let mut done = Vec::new();
+        for (u, unit) in self.units.iter_mut().enumerate() {
+            let Some(flight) = unit.slot else {
+                continue;
+            };
+            busy = true;
+            self.unit_busy[u] += 1;
+            if self.stats.cycle >= flight.done_at {
+                if let Some(entry) = self.timeline.get_mut(&flight.seq) {
+                    entry.completed = self.stats.cycle;
+                }
+                if let Some(events) = &mut self.events {
+                    events.push(SimEvent::Complete {
+                        seq: flight.seq,
+                        unit: u,
+                    });
+                }
+                done.push(flight);
+                unit.slot = None;
+            }
+        }
+        if busy {
+            self.stats.busy_cycles += 1;
+        }
+        done.sort_by_key(|f| f.seq);
+        if self.config.rob_depth > 0 && !self.config.out_of_order {
+            // Retire in issue order: only what is older than every
+            // instruction still executing.
+            self.rob.extend(done);
+            self.rob.make_contiguous().sort_by_key(|f| f.seq);
+            let oldest = self.in_flight().map(|f| f.seq).min().unwrap_or(u64::MAX);
+            let ready = self.rob.iter().take_while(|f| f.seq < oldest).count();
+            done = self.rob.drain(..ready).collect();
+        }
+        for flight in done {
+            let Inst { funct, xs1, xs2, .. } = flight.inst;
+            if let Some(entry) = self.timeline.get_mut(&flight.seq) {
+                entry.retired = self.stats.cycle;
+            }
+            self.commit(&flight);
+            self.note(SimEvent::Retire {
+                seq: flight.seq,
+                funct,
+                xs1,
+                xs2,
+            });
+            self.stats.retired += 1;
+            if let Some(retired) = sample.get_mut(2) {
+                *retired += 1;
+            }
+        }
+        if let Some(vcd) = &mut self.vcd {
+            vcd.sample(self.stats.cycle - 1, &sample);
+        }
+        if let Some(occupancy) = &mut self.occupancy {
+            let (queued, rob, busy_units) = held;
+            occupancy.record(CycleSample {
+                queued,
+                rob,
+                busy_units,
+                issued: self.stats.issued - issued,
+                retired: self.stats.retired - retired,
+                stall,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::{BankPorts, PortKind, DRAM_BASE};
+
+    #[test]
+    fn effects_become_visible_at_retire() {
+        let mut sim = NpuSim::new(NpuSimConfig {
+            mem_size: 1 << 20,
+            queue_depth: 2,
+            ..NpuSimConfig::default()
+        });
+        sim.npu_mut().write_dram(DRAM_BASE, &[0xab; 64]);
+        sim.push_inst(32, 1, (1 << 5) | (1 << 10)).unwrap(); // mset, 1 cycle
+        sim.push_inst(33, 1 | (4 << 30), DRAM_BASE | (1 << 39)).unwrap(); // mvin 4 rows
+        assert!(sim.push_inst(0, 0, 0).is_err(), "queue depth is 2");
+
+        assert_eq!(sim.tick(1), 1);
+        assert_eq!(sim.read_bank(1).unwrap()[0], 0);
+        assert_eq!(sim.tick(3), 0);
+        assert_eq!(sim.read_bank(1).unwrap()[0], 0, "mvin still in flight");
+        assert_eq!(sim.tick(1), 1);
+        assert_eq!(sim.read_bank(1).unwrap()[..64], [0xab; 64]);
+
+        sim.push_inst(0, 0, 0).unwrap();
+        assert_eq!(sim.run_until_idle(), 1);
+        let stats = sim.stats();
+        assert_eq!((stats.cycle, stats.retired, stats.busy_cycles), (6, 3, 6));
+        assert_eq!(sim.npu().total_latency(), 6);
+    }
+
+    #[test]
+    fn reset_and_reconfigure_run_programs_back_to_back() {
+        let run = |sim: &mut NpuSim| {
+            sim.push_inst(32, 1, (1 << 5) | (1 << 10)).unwrap();
+            sim.push_inst(32, 2, (1 << 5) | (1 << 10)).unwrap();
+            sim.push_inst(33, 1 | (4 << 30), DRAM_BASE | (1 << 39)).unwrap();
+            sim.push_inst(33, 2 | (4 << 30), DRAM_BASE | (1 << 39)).unwrap();
+            sim.run_until_idle();
+            sim.stats()
+        };
+        let mut sim = NpuSim::new(NpuSimConfig {
+            mem_size: 1 << 20,
+            ..NpuSimConfig::default()
+        });
+        sim.npu_mut().write_dram(DRAM_BASE, &[0xab; 64]);
+        let first = run(&mut sim);
+
+        sim.push_inst(0, 0, 0).unwrap();
+        sim.tick(1);
+        sim.reset();
+        assert!(sim.is_idle());
+        assert_eq!(sim.read_bank(1), None, "banks are freed");
+        assert_eq!(run(&mut sim), first);
+        assert_eq!(sim.read_bank(2).unwrap()[..64], [0xab; 64], "DRAM is kept");
+
+        let config = NpuSimConfig {
+            units: 2,
+            ..sim.config()
+        };
+        sim.reconfigure(config).unwrap();
+        assert_eq!(sim.unit_busy_cycles().len(), 2);
+        assert_eq!(run(&mut sim).retired, 4);
+        assert_eq!(sim.unit_busy_cycles().iter().filter(|&&c| c > 0).count(), 2);
+        let err = sim.reconfigure(NpuSimConfig {
+            mem_size: 1 << 21,
+            ..config
+        });
+        assert!(err.unwrap_err().contains("build a new NpuSim"));
+    }
+
+    #[test]
+    fn scoreboard_overtakes_a_stalled_head() {
+        let run = |arbitration| {
+            let mut sim = NpuSim::new(NpuSimConfig {
+                mem_size: 1 << 20,
+                units: 2,
+                arbitration,
+                ..NpuSimConfig::default()
+            });
+            sim.npu_mut().write_dram(DRAM_BASE, &[0x11; 256]);
+            for bank in 1..=4 {
+                sim.push_inst(32, bank, (1 << 5) | (1 << 10)).unwrap();
+            }
+            sim.push_inst(33, 1 | (16 << 30), DRAM_BASE | (1 << 39)).unwrap(); // 16 cycles
+            sim.push_inst(37, 1 | (2 << 10) | (1 << 30), 0).unwrap(); // needs bank1
+            sim.push_inst(37, 3 | (4 << 10) | (16 << 30), 0).unwrap(); // independent
+            let cycles = sim.run_until_idle();
+            assert_eq!(sim.read_bank(2).unwrap()[..16], [0x11; 16]);
+            (cycles, sim.stats().hazard_stalls, sim.unit_busy_cycles().to_vec())
+        };
+        // In order, the independent mcopy waits for the one stuck behind mvin.
+        // msets drain the other unit first, so they count as stalls too.
+        assert_eq!(run(Arbitration::RoundRobin), (4 + 16 + 16, 4 + 16, vec![34, 3]));
+        assert_eq!(run(Arbitration::Scoreboard), (4 + 16 + 1, 4, vec![21, 16]));
+    }
+
+    #[test]
+    fn fence_drains_units_and_holds_back_younger_work() {
+        let mut sim = NpuSim::new(NpuSimConfig {
+            mem_size: 1 << 20,
+            units: 2,
+            arbitration: Arbitration::Scoreboard,
+            ..NpuSimConfig::default()
+        });
+        for bank in 1..=3 {
+            sim.push_inst(32, bank, (1 << 5) | (1 << 10)).unwrap();
+        }
+        sim.tick(3);
+        sim.push_inst(33, 1 | (16 << 30), DRAM_BASE | (1 << 39)).unwrap(); // 16 cycles
+        sim.push_inst(0, 0, 0).unwrap();
+        sim.push_inst(37, 2 | (3 << 10) | (1 << 30), 0).unwrap(); // independent of bank1
+
+        // Without the fence the mcopy would overlap the mvin on unit 1.
+        assert_eq!(sim.tick(16), 1);
+        assert_eq!(sim.stats().queued, 2);
+        assert_eq!(sim.tick(1), 1, "fence retires once the mvin has");
+        assert_eq!(sim.run_until_idle(), 1);
+    }
+
+    #[test]
+    fn loop_ws_expands_into_queued_micro_ops() {
+        let run = |units: usize| {
+            let mut sim = NpuSim::new(NpuSimConfig {
+                mem_size: 1 << 20,
+                units,
+                arbitration: Arbitration::Scoreboard,
+                ..NpuSimConfig::default()
+            });
+            let a: Vec<u8> = (0..16 * 32).map(|i| (i * 37 % 251) as u8).collect();
+            sim.npu_mut().write_dram(DRAM_BASE, &a);
+            sim.npu_mut().write_dram(DRAM_BASE + 0x1000, &a);
+            for (bank, width) in [(1, 0), (2, 0), (3, 2)] {
+                sim.push_inst(32, bank, (1 << 5) | (1 << 10) | (width << 11)).unwrap();
+            }
+            sim.push_inst(52, DRAM_BASE | (1 << 39), (DRAM_BASE + 0x1000) | (2 << 39))
+                .unwrap();
+            sim.push_inst(53, (DRAM_BASE + 0x2000) | (3 << 39), 16 | (32 << 16) | (16 << 32))
+                .unwrap();
+            sim.run_until_idle();
+            (
+                sim.stats().expanded,
+                sim.npu().read_dram(DRAM_BASE + 0x2000, 16 * 16 * 4),
+            )
+        };
+        let (expanded, c) = run(1);
+        // Two K tiles of mvin A, mvin B and matmul, then one mvout.
+        assert_eq!(expanded, 7);
+        assert!(c.iter().any(|&b| b != 0));
+        assert_eq!(run(2), (expanded, c));
+    }
+
+    #[test]
+    fn issue_width_limits_issues_per_cycle_and_stalls_are_attributed() {
+        let run = |units: usize, issue_width: usize| {
+            let mut sim = NpuSim::new(NpuSimConfig {
+                mem_size: 1 << 20,
+                units,
+                issue_width,
+                arbitration: Arbitration::Scoreboard,
+                ..NpuSimConfig::default()
+            });
+            for bank in 1..=4 {
+                sim.push_inst(32, bank, (1 << 5) | (1 << 10)).unwrap();
+            }
+            sim.push_inst(37, 1 | (2 << 10) | (4 << 30), 0).unwrap();
+            sim.push_inst(37, 3 | (4 << 10) | (4 << 30), 0).unwrap();
+            let cycles = sim.run_until_idle();
+            let s = sim.stats();
+            (cycles, s.width_stalls, s.unit_stalls, s.drain_stalls)
+        };
+        // Each mset after the first waits a cycle for the other to drain.
+        assert_eq!(run(2, 0), (8, 0, 0, 3));
+        // The second mcopy issues a cycle later.
+        assert_eq!(run(2, 1), (9, 1, 0, 3));
+        // With one unit, work waits behind each mset and the first mcopy.
+        let (_, _, unit_stalls, _) = run(1, 0);
+        assert_eq!(unit_stalls, 4 + 4);
+    }
+
+    #[test]
+    fn full_reorder_buffer_stalls_issue_and_retires_in_order() {
+        let run = |rob_depth: usize| {
+            let mut sim = NpuSim::new(NpuSimConfig {
+                mem_size: 1 << 20,
+                units: 2,
+                rob_depth,
+                arbitration: Arbitration::Scoreboard,
+                ..NpuSimConfig::default()
+            });
+            for bank in 1..=6 {
+                sim.push_inst(32, bank, (1 << 5) | (1 << 10)).unwrap();
+            }
+            sim.run_until_idle();
+            sim.push_inst(37, 1 | (2 << 10) | (16 << 30), 0).unwrap(); // long
+            sim.push_inst(37, 3 | (4 << 10) | (1 << 30), 0).unwrap(); // short
+            sim.push_inst(37, 5 | (6 << 10) | (1 << 30), 0).unwrap();
+            sim.tick(4);
+            let early = sim.stats().retired - 6;
+            let cycles = 4 + sim.run_until_idle();
+            (early, cycles, sim.stats().rob_stalls, sim.rob_occupancy().to_vec())
+        };
+        // Without a reorder buffer both short mcopies retire long before the
+        // long one.
+        let (early, cycles, rob_stalls, occupancy) = run(0);
+        assert_eq!((early, rob_stalls), (2, 0));
+        assert!(occupancy.is_empty());
+        // With two entries the short mcopy waits for the long one to retire
+        // and holds the third out until then.
+        let (early_rob, cycles_rob, rob_stalls, occupancy) = run(2);
+        assert_eq!((early_rob, cycles_rob, rob_stalls), (0, cycles + 1, 15));
+        assert_eq!(occupancy[2], 16, "full while the long mcopy runs");
+    }
+
+    #[test]
+    fn renamed_writes_overtake_older_reads_and_retire_out_of_order() {
+        let run = |out_of_order: bool| {
+            let mut sim = NpuSim::new(NpuSimConfig {
+                mem_size: 1 << 20,
+                units: 2,
+                rob_depth: 4,
+                arbitration: Arbitration::Scoreboard,
+                out_of_order,
+                ..NpuSimConfig::default()
+            });
+            sim.npu_mut().write_dram(DRAM_BASE, &[0x11; 256]);
+            sim.npu_mut().write_dram(DRAM_BASE + 0x100, &[0x22; 64]);
+            for bank in 1..=2 {
+                sim.push_inst(32, bank, (1 << 5) | (1 << 10)).unwrap();
+            }
+            sim.push_inst(33, 1 | (16 << 30), DRAM_BASE | (1 << 39)).unwrap();
+            sim.run_until_idle();
+            sim.push_inst(37, 1 | (2 << 10) | (16 << 30), 0).unwrap(); // reads bank1
+            sim.push_inst(33, 1 | (4 << 30), (DRAM_BASE + 0x100) | (1 << 39))
+                .unwrap(); // then overwrites it
+            sim.tick(5);
+            let early = sim.read_bank(1).unwrap()[0];
+            let cycles = 5 + sim.run_until_idle();
+            assert_eq!(
+                sim.read_bank(2).unwrap()[..256],
+                [0x11; 256],
+                "mcopy reads bank1 as it was"
+            );
+            let bank1 = sim.read_bank(1).unwrap();
+            assert_eq!(bank1[..64], [0x22; 64]);
+            assert_eq!(bank1[64..256], [0x11; 192], "the rest of bank1 is kept");
+            (early, cycles, sim.stats().renamed)
+        };
+        // In order, the mvin waits for the mcopy to stop reading bank1.
+        assert_eq!(run(false), (0x11, 16 + 4, 0));
+        // Renamed, it runs beside the mcopy and retires first. Both mvins and
+        // the mcopy's write to bank2 were renamed.
+        assert_eq!(run(true), (0x22, 16, 3));
+    }
+
+    #[test]
+    fn units_take_the_kinds_of_work_instructions_declare() {
+        let run = |units: usize, dma_units: usize| {
+            let mut sim = NpuSim::new(NpuSimConfig {
+                mem_size: 1 << 20,
+                units,
+                dma_units,
+                arbitration: Arbitration::Scoreboard,
+                ..NpuSimConfig::default()
+            });
+            for bank in 1..=6 {
+                sim.push_inst(32, bank, (1 << 5) | (1 << 10) | (2 << 11)).unwrap();
+            }
+            sim.run_until_idle();
+            let stalls = sim.stats().hazard_stalls;
+            sim.push_inst(33, 1 | (16 << 30), DRAM_BASE | (1 << 39)).unwrap(); // 16 cycles
+            sim.push_inst(37, 3 | (4 << 10) | (16 << 30), 0).unwrap();
+            sim.push_inst(57, 5 | (16 << 30), 1 << 4).unwrap(); // norm sum, 4 cycles
+            sim.push_inst(57, 6 | (16 << 30), 1 << 4).unwrap();
+            let cycles = sim.run_until_idle();
+            let stalls = sim.stats().hazard_stalls - stalls;
+            (cycles, stalls, sim.unit_busy_cycles()[units..].to_vec())
+        };
+        assert_eq!(run(1, 0), (16 + 16 + 4 + 4, 0, vec![]));
+        // The mvin runs on the DMA unit beside the mcopy and norms. The two
+        // norms share the statistics registers, so the second waits for the
+        // first even with a unit free.
+        assert_eq!(run(1, 1), (16 + 4 + 4, 4, vec![16]));
+        // Norm is a ball and overlaps the mvin and mcopy on other units.
+        assert_eq!(run(4, 0), (16, 4, vec![]));
+    }
+
+    #[test]
+    fn timeline_records_each_stage_and_exports_a_chrome_trace() {
+        let mut sim = NpuSim::new(NpuSimConfig {
+            mem_size: 1 << 20,
+            units: 2,
+            rob_depth: 4,
+            arbitration: Arbitration::Scoreboard,
+            timeline: true,
+            ..NpuSimConfig::default()
+        });
+        for bank in 1..=4 {
+            sim.push_inst(32, bank, (1 << 5) | (1 << 10)).unwrap();
+        }
+        sim.push_inst(37, 1 | (2 << 10) | (16 << 30), 0).unwrap(); // long
+        sim.push_inst(37, 3 | (4 << 10) | (1 << 30), 0).unwrap(); // short
+        sim.run_until_idle();
+
+        let t = sim.timeline();
+        assert_eq!(t.len(), 6);
+        assert!(t
+            .iter()
+            .all(|e| e.queued <= e.issued && e.issued < e.completed && e.completed <= e.retired));
+        let (long, short) = (t[4], t[5]);
+        assert_eq!((long.funct, long.unit, short.unit), (37, 0, 1));
+        assert!(short.completed < long.completed, "the short mcopy finishes first");
+        assert_eq!(short.retired, long.retired, "and waits in the reorder buffer");
+
+        let path = std::env::temp_dir().join(format!("bemu-timeline-{}.json", std::process::id()));
+        sim.write_timeline(&path).unwrap();
+        let trace: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).ok();
+        let events = trace["traceEvents"].as_array().unwrap();
+        let count = |ph: &str, cat: &str| events.iter().filter(|e| e["ph"] == ph && e["cat"] == cat).count();
+        assert_eq!(count("X", "exec"), 6);
+        assert_eq!((count("b", "rob"), count("e", "rob")), (1, 1));
+        assert!(events
+            .iter()
+            .any(|e| e["ph"] == "X" && e["name"] == "mcopy" && e["tid"] == 2));
+
+        assert!(NpuSim::new(NpuSimConfig::default()).write_timeline(&path).is_err());
+    }
+
+    #[test]
+    fn single_port_banks_serialize_shared_reads() {
+        let run = |ports: PortKind| {
+            let mut sim = NpuSim::new(NpuSimConfig {
+                mem_size: 1 << 20,
+                units: 2,
+                arbitration: Arbitration::Scoreboard,
+                ..NpuSimConfig::default()
+            });
+            let ports = BankPorts {
+                default: ports,
+                ..BankPorts::default()
+            };
+            sim.npu_mut().set_bank_ports(ports).unwrap();
+            for bank in 1..=3 {
+                sim.push_inst(32, bank, (1 << 5) | (1 << 10)).unwrap();
+            }
+            sim.push_inst(37, 1 | (2 << 10) | (16 << 30), 0).unwrap(); // both read bank1
+            sim.push_inst(37, 1 | (3 << 10) | (16 << 30), 0).unwrap();
+            sim.push_inst(50, 2 | (2 << 10) | (4 << 30), 0).unwrap(); // in place
+            let cycles = sim.run_until_idle();
+            (
+                cycles,
+                sim.stats().port_stalls,
+                sim.npu().perf_report().bank_conflict_cycles,
+            )
+        };
+        assert_eq!(run(PortKind::Ideal), (3 + 16 + 4, 0, 0));
+        assert_eq!(run(PortKind::Dual), (3 + 16 + 4, 0, 0));
+        // One port: the second mcopy waits for the first. The in-place relu
+        // overlaps it, reading and writing bank2 on alternate cycles.
+        assert_eq!(run(PortKind::Single), (3 + 16 + 16, 16, 4));
+    }
+}
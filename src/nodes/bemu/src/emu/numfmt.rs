@@ -0,0 +1,433 @@
+//===- numfmt.rs - Element number formats ----------------------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// Number formats a bank can hold besides the signed integer widths, for
+// number-format experiments. mset picks one per bank in xs2[16:14]:
+//
+//   0 int       signed integers of the bank's element width
+//   1 fp32      IEEE 754 binary32
+//   2 bf16      bfloat16, rounding to nearest even
+//   3 bf16_rz   bfloat16, rounding toward zero
+//   4 posit8    8-bit posit with two exponent bits (posit standard 2022)
+//   5 bfp8      block floating point: i8 mantissas, one exponent per line
+//
+// A format implements `NumberFormat`: it decodes its bit pattern to f32 and
+// rounds an f32 into one. Instructions that support formats (matmul, mcopy)
+// decode their operands, compute in f32 and round the result into the
+// destination's format, so another format is one impl and one `NumFormat`
+// variant.
+//
+// bfp8 is the exception: an element means nothing without the exponent its
+// 16-byte bank line shares, which the Npu keeps beside the banks. Only
+// mvin_bfp and mvout_bfp, which convert from and to fp32 DRAM data, and
+// matmul, which aligns and renormalizes the blocks, work on it.
+//
+// With the golden check on, every value rounded into a float format is
+// compared with the fp32 value it came from, and the errors are summed per
+// format (`Npu::format_errors`).
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use std::fmt;
+
+use crate::bank::ElemWidth;
+
+/// An element encoding compute works in through f32.
+pub trait NumberFormat {
+    const NAME: &'static str;
+    /// Bytes per element in a bank.
+    const BYTES: usize;
+
+    fn decode(bits: u32) -> f32;
+
+    /// Round `v` to the nearest value the format holds, by its rounding mode.
+    fn encode(v: f32) -> u32;
+}
+
+pub struct Fp32;
+
+impl NumberFormat for Fp32 {
+    const NAME: &'static str = "fp32";
+    const BYTES: usize = 4;
+
+    fn decode(bits: u32) -> f32 {
+        f32::from_bits(bits)
+    }
+
+    fn encode(v: f32) -> u32 {
+        v.to_bits()
+    }
+}
+
+/// bfloat16 rounding to nearest, ties to even.
+pub struct Bf16;
+
+impl NumberFormat for Bf16 {
+    const NAME: &'static str = "bf16";
+    const BYTES: usize = 2;
+
+    fn decode(bits: u32) -> f32 {
+        f32::from_bits(bits << 16)
+    }
+
+    fn encode(v: f32) -> u32 {
+        if v.is_nan() {
+            return 0x7fc0;
+        }
+        let bits = v.to_bits();
+        (bits + 0x7fff + ((bits >> 16) & 1)) >> 16
+    }
+}
+
+/// bfloat16 rounding toward zero.
+pub struct Bf16Rz;
+
+impl NumberFormat for Bf16Rz {
+    const NAME: &'static str = "bf16_rz";
+    const BYTES: usize = 2;
+
+    fn decode(bits: u32) -> f32 {
+        Bf16::decode(bits)
+    }
+
+    fn encode(v: f32) -> u32 {
+        if v.is_nan() {
+            return 0x7fc0;
+        }
+        v.to_bits() >> 16
+    }
+}
+
+/// 8-bit posit, es = 2. Rounds to nearest even and, as posits do, never to
+/// zero or NaR: magnitudes saturate to minpos and maxpos.
+pub struct Posit8;
+
+/// Exponent bits of posit8.
+const POSIT8_ES: i32 = 2;
+
+impl NumberFormat for Posit8 {
+    const NAME: &'static str = "posit8";
+    const BYTES: usize = 1;
+
+    fn decode(bits: u32) -> f32 {
+        let p = bits as u8;
+        match p {
+            0 => return 0.0,
+            0x80 => return f32::NAN,
+            _ => {}
+        }
+        let neg = p & 0x80 != 0;
+        let body = if neg { p.wrapping_neg() } else { p } & 0x7f;
+        // The regime is a run of equal bits after the sign.
+        let r0 = (body >> 6) & 1;
+        let mut run = 0;
+        while run < 7 && (body >> (6 - run)) & 1 == r0 {
+            run += 1;
+        }
+        let k = if r0 == 1 { run as i32 - 1 } else { -(run as i32) };
+        // Exponent bits cut off by the regime read as zero.
+        let rest = 7u32.saturating_sub(run + 1);
+        let tail = u32::from(body) & ((1 << rest) - 1);
+        let es = POSIT8_ES as u32;
+        let (exp, frac, frac_bits) = if rest >= es {
+            (tail >> (rest - es), tail & ((1 << (rest - es)) - 1), rest - es)
+        } else {
+            (tail << (es - rest), 0, 0)
+        };
+        let scale = (k << POSIT8_ES) + exp as i32;
+        let v = (1.0 + frac as f32 / (1u32 << frac_bits) as f32) * 2f32.powi(scale);
+        if neg {
+            -v
+        } else {
+            v
+        }
+    }
+
+    fn encode(v: f32) -> u32 {
+        if v == 0.0 {
+            return 0;
+        }
+        if !v.is_finite() {
+            return 0x80;
+        }
+        // maxpos = 2^24, minpos = 2^-24.
+        let max_scale = 6 << POSIT8_ES;
+        let a = f64::from(v.abs());
+        let body: u8 = if a >= 2f64.powi(max_scale) {
+            0x7f
+        } else if a <= 2f64.powi(-max_scale) {
+            0x01
+        } else {
+            let bits = a.to_bits();
+            let scale = ((bits >> 52) & 0x7ff) as i32 - 1023;
+            let frac = bits & ((1 << 52) - 1);
+            let (k, exp) = (scale >> POSIT8_ES, (scale & ((1 << POSIT8_ES) - 1)) as u64);
+            // regime, exponent and fraction as one bit string of `len` bits.
+            let (regime, regime_len) = if k >= 0 {
+                (((1u64 << (k + 1)) - 1) << 1, k as u32 + 2)
+            } else {
+                (1, (-k) as u32 + 1)
+            };
+            let len = regime_len + POSIT8_ES as u32 + 52;
+            let string = (((regime << POSIT8_ES) | exp) << 52) | frac;
+            let cut = len - 7;
+            let mut body = string >> cut;
+            let guard = (string >> (cut - 1)) & 1 == 1;
+            let sticky = string & ((1 << (cut - 1)) - 1) != 0;
+            if guard && (sticky || body & 1 == 1) {
+                body += 1;
+            }
+            body.clamp(1, 0x7f) as u8
+        };
+        u32::from(if v < 0.0 { body.wrapping_neg() } else { body })
+    }
+}
+
+fn load<F: NumberFormat>(bank: &[u8], i: usize) -> f32 {
+    let mut word = [0; 4];
+    word[..F::BYTES].copy_from_slice(&bank[i * F::BYTES..(i + 1) * F::BYTES]);
+    F::decode(u32::from_le_bytes(word))
+}
+
+fn store<F: NumberFormat>(bank: &mut [u8], i: usize, v: f32) {
+    bank[i * F::BYTES..(i + 1) * F::BYTES].copy_from_slice(&F::encode(v).to_le_bytes()[..F::BYTES]);
+}
+
+/// Block floating point. A block of `BLOCK` i8 mantissas shares one
+/// exponent, and an element is `mantissa * 2^exponent`.
+pub struct Bfp8;
+
+impl Bfp8 {
+    pub const NAME: &'static str = "bfp8";
+    /// Elements per block: one 16-byte bank line.
+    pub const BLOCK: usize = 16;
+
+    /// Shared exponent and mantissas of up to `BLOCK` values. The exponent
+    /// puts the largest magnitude in 7 bits; mantissas round to nearest even
+    /// and saturate, and NaN becomes 0.
+    pub fn encode_block(values: &[f32]) -> (i8, [i8; 16]) {
+        let max = values.iter().filter(|v| !v.is_nan()).fold(0f32, |m, v| m.max(v.abs()));
+        let exp = if max == 0.0 {
+            0
+        } else {
+            (f64::from(max.min(f32::MAX)).log2().floor() as i32 - 6).clamp(i8::MIN as i32, i8::MAX as i32)
+        };
+        let mut mantissas = [0; 16];
+        for (m, &v) in mantissas.iter_mut().zip(values) {
+            if !v.is_nan() {
+                *m = (f64::from(v) * 2f64.powi(-exp)).round_ties_even().clamp(-127.0, 127.0) as i8;
+            }
+        }
+        (exp as i8, mantissas)
+    }
+
+    pub fn decode(mantissa: i8, exp: i8) -> f32 {
+        (f64::from(mantissa) * 2f64.powi(exp as i32)) as f32
+    }
+}
+
+/// Format of the elements of a bank, chosen at mset.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NumFormat {
+    #[default]
+    Int,
+    Fp32,
+    Bf16,
+    Bf16Rz,
+    Posit8,
+    Bfp8,
+}
+
+impl NumFormat {
+    /// mset `xs2[16:14]`.
+    pub fn from_code(code: u64) -> Option<Self> {
+        match code {
+            0 => Some(NumFormat::Int),
+            1 => Some(NumFormat::Fp32),
+            2 => Some(NumFormat::Bf16),
+            3 => Some(NumFormat::Bf16Rz),
+            4 => Some(NumFormat::Posit8),
+            5 => Some(NumFormat::Bfp8),
+            _ => None,
+        }
+    }
+
+    pub fn code(self) -> u64 {
+        self as u64
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            NumFormat::Int => "int",
+            NumFormat::Fp32 => Fp32::NAME,
+            NumFormat::Bf16 => Bf16::NAME,
+            NumFormat::Bf16Rz => Bf16Rz::NAME,
+            NumFormat::Posit8 => Posit8::NAME,
+            NumFormat::Bfp8 => Bfp8::NAME,
+        }
+    }
+
+    pub fn is_float(self) -> bool {
+        self != NumFormat::Int
+    }
+
+    /// Element width the format occupies; `None` for integers, which take
+    /// the width mset gives.
+    pub fn width(self) -> Option<ElemWidth> {
+        match self {
+            NumFormat::Int => None,
+            NumFormat::Fp32 => Some(ElemWidth::I32),
+            NumFormat::Bf16 | NumFormat::Bf16Rz => Some(ElemWidth::I16),
+            NumFormat::Posit8 | NumFormat::Bfp8 => Some(ElemWidth::I8),
+        }
+    }
+
+    /// Element `i` of a bank in this format, integers of `width` read as
+    /// their value. Not for bfp8, whose elements need their block exponent.
+    pub fn load(self, width: ElemWidth, bank: &[u8], i: usize) -> f32 {
+        match self {
+            NumFormat::Int => width.load(bank, i) as f32,
+            NumFormat::Fp32 => load::<Fp32>(bank, i),
+            NumFormat::Bf16 => load::<Bf16>(bank, i),
+            NumFormat::Bf16Rz => load::<Bf16Rz>(bank, i),
+            NumFormat::Posit8 => load::<Posit8>(bank, i),
+            NumFormat::Bfp8 => panic!("bfp8 elements need their block exponent"),
+        }
+    }
+
+    /// Round `v` into element `i`. Integers round to nearest and saturate to
+    /// `width`; returns whether they saturated.
+    pub fn store(self, width: ElemWidth, bank: &mut [u8], i: usize, v: f32) -> bool {
+        match self {
+            NumFormat::Int => return width.store(bank, i, v.round() as i64),
+            NumFormat::Fp32 => store::<Fp32>(bank, i, v),
+            NumFormat::Bf16 => store::<Bf16>(bank, i, v),
+            NumFormat::Bf16Rz => store::<Bf16Rz>(bank, i, v),
+            NumFormat::Posit8 => store::<Posit8>(bank, i, v),
+            NumFormat::Bfp8 => panic!("bfp8 elements need their block exponent"),
+        }
+        false
+    }
+
+    /// `v` after a round trip through the format.
+    pub fn round(self, v: f32) -> f32 {
+        let mut word = [0; 4];
+        self.store(ElemWidth::I32, &mut word, 0, v);
+        self.load(ElemWidth::I32, &word, 0)
+    }
+}
+
+/// How far the values rounded into one format landed from their fp32
+/// originals.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FormatError {
+    pub values: u64,
+    pub max_abs: f64,
+    pub sum_abs: f64,
+    /// Largest error relative to a non-zero original.
+    pub max_rel: f64,
+}
+
+impl FormatError {
+    pub fn record(&mut self, original: f32, rounded: f32) {
+        let err = (f64::from(rounded) - f64::from(original)).abs();
+        self.values += 1;
+        self.max_abs = self.max_abs.max(err);
+        self.sum_abs += err;
+        if original != 0.0 {
+            self.max_rel = self.max_rel.max(err / f64::from(original).abs());
+        }
+    }
+
+    pub fn mean_abs(&self) -> f64 {
+        if self.values == 0 {
+            0.0
+        } else {
+            self.sum_abs / self.values as f64
+        }
+    }
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} values, max abs error {:.3e}, mean {:.3e}, max rel {:.3e}",
+            self.values,
+            self.max_abs,
+            self.mean_abs(),
+            self.max_rel
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bf16_rounding_modes() {
+        // 1 + 2^-8 is halfway between two bf16 values; ties go to even.
+        let halfway = 1.0 + 2f32.powi(-8);
+        assert_eq!(NumFormat::Bf16.round(halfway), 1.0);
+        assert_eq!(NumFormat::Bf16.round(1.0 + 3.0 * 2f32.powi(-8)), 1.0 + 2f32.powi(-6));
+        let above = 1.0 + 2f32.powi(-8) + 2f32.powi(-12);
+        assert_eq!(NumFormat::Bf16.round(above), 1.0 + 2f32.powi(-7));
+        assert_eq!(NumFormat::Bf16Rz.round(above), 1.0);
+        assert_eq!(NumFormat::Bf16Rz.round(-above), -1.0);
+    }
+
+    #[test]
+    fn posit8_decodes_the_standard_values_and_rounds_to_them() {
+        let cases = [
+            (0x40, 1.0),
+            (0x50, 4.0),
+            (0x48, 2.0),
+            (0x44, 1.5),
+            (0x30, 0.25),
+            (0x7f, 2f32.powi(24)),
+            (0x01, 2f32.powi(-24)),
+            (0xc0, -1.0),
+        ];
+        for (bits, v) in cases {
+            assert_eq!(Posit8::decode(bits), v, "0x{bits:02x}");
+            assert_eq!(Posit8::encode(v), bits, "{v}");
+        }
+        assert!(Posit8::decode(0x80).is_nan());
+        // Every pattern survives a round trip.
+        for bits in (0..=0xff).filter(|&b| b != 0x80) {
+            assert_eq!(Posit8::encode(Posit8::decode(bits)), bits);
+        }
+        // Ties between 1.0 and 1.125 go to the even pattern; posits saturate
+        // instead of overflowing or flushing to zero.
+        assert_eq!(Posit8::encode(1.0625), 0x40);
+        assert_eq!(Posit8::encode(1.07), 0x41);
+        assert_eq!(Posit8::encode(1e30), 0x7f);
+        assert_eq!(Posit8::encode(-1e-30), 0xff);
+    }
+
+    #[test]
+    fn bfp8_blocks_share_the_exponent_of_their_largest_value() {
+        let (exp, m) = Bfp8::encode_block(&[96.0, 1.0, -0.75, 0.25]);
+        // 96 = 2^6 * 1.5, so 2^0 steps put it in 7 bits; 0.75 rounds to even.
+        assert_eq!((exp, &m[..4]), (0, &[96, 1, -1, 0][..]));
+        assert_eq!(Bfp8::decode(m[2], exp), -1.0);
+        let (exp, m) = Bfp8::encode_block(&[0.375, 0.0078125]);
+        assert_eq!((exp, &m[..2]), (-8, &[96, 2][..]));
+        assert_eq!(Bfp8::encode_block(&[0.0; 16]), (0, [0; 16]));
+        assert_eq!(Bfp8::encode_block(&[f32::INFINITY, 1.0]).1[..2], [127, 0]);
+    }
+}
@@ -0,0 +1,366 @@
+//===- perf.rs - Utilization counters and roofline report ------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// Instructions count the work they do in `PerfCounters`: multiply-accumulates,
+// bytes read and written in banks, and bytes moved to and from DRAM. Npu adds
+// the cycles of each funct, stalls included. `PerfReport` combines them with
+// the DMA statistics and the model's peaks:
+//
+//   - compute peak: one MAC per PE of the systolic array each cycle, 256 for
+//     the default 16 x 16 array,
+//   - DRAM peak: one DMA beat per cycle, or the DRAM timing model's
+//     `bytes_per_cycle` when that is lower.
+//
+// Arithmetic intensity is MACs per DRAM byte. A run whose intensity is below
+// the ridge point (compute peak / DRAM peak) can at best reach intensity x
+// DRAM peak MACs per cycle, so it is memory-bound; above it, compute-bound.
+//
+// With an energy cost table (energy.rs) the report also prices the run.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::cache::CacheStats;
+use crate::dma::{DmaStats, DMA_BEAT_BYTES};
+use crate::energy::EnergyBreakdown;
+use crate::inst::decode::INSTRUCTIONS;
+use crate::inst::f48_matmul::Matmul;
+use crate::inst::f49_conv::Conv;
+use crate::inst::f54_spmm::Spmm;
+use crate::inst::instruction::Instruction;
+use crate::npu::Npu;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PerfCounters {
+    pub macs: u64,
+    pub bank_read_bytes: u64,
+    pub bank_write_bytes: u64,
+    pub dram_read_bytes: u64,
+    pub dram_write_bytes: u64,
+    /// Cycles instructions waited for ports of a bank they share.
+    pub bank_conflict_cycles: u64,
+    /// funct -> (instructions, cycles including stalls).
+    pub per_funct: BTreeMap<u32, (u64, u64)>,
+}
+
+/// Counters the `counter` instruction reads, by id.
+pub const COUNTERS: &[&str] = &[
+    "cycles",
+    "instructions",
+    "macs",
+    "dram_read_bytes",
+    "dram_write_bytes",
+    "bank_read_bytes",
+    "bank_write_bytes",
+    "dma_stall_cycles",
+    "compute_cycles",
+    "compute_ops",
+    "dma_transfers",
+    "bank_conflict_cycles",
+];
+
+impl PerfCounters {
+    /// Counter `id` of `COUNTERS`, given the cycle and instruction count so
+    /// far and the DMA statistics.
+    pub(crate) fn read(&self, id: usize, cycles: u64, instructions: u64, dma: &DmaStats) -> Option<u64> {
+        let funct_cycles = |funct: u32| self.per_funct.get(&funct).map_or(0, |&(_, cycles)| cycles);
+        let funct_count = |funct: u32| self.per_funct.get(&funct).map_or(0, |&(count, _)| count);
+        Some(match *COUNTERS.get(id)? {
+            "cycles" => cycles,
+            "instructions" => instructions,
+            "macs" => self.macs,
+            "dram_read_bytes" => self.dram_read_bytes,
+            "dram_write_bytes" => self.dram_write_bytes,
+            "bank_read_bytes" => self.bank_read_bytes,
+            "bank_write_bytes" => self.bank_write_bytes,
+            "dma_stall_cycles" => dma.throttle_cycles + dma.dram_cycles + dma.penalty_cycles,
+            "compute_cycles" => funct_cycles(Matmul::FUNCT) + funct_cycles(Conv::FUNCT) + funct_cycles(Spmm::FUNCT),
+            "compute_ops" => funct_count(Matmul::FUNCT) + funct_count(Conv::FUNCT) + funct_count(Spmm::FUNCT),
+            "dma_transfers" => dma.transfers,
+            _ => self.bank_conflict_cycles,
+        })
+    }
+
+    /// `bytes` moved from DRAM into a bank.
+    pub(crate) fn dma_in(&mut self, bytes: u64) {
+        self.dram_read_bytes += bytes;
+        self.bank_write_bytes += bytes;
+    }
+
+    /// `bytes` moved from a bank out to DRAM.
+    pub(crate) fn dma_out(&mut self, bytes: u64) {
+        self.bank_read_bytes += bytes;
+        self.dram_write_bytes += bytes;
+    }
+}
+
+/// Per-instruction row of a `PerfReport`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InstPerf {
+    pub funct: u32,
+    pub name: String,
+    pub count: u64,
+    pub cycles: u64,
+    /// Dynamic energy in picojoules, with an energy cost table.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub energy_pj: Option<f64>,
+}
+
+/// End-of-run utilization statistics, written as JSON by `--stats FILE`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PerfReport {
+    pub cycles: u64,
+    pub instructions: u64,
+    pub macs: u64,
+    pub bank_read_bytes: u64,
+    pub bank_write_bytes: u64,
+    pub dram_read_bytes: u64,
+    pub dram_write_bytes: u64,
+    pub dma: DmaStats,
+    /// Memory-side cache hits and misses, with a cache.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<CacheStats>,
+    #[serde(default)]
+    pub bank_conflict_cycles: u64,
+    pub peak_macs_per_cycle: u64,
+    pub peak_dram_bytes_per_cycle: u64,
+    /// MACs per DRAM byte.
+    pub intensity: f64,
+    pub macs_per_cycle: f64,
+    pub dram_bytes_per_cycle: f64,
+    /// Intensity at which the DRAM and compute roofs meet.
+    pub ridge: f64,
+    pub memory_bound: bool,
+    /// Energy in picojoules, with an energy cost table.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub energy: Option<EnergyBreakdown>,
+    pub per_inst: Vec<InstPerf>,
+}
+
+impl PerfReport {
+    pub fn capture(npu: &Npu) -> Self {
+        let c = &npu.perf;
+        let cycles = npu.total_latency();
+        let peak_macs = npu.array_geometry().peak_macs();
+        let peak_dram = match npu.dma.dram.as_ref().map(|d| d.timing.bytes_per_cycle) {
+            Some(bpc) if bpc > 0 => bpc.min(DMA_BEAT_BYTES),
+            _ => DMA_BEAT_BYTES,
+        };
+        let dram_bytes = c.dram_read_bytes + c.dram_write_bytes;
+        let ratio = |a: u64, b: u64| if b == 0 { 0.0 } else { a as f64 / b as f64 };
+        let intensity = ratio(c.macs, dram_bytes);
+        let ridge = ratio(peak_macs, peak_dram);
+        Self {
+            cycles,
+            instructions: npu.instruction_count(),
+            macs: c.macs,
+            bank_read_bytes: c.bank_read_bytes,
+            bank_write_bytes: c.bank_write_bytes,
+            dram_read_bytes: c.dram_read_bytes,
+            dram_write_bytes: c.dram_write_bytes,
+            dma: npu.dma_stats(),
+            cache: npu.cache_stats(),
+            bank_conflict_cycles: c.bank_conflict_cycles,
+            peak_macs_per_cycle: peak_macs,
+            peak_dram_bytes_per_cycle: peak_dram,
+            intensity,
+            macs_per_cycle: ratio(c.macs, cycles),
+            dram_bytes_per_cycle: ratio(dram_bytes, cycles),
+            ridge,
+            memory_bound: intensity < ridge,
+            energy: npu.energy.as_ref().map(|e| e.breakdown(c, cycles)),
+            per_inst: c
+                .per_funct
+                .iter()
+                .map(|(&funct, &(count, cycles))| InstPerf {
+                    funct,
+                    name: INSTRUCTIONS
+                        .iter()
+                        .find(|(f, _)| *f == funct)
+                        .map(|(_, name)| name.to_string())
+                        .or_else(|| {
+                            npu.extension_for(funct)
+                                .map(|i| npu.extensions.get(i).name().to_string())
+                        })
+                        .unwrap_or_else(|| format!("funct{funct}")),
+                    count,
+                    cycles,
+                    energy_pj: npu
+                        .energy
+                        .as_ref()
+                        .map(|e| e.per_funct.get(&funct).copied().unwrap_or_default()),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, json).map_err(|e| format!("failed to write stats {}: {e}", path.display()))
+    }
+}
+
+impl fmt::Display for PerfReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pct = |a: f64, peak: u64| 100.0 * a / peak as f64;
+        writeln!(
+            f,
+            "{} cycles, {} instructions, {} MACs, DRAM {} B read / {} B written, banks {} B read / {} B written",
+            self.cycles,
+            self.instructions,
+            self.macs,
+            self.dram_read_bytes,
+            self.dram_write_bytes,
+            self.bank_read_bytes,
+            self.bank_write_bytes
+        )?;
+        writeln!(
+            f,
+            "compute {:.2} MACs/cycle ({:.1}% of {}), DRAM {:.2} B/cycle ({:.1}% of {})",
+            self.macs_per_cycle,
+            pct(self.macs_per_cycle, self.peak_macs_per_cycle),
+            self.peak_macs_per_cycle,
+            self.dram_bytes_per_cycle,
+            pct(self.dram_bytes_per_cycle, self.peak_dram_bytes_per_cycle),
+            self.peak_dram_bytes_per_cycle
+        )?;
+        let stalls = self.dma.throttle_cycles
+            + self.dma.dram_cycles
+            + self.dma.penalty_cycles
+            + self.cache.map_or(0, |c| c.cycles);
+        writeln!(
+            f,
+            "roofline: {:.2} MACs/B against a ridge of {:.2} -> {}; {stalls} DMA stall cycles",
+            self.intensity,
+            self.ridge,
+            if self.memory_bound {
+                "memory-bound"
+            } else {
+                "compute-bound"
+            }
+        )?;
+        if let Some(cache) = &self.cache {
+            writeln!(f, "cache: {cache}")?;
+        }
+        if self.bank_conflict_cycles > 0 {
+            writeln!(f, "bank ports: {} conflict cycles", self.bank_conflict_cycles)?;
+        }
+        if let Some(e) = &self.energy {
+            let nj = |pj: f64| pj / 1000.0;
+            write!(
+                f,
+                "energy {:.3} nJ: MAC {:.3}, SRAM {:.3}, DRAM {:.3}, bus {:.3}, static {:.3}",
+                nj(e.total()),
+                nj(e.mac),
+                nj(e.sram),
+                nj(e.dram),
+                nj(e.bus),
+                nj(e.leakage)
+            )?;
+            if self.macs > 0 {
+                write!(f, "; {:.2} pJ/MAC", e.total() / self.macs as f64)?;
+            }
+            writeln!(f)?;
+        }
+        write!(f, "{:<10} {:>10} {:>12}", "inst", "count", "cycles")?;
+        if self.energy.is_some() {
+            write!(f, " {:>12}", "energy-nJ")?;
+        }
+        writeln!(f)?;
+        for inst in &self.per_inst {
+            write!(f, "{:<10} {:>10} {:>12}", inst.name, inst.count, inst.cycles)?;
+            if let Some(pj) = inst.energy_pj {
+                write!(f, " {:>12.3}", pj / 1000.0)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::DRAM_BASE;
+
+    #[test]
+    fn report_counts_macs_and_bytes_and_classifies_the_run() {
+        let mut npu = Npu::new(1 << 20);
+        for bank in 1..=3 {
+            npu.exec(32, bank, (1 << 5) | (1 << 10), 0);
+        }
+        npu.exec(33, 1 | (4 << 30), DRAM_BASE | (1 << 39), 0);
+        npu.exec(33, 2 | (16 << 30), (DRAM_BASE + 0x1000) | (1 << 39), 0);
+        npu.exec(48, 1 | (2 << 10) | (3 << 20) | (4 << 30), 0, 0);
+        npu.exec(16, 3 | (4 << 30), (DRAM_BASE + 0x2000) | (1 << 39), 0);
+
+        let perf = npu.perf_report();
+        assert_eq!(perf.macs, 4 * 16 * 16);
+        assert_eq!((perf.dram_read_bytes, perf.dram_write_bytes), (20 * 16, 4 * 16));
+        assert_eq!(perf.bank_read_bytes, 20 * 16 + 4 * 16);
+        assert_eq!(perf.bank_write_bytes, 20 * 16 + 4 * 16);
+        // 1024 MACs over 384 DRAM bytes is below the 256 / 16 ridge.
+        assert!(perf.memory_bound);
+        let matmul = perf.per_inst.iter().find(|i| i.name == "matmul").unwrap();
+        assert_eq!((matmul.count, matmul.cycles), (1, 4 + 16));
+        let total: u64 = perf.per_inst.iter().map(|i| i.cycles).sum();
+        assert_eq!(total, perf.cycles);
+
+        // The counter instruction sees the same totals, itself included.
+        let read = |npu: &mut Npu, id: u64| npu.exec(40, id, 0, 0);
+        assert_eq!(read(&mut npu, 2), perf.macs);
+        assert_eq!(read(&mut npu, 0), perf.cycles + 2);
+        assert_eq!(read(&mut npu, 1), perf.instructions + 3);
+        assert_eq!(read(&mut npu, 8), matmul.cycles);
+
+        npu.reset();
+        assert_eq!(npu.perf_report().macs, 0);
+    }
+
+    #[test]
+    fn energy_is_priced_per_event_and_attributed_to_instructions() {
+        let mut npu = Npu::new(1 << 20);
+        let table = crate::EnergyTable {
+            mac: 1.0,
+            sram_read: 10.0,
+            sram_write: 20.0,
+            dram_read: 100.0,
+            dram_write: 200.0,
+            bus: 1000.0,
+            leakage: 0.5,
+        };
+        npu.set_energy_table(Some(table)).unwrap();
+        npu.exec(32, 1, (1 << 5) | (1 << 10), 0);
+        npu.exec(33, 1 | (4 << 30), DRAM_BASE | (1 << 39), 0);
+        npu.exec(16, 1 | (2 << 30), (DRAM_BASE + 0x1000) | (1 << 39), 0);
+
+        let perf = npu.perf_report();
+        let e = perf.energy.unwrap();
+        assert_eq!(
+            (e.sram, e.dram, e.bus),
+            (2.0 * 10.0 + 4.0 * 20.0, 400.0 + 400.0, 6000.0)
+        );
+        assert_eq!(e.leakage, 0.5 * perf.cycles as f64);
+        let mvin = perf.per_inst.iter().find(|i| i.name == "mvin").unwrap();
+        assert_eq!(mvin.energy_pj, Some(4.0 * (20.0 + 100.0 + 1000.0)));
+        let dynamic: f64 = perf.per_inst.iter().filter_map(|i| i.energy_pj).sum();
+        assert_eq!(dynamic + e.leakage, e.total());
+    }
+}
@@ -0,0 +1,346 @@
+//===- program.rs - Text instruction programs ------------------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// A program is a text file of accelerator instructions, one per line, run in
+// order on an Npu:
+//
+//   # load A and write it back
+//   load:
+//     mset  bank=1 cols=1
+//     mvin  bank=1 addr=0x80001000 rows=4
+//   store:
+//     mvout bank=1 addr=0x80002000 rows=4 stride=1
+//     fence
+//     inst  33 0x40000001 0x8080001000     # raw funct xs1 xs2
+//
+// Operands are `name=value` in any order, separated by spaces or commas;
+// values are decimal or 0x hex. Mnemonics may carry the `bb.` prefix of the
+// C intrinsics, `bb.mvin bank=3, addr=0x80001000, rows=16`.
+// `#` starts a comment. A `label:` line names the instructions after it in
+// the report. `inst FUNCT XS1 XS2` issues anything, including functs this
+// parser has no mnemonic for. Mnemonics and the bits their operands occupy
+// come from an `Isa` (isa.rs).
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::isa::{base_isa, Isa};
+use crate::npu::Npu;
+
+/// One encoded instruction and where it came from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProgramInst {
+    pub line: usize,
+    pub label: Option<String>,
+    pub mnemonic: String,
+    pub funct: u32,
+    pub xs1: u64,
+    pub xs2: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Program {
+    pub insts: Vec<ProgramInst>,
+}
+
+/// Funct of a base mnemonic, e.g. `mvin` -> 33.
+pub(crate) fn funct_of(mnemonic: &str) -> Option<u32> {
+    base_isa().funct_of(mnemonic.strip_prefix("bb.").unwrap_or(mnemonic))
+}
+
+pub(crate) fn parse_value(s: &str) -> Option<u64> {
+    match s {
+        "dram" | "i8" | "int" => Some(0),
+        "bank" | "i16" | "fp32" => Some(1),
+        "i32" | "bf16" => Some(2),
+        "bf16_rz" => Some(3),
+        "posit8" => Some(4),
+        "bfp8" => Some(5),
+        _ => match s.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(&hex.replace('_', ""), 16).ok(),
+            None => s.replace('_', "").parse().ok(),
+        },
+    }
+}
+
+impl Program {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        Self::load_with(path, base_isa())
+    }
+
+    /// Load a program written against `isa`.
+    pub fn load_with(path: &Path, isa: &Isa) -> Result<Self, String> {
+        let text =
+            std::fs::read_to_string(path).map_err(|e| format!("failed to read program {}: {e}", path.display()))?;
+        Self::parse_with(&text, isa).map_err(|e| format!("{}:{e}", path.display()))
+    }
+
+    /// Errors are prefixed with the 1-based line number.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        Self::parse_with(text, base_isa())
+    }
+
+    /// Parse a program written against `isa`.
+    pub fn parse_with(text: &str, isa: &Isa) -> Result<Self, String> {
+        let mut insts = Vec::new();
+        let mut label = None;
+        for (i, raw) in text.lines().enumerate() {
+            let line = i + 1;
+            let code = raw.split('#').next().unwrap_or("").trim();
+            if code.is_empty() {
+                continue;
+            }
+            if let Some(name) = code.strip_suffix(':') {
+                if name.is_empty() || name.contains(char::is_whitespace) {
+                    return Err(format!("{line}: invalid label {code:?}"));
+                }
+                label = Some(name.to_string());
+                continue;
+            }
+            let mut words = code
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|w| !w.is_empty());
+            let word = words.next().unwrap_or_default();
+            let mnemonic = word.strip_prefix("bb.").unwrap_or(word);
+            let args: Vec<&str> = words.collect();
+            let (funct, xs1, xs2) = if mnemonic == "inst" {
+                let [funct, xs1, xs2] = args[..] else {
+                    return Err(format!("{line}: inst takes FUNCT XS1 XS2"));
+                };
+                let value = |s: &str| parse_value(s).ok_or_else(|| format!("{line}: invalid number {s:?}"));
+                (value(funct)? as u32, value(xs1)?, value(xs2)?)
+            } else {
+                let format = isa
+                    .get(mnemonic)
+                    .ok_or_else(|| format!("{line}: unknown mnemonic {mnemonic:?}"))?;
+                let mut values = BTreeMap::new();
+                for arg in args {
+                    let (name, value) = arg
+                        .split_once('=')
+                        .ok_or_else(|| format!("{line}: expected name=value, got {arg:?}"))?;
+                    let name = format
+                        .field(name)
+                        .map(|f| f.name.as_str())
+                        .ok_or_else(|| format!("{line}: {mnemonic} has no operand {name:?}"))?;
+                    let value = parse_value(value).ok_or_else(|| format!("{line}: invalid value {value:?}"))?;
+                    if values.insert(name, value).is_some() {
+                        return Err(format!("{line}: operand {name} given twice"));
+                    }
+                }
+                let (xs1, xs2) = format.encode(&values).map_err(|e| format!("{line}: {e}"))?;
+                (format.funct, xs1, xs2)
+            };
+            insts.push(ProgramInst {
+                line,
+                label: label.clone(),
+                mnemonic: mnemonic.to_string(),
+                funct,
+                xs1,
+                xs2,
+            });
+        }
+        Ok(Self { insts })
+    }
+
+    /// Execute every instruction in order and record what each cost.
+    pub fn run(&self, npu: &mut Npu) -> ProgramReport {
+        let start = npu.total_latency();
+        let steps = self
+            .insts
+            .iter()
+            .map(|inst| {
+                let before = npu.total_latency();
+                npu.exec(inst.funct, inst.xs1, inst.xs2, 0);
+                (inst.clone(), npu.total_latency() - before)
+            })
+            .collect();
+        ProgramReport {
+            steps,
+            cycles: npu.total_latency() - start,
+        }
+    }
+
+    /// Run one program per tenant of the NPU's partition (see `tenant.rs`),
+    /// taking one instruction from each in turn, and report each program's
+    /// own instructions and cycles.
+    pub fn run_tenants(programs: &[Program], npu: &mut Npu) -> Result<Vec<ProgramReport>, String> {
+        let mut reports: Vec<ProgramReport> = programs
+            .iter()
+            .map(|_| ProgramReport {
+                steps: Vec::new(),
+                cycles: 0,
+            })
+            .collect();
+        let longest = programs.iter().map(|p| p.insts.len()).max().unwrap_or(0);
+        for i in 0..longest {
+            for (tenant, program) in programs.iter().enumerate() {
+                let Some(inst) = program.insts.get(i) else {
+                    continue;
+                };
+                npu.set_tenant(tenant)?;
+                let before = npu.total_latency();
+                npu.exec(inst.funct, inst.xs1, inst.xs2, 0);
+                let cycles = npu.total_latency() - before;
+                reports[tenant].steps.push((inst.clone(), cycles));
+                reports[tenant].cycles += cycles;
+            }
+        }
+        Ok(reports)
+    }
+}
+
+/// Per-instruction cycle counts of one program run.
+#[derive(Clone, Debug)]
+pub struct ProgramReport {
+    pub steps: Vec<(ProgramInst, u64)>,
+    pub cycles: u64,
+}
+
+impl fmt::Display for ProgramReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:>6} {:<16} {:<10} {:>10}", "line", "label", "inst", "cycles")?;
+        for (inst, cycles) in &self.steps {
+            let label = inst.label.as_deref().unwrap_or("");
+            writeln!(f, "{:>6} {:<16} {:<10} {:>10}", inst.line, label, inst.mnemonic, cycles)?;
+        }
+        let mut per: BTreeMap<&str, (u64, u64)> = BTreeMap::new();
+        for (inst, cycles) in &self.steps {
+            let e = per.entry(&inst.mnemonic).or_default();
+            e.0 += 1;
+            e.1 += cycles;
+        }
+        writeln!(f, "{} instructions, {} cycles", self.steps.len(), self.cycles)?;
+        for (mnemonic, (count, cycles)) in per {
+            writeln!(f, "  {mnemonic:<10} x{count:<6} {cycles:>10} cycles")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::DRAM_BASE;
+
+    #[test]
+    fn program_encodes_like_hand_built_operands() {
+        let program = Program::parse(
+            "# copy one row\n\
+             load:\n\
+             \x20 mset bank=1\n\
+             \x20 mvin bank=1 addr=0x80001000 rows=1   # one row\n\
+             store:\n\
+             \x20 mvout rows=1 bank=1 addr=0x80002000\n\
+             \x20 inst 0 0 0\n",
+        )
+        .unwrap();
+        let encoded: Vec<_> = program.insts.iter().map(|i| (i.funct, i.xs1, i.xs2)).collect();
+        assert_eq!(
+            encoded,
+            [
+                (32, 1, (1 << 5) | (1 << 10)),
+                (33, 1 | (1 << 30), (DRAM_BASE + 0x1000) | (1 << 39)),
+                (16, 1 | (1 << 30), (DRAM_BASE + 0x2000) | (1 << 39)),
+                (0, 0, 0),
+            ]
+        );
+        assert_eq!(program.insts[2].label.as_deref(), Some("store"));
+        let intrinsic = Program::parse("bb.mvin bank=1, addr=0x80001000, rows=1").unwrap();
+        assert_eq!(intrinsic.insts[0].xs2, encoded[1].2);
+
+        let mut npu = Npu::new(1 << 20);
+        npu.write_dram(DRAM_BASE + 0x1000, &[7; 16]);
+        let report = program.run(&mut npu);
+        assert_eq!(report.cycles, 4);
+        assert_eq!(npu.read_dram(DRAM_BASE + 0x2000, 16), [7; 16]);
+
+        let err = Program::parse("mvin bank=1 rows=1").unwrap_err();
+        assert_eq!(err, "1: mvin needs addr=");
+    }
+
+    #[test]
+    fn strided_tile_moves_in_one_instruction() {
+        // An 8x64-byte matrix; stage the 4x32-byte tile at row 2, byte 16.
+        let matrix: Vec<u8> = (0..8 * 64).map(|i| i as u8).collect();
+        let program = Program::parse(
+            "mset bank=1\n\
+             mvin bank=1 addr=0x80001090 rows=4 cols=2 stride=4 bank_stride=3\n\
+             mvout bank=1 addr=0x80002000 rows=4 cols=2 stride=2 bank_stride=3\n",
+        )
+        .unwrap();
+        let mut npu = Npu::new(1 << 20);
+        npu.enable_golden_check();
+        npu.write_dram(DRAM_BASE + 0x1000, &matrix);
+        program.run(&mut npu);
+
+        let tile: Vec<u8> = (2..6).flat_map(|r| matrix[r * 64 + 16..r * 64 + 48].to_vec()).collect();
+        assert_eq!(npu.read_dram(DRAM_BASE + 0x2000, 4 * 32), tile);
+        // Bank rows are three lines apart, so the tile's second row starts at line 3.
+        assert_eq!(npu.bank(1).unwrap()[48..80], tile[32..64]);
+        assert_eq!(npu.golden_mismatches(), &[]);
+    }
+
+    #[test]
+    fn tenants_keep_to_their_banks() {
+        use crate::tenant::{Partition, TenantConfig};
+
+        let part = |name: &str, banks| Partition {
+            name: name.to_string(),
+            banks,
+        };
+        let mut npu = Npu::new(1 << 20);
+        let config = TenantConfig {
+            tenants: vec![part("a", [0, 16]), part("b", [16, 32])],
+        };
+        npu.set_tenants(Some(config)).unwrap();
+        npu.write_dram(DRAM_BASE + 0x1000, &[7; 64]);
+        let a = Program::parse(
+            "mset bank=1\n\
+             mvin bank=1 addr=0x80001000 rows=4\n\
+             mvout bank=1 addr=0x80002000 rows=4\n",
+        )
+        .unwrap();
+        // b's mvout names a's bank, so it is dropped and a's data stays put.
+        let b = Program::parse(
+            "mset bank=17\n\
+             mvin bank=17 addr=0x80001000 rows=1\n\
+             mvout bank=1 addr=0x80003000 rows=4\n",
+        )
+        .unwrap();
+        let reports = Program::run_tenants(&[a, b], &mut npu).unwrap();
+
+        assert!(npu.bank_map.resolve(17).is_some_and(|p| p >= 16));
+        assert_eq!(npu.read_dram(DRAM_BASE + 0x2000, 64), [7; 64]);
+        assert_eq!(npu.read_dram(DRAM_BASE + 0x3000, 64), [0; 64]);
+        let stats = npu.tenant_stats().unwrap();
+        let (a, b) = (stats[0].1, stats[1].1);
+        assert_eq!((stats[0].0, a.instructions, a.violations), ("a", 3, 0));
+        assert_eq!((stats[1].0, b.instructions, b.violations), ("b", 2, 1));
+        assert_eq!((a.dram_read_bytes, a.dram_write_bytes), (64, 64));
+        assert_eq!((b.dram_read_bytes, b.dram_write_bytes), (16, 0));
+        assert_eq!(a.cycles, reports[0].cycles);
+        assert_eq!(
+            npu.check_operands(16, 1 | (4 << 30), DRAM_BASE + 0x3000),
+            Err(crate::NpuError::CrossTenant {
+                vbank: 1,
+                tenant: "b".to_string()
+            })
+        );
+    }
+}
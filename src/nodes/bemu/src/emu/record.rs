@@ -0,0 +1,310 @@
+//===- record.rs - Instruction trace recording and replay ------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// A recording is every instruction an Npu executed, in a compact binary file
+// that replays on a fresh model without Spike or the workload ELF. The guest
+// writes DRAM behind the model's back, so each instruction that DMAs out of
+// DRAM is followed by the bytes it read; replay writes them back before
+// re-executing it.
+//
+//   header   b"BBTRACE1"
+//   'I'      funct u32, xs1 u64, xs2 u64, cycle u64, host_ns u64
+//   'D'      addr u64, len u32, bytes       (DRAM read by the preceding 'I')
+//   'R'      the model was reset
+//
+// All integers are little-endian. `cycle` is the model's cycle count at
+// issue and `host_ns` the wall-clock time since recording started. The
+// instruction is written and flushed before it executes, so a recording of
+// a run that aborted ends with the instruction that aborted it.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+use crate::isa::Isa;
+use crate::npu::Npu;
+
+const MAGIC: &[u8; 8] = b"BBTRACE1";
+const TAG_INST: u8 = b'I';
+const TAG_DRAM: u8 = b'D';
+const TAG_RESET: u8 = b'R';
+
+/// Writes the instructions of a running Npu to a recording.
+pub(crate) struct Recorder {
+    out: BufWriter<File>,
+    start: Instant,
+}
+
+impl Recorder {
+    pub(crate) fn create(path: &Path) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("failed to create recording {}: {e}", path.display()))?;
+        let mut out = BufWriter::new(file);
+        out.write_all(MAGIC).map_err(|e| e.to_string())?;
+        Ok(Self {
+            out,
+            start: Instant::now(),
+        })
+    }
+
+    pub(crate) fn inst(&mut self, funct: u32, xs1: u64, xs2: u64, cycle: u64) {
+        let host_ns = self.start.elapsed().as_nanos() as u64;
+        let mut rec = Vec::with_capacity(37);
+        rec.push(TAG_INST);
+        rec.extend_from_slice(&funct.to_le_bytes());
+        for v in [xs1, xs2, cycle, host_ns] {
+            rec.extend_from_slice(&v.to_le_bytes());
+        }
+        self.write(&rec);
+    }
+
+    pub(crate) fn dram(&mut self, addr: u64, bytes: &[u8]) {
+        let mut rec = Vec::with_capacity(13 + bytes.len());
+        rec.push(TAG_DRAM);
+        rec.extend_from_slice(&addr.to_le_bytes());
+        rec.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        rec.extend_from_slice(bytes);
+        self.write(&rec);
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.write(&[TAG_RESET]);
+    }
+
+    fn write(&mut self, rec: &[u8]) {
+        if let Err(e) = self.out.write_all(rec).and_then(|_| self.out.flush()) {
+            panic!("failed to write recording: {e}");
+        }
+    }
+}
+
+/// One recorded instruction and the DRAM it read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordedInst {
+    pub funct: u32,
+    pub xs1: u64,
+    pub xs2: u64,
+    /// Model cycle count when the instruction issued.
+    pub cycle: u64,
+    /// Wall-clock nanoseconds since recording started.
+    pub host_ns: u64,
+    pub dram: Vec<(u64, Vec<u8>)>,
+    /// The model was reset before this instruction.
+    pub after_reset: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Recording {
+    pub insts: Vec<RecordedInst>,
+}
+
+impl Recording {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("failed to read recording {}: {e}", path.display()))?;
+        Self::parse(&bytes).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let rest = bytes.strip_prefix(MAGIC).ok_or("not a BEMU recording")?;
+        let mut r = Reader { bytes: rest, pos: 0 };
+        let mut insts: Vec<RecordedInst> = Vec::new();
+        let mut after_reset = false;
+        while !r.at_end() {
+            match r.take(1)?[0] {
+                TAG_INST => insts.push(RecordedInst {
+                    funct: r.u32()?,
+                    xs1: r.u64()?,
+                    xs2: r.u64()?,
+                    cycle: r.u64()?,
+                    host_ns: r.u64()?,
+                    dram: Vec::new(),
+                    after_reset: std::mem::take(&mut after_reset),
+                }),
+                TAG_RESET => after_reset = true,
+                TAG_DRAM => {
+                    let addr = r.u64()?;
+                    let len = r.u32()? as usize;
+                    let data = r.take(len)?.to_vec();
+                    let inst = insts.last_mut().ok_or("DRAM record before any instruction")?;
+                    inst.dram.push((addr, data));
+                }
+                t => {
+                    return Err(format!(
+                        "unknown record tag 0x{t:02x} at byte {}",
+                        MAGIC.len() + r.pos - 1
+                    ))
+                }
+            }
+        }
+        Ok(Self { insts })
+    }
+
+    /// The recorded instructions as program text, one per line with the
+    /// cycle it issued at, for reading or for `Program::parse`.
+    pub fn disassemble(&self, isa: &Isa) -> String {
+        let mut text = String::new();
+        for inst in &self.insts {
+            if inst.after_reset {
+                text += "# reset\n";
+            }
+            let line = isa.disassemble(inst.funct, inst.xs1, inst.xs2);
+            text += &format!("{line:<60} # cycle {}\n", inst.cycle);
+        }
+        text
+    }
+
+    /// Re-execute every instruction on `npu`, restoring the DRAM each one
+    /// read first, and compare issue cycles with the recording.
+    pub fn replay(&self, npu: &mut Npu) -> ReplayReport {
+        let mut cycles = 0;
+        let mut first_divergence = None;
+        let mut diverged = 0;
+        // Cycle counts are compared relative to the first instruction after
+        // the start or the latest reset.
+        let (mut base, mut recorded_base) = (npu.total_latency(), self.insts.first().map_or(0, |i| i.cycle));
+        for (i, inst) in self.insts.iter().enumerate() {
+            if inst.after_reset {
+                cycles += npu.total_latency() - base;
+                npu.reset();
+                (base, recorded_base) = (npu.total_latency(), inst.cycle);
+            }
+            let (replayed, recorded) = (npu.total_latency() - base, inst.cycle.wrapping_sub(recorded_base));
+            if replayed != recorded {
+                diverged += 1;
+                first_divergence.get_or_insert((i, recorded, replayed));
+            }
+            for (addr, data) in &inst.dram {
+                npu.write_dram(*addr, data);
+            }
+            npu.exec(inst.funct, inst.xs1, inst.xs2, 0);
+        }
+        ReplayReport {
+            instructions: self.insts.len(),
+            cycles: cycles + npu.total_latency() - base,
+            recorded_ns: self.insts.last().map_or(0, |i| i.host_ns),
+            diverged,
+            first_divergence,
+        }
+    }
+}
+
+/// Outcome of replaying a recording.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplayReport {
+    pub instructions: usize,
+    pub cycles: u64,
+    /// Wall-clock time the recorded run took to reach its last instruction.
+    pub recorded_ns: u64,
+    /// Instructions that issued at a different cycle than recorded.
+    pub diverged: usize,
+    /// (index, recorded cycle, replayed cycle) of the first of them, both
+    /// relative to the first instruction since the last reset.
+    pub first_divergence: Option<(usize, u64, u64)>,
+}
+
+impl fmt::Display for ReplayReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} instructions, {} cycles (recorded run took {:.3} s to issue them)",
+            self.instructions,
+            self.cycles,
+            self.recorded_ns as f64 / 1e9
+        )?;
+        match self.first_divergence {
+            None => writeln!(f, "issue cycles match the recording"),
+            Some((i, recorded, replayed)) => writeln!(
+                f,
+                "{} instructions issued at a different cycle; first is #{i}: recorded {recorded}, replayed {replayed}",
+                self.diverged
+            ),
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn at_end(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let s = self
+            .bytes
+            .get(self.pos..self.pos + n)
+            .ok_or_else(|| format!("recording truncated at byte {}", MAGIC.len() + self.pos))?;
+        self.pos += n;
+        Ok(s)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().expect("4 bytes")))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().expect("8 bytes")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::DRAM_BASE;
+
+    #[test]
+    fn replay_reproduces_the_recorded_run() {
+        let path = std::env::temp_dir().join(format!("bemu-record-{}.bin", std::process::id()));
+        let mut npu = Npu::new(1 << 20);
+        npu.record_trace(&path).unwrap();
+        npu.exec(32, 1, (1 << 5) | (1 << 10), 0);
+        // Written by the guest behind the model's back.
+        npu.memory_mut()[0x100..0x140].fill(0x3c);
+        npu.exec(33, 1 | (4 << 30), (DRAM_BASE + 0x100) | (1 << 39), 0);
+        npu.exec(16, 1 | (4 << 30), (DRAM_BASE + 0x200) | (1 << 39), 0);
+        npu.stop_recording();
+
+        let rec = Recording::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(rec.insts.len(), 3);
+        assert_eq!(rec.insts[1].dram.len(), 4, "one entry per DMA row");
+
+        let mut fresh = Npu::new(1 << 20);
+        let report = rec.replay(&mut fresh);
+        assert_eq!((report.cycles, report.first_divergence), (npu.total_latency(), None));
+        assert_eq!(fresh.read_dram(DRAM_BASE + 0x200, 64), [0x3c; 64]);
+        assert_eq!(fresh.snapshot().banks, npu.snapshot().banks);
+
+        let text = rec.disassemble(&Isa::base());
+        assert!(
+            text.starts_with("mset bank=1 rows=0 cols=1 alloc=1 width=0 acc=0 format=0"),
+            "{text}"
+        );
+        let program = crate::Program::parse(&text).unwrap();
+        let words: Vec<_> = program.insts.iter().map(|i| (i.funct, i.xs1, i.xs2)).collect();
+        let recorded: Vec<_> = rec.insts.iter().map(|i| (i.funct, i.xs1, i.xs2)).collect();
+        assert_eq!(words, recorded);
+
+        let err = Recording::parse(b"BBTRACE1I\x01\x00").unwrap_err();
+        assert_eq!(err, "recording truncated at byte 9");
+    }
+}
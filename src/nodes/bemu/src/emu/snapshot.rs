@@ -0,0 +1,219 @@
+//===- snapshot.rs - Accelerator state snapshots and diff ------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// A snapshot is the architectural state of an Npu written as JSON: counters,
+// bank configs, the vbank -> pbank map, the MMIO region table and MMIO SRAM,
+// every physical bank, and a hash per non-zero DRAM page (DRAM is too large
+// to store whole). `Snapshot::diff` walks two snapshots in that order and
+// lists every differing field; the first entry is where two runs diverged.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use bebop_bank_hash::bank_hash;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::bank::DRAM_BASE;
+use crate::npu::Npu;
+
+const SNAPSHOT_VERSION: u32 = 2;
+const DRAM_PAGE: usize = 4096;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub cycle: u64,
+    pub instructions: u64,
+    pub init_seed: Option<u64>,
+    /// Per vbank: (allocated, cols, element bits).
+    pub bank_cfgs: Vec<(bool, u64, u32)>,
+    /// Per pbank: the (vbank, group) bound to it.
+    pub bank_map: Vec<Option<(u32, u32)>>,
+    /// Per main bank: (mmio_addr, size_rows) of a valid region.
+    pub mmio_regions: Vec<Option<(u16, u8)>>,
+    /// MMIO SRAM, hex.
+    pub mmio: String,
+    /// Physical banks, hex.
+    pub banks: Vec<String>,
+    /// DRAM page address -> hash, for pages that are not all zero.
+    pub dram_pages: BTreeMap<u64, u64>,
+}
+
+impl Snapshot {
+    pub fn capture(npu: &Npu) -> Self {
+        let dram_pages = npu
+            .memory
+            .chunks(DRAM_PAGE)
+            .enumerate()
+            .filter(|(_, page)| page.iter().any(|&b| b != 0))
+            .map(|(i, page)| (DRAM_BASE + (i * DRAM_PAGE) as u64, bank_hash(page)))
+            .collect();
+        Self {
+            version: SNAPSHOT_VERSION,
+            cycle: npu.total_lat,
+            instructions: npu.npu_instruction_id,
+            init_seed: npu.init_seed(),
+            bank_cfgs: npu
+                .bank_cfgs
+                .iter()
+                .map(|c| (c.allocated, c.cols, c.width.bits()))
+                .collect(),
+            bank_map: npu.bmt(),
+            mmio_regions: npu
+                .mmio_region_table
+                .iter()
+                .map(|r| r.valid.then_some((r.mmio_addr, r.size_rows)))
+                .collect(),
+            mmio: to_hex(npu.mmio_banks.as_flattened()),
+            banks: npu.banks.iter().map(|b| to_hex(b)).collect(),
+            dram_pages,
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, json).map_err(|e| format!("failed to write snapshot {}: {e}", path.display()))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json =
+            std::fs::read_to_string(path).map_err(|e| format!("failed to read snapshot {}: {e}", path.display()))?;
+        let snapshot: Self =
+            serde_json::from_str(&json).map_err(|e| format!("invalid snapshot {}: {e}", path.display()))?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(format!(
+                "snapshot {} has version {}, expected {SNAPSHOT_VERSION}",
+                path.display(),
+                snapshot.version
+            ));
+        }
+        Ok(snapshot)
+    }
+
+    /// Every field that differs from `other`, in snapshot order.
+    pub fn diff(&self, other: &Snapshot) -> Vec<FieldDiff> {
+        diff(self, other)
+    }
+}
+
+/// One differing field, e.g. `bank[3][0x40]: 0x00 != 0x7f`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldDiff {
+    pub field: String,
+    pub left: String,
+    pub right: String,
+}
+
+impl fmt::Display for FieldDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} != {}", self.field, self.left, self.right)
+    }
+}
+
+/// Every differing field of `a` and `b`, in snapshot order.
+fn diff(a: &Snapshot, b: &Snapshot) -> Vec<FieldDiff> {
+    let mut out = Vec::new();
+    let mut push = |field: String, left: String, right: String| out.push(FieldDiff { field, left, right });
+
+    if a.cycle != b.cycle {
+        push("cycle".into(), a.cycle.to_string(), b.cycle.to_string());
+    }
+    if a.instructions != b.instructions {
+        push(
+            "instructions".into(),
+            a.instructions.to_string(),
+            b.instructions.to_string(),
+        );
+    }
+    if a.init_seed != b.init_seed {
+        push(
+            "init_seed".into(),
+            format!("{:?}", a.init_seed),
+            format!("{:?}", b.init_seed),
+        );
+    }
+    diff_list("bank_cfg", &a.bank_cfgs, &b.bank_cfgs, &mut push);
+    diff_list("bank_map", &a.bank_map, &b.bank_map, &mut push);
+    diff_list("mmio_region", &a.mmio_regions, &b.mmio_regions, &mut push);
+    if let Some((off, l, r)) = first_byte_diff(&a.mmio, &b.mmio) {
+        push(format!("mmio[0x{off:x}]"), l, r);
+    }
+    for (i, (l, r)) in a.banks.iter().zip(&b.banks).enumerate() {
+        if let Some((off, l, r)) = first_byte_diff(l, r) {
+            push(format!("bank[{i}][0x{off:x}]"), l, r);
+        }
+    }
+    if a.banks.len() != b.banks.len() {
+        push("banks.len".into(), a.banks.len().to_string(), b.banks.len().to_string());
+    }
+    let pages: std::collections::BTreeSet<u64> = a.dram_pages.keys().chain(b.dram_pages.keys()).copied().collect();
+    for page in pages {
+        let (l, r) = (a.dram_pages.get(&page), b.dram_pages.get(&page));
+        if l != r {
+            let show = |h: Option<&u64>| h.map_or("zero".to_string(), |h| format!("hash 0x{h:016x}"));
+            push(format!("dram[0x{page:x}]"), show(l), show(r));
+        }
+    }
+    out
+}
+
+fn diff_list<T: PartialEq + fmt::Debug>(name: &str, a: &[T], b: &[T], push: &mut impl FnMut(String, String, String)) {
+    for i in 0..a.len().max(b.len()) {
+        let (l, r) = (a.get(i), b.get(i));
+        if l != r {
+            push(format!("{name}[{i}]"), format!("{l:?}"), format!("{r:?}"));
+        }
+    }
+}
+
+/// Offset and values of the first differing byte of two hex strings.
+fn first_byte_diff(a: &str, b: &str) -> Option<(usize, String, String)> {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let byte = |s: &[u8], i: usize| {
+        s.get(2 * i..2 * i + 2)
+            .map_or("none".to_string(), |h| format!("0x{}", String::from_utf8_lossy(h)))
+    };
+    (0..a.len().max(b.len()).div_ceil(2))
+        .find(|&i| a.get(2 * i..2 * i + 2) != b.get(2 * i..2 * i + 2))
+        .map(|i| (i, byte(a, i), byte(b, i)))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_reports_first_divergence_in_state_order() {
+        let mut npu = Npu::new(1 << 16);
+        npu.exec(32, 1, (1 << 5) | (1 << 10), 0);
+        let a = Snapshot::capture(&npu);
+        assert!(diff(&a, &a).is_empty());
+
+        npu.bank_mut(1).unwrap()[0x40] = 0x7f;
+        npu.write_dram(DRAM_BASE + 0x2000, &[1]);
+        let b = Snapshot::capture(&npu);
+        let d = diff(&a, &b);
+        let fields: Vec<_> = d.iter().map(|f| f.field.as_str()).collect();
+        assert_eq!(fields, ["bank[0][0x40]", "dram[0x80002000]"]);
+        assert_eq!(d[0].to_string(), "bank[0][0x40]: 0x00 != 0x7f");
+    }
+}
@@ -0,0 +1,186 @@
+//===- tenant.rs - Bank-partitioned multi-tenant mode ----------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// Lets several logical tenants, such as two host processes, share one
+// accelerator, as a basis for virtualization studies. Each tenant owns a
+// static range of banks, [first, end): the vbank ids it may name and the
+// physical banks behind them. Every instruction carries the tag of the
+// tenant that issued it (`Npu::set_tenant`), and:
+//
+//   mset       allocates physical banks from the tenant's range only
+//   any bank   operand outside the range, by vbank id or by a physical bank
+//              of any of its groups, faults: the instruction is dropped and
+//              counted as a violation instead of running
+//
+// A bmt remap may still point a vbank outside its tenant's range; the next
+// instruction that uses it faults. DRAM is not partitioned, since the host
+// MMU already isolates processes there.
+//
+// Cycles and DRAM bytes are charged to the tenant whose instruction spent
+// them, so bandwidth per tenant is bytes over that tenant's cycles.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Range;
+
+/// One tenant's share of the banks.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Partition {
+    pub name: String,
+    /// [first, end) of both the vbank ids and the physical banks it owns.
+    pub banks: [usize; 2],
+}
+
+impl Partition {
+    pub fn banks(&self) -> Range<usize> {
+        self.banks[0]..self.banks[1]
+    }
+}
+
+/// A `--tenants` file:
+///
+/// ```toml
+/// [[tenant]]
+/// name = "a"
+/// banks = [0, 8]
+///
+/// [[tenant]]
+/// name = "b"
+/// banks = [8, 16]
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TenantConfig {
+    #[serde(rename = "tenant")]
+    pub tenants: Vec<Partition>,
+}
+
+impl TenantConfig {
+    pub fn validate(&self, num_banks: usize) -> Result<(), String> {
+        if self.tenants.is_empty() {
+            return Err("tenants: at least one [[tenant]] is needed".to_string());
+        }
+        for (i, t) in self.tenants.iter().enumerate() {
+            let r = t.banks();
+            if r.is_empty() || r.end > num_banks {
+                return Err(format!(
+                    "tenants: {} banks [{}, {}) must be a non-empty range within the {num_banks} banks",
+                    t.name, r.start, r.end
+                ));
+            }
+            if let Some(o) = self.tenants[..i]
+                .iter()
+                .find(|o| o.banks().start < r.end && r.start < o.banks().end)
+            {
+                return Err(format!("tenants: {} and {} share banks", o.name, t.name));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What one tenant's instructions did since the last reset.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct TenantStats {
+    pub instructions: u64,
+    pub cycles: u64,
+    pub dram_read_bytes: u64,
+    pub dram_write_bytes: u64,
+    /// Instructions dropped for touching another tenant's banks.
+    pub violations: u64,
+}
+
+impl TenantStats {
+    /// DRAM bytes moved per cycle of this tenant's instructions.
+    pub fn bandwidth(&self) -> f64 {
+        match self.cycles {
+            0 => 0.0,
+            c => (self.dram_read_bytes + self.dram_write_bytes) as f64 / c as f64,
+        }
+    }
+}
+
+impl fmt::Display for TenantStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} instructions, {} cycles, {} B read, {} B written ({:.2} B/cycle), {} violations",
+            self.instructions,
+            self.cycles,
+            self.dram_read_bytes,
+            self.dram_write_bytes,
+            self.bandwidth(),
+            self.violations
+        )
+    }
+}
+
+/// The partition, the tenant issuing now and what each tenant has spent.
+#[derive(Clone, Debug)]
+pub(crate) struct Tenants {
+    pub(crate) config: TenantConfig,
+    pub(crate) current: usize,
+    pub(crate) stats: Vec<TenantStats>,
+}
+
+impl Tenants {
+    pub(crate) fn new(config: TenantConfig) -> Self {
+        let stats = vec![TenantStats::default(); config.tenants.len()];
+        Self {
+            config,
+            current: 0,
+            stats,
+        }
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.stats.fill(TenantStats::default());
+    }
+
+    /// Banks of the tenant issuing now.
+    pub(crate) fn banks(&self) -> Range<usize> {
+        self.config.tenants[self.current].banks()
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.config.tenants[self.current].name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_rejects_overlapping_or_missing_banks() {
+        let part = |name: &str, banks| Partition {
+            name: name.to_string(),
+            banks,
+        };
+        let config = TenantConfig {
+            tenants: vec![part("a", [0, 8]), part("b", [8, 16])],
+        };
+        assert_eq!(config.validate(16), Ok(()));
+        assert!(config.validate(12).is_err());
+        let mut overlap = config.clone();
+        overlap.tenants[1].banks = [4, 12];
+        assert_eq!(overlap.validate(16), Err("tenants: a and b share banks".to_string()));
+        assert!(TenantConfig::default().validate(16).is_err());
+    }
+}
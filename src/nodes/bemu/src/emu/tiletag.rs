@@ -0,0 +1,243 @@
+//===- tiletag.rs - Debug type and layout tags on bank lines ---------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// Debug-only metadata that catches compute reading bank data as a type or
+// layout other than the one it was written as, which otherwise shows up
+// only as garbage results. Off unless `Npu::enable_tile_tags` is called.
+//
+// Every physical bank line carries an optional tag: the element width and
+// number format it was written as, and how many lines one row of its tile
+// spans. Writers set it:
+//
+//   mvin      the bank's type at mset, lines per row of the 2-D tile
+//   mvin_bfp  bfp8, one line per row
+//   matmul    C's type, the lines of one row of C
+//   conv      the output bank's type, one line per row
+//   mcopy     the destination's type, one line per row
+//
+// Any other bank write, and mset, clear the tags of the lines it may have
+// touched, and untagged lines are not checked. Before matmul or conv runs,
+// the lines it reads are compared with the type it takes from each bank's
+// mset and, for matmul, the row length its k_lines and n_lines give. Rows
+// of one line are a flat array and match any row length. A mismatch is a
+// `tile-tag-mismatch` warning. Tags belong to physical lines, so data left
+// behind by a bmt remap is caught as well.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use std::fmt;
+
+use crate::bank::{bank_operands, Access, ElemWidth};
+use crate::inst::decode::{rs1_b0, rs1_b1, rs1_b2, rs1_iter, rs1_tile, tile_lines};
+use crate::inst::f32_mset::Mset;
+use crate::inst::f33_mvin::Mvin;
+use crate::inst::f37_mcopy::Mcopy;
+use crate::inst::f41_mvin_bfp::MvinBfp;
+use crate::inst::f48_matmul::Matmul;
+use crate::inst::f49_conv::{Conv, ConvShape};
+use crate::inst::instruction::Instruction;
+use crate::npu::Npu;
+use crate::numfmt::NumFormat;
+
+/// What a bank line was written as.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TileTag {
+    pub width: ElemWidth,
+    pub format: NumFormat,
+    /// Lines one row of the tile spans.
+    pub row_lines: usize,
+}
+
+impl TileTag {
+    /// Whether data tagged `self` can be read as `want`.
+    fn matches(&self, want: &TileTag) -> bool {
+        self.width == want.width
+            && self.format == want.format
+            && (self.row_lines == 1 || want.row_lines == 1 || self.row_lines == want.row_lines)
+    }
+}
+
+impl fmt::Display for TileTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.format {
+            NumFormat::Int => write!(f, "i{}", self.width.bits())?,
+            format => write!(f, "{}", format.name())?,
+        }
+        write!(f, " in {}-line rows", self.row_lines)
+    }
+}
+
+/// Tags of every line of every physical bank.
+pub(crate) struct TileTags {
+    lines: Vec<Vec<Option<TileTag>>>,
+}
+
+/// (pbank, type) of a mapped single-group `vbank`.
+fn bank_tag(npu: &Npu, vbank: u64, row_lines: usize) -> Option<(usize, TileTag)> {
+    let cfg = *npu.bank_cfgs.get(vbank as usize)?;
+    if !cfg.allocated || cfg.cols > 1 {
+        return None;
+    }
+    let tag = TileTag {
+        width: cfg.width,
+        format: cfg.format,
+        row_lines,
+    };
+    Some((npu.bank_map.resolve(vbank as u32)?, tag))
+}
+
+impl TileTags {
+    pub(crate) fn new(banks: usize, depth: usize) -> Self {
+        Self {
+            lines: vec![vec![None; depth]; banks],
+        }
+    }
+
+    /// Untag every line of `pbank`.
+    pub(crate) fn clear(&mut self, pbank: usize) {
+        self.lines[pbank].fill(None);
+    }
+
+    /// Give `to` the tags of `from`.
+    pub(crate) fn copy(&mut self, from: usize, to: usize) {
+        let tags = self.lines[from].clone();
+        self.lines[to] = tags;
+    }
+
+    fn set(&mut self, pbank: usize, lines: impl IntoIterator<Item = usize>, tag: TileTag) {
+        let bank = &mut self.lines[pbank];
+        for line in lines {
+            if let Some(slot) = bank.get_mut(line) {
+                *slot = Some(tag);
+            }
+        }
+    }
+
+    /// One diagnostic per operand whose lines were written as something
+    /// other than what the instruction about to run reads them as.
+    pub(crate) fn check(&self, npu: &Npu, funct: u32, xs1: u64, xs2: u64) -> Vec<String> {
+        let mut reads = Vec::new();
+        match funct {
+            Matmul::FUNCT => {
+                let (Some((_, a)), Some((_, b))) = (bank_tag(npu, rs1_b0(xs1), 1), bank_tag(npu, rs1_b1(xs1), 1))
+                else {
+                    return Vec::new();
+                };
+                let (k, n) = Matmul::dims(xs2, a.width, b.width);
+                let (k_lines, n_lines) = (k * a.width.bytes() / 16, n * b.width.bytes() / 16);
+                let m = rs1_iter(xs1) as usize;
+                reads.push(("A", rs1_b0(xs1), k_lines, m * k_lines));
+                reads.push(("B", rs1_b1(xs1), n_lines, k * n_lines));
+            }
+            Conv::FUNCT => {
+                let s = ConvShape::decode(xs2);
+                let elems = [
+                    ("input", rs1_b0(xs1), s.in_ch * s.in_h * s.in_w),
+                    ("weight", rs1_b1(xs1), s.out_ch * s.in_ch * s.kernel_h * s.kernel_w),
+                ];
+                for (what, vbank, len) in elems {
+                    if let Some((_, tag)) = bank_tag(npu, vbank, 1) {
+                        reads.push((what, vbank, 1, (len * tag.width.bytes()).div_ceil(16)));
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        let name = if funct == Matmul::FUNCT {
+            Matmul::NAME
+        } else {
+            Conv::NAME
+        };
+        let mut found = Vec::new();
+        for (what, vbank, row_lines, lines) in reads {
+            let Some((p, want)) = bank_tag(npu, vbank, row_lines) else {
+                continue;
+            };
+            let bank = &self.lines[p];
+            let mut bad =
+                (0..lines.min(bank.len())).filter_map(|l| Some((l, bank[l]?)).filter(|(_, t)| !t.matches(&want)));
+            if let Some((line, got)) = bad.next() {
+                found.push(format!(
+                    "{name} {what} bank{vbank} line {line}: written as {got}, read as {want} ({} lines differ)",
+                    1 + bad.count()
+                ));
+            }
+        }
+        found
+    }
+
+    /// Tag the lines the instruction that just ran wrote.
+    pub(crate) fn after(&mut self, npu: &Npu, funct: u32, xs1: u64, xs2: u64) {
+        for (vbank, access) in bank_operands(funct, xs1) {
+            if access == Access::Write {
+                self.clear_vbank(npu, vbank);
+            }
+        }
+        match funct {
+            Mset::FUNCT => self.clear_vbank(npu, rs1_b0(xs1)),
+            Mvin::FUNCT => {
+                let (len, _) = rs1_tile(xs1);
+                let Some((p, tag)) = bank_tag(npu, rs1_b0(xs1), len as usize) else {
+                    return;
+                };
+                self.set(p, tile_lines(xs1, xs2).map(|(_, off)| off / 16), tag);
+            }
+            MvinBfp::FUNCT => {
+                if let Some((p, tag)) = bank_tag(npu, rs1_b0(xs1), 1) {
+                    self.set(p, 0..rs1_iter(xs1) as usize, tag);
+                }
+            }
+            Matmul::FUNCT => {
+                let (Some((_, a)), Some((_, b)), Some((p, c))) = (
+                    bank_tag(npu, rs1_b0(xs1), 1),
+                    bank_tag(npu, rs1_b1(xs1), 1),
+                    bank_tag(npu, rs1_b2(xs1), 1),
+                ) else {
+                    return;
+                };
+                let (_, n) = Matmul::dims(xs2, a.width, b.width);
+                let row_lines = (n * c.width.bytes()).div_ceil(16);
+                let tag = TileTag { row_lines, ..c };
+                self.set(p, 0..rs1_iter(xs1) as usize * row_lines, tag);
+            }
+            Conv::FUNCT => {
+                let s = ConvShape::decode(xs2);
+                let (out_h, out_w) = s.out_dims();
+                if let Some((p, tag)) = bank_tag(npu, rs1_b2(xs1), 1) {
+                    self.set(p, 0..(s.out_ch * out_h * out_w * tag.width.bytes()).div_ceil(16), tag);
+                }
+            }
+            Mcopy::FUNCT => {
+                let (Some((_, src)), Some((p, tag))) = (bank_tag(npu, rs1_b0(xs1), 1), bank_tag(npu, rs1_b1(xs1), 1))
+                else {
+                    return;
+                };
+                let elems = rs1_iter(xs1) as usize * 16 / src.width.bytes();
+                self.set(p, 0..(elems * tag.width.bytes()).div_ceil(16), tag);
+            }
+            _ => {}
+        }
+    }
+
+    fn clear_vbank(&mut self, npu: &Npu, vbank: u64) {
+        for (p, slot) in npu.bank_map.slots.iter().enumerate() {
+            if slot.valid && slot.vbank_id == vbank as u32 {
+                self.clear(p);
+            }
+        }
+    }
+}
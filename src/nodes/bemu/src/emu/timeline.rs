@@ -0,0 +1,112 @@
+//===- timeline.rs - NpuSim per-instruction timeline -----------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// With `NpuSimConfig::timeline` set, NpuSim records when each instruction
+// was queued, issued, left its execution unit and retired. `write_timeline`
+// exports that as Chrome trace-event JSON, which chrome://tracing and
+// Perfetto draw as a Gantt chart. One microsecond of trace time is one cycle:
+//
+//   queue   waiting in the issue queue, queued -> issued
+//   unit N  executing on unit N, issued -> completed
+//   rob     finished but waiting for older instructions, completed -> retired
+//           (with a reorder buffer only)
+//
+// Queue and reorder buffer waits overlap each other, so they are async
+// events; execution is a complete event on the unit's track. Micro-ops a
+// loop_ws decodes into count as queued when the loop_ws was.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::path::Path;
+
+use crate::inst::decode::INSTRUCTIONS;
+
+/// One instruction's trip through NpuSim, in cycles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct TimelineEntry {
+    /// Issue order.
+    pub seq: u64,
+    pub funct: u32,
+    pub xs1: u64,
+    pub xs2: u64,
+    pub unit: usize,
+    pub queued: u64,
+    pub issued: u64,
+    /// Left its execution unit.
+    pub completed: u64,
+    /// Took effect.
+    pub retired: u64,
+}
+
+impl TimelineEntry {
+    fn name(&self) -> String {
+        INSTRUCTIONS
+            .iter()
+            .find(|(f, _)| *f == self.funct)
+            .map_or_else(|| format!("funct{}", self.funct), |(_, name)| name.to_string())
+    }
+}
+
+/// Chrome trace-event JSON for `entries` of a simulator with `units`
+/// execution units.
+pub(crate) fn chrome_trace(entries: &[TimelineEntry], units: usize) -> Value {
+    let thread = |tid: usize, name: String| json!({ "ph": "M", "name": "thread_name", "pid": 0, "tid": tid, "args": { "name": name } });
+    let rob_tid = units + 1;
+    let mut events = vec![
+        json!({ "ph": "M", "name": "process_name", "pid": 0, "args": { "name": "npusim" } }),
+        thread(0, "queue".to_string()),
+        thread(rob_tid, "rob".to_string()),
+    ];
+    events.extend((0..units).map(|u| thread(u + 1, format!("unit {u}"))));
+
+    let wait = |e: &TimelineEntry, cat: &str, tid: usize, from: u64, to: u64| {
+        let event = |ph: &str, ts: u64| json!({ "ph": ph, "cat": cat, "name": e.name(), "id": e.seq, "pid": 0, "tid": tid, "ts": ts });
+        [event("b", from), event("e", to)]
+    };
+    for e in entries {
+        if e.issued > e.queued {
+            events.extend(wait(e, "queue", 0, e.queued, e.issued));
+        }
+        events.push(json!({
+            "ph": "X",
+            "cat": "exec",
+            "name": e.name(),
+            "pid": 0,
+            "tid": e.unit + 1,
+            "ts": e.issued,
+            "dur": e.completed - e.issued,
+            "args": {
+                "seq": e.seq,
+                "funct": e.funct,
+                "xs1": format!("{:#x}", e.xs1),
+                "xs2": format!("{:#x}", e.xs2),
+                "queued": e.queued,
+                "retired": e.retired,
+            },
+        }));
+        if e.retired > e.completed {
+            events.extend(wait(e, "rob", rob_tid, e.completed, e.retired));
+        }
+    }
+    json!({ "traceEvents": events })
+}
+
+pub(crate) fn write_chrome_trace(path: &Path, entries: &[TimelineEntry], units: usize) -> Result<(), String> {
+    let json = serde_json::to_string(&chrome_trace(entries, units)).map_err(|e| e.to_string())?;
+    std::fs::write(path, json).map_err(|e| format!("failed to write timeline {}: {e}", path.display()))
+}
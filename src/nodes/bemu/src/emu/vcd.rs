@@ -0,0 +1,158 @@
+//===- vcd.rs - NpuSim waveform dump ---------------------------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// Writes NpuSim's pipeline state as a Value Change Dump that GTKWave and
+// similar viewers open. One VCD time unit is one cycle, with one sample per
+// cycle:
+//
+//   npusim.queued         instructions waiting in the issue queue
+//   npusim.issued         instructions issued this cycle
+//   npusim.retired        instructions retired this cycle
+//   npusim.hazard_stall   a unit was free but nothing could issue
+//   npusim.rob            reorder buffer entries in use (with a reorder
+//                         buffer only)
+//   npusim.unitN.busy     unit N holds an instruction
+//   npusim.unitN.funct    funct of that instruction (0 when idle)
+//   npusim.unitN.remaining  cycles until it retires, counting this one
+//
+// Unit signals show what the unit held during the cycle, so an instruction
+// that retires in the cycle it issued still shows up. Only changes are
+// written, so idle stretches cost nothing.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// One sample per signal, in the order the signals were declared.
+pub(crate) struct VcdWriter {
+    out: BufWriter<File>,
+    widths: Vec<u32>,
+    last: Vec<Option<u64>>,
+}
+
+/// Short VCD identifier for signal `i`, from the printable ASCII range.
+fn ident(mut i: usize) -> String {
+    let mut id = String::new();
+    loop {
+        id.push((b'!' + (i % 94) as u8) as char);
+        i /= 94;
+        if i == 0 {
+            return id;
+        }
+        i -= 1;
+    }
+}
+
+impl VcdWriter {
+    /// Create `path` and declare the signals of a simulator with `units`
+    /// execution units and, if `rob`, a reorder buffer.
+    pub(crate) fn create(path: &Path, units: usize, rob: bool) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("failed to create waveform {}: {e}", path.display()))?;
+        let mut out = BufWriter::new(file);
+        let mut widths = Vec::new();
+        let mut header = String::from("$version bebop npusim $end\n$timescale 1ns $end\n$scope module npusim $end\n");
+        let mut var = |header: &mut String, width: u32, name: &str| {
+            header.push_str(&format!("$var wire {width} {} {name} $end\n", ident(widths.len())));
+            widths.push(width);
+        };
+        var(&mut header, 16, "queued");
+        var(&mut header, 8, "issued");
+        var(&mut header, 8, "retired");
+        var(&mut header, 1, "hazard_stall");
+        if rob {
+            var(&mut header, 16, "rob");
+        }
+        for u in 0..units {
+            header.push_str(&format!("$scope module unit{u} $end\n"));
+            var(&mut header, 1, "busy");
+            var(&mut header, 7, "funct");
+            var(&mut header, 32, "remaining");
+            header.push_str("$upscope $end\n");
+        }
+        header.push_str("$upscope $end\n$enddefinitions $end\n");
+        out.write_all(header.as_bytes()).map_err(|e| e.to_string())?;
+        let last = vec![None; widths.len()];
+        Ok(Self { out, widths, last })
+    }
+
+    /// Record the values of `cycle`, in declaration order.
+    pub(crate) fn sample(&mut self, cycle: u64, values: &[u64]) {
+        let mut changes = String::new();
+        for (i, (&v, last)) in values.iter().zip(&mut self.last).enumerate() {
+            if *last == Some(v) {
+                continue;
+            }
+            *last = Some(v);
+            if self.widths[i] == 1 {
+                changes.push_str(&format!("{}{}\n", v & 1, ident(i)));
+            } else {
+                changes.push_str(&format!("b{v:b} {}\n", ident(i)));
+            }
+        }
+        if changes.is_empty() {
+            return;
+        }
+        if let Err(e) = write!(self.out, "#{cycle}\n{changes}") {
+            panic!("failed to write waveform: {e}");
+        }
+    }
+}
+
+impl Drop for VcdWriter {
+    fn drop(&mut self) {
+        let _ = self.out.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bank::DRAM_BASE;
+    use crate::{NpuSim, NpuSimConfig};
+
+    #[test]
+    fn waveform_shows_units_and_queue() {
+        assert_eq!(
+            (super::ident(0), super::ident(93), super::ident(94)),
+            ("!".into(), "~".into(), "!!".into())
+        );
+
+        let path = std::env::temp_dir().join(format!("bemu-npusim-{}.vcd", std::process::id()));
+        let mut sim = NpuSim::new(NpuSimConfig {
+            mem_size: 1 << 20,
+            units: 2,
+            ..NpuSimConfig::default()
+        });
+        sim.write_vcd(&path).unwrap();
+        sim.push_inst(32, 1, (1 << 5) | (1 << 10)).unwrap();
+        sim.push_inst(33, 1 | (4 << 30), DRAM_BASE | (1 << 39)).unwrap();
+        sim.run_until_idle();
+        sim.stop_vcd();
+
+        let vcd = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(vcd.contains("$scope module unit1 $end"));
+        // Cycle 0: mset issues to unit 0 and retires; mvin waits for it.
+        assert!(
+            vcd.contains("#0\nb1 !\nb1 \"\nb1 #\n1$\n1%\nb100000 &\nb1 '\n"),
+            "{vcd}"
+        );
+        // Cycle 1: round-robin issues mvin (funct 33) to unit 1 for 4 cycles.
+        assert!(vcd.contains("1(\nb100001 )\nb100 *\n#2\n"), "{vcd}");
+        assert!(vcd.ends_with("#4\nb1 #\nb1 *\n"), "{vcd}");
+    }
+}
@@ -0,0 +1,151 @@
+//===- warnings.rs - Dubious-but-legal behaviour collector -----------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// Instructions report behaviour the hardware accepts but that usually means a
+// workload bug (a clamped field, an empty DMA, reallocating a live bank).
+// Occurrences are aggregated per kind with the cycle of the first and last
+// hit, and printed as one table when the run ends. Under `BEBOP_STRICT` the
+// first occurrence aborts instead.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use bebop_bank_hash::strict_mode;
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WarningKind {
+    /// mset alloc on a vbank that is already allocated; its data is dropped.
+    MsetRealloc,
+    /// mset free on a vbank that was never allocated.
+    MsetFreeUnallocated,
+    /// mmio_set region runs past the end of MMIO SRAM.
+    MmioRegionOverflow,
+    /// mvin_mmio col above 16 bytes, clamped to a full row.
+    MvinMmioColClamped,
+    /// mvin_mmio with row = 0 or col = 0: nothing is loaded.
+    MvinMmioEmpty,
+    /// mvin or dma_sg sums in an accumulator bank clamped to i32.
+    MvinSaturated,
+    /// mcopy into a narrower bank clamped out-of-range elements.
+    McopySaturated,
+    /// matmul results clamped to the C bank's element width.
+    MatmulSaturated,
+    /// conv outputs clamped to the output bank's element width.
+    ConvSaturated,
+    /// layernorm or softmax outputs clamped to the output bank's width.
+    NormSaturated,
+    /// matmul or conv read bank lines written as another type or layout
+    /// (only with tile tags on).
+    TileTagMismatch,
+    /// An instruction named a bank outside its tenant's partition and was
+    /// dropped (only in multi-tenant mode).
+    CrossTenant,
+}
+
+impl WarningKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WarningKind::MsetRealloc => "mset-realloc",
+            WarningKind::MsetFreeUnallocated => "mset-free-unallocated",
+            WarningKind::MmioRegionOverflow => "mmio-region-overflow",
+            WarningKind::MvinMmioColClamped => "mvin-mmio-col-clamped",
+            WarningKind::MvinMmioEmpty => "mvin-mmio-empty",
+            WarningKind::MvinSaturated => "mvin-saturated",
+            WarningKind::McopySaturated => "mcopy-saturated",
+            WarningKind::MatmulSaturated => "matmul-saturated",
+            WarningKind::ConvSaturated => "conv-saturated",
+            WarningKind::NormSaturated => "norm-saturated",
+            WarningKind::TileTagMismatch => "tile-tag-mismatch",
+            WarningKind::CrossTenant => "cross-tenant",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct WarningStat {
+    pub count: u64,
+    pub first_cycle: u64,
+    pub last_cycle: u64,
+    /// Detail of the first occurrence.
+    pub first: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Warnings {
+    cycle: u64,
+    stats: BTreeMap<WarningKind, WarningStat>,
+}
+
+impl Warnings {
+    pub(crate) fn set_cycle(&mut self, cycle: u64) {
+        self.cycle = cycle;
+    }
+
+    /// `detail` is only formatted for the first occurrence of each kind.
+    pub(crate) fn record(&mut self, kind: WarningKind, detail: impl FnOnce() -> String) {
+        if strict_mode() {
+            panic!("strict: {}: {}", kind.as_str(), detail());
+        }
+        let cycle = self.cycle;
+        self.stats
+            .entry(kind)
+            .and_modify(|s| {
+                s.count += 1;
+                s.last_cycle = cycle;
+            })
+            .or_insert_with(|| WarningStat {
+                count: 1,
+                first_cycle: cycle,
+                last_cycle: cycle,
+                first: detail(),
+            });
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.stats.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stats.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (WarningKind, &WarningStat)> {
+        self.stats.iter().map(|(k, s)| (*k, s))
+    }
+}
+
+impl fmt::Display for Warnings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<24} {:>8} {:>12} {:>12}  first occurrence",
+            "warning", "count", "first-cycle", "last-cycle"
+        )?;
+        for (kind, s) in self.iter() {
+            writeln!(
+                f,
+                "{:<24} {:>8} {:>12} {:>12}  {}",
+                kind.as_str(),
+                s.count,
+                s.first_cycle,
+                s.last_cycle,
+                s.first
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+//===- watch.rs - DRAM and bank watchpoints --------------------------------===//
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===-----------------------------------------------------------------===//-----===//
+//
+// A watchpoint names a DRAM range or a vbank range. After every instruction
+// the Npu records a hit for each watchpoint the instruction touched:
+//
+//   dram  any DMA row the instruction read or wrote overlaps the range,
+//         including dma_sg descriptors
+//   bank  the instruction changed a byte of the range
+//
+// Bank reads are not tracked per offset, so a bank watchpoint only sees
+// writes. Hits name the instruction by its `Npu::instruction_count`, so they
+// line up with trace and recording output. `program --watch` logs them and
+// the debugger stops on them.
+//
+// On the command line a watchpoint is written `dram:ADDR:LEN` or
+// `bank:V:OFF:LEN`, numbers decimal or 0x hex.
+//
+//===-----------------------------------------------------------------===//-----===//
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::inst::decode::INSTRUCTIONS;
+use crate::program::parse_value;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Watchpoint {
+    Dram { addr: u64, len: u64 },
+    Bank { vbank: u32, offset: usize, len: usize },
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Watchpoint::Dram { addr, len } => write!(f, "dram [0x{addr:x}, 0x{:x})", addr + len),
+            Watchpoint::Bank { vbank, offset, len } => {
+                write!(f, "bank {vbank} [0x{offset:x}, 0x{:x})", offset + len)
+            }
+        }
+    }
+}
+
+impl FromStr for Watchpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let num = |w: &str| parse_value(w).ok_or_else(|| format!("watchpoint {s}: not a number: {w}"));
+        match parts.as_slice() {
+            ["dram", addr, len] => Ok(Watchpoint::Dram {
+                addr: num(addr)?,
+                len: num(len)?,
+            }),
+            ["bank", vbank, offset, len] => Ok(Watchpoint::Bank {
+                vbank: num(vbank)? as u32,
+                offset: num(offset)? as usize,
+                len: num(len)? as usize,
+            }),
+            _ => Err(format!("watchpoint {s}: expected dram:ADDR:LEN or bank:V:OFF:LEN")),
+        }
+    }
+}
+
+/// One instruction touching one watchpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WatchHit {
+    pub id: usize,
+    pub watchpoint: Watchpoint,
+    /// `Npu::instruction_count` once the instruction had issued.
+    pub inst: u64,
+    pub funct: u32,
+    /// Cycle count after the instruction.
+    pub cycle: u64,
+    pub write: bool,
+    /// First DRAM address or bank offset touched inside the range.
+    pub at: u64,
+}
+
+impl fmt::Display for WatchHit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = INSTRUCTIONS
+            .iter()
+            .find(|(funct, _)| *funct == self.funct)
+            .map_or_else(|| format!("funct{}", self.funct), |(_, name)| name.to_string());
+        write!(
+            f,
+            "watchpoint {} ({}): inst #{} {name} {} 0x{:x} at cycle {}",
+            self.id,
+            self.watchpoint,
+            self.inst,
+            if self.write { "wrote" } else { "read" },
+            self.at,
+            self.cycle
+        )
+    }
+}
+
+/// First byte of `[addr, addr + len)` inside `[start, start + size)`.
+pub(crate) fn overlap(addr: u64, len: u64, start: u64, size: u64) -> Option<u64> {
+    let (lo, hi) = (addr.max(start), (addr + len).min(start + size));
+    (lo < hi).then_some(lo)
+}
+
+/// Watchpoints of an Npu and the hits not yet taken.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Watches {
+    pub(crate) points: Vec<(usize, Watchpoint)>,
+    /// Ids are never reused.
+    next_id: usize,
+    pub(crate) hits: Vec<WatchHit>,
+}
+
+impl Watches {
+    pub(crate) fn add(&mut self, w: Watchpoint) -> usize {
+        self.next_id += 1;
+        self.points.push((self.next_id, w));
+        self.next_id
+    }
+
+    pub(crate) fn remove(&mut self, id: usize) -> bool {
+        let before = self.points.len();
+        self.points.retain(|(i, _)| *i != id);
+        self.points.len() != before
+    }
+}
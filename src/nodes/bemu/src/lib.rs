@@ -1,18 +1,152 @@
+#[cfg(feature = "host")]
 mod sim;
 
+#[cfg(feature = "host")]
 #[path = "../native/ffi.rs"]
 mod ffi;
 
+#[cfg(feature = "host")]
 #[path = "../native/spike.rs"]
 mod spike;
 
 #[path = "emu/bank/mod.rs"]
 mod bank;
 
+#[path = "emu/bankdump.rs"]
+mod bankdump;
+
+#[path = "emu/cache.rs"]
+mod cache;
+
+#[path = "emu/checkpoint.rs"]
+mod checkpoint;
+
+#[path = "emu/compare.rs"]
+mod compare;
+
 #[path = "emu/inst/mod.rs"]
 mod inst;
 
+#[path = "emu/coverage.rs"]
+mod coverage;
+
+#[path = "emu/cycle_debugger.rs"]
+mod cycle_debugger;
+
+#[path = "emu/debugger.rs"]
+mod debugger;
+
+#[path = "emu/dma.rs"]
+mod dma;
+
+#[path = "emu/dram.rs"]
+mod dram;
+
+#[path = "emu/energy.rs"]
+mod energy;
+
+#[path = "emu/error.rs"]
+mod error;
+
+#[path = "emu/extension.rs"]
+mod extension;
+
+#[path = "emu/fault.rs"]
+mod fault;
+
+#[path = "emu/fill.rs"]
+mod fill;
+
+#[path = "emu/golden.rs"]
+mod golden;
+
+#[path = "emu/isa.rs"]
+mod isa;
+
+#[path = "emu/layout.rs"]
+mod layout;
+
+#[path = "emu/lower.rs"]
+mod lower;
+
+#[path = "emu/manifest.rs"]
+mod manifest;
+
+#[path = "emu/npu.rs"]
+mod npu;
+
+#[path = "emu/npusim.rs"]
+mod npusim;
+
+#[path = "emu/numfmt.rs"]
+mod numfmt;
+
+#[path = "emu/occupancy.rs"]
+mod occupancy;
+
+#[path = "emu/perf.rs"]
+mod perf;
+
+#[path = "emu/program.rs"]
+mod program;
+
+#[path = "emu/record.rs"]
+mod record;
+
+#[path = "emu/snapshot.rs"]
+mod snapshot;
+
+#[path = "emu/tenant.rs"]
+mod tenant;
+
+#[path = "emu/tiletag.rs"]
+mod tiletag;
+
+#[path = "emu/timeline.rs"]
+mod timeline;
+
+#[path = "emu/vcd.rs"]
+mod vcd;
+
+#[path = "emu/warnings.rs"]
+mod warnings;
+
+#[path = "emu/watch.rs"]
+mod watch;
+
 mod trace;
 
+pub use bank::{ArrayGeometry, BankGeometry, BankPorts, ElemWidth, PortKind, DRAM_BASE};
+pub use bankdump::{BankDump, DumpedBank, ElementDiff};
+pub use cache::{Cache, CacheConfig, CacheStats};
+pub use checkpoint::{Checkpoint, Checkpoints, DmaState, InFlightState, SimState};
+pub use compare::{compare, Comparison, Divergence};
+pub use coverage::Coverage;
+pub use cycle_debugger::CycleDebugger;
+pub use debugger::{Breakpoint, Debugger};
+pub use dma::{DmaStats, Fp32Paths, MisalignedDma};
+pub use dram::DramTiming;
+pub use energy::{EnergyBreakdown, EnergyTable};
+pub use error::NpuError;
+pub use extension::Extension;
+pub use fault::{FaultConfig, FaultEvent, FaultOutcome, FaultStats, FaultTarget, Protection};
+pub use golden::{GoldenMismatch, Provenance};
+pub use isa::{Isa, IsaField, IsaInst, Reg};
+pub use layout::Layout;
+pub use lower::{lower, Lowered, LoweredTensor};
+pub use manifest::{BankManifest, DmaManifest, DramManifest, InstManifest, Manifest, MmioManifest};
+pub use npu::{Npu, DEFAULT_MEM_SIZE};
+pub use npusim::{Arbitration, NpuSim, NpuSimConfig, NpuSimStats, SimEvent, StallCause};
+pub use numfmt::{Bf16, Bf16Rz, Bfp8, FormatError, Fp32, NumFormat, NumberFormat, Posit8};
+pub use perf::{InstPerf, PerfCounters, PerfReport};
+pub use program::{Program, ProgramInst, ProgramReport};
+pub use record::{RecordedInst, Recording, ReplayReport};
+#[cfg(feature = "host")]
 pub use sim::BemuInstance;
+pub use snapshot::{FieldDiff, Snapshot};
+pub use tenant::{Partition, TenantConfig, TenantStats};
+pub use tiletag::TileTag;
+pub use timeline::TimelineEntry;
 pub use trace::TraceConfig;
+pub use warnings::{WarningKind, WarningStat, Warnings};
+pub use watch::{WatchHit, Watchpoint};
@@ -22,7 +22,17 @@
 use snafu::{OptionExt, ResultExt, Whatever};
 use std::path::Path;
 
-use crate::{spike::SpikeInstance, trace::TraceConfig};
+use crate::bank::{ArrayGeometry, BankGeometry, BankPorts};
+use crate::cache::CacheConfig;
+use crate::coverage::Coverage;
+use crate::dma::{DmaStats, MisalignedDma};
+use crate::dram::DramTiming;
+use crate::energy::EnergyTable;
+use crate::npu::Npu;
+use crate::{
+    manifest::Manifest, perf::PerfReport, snapshot::Snapshot, spike::SpikeInstance, trace::TraceConfig,
+    warnings::Warnings,
+};
 
 pub struct BemuInstance {
     spike: SpikeInstance,
@@ -61,4 +71,94 @@ impl BemuInstance {
     pub fn total_latency(&self) -> u64 {
         self.spike.total_latency()
     }
+
+    pub fn warnings(&self) -> &Warnings {
+        self.spike.warnings()
+    }
+
+    /// Fill DRAM and banks with seeded garbage. Call before loading the ELF.
+    pub fn randomize(&mut self, seed: u64) {
+        self.spike.randomize(seed);
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        self.spike.snapshot()
+    }
+
+    /// Save the accelerator state; Spike's hart state is not included.
+    pub fn save_checkpoint(&self, path: &Path) -> Result<(), Whatever> {
+        self.spike.save_checkpoint(path).with_whatever_context(|e| e.clone())
+    }
+
+    /// Restore accelerator state saved by `save_checkpoint`.
+    pub fn load_checkpoint(&mut self, path: &Path) -> Result<(), Whatever> {
+        self.spike.load_checkpoint(path).with_whatever_context(|e| e.clone())
+    }
+
+    pub fn manifest(&self) -> Manifest {
+        self.spike.manifest()
+    }
+
+    /// The accelerator model Spike issues RoCC instructions to.
+    pub fn npu(&self) -> &Npu {
+        self.spike.npu()
+    }
+
+    /// Configure the accelerator; call before loading the ELF.
+    pub fn npu_mut(&mut self) -> &mut Npu {
+        self.spike.npu_mut()
+    }
+
+    pub fn set_misaligned_dma(&mut self, policy: MisalignedDma) {
+        self.spike.set_misaligned_dma(policy);
+    }
+
+    pub fn dma_stats(&self) -> DmaStats {
+        self.spike.dma_stats()
+    }
+
+    pub fn perf_report(&self) -> PerfReport {
+        self.spike.perf_report()
+    }
+
+    pub fn set_dram_timing(&mut self, timing: Option<DramTiming>) -> Result<(), Whatever> {
+        self.spike.set_dram_timing(timing).with_whatever_context(|e| e.clone())
+    }
+
+    pub fn set_bank_geometry(&mut self, geometry: BankGeometry) -> Result<(), Whatever> {
+        self.spike
+            .set_bank_geometry(geometry)
+            .with_whatever_context(|e| e.clone())
+    }
+
+    pub fn set_array_geometry(&mut self, array: ArrayGeometry) -> Result<(), Whatever> {
+        self.spike
+            .set_array_geometry(array)
+            .with_whatever_context(|e| e.clone())
+    }
+
+    pub fn set_bank_ports(&mut self, ports: BankPorts) -> Result<(), Whatever> {
+        self.spike.set_bank_ports(ports).with_whatever_context(|e| e.clone())
+    }
+
+    pub fn set_energy_table(&mut self, table: Option<EnergyTable>) -> Result<(), Whatever> {
+        self.spike.set_energy_table(table).with_whatever_context(|e| e.clone())
+    }
+
+    pub fn set_cache(&mut self, config: Option<CacheConfig>) -> Result<(), Whatever> {
+        self.spike.set_cache(config).with_whatever_context(|e| e.clone())
+    }
+
+    pub fn enable_coverage(&mut self) {
+        self.spike.enable_coverage();
+    }
+
+    /// Record every accelerator instruction to `path` for `bebop replay`.
+    pub fn record_trace(&mut self, path: &Path) -> Result<(), Whatever> {
+        self.spike.record_trace(path).with_whatever_context(|e| e.clone())
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.spike.coverage()
+    }
 }
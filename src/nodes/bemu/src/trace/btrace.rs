@@ -1,6 +1,7 @@
 use super::trace::with_current_trace;
+use crate::inst::decode::FUNCTS;
 use bebop_bank_hash::{
-    submit_runtime_bank_hash_packet, BankHashEventClass, BankHashPacket, BankHashPacketId, BankHashSource,
+    strict_mode, submit_runtime_bank_hash_packet, BankHashEventClass, BankHashPacket, BankHashPacketId, BankHashSource,
     BankHashTime, CanonicalBankHashPacket,
 };
 use std::collections::BTreeMap;
@@ -63,6 +64,7 @@ pub(super) fn init(log_dir: &Path, enabled: bool) -> io::Result<BtraceState> {
     if !enabled {
         return Ok(BtraceState::default());
     }
+    check_registered_functs();
 
     let raw_file = OpenOptions::new()
         .create(true)
@@ -96,17 +98,42 @@ fn write_btrace_log(state: &mut BtraceState, line: &str) {
     }
 }
 
-fn classify_bemu_bank_hash(funct7: u32) -> BankHashEventClass {
+fn bank_hash_event_class(funct7: u32) -> BankHashEventClass {
     match funct7 {
-        0 | 1 | 3 | 4 => BankHashEventClass::ControlOnly,
-        2 | 32 | 34 | 80..=86 | 96..=104 => BankHashEventClass::ConfigOnly,
-        16 | 35 | 87 | 105 => BankHashEventClass::MemoryOnly,
-        33 | 48 | 49 | 50 | 51 | 52 | 53 | 55 | 64 | 65 | 66 | 67 => BankHashEventClass::BankDataWrite,
-        other => {
-            eprintln!("warning: unknown BEMU bank hash event class for funct7_{other}");
-            BankHashEventClass::Unknown
+        0 | 1 | 3 | 4 | 40 => BankHashEventClass::ControlOnly,
+        2 | 32 | 34 | 36 | 38 | 42 | 80..=86 | 96..=104 => BankHashEventClass::ConfigOnly,
+        16 | 17 | 35 | 87 | 105 => BankHashEventClass::MemoryOnly,
+        33 | 37 | 39 | 41 | 48 | 49 | 50 | 51 | 52 | 53 | 54 | 55 | 56 | 57 | 64 | 65 | 66 | 67 => {
+            BankHashEventClass::BankDataWrite
         }
+        _ => BankHashEventClass::Unknown,
+    }
+}
+
+fn classify_bemu_bank_hash(funct7: u32) -> BankHashEventClass {
+    let class = bank_hash_event_class(funct7);
+    if class == BankHashEventClass::Unknown {
+        eprintln!("warning: unknown BEMU bank hash event class for funct7_{funct7}");
+    }
+    class
+}
+
+/// Report registered instructions the classifier does not know before any
+/// of them runs, instead of once per event mid-trace.
+fn check_registered_functs() {
+    let unclassified: Vec<u32> = FUNCTS
+        .iter()
+        .copied()
+        .filter(|&f| bank_hash_event_class(f) == BankHashEventClass::Unknown)
+        .collect();
+    if unclassified.is_empty() {
+        return;
+    }
+    let msg = format!("registered funct7 {unclassified:?} have no bank hash event class; their events will be dropped");
+    if strict_mode() {
+        panic!("strict: {msg}");
     }
+    eprintln!("warning: {msg}");
 }
 
 pub fn bemu_bank_hash(instruction_id: u64, bank_id: u32, funct7: u32, op_type: &str, hash: u64, pc: u64) {
@@ -120,11 +147,19 @@ pub fn bemu_bank_hash(instruction_id: u64, bank_id: u32, funct7: u32, op_type: &
             BankHashTime::Cycle(trace.bemu_clk()),
         );
         let raw_line = trace.btrace.next_raw_line();
-        if let Ok(line) = packet.to_ndjson() {
-            write_bank_hash_trace(&mut trace.btrace, &line);
+        match packet.to_ndjson() {
+            Ok(line) => write_bank_hash_trace(&mut trace.btrace, &line),
+            Err(e) if strict_mode() => panic!("strict: failed to serialize bank hash packet {packet:?}: {e}"),
+            Err(_) => {}
         }
 
         let event_class = classify_bemu_bank_hash(funct7);
+        if event_class == BankHashEventClass::Unknown && strict_mode() {
+            panic!(
+                "strict: funct7_{funct7} has no bank hash event class \
+                 (instruction_id={instruction_id} bank={bank_id} pc=0x{pc:x} hash=0x{hash:016x})"
+            );
+        }
         let comparable_seq = trace.btrace.comparable_seq(instruction_id, event_class);
         let btrace_packet = CanonicalBankHashPacket::new(
             BankHashSource::Bemu,
@@ -140,8 +175,10 @@ pub fn bemu_bank_hash(instruction_id: u64, bank_id: u32, funct7: u32, op_type: &
             format!("bemu_bank_hash.ndjson:{raw_line}"),
             raw_line,
         );
-        if let Ok(line) = btrace_packet.to_ndjson() {
-            write_btrace_log(&mut trace.btrace, &line);
+        match btrace_packet.to_ndjson() {
+            Ok(line) => write_btrace_log(&mut trace.btrace, &line),
+            Err(e) if strict_mode() => panic!("strict: failed to serialize btrace packet {btrace_packet:?}: {e}"),
+            Err(_) => {}
         }
         submit_runtime_bank_hash_packet(&btrace_packet);
     });
@@ -171,6 +208,15 @@ mod tests {
             assert_ne!(classify_bemu_bank_hash(funct7), BankHashEventClass::Unknown);
         }
 
+        // Whatever the active chip registers must be classified too.
+        for &funct7 in FUNCTS {
+            assert_ne!(
+                bank_hash_event_class(funct7),
+                BankHashEventClass::Unknown,
+                "funct7_{funct7} is registered but has no bank hash event class"
+            );
+        }
+
         assert_eq!(classify_bemu_bank_hash(0), BankHashEventClass::ControlOnly);
         assert_eq!(classify_bemu_bank_hash(32), BankHashEventClass::ConfigOnly);
         assert_eq!(classify_bemu_bank_hash(16), BankHashEventClass::MemoryOnly);
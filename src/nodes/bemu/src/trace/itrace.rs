@@ -1,4 +1,5 @@
 use super::trace::with_current_trace;
+use crate::isa::base_isa;
 use std::fs::{File, OpenOptions};
 use std::io;
 use std::path::Path;
@@ -28,12 +29,13 @@ pub(super) fn init(log_dir: &Path, enabled: bool) -> io::Result<Option<File>> {
 pub fn itrace(event: ITraceEvent) {
     with_current_trace(|trace| {
         let json = format!(
-            r#"{{"type":"itrace","clk":{},"event":"complete","funct":"0x{:02x}","pc":"0x{:016x}","rs1":"0x{:016x}","rs2":"0x{:016x}"}}"#,
+            r#"{{"type":"itrace","clk":{},"event":"complete","funct":"0x{:02x}","pc":"0x{:016x}","rs1":"0x{:016x}","rs2":"0x{:016x}","asm":"{}"}}"#,
             trace.bemu_clk(),
             event.funct,
             event.pc,
             event.rs1,
-            event.rs2
+            event.rs2,
+            base_isa().disassemble(event.funct, event.rs1, event.rs2)
         );
 
         trace.write_itrace(&json);
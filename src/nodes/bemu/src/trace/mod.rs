@@ -1,9 +1,11 @@
 mod btrace;
 mod itrace;
 mod mtrace;
+mod rtrace;
 mod trace;
 
 pub use btrace::bemu_bank_hash;
 pub use itrace::{itrace, ITraceEvent};
 pub use mtrace::{mtrace, MTraceEvent};
+pub use rtrace::rtrace;
 pub use trace::{with_trace_ptr, TraceConfig, TraceState};
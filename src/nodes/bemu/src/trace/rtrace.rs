@@ -0,0 +1,28 @@
+/// Environment variable that turns on the `[RTRACE]` lines instructions print
+/// to stderr. Set it empty, to `1` or to `all` for every instruction, or to a
+/// comma-separated list of instruction names (`mvin,matmul`) for just those.
+/// `bebop --rtrace` sets it.
+const RTRACE_ENV: &str = "BEMU_RTRACE";
+
+/// Whether instruction `name` should print its `[RTRACE]` lines.
+pub fn rtrace(name: &str) -> bool {
+    std::env::var(RTRACE_ENV).is_ok_and(|scopes| scope_enabled(&scopes, name))
+}
+
+fn scope_enabled(scopes: &str, name: &str) -> bool {
+    let scopes = scopes.trim();
+    matches!(scopes, "" | "1" | "all") || scopes.split(',').any(|s| s.trim() == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::scope_enabled;
+
+    #[test]
+    fn scopes_select_instructions() {
+        assert!(scope_enabled("1", "mvin"));
+        assert!(scope_enabled("", "conv"));
+        assert!(scope_enabled("mvin, matmul", "matmul"));
+        assert!(!scope_enabled("mvin,matmul", "mvout"));
+    }
+}
@@ -2,7 +2,7 @@ use crate::{BankHashEventClass, BankHashSource, CanonicalBankHashPacket};
 use serde::Serialize;
 #[cfg(test)]
 use serde_json::Value;
-use snafu::{ResultExt, Whatever};
+use snafu::{whatever, ResultExt, Whatever};
 use std::collections::{BTreeMap, BTreeSet};
 use std::fs::File;
 use std::io::{BufWriter, Write};
@@ -201,6 +201,8 @@ struct StreamingComparator {
     writer: BufWriter<File>,
     output_path: PathBuf,
     summary: BankHashCompareSummary,
+    /// Fail on packets that are otherwise warned about and skipped.
+    strict: bool,
 }
 
 impl StreamingComparator {
@@ -212,6 +214,7 @@ impl StreamingComparator {
             writer,
             output_path,
             summary: BankHashCompareSummary::default(),
+            strict: crate::strict_mode(),
         }
     }
 
@@ -221,6 +224,9 @@ impl StreamingComparator {
         }
 
         let Some(comparable_seq) = packet.comparable_seq else {
+            if self.strict {
+                whatever!("strict: bank_data_write packet without comparable_seq: {packet:?}");
+            }
             eprintln!("warning: skipping online bank hash packet: bank_data_write missing comparable_seq");
             return Ok(());
         };
@@ -231,6 +237,9 @@ impl StreamingComparator {
             version: packet.version,
         };
         if self.emitted.contains(&key) {
+            if self.strict {
+                whatever!("strict: duplicate online btrace key {key:?} after compare: {packet:?}");
+            }
             eprintln!("warning: duplicate online btrace key after compare; ignoring");
             return Ok(());
         }
@@ -431,4 +440,45 @@ mod tests {
         assert_eq!(values[1]["result"], "MISSING_BEMU");
         assert_eq!(values[1]["comparable_seq"], 2);
     }
+
+    #[test]
+    fn strict_mode_fails_on_packets_it_would_skip() {
+        let packet = |source, seq| {
+            CanonicalBankHashPacket::new(
+                source,
+                2,
+                seq,
+                0,
+                33,
+                "funct7_33",
+                BankHashEventClass::BankDataWrite,
+                7,
+                crate::BankHashTime::Cycle(2),
+                Some(2147486388),
+                "bank_hash.ndjson:2",
+                2,
+            )
+        };
+        for strict in [false, true] {
+            let output =
+                std::env::temp_dir().join(format!("bebop-bank-hash-strict-{}-{strict}.ndjson", std::process::id()));
+            let mut comparator = StreamingComparator::new(create_compare_writer(&output).unwrap(), output.clone());
+            comparator.strict = strict;
+
+            let unsequenced = comparator.ingest_packet(packet(BankHashSource::Bemu, None));
+            assert_eq!(unsequenced.is_err(), strict);
+
+            comparator.ingest_packet(packet(BankHashSource::Rtl, Some(1))).unwrap();
+            comparator.ingest_packet(packet(BankHashSource::Bemu, Some(1))).unwrap();
+            let duplicate = comparator.ingest_packet(packet(BankHashSource::Bemu, Some(1)));
+            match duplicate {
+                Err(e) => assert!(strict && e.to_string().starts_with("strict: duplicate online btrace key")),
+                Ok(()) => assert!(!strict),
+            }
+
+            let summary = comparator.finish().unwrap();
+            assert_eq!(summary.total(), 1);
+            std::fs::remove_file(&output).unwrap();
+        }
+    }
 }
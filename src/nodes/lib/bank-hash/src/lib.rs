@@ -1,11 +1,17 @@
+use bebop_lock_audit::AuditedMutex;
 use serde::{Deserialize, Serialize};
 use std::sync::mpsc::{self, Receiver, Sender};
-use std::sync::{Mutex, OnceLock};
 
 mod comparator;
 
 pub use comparator::{run_online_with_summary as run_online_compare_with_summary, BankHashCompareSummary};
 
+/// `BEBOP_STRICT` (set by `bebop --strict`) turns events that are normally
+/// warned about and dropped into hard errors.
+pub fn strict_mode() -> bool {
+    std::env::var_os("BEBOP_STRICT").is_some()
+}
+
 const FNV1A_64_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
 const FNV1A_64_PRIME: u64 = 0x0000_0100_0000_01b3;
 
@@ -184,10 +190,11 @@ impl CanonicalBankHashPacket {
     }
 }
 
-static RUNTIME_PACKET_SINK: OnceLock<Mutex<Option<Sender<CanonicalBankHashPacket>>>> = OnceLock::new();
+static RUNTIME_PACKET_SINK: AuditedMutex<Option<Sender<CanonicalBankHashPacket>>> =
+    AuditedMutex::new("bank-hash.runtime_packet_sink", None);
 
-fn get_runtime_packet_sink() -> &'static Mutex<Option<Sender<CanonicalBankHashPacket>>> {
-    RUNTIME_PACKET_SINK.get_or_init(|| Mutex::new(None))
+fn get_runtime_packet_sink() -> &'static AuditedMutex<Option<Sender<CanonicalBankHashPacket>>> {
+    &RUNTIME_PACKET_SINK
 }
 
 pub fn init_runtime_packet_channel() -> Receiver<CanonicalBankHashPacket> {
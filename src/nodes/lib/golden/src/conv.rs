@@ -0,0 +1,168 @@
+/// 2-D convolution over a CHW feature map with OIHW weights.
+///
+/// `transposed` selects the transposed (fractionally strided) convolution with
+/// IOHW weights; stride, padding and dilation keep their forward meaning, so a
+/// transposed conv with the same params maps the forward output shape back.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Conv2dParams {
+    pub in_ch: usize,
+    pub out_ch: usize,
+    pub in_h: usize,
+    pub in_w: usize,
+    pub kernel_h: usize,
+    pub kernel_w: usize,
+    pub stride: usize,
+    pub padding: usize,
+    pub dilation: usize,
+    pub transposed: bool,
+}
+
+impl Conv2dParams {
+    /// Plain convolution: stride 1, no padding, no dilation.
+    pub fn new(in_ch: usize, out_ch: usize, in_h: usize, in_w: usize, kernel_h: usize, kernel_w: usize) -> Self {
+        Self {
+            in_ch,
+            out_ch,
+            in_h,
+            in_w,
+            kernel_h,
+            kernel_w,
+            stride: 1,
+            padding: 0,
+            dilation: 1,
+            transposed: false,
+        }
+    }
+
+    /// Output (height, width).
+    pub fn out_dims(&self) -> (usize, usize) {
+        let span = |k: usize| self.dilation * (k - 1) + 1;
+        if self.transposed {
+            let out = |n: usize, k: usize| ((n - 1) * self.stride + span(k)).saturating_sub(2 * self.padding);
+            (out(self.in_h, self.kernel_h), out(self.in_w, self.kernel_w))
+        } else {
+            let out = |n: usize, k: usize| (n + 2 * self.padding).saturating_sub(span(k)) / self.stride + 1;
+            (out(self.in_h, self.kernel_h), out(self.in_w, self.kernel_w))
+        }
+    }
+
+    fn weight_len(&self) -> usize {
+        self.in_ch * self.out_ch * self.kernel_h * self.kernel_w
+    }
+}
+
+pub fn conv2d(input: &[f64], weight: &[f64], p: &Conv2dParams) -> Vec<f64> {
+    assert!(
+        p.stride > 0 && p.dilation > 0,
+        "conv2d: stride and dilation must be > 0"
+    );
+    assert_eq!(input.len(), p.in_ch * p.in_h * p.in_w, "conv2d: input shape");
+    assert_eq!(weight.len(), p.weight_len(), "conv2d: weight shape");
+    let (out_h, out_w) = p.out_dims();
+    let mut out = vec![0.0; p.out_ch * out_h * out_w];
+
+    // Both forms walk (input pixel, kernel tap) pairs; they differ only in
+    // which side the strided, dilated index lands on.
+    for oc in 0..p.out_ch {
+        for ic in 0..p.in_ch {
+            for kh in 0..p.kernel_h {
+                for kw in 0..p.kernel_w {
+                    let w = if p.transposed {
+                        weight[((ic * p.out_ch + oc) * p.kernel_h + kh) * p.kernel_w + kw]
+                    } else {
+                        weight[((oc * p.in_ch + ic) * p.kernel_h + kh) * p.kernel_w + kw]
+                    };
+                    if p.transposed {
+                        for ih in 0..p.in_h {
+                            let Some(oh) = (ih * p.stride + kh * p.dilation).checked_sub(p.padding) else {
+                                continue;
+                            };
+                            if oh >= out_h {
+                                continue;
+                            }
+                            for iw in 0..p.in_w {
+                                let Some(ow) = (iw * p.stride + kw * p.dilation).checked_sub(p.padding) else {
+                                    continue;
+                                };
+                                if ow < out_w {
+                                    out[(oc * out_h + oh) * out_w + ow] += w * input[(ic * p.in_h + ih) * p.in_w + iw];
+                                }
+                            }
+                        }
+                    } else {
+                        for oh in 0..out_h {
+                            let Some(ih) = (oh * p.stride + kh * p.dilation).checked_sub(p.padding) else {
+                                continue;
+                            };
+                            if ih >= p.in_h {
+                                continue;
+                            }
+                            for ow in 0..out_w {
+                                let Some(iw) = (ow * p.stride + kw * p.dilation).checked_sub(p.padding) else {
+                                    continue;
+                                };
+                                if iw < p.in_w {
+                                    out[(oc * out_h + oh) * out_w + ow] += w * input[(ic * p.in_h + ih) * p.in_w + iw];
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strided_padded_dilated_shapes_round_trip() {
+        let mut p = Conv2dParams::new(2, 3, 9, 7, 3, 3);
+        p.stride = 2;
+        p.padding = 1;
+        p.dilation = 2;
+        assert_eq!(p.out_dims(), (4, 3));
+
+        let t = Conv2dParams {
+            in_ch: 3,
+            out_ch: 2,
+            in_h: 4,
+            in_w: 3,
+            transposed: true,
+            ..p
+        };
+        // (4-1)*2 + 5 - 2 = 9, (3-1)*2 + 5 - 2 = 7
+        assert_eq!(t.out_dims(), (9, 7));
+    }
+
+    #[test]
+    fn transposed_conv_is_adjoint_of_forward() {
+        // <conv(x), y> == <x, conv_t(y)> for the same weights. Sizes are
+        // picked so the stride divides evenly and conv_t(y) has x's shape.
+        let mut p = Conv2dParams::new(2, 3, 7, 5, 3, 2);
+        p.stride = 2;
+        p.padding = 1;
+        p.dilation = 2;
+        let (oh, ow) = p.out_dims();
+        let x: Vec<f64> = (0..2 * 7 * 5).map(|i| (i % 7) as f64 - 3.0).collect();
+        let y: Vec<f64> = (0..3 * oh * ow).map(|i| (i % 5) as f64 - 2.0).collect();
+        // Forward OIHW weights are IOHW from the transposed side.
+        let w: Vec<f64> = (0..p.weight_len()).map(|i| (i % 3) as f64 - 1.0).collect();
+
+        let t = Conv2dParams {
+            in_ch: 3,
+            out_ch: 2,
+            in_h: oh,
+            in_w: ow,
+            transposed: true,
+            ..p
+        };
+        assert_eq!(t.out_dims(), (7, 5));
+
+        let dot = |a: &[f64], b: &[f64]| a.iter().zip(b).map(|(a, b)| a * b).sum::<f64>();
+        assert_eq!(dot(&conv2d(&x, &w, &p), &y), dot(&x, &conv2d(&y, &w, &t)));
+    }
+}
@@ -0,0 +1,27 @@
+//! f64 reference implementations of the kernels the accelerator computes.
+//!
+//! Checkers and tests compare simulator output against these instead of
+//! carrying their own loops. Shapes are passed the way the instructions encode
+//! them (rows/cols/iter counts, stride, padding, dilation), all tensors are
+//! dense row-major, and feature maps are CHW with one image per call.
+
+mod conv;
+mod matmul;
+mod norm;
+mod pool;
+
+pub use conv::{conv2d, Conv2dParams};
+pub use matmul::{matmul, matmul_acc};
+pub use norm::{batch_norm, layer_norm};
+pub use pool::{pool2d, Pool2dParams, PoolKind};
+
+/// Widen integer bank data (i8 inputs, i32 accumulators) for comparison.
+pub fn widen<T: Copy + Into<f64>>(xs: &[T]) -> Vec<f64> {
+    xs.iter().map(|&x| x.into()).collect()
+}
+
+/// Largest absolute difference between two equally sized tensors.
+pub fn max_abs_diff(a: &[f64], b: &[f64]) -> f64 {
+    assert_eq!(a.len(), b.len(), "max_abs_diff: length mismatch");
+    a.iter().zip(b).map(|(x, y)| (x - y).abs()).fold(0.0, f64::max)
+}
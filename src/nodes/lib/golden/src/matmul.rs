@@ -0,0 +1,37 @@
+/// `a` (m x k) times `b` (k x n).
+pub fn matmul(a: &[f64], b: &[f64], m: usize, k: usize, n: usize) -> Vec<f64> {
+    let mut c = vec![0.0; m * n];
+    matmul_acc(&mut c, a, b, m, k, n);
+    c
+}
+
+/// `c += a * b`, the accumulate form used when a product is split along k.
+pub fn matmul_acc(c: &mut [f64], a: &[f64], b: &[f64], m: usize, k: usize, n: usize) {
+    assert_eq!(a.len(), m * k, "matmul: a is not {m}x{k}");
+    assert_eq!(b.len(), k * n, "matmul: b is not {k}x{n}");
+    assert_eq!(c.len(), m * n, "matmul: c is not {m}x{n}");
+    for i in 0..m {
+        for p in 0..k {
+            let aip = a[i * k + p];
+            for j in 0..n {
+                c[i * n + j] += aip * b[p * n + j];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_k_accumulates_to_full_product() {
+        let a = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0]; // 2x3
+        let b = [7.0, 8.0, 9.0, 10.0, 11.0, 12.0]; // 3x2
+        assert_eq!(matmul(&a, &b, 2, 3, 2), [58.0, 64.0, 139.0, 154.0]);
+
+        let mut c = matmul(&[1.0, 2.0, 4.0, 5.0], &b[..4], 2, 2, 2);
+        matmul_acc(&mut c, &[3.0, 6.0], &b[4..], 2, 1, 2);
+        assert_eq!(c, [58.0, 64.0, 139.0, 154.0]);
+    }
+}
@@ -0,0 +1,47 @@
+/// Normalize each of `rows` rows of length `cols` to zero mean and unit
+/// variance (biased), then scale by `gamma` and shift by `beta` per column.
+pub fn layer_norm(x: &[f64], rows: usize, cols: usize, gamma: &[f64], beta: &[f64], eps: f64) -> Vec<f64> {
+    assert_eq!(x.len(), rows * cols, "layer_norm: x is not {rows}x{cols}");
+    assert_eq!(gamma.len(), cols, "layer_norm: gamma length");
+    assert_eq!(beta.len(), cols, "layer_norm: beta length");
+    let mut out = Vec::with_capacity(x.len());
+    for row in x.chunks_exact(cols) {
+        let mean = row.iter().sum::<f64>() / cols as f64;
+        let var = row.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / cols as f64;
+        let inv = 1.0 / (var + eps).sqrt();
+        out.extend(
+            row.iter()
+                .enumerate()
+                .map(|(j, v)| (v - mean) * inv * gamma[j] + beta[j]),
+        );
+    }
+    out
+}
+
+/// Inference-time batch norm over a CHW feature map with per-channel running
+/// statistics: `(x - mean) / sqrt(var + eps) * gamma + beta`.
+pub fn batch_norm(
+    x: &[f64],
+    channels: usize,
+    mean: &[f64],
+    var: &[f64],
+    gamma: &[f64],
+    beta: &[f64],
+    eps: f64,
+) -> Vec<f64> {
+    assert!(
+        channels > 0 && x.len().is_multiple_of(channels),
+        "batch_norm: x is not {channels} channels"
+    );
+    for (name, v) in [("mean", mean), ("var", var), ("gamma", gamma), ("beta", beta)] {
+        assert_eq!(v.len(), channels, "batch_norm: {name} length");
+    }
+    let plane = x.len() / channels;
+    x.iter()
+        .enumerate()
+        .map(|(i, v)| {
+            let c = i / plane;
+            (v - mean[c]) / (var[c] + eps).sqrt() * gamma[c] + beta[c]
+        })
+        .collect()
+}
@@ -0,0 +1,63 @@
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PoolKind {
+    Max,
+    /// Divides by the full window size; padded taps count as zero.
+    Avg,
+}
+
+/// Square-window pooling over a CHW feature map, channel by channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pool2dParams {
+    pub kind: PoolKind,
+    pub channels: usize,
+    pub in_h: usize,
+    pub in_w: usize,
+    pub kernel: usize,
+    pub stride: usize,
+    pub padding: usize,
+}
+
+impl Pool2dParams {
+    /// Output (height, width).
+    pub fn out_dims(&self) -> (usize, usize) {
+        let out = |n: usize| (n + 2 * self.padding).saturating_sub(self.kernel) / self.stride + 1;
+        (out(self.in_h), out(self.in_w))
+    }
+}
+
+pub fn pool2d(input: &[f64], p: &Pool2dParams) -> Vec<f64> {
+    assert!(p.kernel > 0 && p.stride > 0, "pool2d: kernel and stride must be > 0");
+    assert_eq!(input.len(), p.channels * p.in_h * p.in_w, "pool2d: input shape");
+    let (out_h, out_w) = p.out_dims();
+    let mut out = Vec::with_capacity(p.channels * out_h * out_w);
+
+    for c in 0..p.channels {
+        for oh in 0..out_h {
+            for ow in 0..out_w {
+                let mut acc = match p.kind {
+                    PoolKind::Max => f64::NEG_INFINITY,
+                    PoolKind::Avg => 0.0,
+                };
+                for kh in 0..p.kernel {
+                    for kw in 0..p.kernel {
+                        let ih = (oh * p.stride + kh).checked_sub(p.padding).filter(|&h| h < p.in_h);
+                        let iw = (ow * p.stride + kw).checked_sub(p.padding).filter(|&w| w < p.in_w);
+                        let v = match (ih, iw) {
+                            (Some(h), Some(w)) => input[(c * p.in_h + h) * p.in_w + w],
+                            _ => 0.0,
+                        };
+                        acc = match p.kind {
+                            PoolKind::Max => acc.max(v),
+                            PoolKind::Avg => acc + v,
+                        };
+                    }
+                }
+                out.push(match p.kind {
+                    PoolKind::Max => acc,
+                    PoolKind::Avg => acc / (p.kernel * p.kernel) as f64,
+                });
+            }
+        }
+    }
+    out
+}
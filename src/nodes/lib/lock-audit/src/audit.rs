@@ -0,0 +1,209 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::{LockResult, Mutex, MutexGuard, OnceLock, PoisonError, TryLockError};
+use std::time::{Duration, Instant};
+
+/// Holds longer than this are reported as long holds.
+const LONG_HOLD: Duration = Duration::from_millis(10);
+
+#[derive(Clone, Debug, Default)]
+pub struct LockStats {
+    pub acquisitions: u64,
+    /// Acquisitions that had to wait for another thread.
+    pub contended: u64,
+    pub wait: Duration,
+    pub held: Duration,
+    pub max_hold: Duration,
+    pub long_holds: u64,
+}
+
+#[derive(Default)]
+struct Registry {
+    locks: BTreeMap<&'static str, LockStats>,
+    /// (outer, inner) -> times `inner` was taken while `outer` was held.
+    order: BTreeMap<(&'static str, &'static str), u64>,
+}
+
+static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+
+thread_local! {
+    static HELD: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+}
+
+fn registry() -> MutexGuard<'static, Registry> {
+    REGISTRY
+        .get_or_init(|| Mutex::new(Registry::default()))
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+pub struct Guard<'a, T> {
+    name: &'static str,
+    acquired: Instant,
+    inner: MutexGuard<'a, T>,
+}
+
+pub(crate) fn lock<'a, T>(name: &'static str, mutex: &'a Mutex<T>) -> LockResult<Guard<'a, T>> {
+    let start = Instant::now();
+    let (result, contended) = match mutex.try_lock() {
+        Ok(guard) => (Ok(guard), false),
+        Err(TryLockError::Poisoned(e)) => (Err(e), false),
+        Err(TryLockError::WouldBlock) => (mutex.lock(), true),
+    };
+    let acquired = Instant::now();
+
+    HELD.with(|held| {
+        let mut held = held.borrow_mut();
+        let mut reg = registry();
+        for &outer in held.iter().filter(|&&outer| outer != name) {
+            *reg.order.entry((outer, name)).or_default() += 1;
+        }
+        let stats = reg.locks.entry(name).or_default();
+        stats.acquisitions += 1;
+        if contended {
+            stats.contended += 1;
+            stats.wait += acquired - start;
+        }
+        held.push(name);
+    });
+
+    let wrap = |inner| Guard { name, acquired, inner };
+    match result {
+        Ok(guard) => Ok(wrap(guard)),
+        Err(poisoned) => Err(PoisonError::new(wrap(poisoned.into_inner()))),
+    }
+}
+
+impl<T> Drop for Guard<'_, T> {
+    fn drop(&mut self) {
+        let hold = self.acquired.elapsed();
+        // Guards may be dropped out of acquisition order; remove the newest one.
+        let _ = HELD.try_with(|held| {
+            let mut held = held.borrow_mut();
+            if let Some(pos) = held.iter().rposition(|&n| n == self.name) {
+                held.remove(pos);
+            }
+        });
+        let mut reg = registry();
+        let stats = reg.locks.entry(self.name).or_default();
+        stats.held += hold;
+        stats.max_hold = stats.max_hold.max(hold);
+        if hold > LONG_HOLD {
+            stats.long_holds += 1;
+        }
+    }
+}
+
+impl<T> Deref for Guard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for Guard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct AuditReport {
+    pub locks: BTreeMap<&'static str, LockStats>,
+    pub order: BTreeMap<(&'static str, &'static str), u64>,
+    /// Lock pairs observed in both orders: potential deadlocks.
+    pub inversions: Vec<(&'static str, &'static str)>,
+}
+
+/// Snapshot of everything recorded so far in this process.
+pub fn report() -> AuditReport {
+    let reg = registry();
+    let inversions = reg
+        .order
+        .keys()
+        .filter(|&&(a, b)| a < b && reg.order.contains_key(&(b, a)))
+        .copied()
+        .collect();
+    AuditReport {
+        locks: reg.locks.clone(),
+        order: reg.order.clone(),
+        inversions,
+    }
+}
+
+impl fmt::Display for AuditReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "lock audit: {} locks, {} order inversions",
+            self.locks.len(),
+            self.inversions.len()
+        )?;
+        writeln!(
+            f,
+            "  {:<32} {:>10} {:>10} {:>10} {:>12} {:>10}",
+            "lock", "acquired", "contended", "wait_ms", "max_hold_ms", "long_holds"
+        )?;
+        for (name, s) in &self.locks {
+            writeln!(
+                f,
+                "  {:<32} {:>10} {:>10} {:>10.3} {:>12.3} {:>10}",
+                name,
+                s.acquisitions,
+                s.contended,
+                s.wait.as_secs_f64() * 1e3,
+                s.max_hold.as_secs_f64() * 1e3,
+                s.long_holds
+            )?;
+        }
+        for ((outer, inner), count) in &self.order {
+            writeln!(f, "  order {outer} -> {inner} ({count}x)")?;
+        }
+        for (a, b) in &self.inversions {
+            writeln!(f, "  INVERSION {a} <-> {b}: taken in both orders, may deadlock")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AuditedMutex;
+    use std::time::Duration;
+
+    #[test]
+    fn records_order_inversion() {
+        static A: AuditedMutex<u32> = AuditedMutex::new("test.inv.a", 0);
+        static B: AuditedMutex<u32> = AuditedMutex::new("test.inv.b", 0);
+        {
+            let _a = A.lock().unwrap();
+            let _b = B.lock().unwrap();
+        }
+        {
+            let _b = B.lock().unwrap();
+            let _a = A.lock().unwrap();
+        }
+        let report = super::report();
+        assert!(report.inversions.contains(&("test.inv.a", "test.inv.b")));
+        assert_eq!(report.locks["test.inv.a"].acquisitions, 2);
+    }
+
+    #[test]
+    fn records_long_hold() {
+        static L: AuditedMutex<()> = AuditedMutex::new("test.long", ());
+        {
+            let _g = L.lock().unwrap();
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        let stats = &super::report().locks["test.long"];
+        assert_eq!(stats.long_holds, 1);
+        assert!(stats.max_hold >= Duration::from_millis(20));
+        assert!(!super::report()
+            .order
+            .keys()
+            .any(|&(a, b)| a == "test.long" || b == "test.long"));
+    }
+}
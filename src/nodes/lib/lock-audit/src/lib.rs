@@ -0,0 +1,54 @@
+//! Named mutex for the process-wide statics shared by the simulators.
+//!
+//! With the `audit` feature every acquisition is recorded: which locks were
+//! already held by the thread (the lock order), whether the lock was contended,
+//! and how long it was held. [`report`] turns that into a per-lock summary and
+//! a list of order inversions, i.e. pairs of locks taken in both orders, which
+//! can deadlock. Without the feature `lock()` returns the std guard directly.
+
+#[cfg(not(feature = "audit"))]
+use std::sync::MutexGuard;
+use std::sync::{LockResult, Mutex};
+
+#[cfg(feature = "audit")]
+mod audit;
+
+#[cfg(feature = "audit")]
+pub use audit::{report, AuditReport, Guard, LockStats};
+
+pub struct AuditedMutex<T> {
+    name: &'static str,
+    inner: Mutex<T>,
+}
+
+impl<T> AuditedMutex<T> {
+    pub const fn new(name: &'static str, value: T) -> Self {
+        Self {
+            name,
+            inner: Mutex::new(value),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    #[cfg(not(feature = "audit"))]
+    pub fn lock(&self) -> LockResult<MutexGuard<'_, T>> {
+        self.inner.lock()
+    }
+
+    #[cfg(feature = "audit")]
+    pub fn lock(&self) -> LockResult<Guard<'_, T>> {
+        audit::lock(self.name, &self.inner)
+    }
+
+    pub fn into_inner(self) -> LockResult<T> {
+        self.inner.into_inner()
+    }
+}
+
+/// Whether acquisitions are being recorded in this build.
+pub const fn enabled() -> bool {
+    cfg!(feature = "audit")
+}
@@ -1,3 +1,7 @@
+use bebop_lock_audit::AuditedMutex;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
 use crate::state;
 
 pub struct ITraceEvent {
@@ -11,7 +15,90 @@ pub struct ITraceEvent {
     pub bank_enable: u8,
 }
 
+/// What is known about an in-flight instruction between alloc and complete.
+/// Complete events from the RTL carry no operands or timestamps; they are
+/// taken from here so complete records (and anything inspecting the ROB) see
+/// the full instruction.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RobEntry {
+    pub rob_id: u32,
+    pub domain_id: u32,
+    pub funct: u32,
+    pub pc: u64,
+    pub rs1: u64,
+    pub rs2: u64,
+    pub bank_enable: u8,
+    pub alloc_clk: Option<u64>,
+    pub issue_clk: Option<u64>,
+}
+
+static ROB: OnceLock<AuditedMutex<HashMap<u32, RobEntry>>> = OnceLock::new();
+
+fn rob() -> &'static AuditedMutex<HashMap<u32, RobEntry>> {
+    ROB.get_or_init(|| AuditedMutex::new("rtl-trace.rob", HashMap::new()))
+}
+
+/// Instructions allocated or issued but not yet completed, by rob_id.
+pub fn rob_entries() -> Vec<RobEntry> {
+    let mut entries: Vec<RobEntry> = rob().lock().unwrap().values().cloned().collect();
+    entries.sort_by_key(|e| e.rob_id);
+    entries
+}
+
+pub(crate) fn reset_rob() {
+    rob().lock().unwrap().clear();
+}
+
+/// Record `event` in the ROB table and return the entry it belongs to. A
+/// complete event removes the entry.
+fn track(event: &ITraceEvent, clk: u64) -> RobEntry {
+    let mut rob = rob().lock().unwrap();
+    if event.is_issue == 0 {
+        let entry = rob.remove(&event.rob_id);
+        if let Some(alloc_clk) = entry.as_ref().and_then(|e| e.alloc_clk) {
+            crate::latency::record(event.funct, clk.saturating_sub(alloc_clk));
+        }
+        return entry.unwrap_or_else(|| RobEntry {
+            rob_id: event.rob_id,
+            domain_id: event.domain_id,
+            funct: event.funct,
+            pc: event.pc,
+            bank_enable: event.bank_enable,
+            ..RobEntry::default()
+        });
+    }
+
+    let entry = rob.entry(event.rob_id).or_default();
+    *entry = RobEntry {
+        rob_id: event.rob_id,
+        domain_id: event.domain_id,
+        funct: event.funct,
+        pc: event.pc,
+        rs1: event.rs1,
+        rs2: event.rs2,
+        bank_enable: event.bank_enable,
+        alloc_clk: if event.is_issue == 2 {
+            Some(clk)
+        } else {
+            entry.alloc_clk
+        },
+        issue_clk: if event.is_issue == 1 {
+            Some(clk)
+        } else {
+            entry.issue_clk
+        },
+    };
+    entry.clone()
+}
+
+fn opt_clk(clk: Option<u64>) -> String {
+    clk.map_or_else(|| "null".to_string(), |c| c.to_string())
+}
+
 pub fn itrace(event: ITraceEvent) {
+    let clk = state::rtl_clk();
+    let entry = track(&event, clk);
+
     if !state::itrace_enabled() {
         return;
     }
@@ -25,7 +112,6 @@ pub fn itrace(event: ITraceEvent) {
         _ => "---",
     };
 
-    let clk = state::rtl_clk();
     let event_name = match event.is_issue {
         2 => "alloc",
         1 => "issue",
@@ -48,10 +134,58 @@ pub fn itrace(event: ITraceEvent) {
         )
     } else {
         format!(
-            r#"{{"type":"itrace","clk":{},"event":"{}","rob_id":{},"domain_id":{},"funct":"0x{:02x}","bank_enable":{},"bank":"{}","pc":"0x{:016x}"}}"#,
-            clk, event_name, event.rob_id, event.domain_id, event.funct, event.bank_enable, bank_str, event.pc
+            r#"{{"type":"itrace","clk":{},"event":"{}","rob_id":{},"domain_id":{},"funct":"0x{:02x}","bank_enable":{},"bank":"{}","pc":"0x{:016x}","rs1":"0x{:016x}","rs2":"0x{:016x}","alloc_clk":{},"issue_clk":{}}}"#,
+            clk,
+            event_name,
+            event.rob_id,
+            event.domain_id,
+            event.funct,
+            event.bank_enable,
+            bank_str,
+            event.pc,
+            entry.rs1,
+            entry.rs2,
+            opt_clk(entry.alloc_clk),
+            opt_clk(entry.issue_clk)
         )
     };
 
     state::write_trace(&json);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(is_issue: u8, rob_id: u32) -> ITraceEvent {
+        ITraceEvent {
+            is_issue,
+            rob_id,
+            domain_id: 1,
+            funct: 0x21,
+            pc: 0x8000_0000,
+            rs1: 0x11,
+            rs2: 0x22,
+            bank_enable: 2,
+        }
+    }
+
+    #[test]
+    fn complete_returns_entry_recorded_at_alloc_and_issue() {
+        track(&event(2, 7), 10);
+        track(&event(1, 7), 12);
+        assert!(rob_entries().iter().any(|e| e.rob_id == 7));
+
+        let done = track(
+            &ITraceEvent {
+                rs1: 0,
+                rs2: 0,
+                ..event(0, 7)
+            },
+            20,
+        );
+        assert_eq!((done.rs1, done.rs2), (0x11, 0x22));
+        assert_eq!((done.alloc_clk, done.issue_clk), (Some(10), Some(12)));
+        assert!(!rob_entries().iter().any(|e| e.rob_id == 7));
+    }
+}
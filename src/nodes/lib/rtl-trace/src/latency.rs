@@ -0,0 +1,76 @@
+use bebop_lock_audit::AuditedMutex;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+/// funct -> (alloc-to-complete latency in cycles -> count). Latencies are few
+/// distinct values, so exact counts stay small and percentiles stay exact.
+type Histograms = BTreeMap<u32, BTreeMap<u64, u64>>;
+
+static LATENCY: OnceLock<AuditedMutex<Histograms>> = OnceLock::new();
+
+fn latency() -> &'static AuditedMutex<Histograms> {
+    LATENCY.get_or_init(|| AuditedMutex::new("rtl-trace.latency", BTreeMap::new()))
+}
+
+pub(crate) fn record(funct: u32, cycles: u64) {
+    *latency()
+        .lock()
+        .unwrap()
+        .entry(funct)
+        .or_default()
+        .entry(cycles)
+        .or_default() += 1;
+}
+
+pub(crate) fn reset() {
+    latency().lock().unwrap().clear();
+}
+
+/// Nearest-rank percentile of a histogram holding `count` samples.
+fn percentile(hist: &BTreeMap<u64, u64>, count: u64, p: u64) -> u64 {
+    let rank = (count * p).div_ceil(100).max(1);
+    let mut seen = 0;
+    for (&cycles, &n) in hist {
+        seen += n;
+        if seen >= rank {
+            return cycles;
+        }
+    }
+    0
+}
+
+/// `{"0x21":{"count":..,"p50":..,"p95":..,"p99":..,"max":..},...}`
+pub(crate) fn summary_json() -> String {
+    let hists = latency().lock().unwrap();
+    let entries: Vec<String> = hists
+        .iter()
+        .map(|(funct, hist)| {
+            let count: u64 = hist.values().sum();
+            format!(
+                r#""0x{:02x}":{{"count":{},"p50":{},"p95":{},"p99":{},"max":{}}}"#,
+                funct,
+                count,
+                percentile(hist, count, 50),
+                percentile(hist, count, 95),
+                percentile(hist, count, 99),
+                hist.keys().next_back().copied().unwrap_or(0)
+            )
+        })
+        .collect();
+    format!("{{{}}}", entries.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_rank_percentiles() {
+        // 90 samples of 4 cycles, 9 of 20, 1 of 300
+        let hist = BTreeMap::from([(4, 90), (20, 9), (300, 1)]);
+        assert_eq!(percentile(&hist, 100, 50), 4);
+        assert_eq!(percentile(&hist, 100, 95), 20);
+        assert_eq!(percentile(&hist, 100, 99), 20);
+        assert_eq!(percentile(&hist, 100, 100), 300);
+    }
+}
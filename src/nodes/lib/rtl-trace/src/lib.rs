@@ -2,9 +2,10 @@ mod banktrace;
 mod ctrace;
 mod dpi;
 mod itrace;
+mod latency;
 mod mtrace;
 mod pmctrace;
 mod state;
 mod trace;
 
-pub use trace::{init_trace, write_trace_summary, TraceConfig};
+pub use trace::{init_trace, rob_entries, write_trace_summary, RobEntry, TraceConfig};
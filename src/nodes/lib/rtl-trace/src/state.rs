@@ -1,16 +1,17 @@
+use bebop_lock_audit::AuditedMutex;
 use std::fs::{File, OpenOptions};
 use std::io::{self, Write};
 use std::path::Path;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::{Mutex, OnceLock};
-
-static TRACE_FILE: OnceLock<Mutex<Option<File>>> = OnceLock::new();
-static ENABLE_ITRACE: OnceLock<Mutex<bool>> = OnceLock::new();
-static ENABLE_MTRACE: OnceLock<Mutex<bool>> = OnceLock::new();
-static ENABLE_PMCTRACE: OnceLock<Mutex<bool>> = OnceLock::new();
-static ENABLE_CTRACE: OnceLock<Mutex<bool>> = OnceLock::new();
-static ENABLE_BANKTRACE: OnceLock<Mutex<bool>> = OnceLock::new();
-static RTL_CLK: OnceLock<Mutex<u64>> = OnceLock::new();
+use std::sync::OnceLock;
+
+static TRACE_FILE: OnceLock<AuditedMutex<Option<File>>> = OnceLock::new();
+static ENABLE_ITRACE: OnceLock<AuditedMutex<bool>> = OnceLock::new();
+static ENABLE_MTRACE: OnceLock<AuditedMutex<bool>> = OnceLock::new();
+static ENABLE_PMCTRACE: OnceLock<AuditedMutex<bool>> = OnceLock::new();
+static ENABLE_CTRACE: OnceLock<AuditedMutex<bool>> = OnceLock::new();
+static ENABLE_BANKTRACE: OnceLock<AuditedMutex<bool>> = OnceLock::new();
+static RTL_CLK: OnceLock<AuditedMutex<u64>> = OnceLock::new();
 static ITRACE_CALLBACKS: AtomicU64 = AtomicU64::new(0);
 static MTRACE_CALLBACKS: AtomicU64 = AtomicU64::new(0);
 static PMC_BALL_CALLBACKS: AtomicU64 = AtomicU64::new(0);
@@ -109,41 +110,44 @@ pub fn write_callback_summary(log_dir: &Path) -> io::Result<()> {
         concat!(
             "{{\"itrace_callbacks\":{},\"mtrace_callbacks\":{},",
             "\"pmctrace_ball_callbacks\":{},\"pmctrace_mem_callbacks\":{},",
-            "\"ctrace_callbacks\":{}}}\n"
+            "\"ctrace_callbacks\":{},\"rob_in_flight\":{},",
+            "\"latency\":{}}}\n"
         ),
         ITRACE_CALLBACKS.load(Ordering::Relaxed),
         MTRACE_CALLBACKS.load(Ordering::Relaxed),
         PMC_BALL_CALLBACKS.load(Ordering::Relaxed),
         PMC_MEM_CALLBACKS.load(Ordering::Relaxed),
         CTRACE_CALLBACKS.load(Ordering::Relaxed),
+        crate::itrace::rob_entries().len(),
+        crate::latency::summary_json(),
     );
     std::fs::write(log_dir.join("rtl-trace-summary.json"), json)
 }
 
-fn trace_file() -> &'static Mutex<Option<File>> {
-    TRACE_FILE.get_or_init(|| Mutex::new(None))
+fn trace_file() -> &'static AuditedMutex<Option<File>> {
+    TRACE_FILE.get_or_init(|| AuditedMutex::new("rtl-trace.trace_file", None))
 }
 
-fn rtl_clk_state() -> &'static Mutex<u64> {
-    RTL_CLK.get_or_init(|| Mutex::new(0))
+fn rtl_clk_state() -> &'static AuditedMutex<u64> {
+    RTL_CLK.get_or_init(|| AuditedMutex::new("rtl-trace.rtl_clk", 0))
 }
 
-fn enable_itrace() -> &'static Mutex<bool> {
-    ENABLE_ITRACE.get_or_init(|| Mutex::new(false))
+fn enable_itrace() -> &'static AuditedMutex<bool> {
+    ENABLE_ITRACE.get_or_init(|| AuditedMutex::new("rtl-trace.enable_itrace", false))
 }
 
-fn enable_mtrace() -> &'static Mutex<bool> {
-    ENABLE_MTRACE.get_or_init(|| Mutex::new(false))
+fn enable_mtrace() -> &'static AuditedMutex<bool> {
+    ENABLE_MTRACE.get_or_init(|| AuditedMutex::new("rtl-trace.enable_mtrace", false))
 }
 
-fn enable_pmctrace() -> &'static Mutex<bool> {
-    ENABLE_PMCTRACE.get_or_init(|| Mutex::new(false))
+fn enable_pmctrace() -> &'static AuditedMutex<bool> {
+    ENABLE_PMCTRACE.get_or_init(|| AuditedMutex::new("rtl-trace.enable_pmctrace", false))
 }
 
-fn enable_ctrace() -> &'static Mutex<bool> {
-    ENABLE_CTRACE.get_or_init(|| Mutex::new(false))
+fn enable_ctrace() -> &'static AuditedMutex<bool> {
+    ENABLE_CTRACE.get_or_init(|| AuditedMutex::new("rtl-trace.enable_ctrace", false))
 }
 
-fn enable_banktrace() -> &'static Mutex<bool> {
-    ENABLE_BANKTRACE.get_or_init(|| Mutex::new(false))
+fn enable_banktrace() -> &'static AuditedMutex<bool> {
+    ENABLE_BANKTRACE.get_or_init(|| AuditedMutex::new("rtl-trace.enable_banktrace", false))
 }
@@ -4,13 +4,15 @@ use std::path::Path;
 use crate::state;
 
 pub use crate::ctrace::ctrace;
-pub use crate::itrace::{itrace, ITraceEvent};
+pub use crate::itrace::{itrace, rob_entries, ITraceEvent, RobEntry};
 pub use crate::mtrace::{mtrace, MTraceEvent};
 pub use crate::pmctrace::{pmctrace_ball, pmctrace_mem};
 pub use crate::state::{set_rtl_clk, TraceConfig};
 
 pub fn init_trace(log_dir: &Path, config: TraceConfig) -> io::Result<()> {
     crate::dpi::force_link();
+    crate::itrace::reset_rob();
+    crate::latency::reset();
     state::init(log_dir, config)
 }
 
@@ -1,9 +1,10 @@
+use bebop_lock_audit::AuditedMutex;
 use once_cell::sync::Lazy;
 use std::collections::HashMap;
 use std::fs::File;
-use std::sync::Mutex;
 
-pub static SYSCALL_STATE: Lazy<Mutex<SyscallState>> = Lazy::new(|| Mutex::new(SyscallState::new()));
+pub static SYSCALL_STATE: Lazy<AuditedMutex<SyscallState>> =
+    Lazy::new(|| AuditedMutex::new("syscall.state", SyscallState::new()));
 
 pub struct SyscallState {
     pub open_files: HashMap<u64, File>,
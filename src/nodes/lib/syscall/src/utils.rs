@@ -1,6 +1,5 @@
 use crate::constants::GUEST_MEM_BASE;
-use once_cell::sync::Lazy;
-use std::sync::Mutex;
+use bebop_lock_audit::AuditedMutex;
 
 #[derive(Clone, Copy)]
 struct GuestMapping {
@@ -9,7 +8,7 @@ struct GuestMapping {
     len: u64,
 }
 
-static GUEST_MAPPINGS: Lazy<Mutex<Vec<GuestMapping>>> = Lazy::new(|| Mutex::new(Vec::new()));
+static GUEST_MAPPINGS: AuditedMutex<Vec<GuestMapping>> = AuditedMutex::new("syscall.guest_mappings", Vec::new());
 
 pub fn align_up(value: u64, align: u64) -> u64 {
     (value + align - 1) & !(align - 1)
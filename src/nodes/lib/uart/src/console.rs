@@ -8,6 +8,9 @@ use std::sync::{mpsc, Arc, Mutex};
 use std::thread::JoinHandle;
 use std::time::Duration;
 
+/// Overrides the console socket path (default: per-process under TMPDIR).
+pub const ENV_CONSOLE_SOCKET: &str = "BEBOP_CONSOLE_SOCKET";
+
 type ConsoleClients = Arc<Mutex<HashMap<u32, Vec<UnixStream>>>>;
 type RxHandler = Arc<dyn Fn(u32, u8) + Send + Sync + 'static>;
 
@@ -61,7 +64,9 @@ impl ConsoleServer {
         }
 
         let path_file = log_dir.join("console.sock.path");
-        let socket_path = std::env::temp_dir().join(format!("bebop-console-{}.sock", std::process::id()));
+        let socket_path = std::env::var_os(ENV_CONSOLE_SOCKET)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| std::env::temp_dir().join(format!("bebop-console-{}.sock", std::process::id())));
         if socket_path.exists() {
             std::fs::remove_file(&socket_path)
                 .map_err(|e| format!("failed to remove stale console socket {}: {e}", socket_path.display()))?;
@@ -1,4 +1,5 @@
 use super::cycle_trace::CycleTraceCollector;
+use bebop_lock_audit::AuditedMutex;
 use bebop_uart::UartTx;
 use std::collections::HashMap;
 use std::ffi::CString;
@@ -6,7 +7,7 @@ use std::fs::File;
 use std::io::Write;
 use std::os::unix::fs::FileExt;
 use std::sync::mpsc::Sender;
-use std::sync::{Mutex, OnceLock};
+use std::sync::OnceLock;
 
 const SIM_EXIT_ADDR: u64 = 0x6000_0000;
 const UART_BASE_ADDR: u64 = 0x6002_0000;
@@ -60,10 +61,10 @@ struct RuntimeState {
     cycle_trace_error: Option<String>,
 }
 
-static STATE: OnceLock<Mutex<RuntimeState>> = OnceLock::new();
+static STATE: OnceLock<AuditedMutex<RuntimeState>> = OnceLock::new();
 
-fn state() -> &'static Mutex<RuntimeState> {
-    STATE.get_or_init(|| Mutex::new(RuntimeState::default()))
+fn state() -> &'static AuditedMutex<RuntimeState> {
+    STATE.get_or_init(|| AuditedMutex::new("p2e.ctb_state", RuntimeState::default()))
 }
 
 pub fn reset_runtime_state() {
@@ -6,6 +6,6 @@ mod ffi;
 #[path = "mmio/mmio.rs"]
 mod mmio;
 
-pub use bebop_rtl_trace::{init_trace, TraceConfig};
+pub use bebop_rtl_trace::{init_trace, write_trace_summary, TraceConfig};
 pub use mmio::{drain_uart_tx, exit_code, push_uart_rx};
 pub use sim::{setup_ctrlc_handler, should_exit, Simulator};
@@ -0,0 +1,178 @@
+//===------ bench.rs -------- BEMU benchmark suite ------------------------===//
+//
+// Copyright 2026 The Aerospace Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===----------------------------------------------------------------------===//
+//
+// A fixed set of kernels run directly on the BEMU accelerator model, reported
+// as a markdown table. Run it before and after a model change and diff the two
+// tables to see the performance impact. Every kernel starts from a fresh
+// model, so results are deterministic.
+//
+//===----------------------------------------------------------------------===//
+
+use bebop_bemu::{Npu, DEFAULT_MEM_SIZE};
+use snafu::{ResultExt, Whatever};
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+const MSET: u32 = 32;
+const MVIN: u32 = 33;
+const MVOUT: u32 = 16;
+
+const SRC: u64 = 0x8010_0000;
+const DST: u64 = 0x8020_0000;
+const ROW_BYTES: u64 = 16;
+
+pub struct BenchConfig {
+    pub output: Option<PathBuf>,
+}
+
+struct Kernel {
+    name: &'static str,
+    /// Issues the kernel and returns the number of DRAM bytes it moves.
+    run: fn(&mut Npu) -> u64,
+}
+
+const KERNELS: &[Kernel] = &[
+    Kernel {
+        name: "mvin 16x16B",
+        run: |npu| mvin(npu, 1, 16, 1, 1),
+    },
+    Kernel {
+        name: "mvin 256x16B",
+        run: |npu| mvin(npu, 1, 256, 1, 1),
+    },
+    Kernel {
+        name: "mvin 1024x16B",
+        run: |npu| mvin(npu, 1, 1024, 1, 1),
+    },
+    Kernel {
+        name: "mvin 256x16B stride 4",
+        run: |npu| mvin(npu, 1, 256, 4, 1),
+    },
+    Kernel {
+        name: "mvin 256x64B (4 groups)",
+        run: |npu| mvin(npu, 1, 256, 1, 4),
+    },
+    Kernel {
+        name: "mvout 256x16B",
+        run: |npu| mvin(npu, 1, 256, 1, 1) + mvout(npu, 1, 256, 1, 1),
+    },
+    Kernel {
+        name: "memcpy 16KiB via 1 bank",
+        run: |npu| mvin(npu, 1, 1024, 1, 1) + mvout(npu, 1, 1024, 1, 1),
+    },
+    Kernel {
+        name: "memcpy 16KiB via 4 banks",
+        run: memcpy_4_banks,
+    },
+];
+
+pub fn run(config: BenchConfig) -> Result<(), Whatever> {
+    let mut table = String::new();
+    writeln!(table, "| kernel | instructions | cycles | bytes | bytes/cycle |").unwrap();
+    writeln!(table, "|---|---:|---:|---:|---:|").unwrap();
+
+    for kernel in KERNELS {
+        let mut npu = Npu::new(DEFAULT_MEM_SIZE);
+        let bytes = (kernel.run)(&mut npu);
+        let cycles = npu.total_latency();
+        writeln!(
+            table,
+            "| {} | {} | {} | {} | {:.2} |",
+            kernel.name,
+            npu.instruction_count(),
+            cycles,
+            bytes,
+            bytes as f64 / cycles.max(1) as f64
+        )
+        .unwrap();
+    }
+
+    match &config.output {
+        Some(path) => {
+            std::fs::write(path, &table).whatever_context("failed to write bench-suite table")?;
+            println!("[INFO] Bench suite written to {}", path.display());
+        }
+        None => print!("{table}"),
+    }
+    Ok(())
+}
+
+fn alloc(npu: &mut Npu, bank: u64, cols: u64) {
+    npu.exec(MSET, bank, (cols << 5) | (1 << 10), 0);
+}
+
+/// Move `rows` rows of `cols` 16-byte groups from SRC into `bank`.
+fn mvin(npu: &mut Npu, bank: u64, rows: u64, stride: u64, cols: u64) -> u64 {
+    alloc(npu, bank, cols);
+    npu.exec(MVIN, bank | (rows << 30), SRC | (stride << 39), 0);
+    rows * cols * ROW_BYTES
+}
+
+/// Move `rows` rows of `cols` 16-byte groups from `bank` to DST.
+fn mvout(npu: &mut Npu, bank: u64, rows: u64, stride: u64, cols: u64) -> u64 {
+    npu.exec(MVOUT, bank | (rows << 30), DST | (stride << 39), 0);
+    rows * cols * ROW_BYTES
+}
+
+fn memcpy_4_banks(npu: &mut Npu) -> u64 {
+    let rows = 256;
+    let mut bytes = 0;
+    for bank in 0..4 {
+        let off = bank * rows * ROW_BYTES;
+        alloc(npu, bank, 1);
+        npu.exec(MVIN, bank | (rows << 30), (SRC + off) | (1 << 39), 0);
+        npu.exec(MVOUT, bank | (rows << 30), (DST + off) | (1 << 39), 0);
+        bytes += 2 * rows * ROW_BYTES;
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_has_a_row_per_kernel_and_repeats() {
+        let path = std::env::temp_dir().join(format!("bebop-bench-{}.md", std::process::id()));
+        let table = || {
+            run(BenchConfig {
+                output: Some(path.clone()),
+            })
+            .unwrap();
+            std::fs::read_to_string(&path).unwrap()
+        };
+        let first = table();
+        assert_eq!(first, table(), "every kernel starts from a fresh model");
+        std::fs::remove_file(&path).unwrap();
+
+        let rows: Vec<Vec<&str>> = first
+            .lines()
+            .skip(2)
+            .map(|l| l.trim_matches('|').split('|').map(str::trim).collect())
+            .collect();
+        let names: Vec<&str> = rows.iter().map(|r| r[0]).collect();
+        let expected: Vec<&str> = KERNELS.iter().map(|k| k.name).collect();
+        assert_eq!(names, expected);
+        // mset + mvin of 16 rows of 16 bytes.
+        assert_eq!((rows[0][1], rows[0][3]), ("2", "256"));
+        for row in &rows {
+            let cycles: u64 = row[2].parse().unwrap();
+            assert!(cycles > 0, "{row:?}");
+        }
+    }
+}
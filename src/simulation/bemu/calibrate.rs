@@ -0,0 +1,376 @@
+//===------ calibrate.rs ---- BEMU latency calibration --------------------===//
+//
+// Copyright 2026 The Aerospace Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===----------------------------------------------------------------------===//
+//
+// Runs microbenchmarks (streaming mvin, bank-to-bank mcopy, dense matmul of
+// several sizes) and compares their cycles with a reference CSV measured on
+// RTL or FireSim, one `kernel,cycles` line per kernel:
+//
+//   # kernel,cycles
+//   mvin 256 rows,270
+//   matmul 64x16x16,85
+//
+// Only the measured instructions count, not the mset and mvin that set
+// them up. Each kernel is also run with one more cycle of bank read and of
+// bank write latency, which gives how many of its instructions pay each.
+// A least-squares fit of the cycle differences to those counts gives the
+// `read_latency` and `write_latency` of `[arch.buckyball]` that bring BEMU
+// closest to the reference; the suite is then rerun with them to show the
+// remaining error.
+//
+//===----------------------------------------------------------------------===//
+
+use super::{configure, init_seed, ArchFile};
+use crate::ModelArgs;
+use bebop_bemu::{Npu, DEFAULT_MEM_SIZE};
+use snafu::{whatever, FromString, ResultExt, Whatever};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+const MSET: u32 = 32;
+const MVIN: u32 = 33;
+const MCOPY: u32 = 37;
+const MATMUL: u32 = 48;
+
+const SRC: u64 = 0x8010_0000;
+
+pub struct CalibrateConfig {
+    pub reference: PathBuf,
+    pub model: ModelArgs,
+    pub output: Option<PathBuf>,
+}
+
+struct Kernel {
+    name: &'static str,
+    /// Allocates banks and loads operands; not measured.
+    setup: fn(&mut Npu),
+    /// The measured instructions.
+    run: fn(&mut Npu),
+}
+
+const KERNELS: &[Kernel] = &[
+    Kernel {
+        name: "mvin 16 rows",
+        setup: |npu| alloc(npu, 1, 0),
+        run: |npu| mvin(npu, 1, 16),
+    },
+    Kernel {
+        name: "mvin 256 rows",
+        setup: |npu| alloc(npu, 1, 0),
+        run: |npu| mvin(npu, 1, 256),
+    },
+    Kernel {
+        name: "mvin 1024 rows",
+        setup: |npu| alloc(npu, 1, 0),
+        run: |npu| mvin(npu, 1, 1024),
+    },
+    Kernel {
+        name: "mcopy 256 rows",
+        setup: |npu| copy_setup(npu, 256),
+        run: |npu| {
+            npu.exec(MCOPY, 1 | (2 << 10) | (256 << 30), 0, 0);
+        },
+    },
+    Kernel {
+        name: "mcopy 1024 rows",
+        setup: |npu| copy_setup(npu, 1024),
+        run: |npu| {
+            npu.exec(MCOPY, 1 | (2 << 10) | (1024 << 30), 0, 0);
+        },
+    },
+    Kernel {
+        name: "matmul 16x16x16",
+        setup: |npu| matmul_setup(npu, 16),
+        run: |npu| matmul(npu, 16),
+    },
+    Kernel {
+        name: "matmul 64x16x16",
+        setup: |npu| matmul_setup(npu, 64),
+        run: |npu| matmul(npu, 64),
+    },
+    Kernel {
+        name: "matmul 256x16x16",
+        setup: |npu| matmul_setup(npu, 256),
+        run: |npu| matmul(npu, 256),
+    },
+];
+
+/// The `--arch` file and model options every run starts from; calibration
+/// varies only the bank latencies.
+struct Arch {
+    file: ArchFile,
+    /// `--random-init` is resolved once into `seed`, so every run starts
+    /// from the same garbage and the seed is printed once.
+    model: ModelArgs,
+    seed: Option<u64>,
+}
+
+impl Arch {
+    fn new(file: ArchFile, mut model: ModelArgs) -> Self {
+        let seed = init_seed(model.random_init.take());
+        Arch { file, model, seed }
+    }
+
+    /// `read_latency` and `write_latency` of the `--arch` file.
+    fn latencies(&self) -> (u64, u64) {
+        let g = &self.file.arch.buckyball;
+        (g.read_latency, g.write_latency)
+    }
+
+    fn model(&self, read_latency: u64, write_latency: u64) -> Result<Npu, Whatever> {
+        let mut file = self.file.clone();
+        file.arch.buckyball.read_latency = read_latency;
+        file.arch.buckyball.write_latency = write_latency;
+        let mut npu = Npu::new(DEFAULT_MEM_SIZE);
+        configure(&mut npu, &self.model, &file)?;
+        if let Some(seed) = self.seed {
+            npu.randomize(seed);
+        }
+        Ok(npu)
+    }
+
+    /// Cycles of the measured part of `kernel`.
+    fn cycles(&self, kernel: &Kernel, read_latency: u64, write_latency: u64) -> Result<u64, Whatever> {
+        let mut npu = self.model(read_latency, write_latency)?;
+        (kernel.setup)(&mut npu);
+        let start = npu.total_latency();
+        (kernel.run)(&mut npu);
+        Ok(npu.total_latency() - start)
+    }
+}
+
+struct Sample {
+    name: &'static str,
+    bemu: u64,
+    reference: u64,
+    /// Measured instructions that pay the read and the write latency.
+    reads: f64,
+    writes: f64,
+}
+
+/// BEMU against the reference before and after applying the fitted bank
+/// latencies.
+struct Calibration {
+    samples: Vec<Sample>,
+    /// Suggested (read_latency, write_latency).
+    suggested: (u64, u64),
+    /// Cycles of each sample's kernel with the suggested latencies.
+    after: Vec<u64>,
+}
+
+pub fn run(config: CalibrateConfig) -> Result<(), Whatever> {
+    let reference = load_reference(&config.reference)?;
+    if reference.is_empty() {
+        whatever!("{} names none of the calibration kernels", config.reference.display());
+    }
+    let arch = Arch::new(ArchFile::of(&config.model)?, config.model);
+    let Calibration {
+        samples,
+        suggested,
+        after,
+    } = calibrate(&arch, &reference)?;
+
+    let error = |bemu: u64, reference: u64| 100.0 * (bemu as f64 - reference as f64) / reference.max(1) as f64;
+    let mut report = String::new();
+    writeln!(report, "| kernel | reference | bemu | error | calibrated | error |").unwrap();
+    writeln!(report, "|---|---:|---:|---:|---:|---:|").unwrap();
+    for (s, &cal) in samples.iter().zip(&after) {
+        writeln!(
+            report,
+            "| {} | {} | {} | {:+.1}% | {} | {:+.1}% |",
+            s.name,
+            s.reference,
+            s.bemu,
+            error(s.bemu, s.reference),
+            cal,
+            error(cal, s.reference)
+        )
+        .unwrap();
+    }
+    let mean = |bemu: &mut dyn Iterator<Item = u64>| {
+        let total: f64 = bemu.zip(&samples).map(|(b, s)| error(b, s.reference).abs()).sum();
+        total / samples.len() as f64
+    };
+    writeln!(
+        report,
+        "\n# mean |error| {:.1}% -> {:.1}% with\n[arch.buckyball]\nread_latency = {}\nwrite_latency = {}",
+        mean(&mut samples.iter().map(|s| s.bemu)),
+        mean(&mut after.iter().copied()),
+        suggested.0,
+        suggested.1
+    )
+    .unwrap();
+
+    match &config.output {
+        Some(path) => {
+            std::fs::write(path, &report).whatever_context("failed to write calibration report")?;
+            println!("[INFO] Calibration report written to {}", path.display());
+        }
+        None => print!("{report}"),
+    }
+    Ok(())
+}
+
+/// Run every kernel `reference` has cycles for and fit the bank latencies
+/// to them.
+fn calibrate(arch: &Arch, reference: &BTreeMap<String, u64>) -> Result<Calibration, Whatever> {
+    let (read, write) = arch.latencies();
+    let mut samples = Vec::new();
+    for kernel in KERNELS {
+        let Some(&cycles) = reference.get(kernel.name) else {
+            println!("[WARN] no reference cycles for `{}`, skipped", kernel.name);
+            continue;
+        };
+        let bemu = arch.cycles(kernel, read, write)?;
+        samples.push(Sample {
+            name: kernel.name,
+            bemu,
+            reference: cycles,
+            reads: (arch.cycles(kernel, read + 1, write)? - bemu) as f64,
+            writes: (arch.cycles(kernel, read, write + 1)? - bemu) as f64,
+        });
+    }
+
+    let (read_delta, write_delta) = fit(&samples);
+    let suggested = (
+        (read as f64 + read_delta).round().max(0.0) as u64,
+        (write as f64 + write_delta).round().max(0.0) as u64,
+    );
+    let mut after = Vec::with_capacity(samples.len());
+    for s in &samples {
+        let kernel = KERNELS.iter().find(|k| k.name == s.name).unwrap();
+        after.push(arch.cycles(kernel, suggested.0, suggested.1)?);
+    }
+    Ok(Calibration {
+        samples,
+        suggested,
+        after,
+    })
+}
+
+/// Kernel name to reference cycles.
+fn load_reference(path: &Path) -> Result<BTreeMap<String, u64>, Whatever> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| Whatever::without_source(format!("failed to read {}: {e}", path.display())))?;
+    let mut reference = BTreeMap::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line == "kernel,cycles" {
+            continue;
+        }
+        let parsed = line
+            .rsplit_once(',')
+            .and_then(|(name, cycles)| Some((name.trim(), cycles.trim().parse::<u64>().ok()?)));
+        let Some((name, cycles)) = parsed else {
+            whatever!("{}:{}: expected `kernel,cycles`, got `{line}`", path.display(), i + 1);
+        };
+        if !KERNELS.iter().any(|k| k.name == name) {
+            let names: Vec<_> = KERNELS.iter().map(|k| k.name).collect();
+            whatever!(
+                "{}:{}: unknown kernel `{name}`; the kernels are {}",
+                path.display(),
+                i + 1,
+                names.join(", ")
+            );
+        }
+        reference.insert(name.to_string(), cycles);
+    }
+    Ok(reference)
+}
+
+/// Least-squares (read, write) latency change minimising the squared cycle
+/// error. Falls back to one shared change when the suite cannot tell reads
+/// from writes.
+fn fit(samples: &[Sample]) -> (f64, f64) {
+    let (mut rr, mut rw, mut ww, mut rd, mut wd) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    for s in samples {
+        let d = s.reference as f64 - s.bemu as f64;
+        rr += s.reads * s.reads;
+        rw += s.reads * s.writes;
+        ww += s.writes * s.writes;
+        rd += s.reads * d;
+        wd += s.writes * d;
+    }
+    let det = rr * ww - rw * rw;
+    if det.abs() > 1e-9 {
+        return ((rd * ww - wd * rw) / det, (wd * rr - rd * rw) / det);
+    }
+    let total = rr + 2.0 * rw + ww;
+    let shared = if total > 0.0 { (rd + wd) / total } else { 0.0 };
+    (shared, shared)
+}
+
+fn alloc(npu: &mut Npu, bank: u64, width: u64) {
+    npu.exec(MSET, bank, (1 << 5) | (1 << 10) | (width << 11), 0);
+}
+
+fn mvin(npu: &mut Npu, bank: u64, rows: u64) {
+    npu.exec(MVIN, bank | (rows << 30), SRC | (1 << 39), 0);
+}
+
+fn copy_setup(npu: &mut Npu, rows: u64) {
+    alloc(npu, 1, 0);
+    alloc(npu, 2, 0);
+    mvin(npu, 1, rows);
+}
+
+/// i8 A of `m` rows and a 16x16 i8 B into i32 C.
+fn matmul_setup(npu: &mut Npu, m: u64) {
+    alloc(npu, 1, 0);
+    alloc(npu, 2, 0);
+    alloc(npu, 3, 2);
+    mvin(npu, 1, m);
+    mvin(npu, 2, 16);
+}
+
+fn matmul(npu: &mut Npu, m: u64) {
+    npu.exec(MATMUL, 1 | (2 << 10) | (3 << 20) | (m << 30), 0, 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recovers_bank_latencies_from_a_reference() {
+        let arch = Arch::new(ArchFile::default(), ModelArgs::default());
+        let reference: BTreeMap<String, u64> = KERNELS
+            .iter()
+            .map(|k| Ok((k.name.to_string(), arch.cycles(k, 3, 2)?)))
+            .collect::<Result<_, Whatever>>()
+            .unwrap();
+        let cal = calibrate(&arch, &reference).unwrap();
+        assert_eq!(cal.suggested, (3, 2));
+        assert!(cal.samples.iter().any(|s| s.bemu != s.reference));
+        let matched: Vec<_> = cal.samples.iter().map(|s| s.reference).collect();
+        assert_eq!(cal.after, matched);
+    }
+
+    #[test]
+    fn reference_names_known_kernels() {
+        let path = std::env::temp_dir().join(format!("bebop-calibrate-{}.csv", std::process::id()));
+        std::fs::write(&path, "# kernel,cycles\nmvin 16 rows, 40\n\n").unwrap();
+        let reference = load_reference(&path).unwrap();
+        assert_eq!(reference.get("mvin 16 rows"), Some(&40));
+
+        std::fs::write(&path, "mvin 17 rows,40\n").unwrap();
+        let err = load_reference(&path).unwrap_err().to_string();
+        std::fs::remove_file(&path).unwrap();
+        assert!(err.contains(":1: unknown kernel `mvin 17 rows`"), "{err}");
+    }
+}
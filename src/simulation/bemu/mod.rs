@@ -1 +1,274 @@
+#[cfg(feature = "bemu-model")]
+pub mod bench;
+#[cfg(feature = "bemu-model")]
+pub mod calibrate;
 pub mod run;
+#[cfg(feature = "script")]
+pub mod script;
+#[cfg(feature = "bemu-model")]
+pub mod server;
+
+/// End-of-run summary: DMA statistics, utilization and roofline, and the
+/// dubious-but-legal behaviour BEMU saw.
+#[cfg(feature = "bemu-model")]
+pub fn print_summary(warnings: &bebop_bemu::Warnings, perf: &bebop_bemu::PerfReport) {
+    let dma = perf.dma;
+    if dma.misaligned > 0 {
+        println!("[WARN] BEMU DMA: {dma}");
+    } else if dma.throttle_cycles > 0 || dma.dram_cycles > 0 {
+        println!("[INFO] BEMU DMA: {dma}");
+    }
+    print!("[INFO] BEMU performance:\n{perf}");
+    if !warnings.is_empty() {
+        println!("[WARN] BEMU warnings:\n{warnings}");
+    }
+}
+
+/// Fault injection counts, and the lines whose flips ECC did not correct.
+#[cfg(feature = "bemu-model")]
+pub fn print_faults(npu: &bebop_bemu::Npu) {
+    const SHOWN: usize = 10;
+    let Some(stats) = npu.fault_stats() else {
+        return;
+    };
+    println!("[INFO] BEMU faults: {stats}");
+    let uncorrected: Vec<_> = npu
+        .fault_events()
+        .iter()
+        .filter(|e| e.outcome != bebop_bemu::FaultOutcome::Corrected)
+        .collect();
+    for event in uncorrected.iter().take(SHOWN) {
+        println!("[WARN] BEMU fault: {event}");
+    }
+    if uncorrected.len() > SHOWN {
+        println!("[WARN] BEMU fault: ... {} more", uncorrected.len() - SHOWN);
+    }
+}
+
+/// Write the `--stats` JSON report.
+#[cfg(feature = "bemu-model")]
+pub fn save_stats(perf: &bebop_bemu::PerfReport, path: &std::path::Path) -> Result<(), snafu::Whatever> {
+    use snafu::FromString;
+    perf.save(path).map_err(snafu::Whatever::without_source)?;
+    println!("[INFO] BEMU stats: {}", path.display());
+    Ok(())
+}
+
+/// Merge this run's ISA coverage into `path` and print the merged report.
+#[cfg(feature = "bemu-model")]
+pub fn save_coverage(coverage: Option<&bebop_bemu::Coverage>, path: &std::path::Path) -> Result<(), snafu::Whatever> {
+    use snafu::FromString;
+    let Some(coverage) = coverage else {
+        return Ok(());
+    };
+    let total = coverage.save_merged(path).map_err(snafu::Whatever::without_source)?;
+    println!("[INFO] ISA coverage (merged into {}):\n{total}", path.display());
+    Ok(())
+}
+
+/// Parse a TOML file, naming it in any error.
+#[cfg(feature = "bemu-model")]
+fn read_toml<T: serde::de::DeserializeOwned>(path: &std::path::Path) -> Result<T, snafu::Whatever> {
+    use snafu::FromString;
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| snafu::Whatever::without_source(format!("failed to read {}: {e}", path.display())))?;
+    toml::from_str(&text).map_err(|e| snafu::Whatever::without_source(format!("{}: {e}", path.display())))
+}
+
+/// An `--arch` TOML file. Every table is optional, and omitted tables and
+/// keys keep their defaults:
+///
+/// ```toml
+/// [arch.buckyball]        # scratchpad banking
+/// num_banks = 32
+/// bank_depth = 1024
+/// word_bits = 128
+/// read_latency = 0
+/// write_latency = 0
+///
+/// [arch.systolic]         # array shape matmul and conv are timed on
+/// rows = 16
+/// cols = 16
+///
+/// [arch.ports]            # every bank is ideal without it
+/// default = "1r1w"
+/// banks = { 0 = "dual" }
+///
+/// [arch.energy]           # picojoules (see `bebop_bemu::EnergyTable`)
+/// mac = 0.25
+/// dram_read = 2560.0
+///
+/// [arch.cache]            # memory-side cache (see `bebop_bemu::CacheConfig`)
+/// size = 262144
+/// ways = 8
+/// line_bytes = 64
+/// mshrs = 4
+/// miss_latency = 40
+///
+/// [[isa.inst]]            # adds a mnemonic or replaces one of the same name
+/// name = "mvin"
+/// funct = 33
+/// fields = [
+///   { name = "bank", reg = "rs1", lo = 0, width = 10 },
+///   { name = "rows", reg = "rs1", lo = 30, width = 34, default = 1 },
+///   { name = "addr", reg = "rs2", lo = 0, width = 39 },
+/// ]
+///
+/// [serve]                 # `serve` only
+/// addr = "0.0.0.0:7878"
+/// ```
+#[cfg(feature = "bemu-model")]
+#[derive(Clone, Default, serde::Deserialize)]
+pub struct ArchFile {
+    #[serde(default)]
+    arch: ArchTables,
+    #[serde(default)]
+    isa: IsaTables,
+    #[serde(default)]
+    serve: ServeTable,
+    #[serde(skip)]
+    path: std::path::PathBuf,
+}
+
+#[cfg(feature = "bemu-model")]
+#[derive(Clone, Default, serde::Deserialize)]
+struct ArchTables {
+    #[serde(default)]
+    buckyball: bebop_bemu::BankGeometry,
+    #[serde(default)]
+    systolic: bebop_bemu::ArrayGeometry,
+    #[serde(default)]
+    ports: bebop_bemu::BankPorts,
+    energy: Option<bebop_bemu::EnergyTable>,
+    cache: Option<bebop_bemu::CacheConfig>,
+}
+
+#[cfg(feature = "bemu-model")]
+#[derive(Clone, Default, serde::Deserialize)]
+struct IsaTables {
+    #[serde(default)]
+    inst: Vec<bebop_bemu::IsaInst>,
+}
+
+#[cfg(feature = "bemu-model")]
+#[derive(Clone, Default, serde::Deserialize)]
+struct ServeTable {
+    addr: Option<String>,
+}
+
+#[cfg(feature = "bemu-model")]
+impl ArchFile {
+    pub fn load(path: &std::path::Path) -> Result<Self, snafu::Whatever> {
+        let file = Self {
+            path: path.to_path_buf(),
+            ..read_toml(path)?
+        };
+        let (g, a) = (file.arch.buckyball, file.arch.systolic);
+        println!(
+            "[INFO] BEMU banks: {} x {} lines, +{} cycles per read, +{} per write; {}x{} systolic array",
+            g.num_banks, g.bank_depth, g.read_latency, g.write_latency, a.rows, a.cols
+        );
+        Ok(file)
+    }
+
+    /// `--arch` of the shared model options, or the defaults without one.
+    pub fn of(model: &crate::ModelArgs) -> Result<Self, snafu::Whatever> {
+        match &model.arch {
+            Some(path) => Self::load(path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Set the banking, array shape, bank ports, energy table and cache of
+    /// `npu`. The bank geometry resets the model, so this goes first.
+    pub fn apply(&self, npu: &mut bebop_bemu::Npu) -> Result<(), snafu::Whatever> {
+        use snafu::FromString;
+        let arch = &self.arch;
+        npu.set_bank_geometry(arch.buckyball)
+            .map_err(snafu::Whatever::without_source)?;
+        npu.set_array_geometry(arch.systolic)
+            .map_err(snafu::Whatever::without_source)?;
+        npu.set_bank_ports(arch.ports.clone())
+            .map_err(snafu::Whatever::without_source)?;
+        npu.set_energy_table(arch.energy)
+            .map_err(snafu::Whatever::without_source)?;
+        npu.set_cache(arch.cache).map_err(snafu::Whatever::without_source)
+    }
+
+    /// The base instruction formats plus the `[[isa.inst]]` tables.
+    pub fn isa(&self) -> Result<bebop_bemu::Isa, snafu::Whatever> {
+        use snafu::FromString;
+        let mut isa = bebop_bemu::Isa::base();
+        isa.extend(self.isa.inst.clone())
+            .map_err(|e| snafu::Whatever::without_source(format!("{}: {e}", self.path.display())))?;
+        Ok(isa)
+    }
+
+    /// Listen address of the `[serve]` table.
+    pub fn serve_addr(&self) -> Option<&str> {
+        self.serve.addr.as_deref()
+    }
+}
+
+/// Build `npu` as the shared model options say: `arch` (loaded from
+/// `--arch` with [`ArchFile::of`]), then `--random-init`, `--dram-timing` and
+/// `--faults`. `--stats` is written by the caller once the run is over.
+#[cfg(feature = "bemu-model")]
+pub fn configure(npu: &mut bebop_bemu::Npu, model: &crate::ModelArgs, arch: &ArchFile) -> Result<(), snafu::Whatever> {
+    use snafu::FromString;
+    arch.apply(npu)?;
+    if let Some(seed) = init_seed(model.random_init) {
+        npu.randomize(seed);
+    }
+    if let Some(path) = &model.dram_timing {
+        npu.set_dram_timing(Some(load_dram_timing(path)?))
+            .map_err(snafu::Whatever::without_source)?;
+    }
+    if let Some(path) = &model.faults {
+        npu.set_faults(Some(load_faults(path)?))
+            .map_err(snafu::Whatever::without_source)?;
+    }
+    Ok(())
+}
+
+/// Read a `--dram-timing` TOML file:
+///
+/// ```toml
+/// t_cas = 14
+/// t_rcd = 14
+/// t_rp = 14
+/// row_bytes = 2048
+/// banks = 8
+/// bytes_per_cycle = 8   # optional
+/// window = 4            # optional, DMA rows in flight
+/// ```
+#[cfg(feature = "bemu-model")]
+pub fn load_dram_timing(path: &std::path::Path) -> Result<bebop_bemu::DramTiming, snafu::Whatever> {
+    read_toml(path)
+}
+
+/// Read a `--faults` TOML file (see `bebop_bemu::FaultConfig`).
+#[cfg(feature = "bemu-model")]
+pub fn load_faults(path: &std::path::Path) -> Result<bebop_bemu::FaultConfig, snafu::Whatever> {
+    read_toml(path)
+}
+
+/// Read a `--tenants` TOML file (see `bebop_bemu::TenantConfig`).
+#[cfg(feature = "bemu-model")]
+pub fn load_tenants(path: &std::path::Path) -> Result<bebop_bemu::TenantConfig, snafu::Whatever> {
+    read_toml(path)
+}
+
+/// Resolve `--random-init [SEED]`, picking and printing a seed when none was
+/// given so the failing run can be reproduced.
+#[cfg(feature = "bemu-model")]
+pub fn init_seed(random_init: Option<Option<u64>>) -> Option<u64> {
+    let seed = random_init?.unwrap_or_else(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0)
+    });
+    println!("[INFO] Random init seed: {seed} (reproduce with --random-init {seed})");
+    Some(seed)
+}
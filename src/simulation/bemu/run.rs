@@ -24,43 +24,101 @@ use snafu::{FromString, Whatever};
 use std::path::PathBuf;
 
 #[cfg(feature = "bemu")]
-use bebop_bemu::{BemuInstance, TraceConfig};
+use bebop_bemu::{BemuInstance, Checkpoints, MisalignedDma, TraceConfig};
 
 pub struct BemuRunConfig {
-    pub elf: PathBuf,
+    /// Workloads run in order on one simulator. Banks, DRAM and the latency
+    /// counter carry over from one workload to the next.
+    pub elfs: Vec<PathBuf>,
     pub log_dir: PathBuf,
     pub pk: bool,
+    /// `--arch`, `--random-init`, `--dram-timing`, `--faults` and `--stats`.
+    pub model: crate::ModelArgs,
+    /// Accelerator state snapshot written after the last workload.
+    pub snapshot: Option<PathBuf>,
+    pub fault_misaligned_dma: bool,
+    /// Checkpoint into `<log-dir>/checkpoints` every this many cycles.
+    pub checkpoint_every: Option<u64>,
+    /// Checkpoints kept on disk; older ones are deleted.
+    pub checkpoint_keep: usize,
+    /// Checkpoint the accelerator is restored from before the first workload.
+    pub resume: Option<PathBuf>,
+    /// ISA coverage file, merged with what it already holds.
+    pub coverage: Option<PathBuf>,
+    /// `--record FILE`: accelerator instruction recording for `bebop replay`.
+    pub record: Option<PathBuf>,
 }
 
 pub fn run(config: BemuRunConfig) -> Result<(), Whatever> {
     #[cfg(feature = "bemu")]
     {
-        let elf = config.elf.display();
         let log_dir = config.log_dir.display();
-        println!("[INFO] Running BEMU: elf={elf} log_dir={log_dir}");
+        println!(
+            "[INFO] Running BEMU: {} workload(s) log_dir={log_dir}",
+            config.elfs.len()
+        );
 
         // Step 1: Initialize BEMU
         let trace_config = TraceConfig::new(false, false);
         let mut bemu = BemuInstance::new(&config.log_dir, trace_config)?;
+        super::configure(bemu.npu_mut(), &config.model, &super::ArchFile::of(&config.model)?)?;
+        if let Some(path) = &config.resume {
+            bemu.load_checkpoint(path)?;
+            println!(
+                "[INFO] BEMU resumed from {} at cycle {}",
+                path.display(),
+                bemu.total_latency()
+            );
+        }
+        if config.fault_misaligned_dma {
+            bemu.set_misaligned_dma(MisalignedDma::Fault);
+        }
+        if config.coverage.is_some() {
+            bemu.enable_coverage();
+        }
+        if let Some(path) = &config.record {
+            bemu.record_trace(path)?;
+            println!("[INFO] BEMU recording: {}", path.display());
+        }
+        write_manifest(&config, &bemu)?;
+        let mut checkpoints = config
+            .checkpoint_every
+            .map(|every| Checkpoints::new(&config.log_dir.join("checkpoints"), every, config.checkpoint_keep))
+            .transpose()
+            .map_err(Whatever::without_source)?;
 
-        // Step 2: Load workload
-        bemu.load_elf(&config.elf)?;
+        let mut failure = None;
+        for elf in &config.elfs {
+            println!("[INFO] BEMU workload: elf={}", elf.display());
+            let start_latency = bemu.total_latency();
 
-        // Step 3: Initialize hart
-        bemu.init_hart(config.pk)?;
+            // Step 2: Load workload
+            bemu.load_elf(elf)?;
 
-        // Step 4: Run bemu in a loop until finished
-        while !bemu.finished() {
-            bemu.step()?;
-        }
-        println!("[INFO] BEMU total latency: {}", bemu.total_latency());
+            // Step 3: Initialize hart
+            bemu.init_hart(config.pk)?;
+
+            // Step 4: Run bemu in a loop until finished
+            while !bemu.finished() {
+                bemu.step()?;
+                if let Some(ckpt) = &mut checkpoints {
+                    ckpt.poll(bemu.npu()).map_err(Whatever::without_source)?;
+                }
+            }
+            println!("[INFO] BEMU workload latency: {}", bemu.total_latency() - start_latency);
 
-        // Step 5: exit bemu simulation
-        let exit_code = bemu.exit_code().unwrap_or(0);
-        if exit_code != 0 {
-            return Err(Whatever::without_source(format!("bemu exited with code {exit_code}")));
+            // Step 5: stop at the first failing workload
+            let exit_code = bemu.exit_code().unwrap_or(0);
+            if exit_code != 0 {
+                failure = Some(format!("bemu exited with code {exit_code} ({})", elf.display()));
+                break;
+            }
+        }
+        finish(&config, &bemu)?;
+        match failure {
+            Some(msg) => Err(Whatever::without_source(msg)),
+            None => Ok(()),
         }
-        Ok(())
     }
 
     #[cfg(not(feature = "bemu"))]
@@ -70,3 +128,44 @@ pub fn run(config: BemuRunConfig) -> Result<(), Whatever> {
         Err(Whatever::without_source(error_msg))
     }
 }
+
+/// Write what a run leaves behind, whether or not a workload failed: the
+/// snapshot, the summary, `--stats` and coverage.
+#[cfg(feature = "bemu")]
+fn finish(config: &BemuRunConfig, bemu: &BemuInstance) -> Result<(), Whatever> {
+    println!("[INFO] BEMU total latency: {}", bemu.total_latency());
+    if let Some(path) = &config.snapshot {
+        bemu.snapshot().save(path).map_err(Whatever::without_source)?;
+        println!("[INFO] BEMU snapshot: {}", path.display());
+    }
+    let perf = bemu.perf_report();
+    super::print_summary(bemu.warnings(), &perf);
+    super::print_faults(bemu.npu());
+    if let Some(path) = &config.model.stats {
+        super::save_stats(&perf, path)?;
+    }
+    if let Some(path) = &config.coverage {
+        super::save_coverage(bemu.coverage(), path)?;
+    }
+    Ok(())
+}
+
+/// Record what this run simulated in `<log-dir>/manifest.json`, before any
+/// workload starts, so the log dir is self-describing even if the run dies.
+#[cfg(feature = "bemu")]
+fn write_manifest(config: &BemuRunConfig, bemu: &BemuInstance) -> Result<(), Whatever> {
+    let manifest = serde_json::json!({
+        "bebop_version": env!("CARGO_PKG_VERSION"),
+        "command": "run bemu",
+        "elfs": config.elfs,
+        "pk": config.pk,
+        "resume": config.resume,
+        "npu": bemu.manifest(),
+    });
+    let path = config.log_dir.join("manifest.json");
+    std::fs::create_dir_all(&config.log_dir)
+        .and_then(|_| std::fs::write(&path, format!("{manifest:#}")))
+        .map_err(|e| Whatever::without_source(format!("failed to write {}: {e}", path.display())))?;
+    println!("[INFO] BEMU manifest: {}", path.display());
+    Ok(())
+}
@@ -0,0 +1,457 @@
+//===------ script.rs ------- BEMU stimulus scripts -----------------------===//
+//
+// Copyright 2026 The Aerospace Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===----------------------------------------------------------------------===//
+//
+// Runs a Rhai script against the BEMU accelerator model. No Spike hart and no
+// ELF are involved: the script issues RoCC instructions itself, pokes DRAM and
+// banks, and asserts on the counters between instructions.
+//
+//   inst(funct, xs1, xs2)             -> rd
+//   rocc(insn, xs1, xs2)              -> rd   (binary custom-0..3 word)
+//   dram_write(addr, [bytes])         /  dram_read(addr, len) -> [bytes]
+//   bank_write(vbank, off, [bytes])   /  bank_read(vbank, off, len) -> [bytes]
+//   cycles(), instructions(), reset()
+//   snapshot(path)                    state snapshot for `bebop snapshot-diff`
+//   dump_banks(path, [vbanks])        bank contents for `bebop bank-diff`
+//   save_checkpoint(path)             full model state, to resume from later
+//   load_checkpoint(path)
+//   assert(cond, msg), assert_eq(actual, expected)
+//
+// With --soak / --soak-secs the script is rerun on a reset model until the
+// bound is hit. Every run must end with the same cycle count, instruction
+// count, bank checksum and DRAM checksum as the first one, and RSS must not
+// keep growing after the first run. A reset keeps DRAM, so a script that
+// reads back what an earlier run left there fails the soak. The outcome is
+// summarised in a JSON stability report.
+//
+//===----------------------------------------------------------------------===//
+
+use bebop_bemu::{MisalignedDma, Npu, DEFAULT_MEM_SIZE};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, AST};
+use serde::Serialize;
+use snafu::{FromString, ResultExt, Whatever};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// RSS growth tolerated after the first soak iteration (allocator slack).
+const SOAK_RSS_SLACK_KIB: u64 = 4096;
+const SOAK_PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+
+pub struct ScriptConfig {
+    pub file: PathBuf,
+    pub soak: Option<u64>,
+    pub soak_secs: Option<u64>,
+    pub report: Option<PathBuf>,
+    pub model: crate::ModelArgs,
+    pub fault_misaligned_dma: bool,
+    pub coverage: Option<PathBuf>,
+}
+
+pub fn run(config: ScriptConfig) -> Result<(), Whatever> {
+    let source = std::fs::read_to_string(&config.file).whatever_context("failed to read script")?;
+    println!("[INFO] Running script: {}", config.file.display());
+
+    let mut npu = Npu::new(DEFAULT_MEM_SIZE);
+    super::configure(&mut npu, &config.model, &super::ArchFile::of(&config.model)?)?;
+    if config.fault_misaligned_dma {
+        npu.set_misaligned_dma(MisalignedDma::Fault);
+    }
+    if config.coverage.is_some() {
+        npu.enable_coverage();
+    }
+    let npu = Rc::new(RefCell::new(npu));
+    let engine = build_engine(&npu);
+    let ast = engine
+        .compile(&source)
+        .map_err(|e| Whatever::without_source(format!("{}: {e}", config.file.display())))?;
+
+    if config.soak.is_some() || config.soak_secs.is_some() {
+        return soak(&config, &engine, &ast, &npu);
+    }
+
+    let result = run_once(&engine, &ast, &config.file);
+    let npu = npu.borrow();
+    let perf = npu.perf_report();
+    super::print_summary(npu.warnings(), &perf);
+    super::print_faults(&npu);
+    if let Some(path) = &config.model.stats {
+        super::save_stats(&perf, path)?;
+    }
+    if let Some(path) = &config.coverage {
+        super::save_coverage(npu.coverage(), path)?;
+    }
+    result?;
+    println!(
+        "[INFO] Script finished: {} instructions, {} cycles",
+        npu.instruction_count(),
+        npu.total_latency()
+    );
+    Ok(())
+}
+
+fn run_once(engine: &Engine, ast: &AST, file: &Path) -> Result<(), Whatever> {
+    engine
+        .run_ast(ast)
+        .map_err(|e| Whatever::without_source(format!("{}: {e}", file.display())))
+}
+
+/// End state of one soak iteration; every iteration must match the first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+struct SoakSample {
+    cycles: u64,
+    instructions: u64,
+    bank_checksum: u64,
+    dram_checksum: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct SoakReport {
+    script: PathBuf,
+    iterations: u64,
+    elapsed_secs: f64,
+    reference: Option<SoakSample>,
+    rss_first_kib: Option<u64>,
+    rss_last_kib: Option<u64>,
+    violations: Vec<String>,
+    stable: bool,
+}
+
+fn soak(config: &ScriptConfig, engine: &Engine, ast: &AST, npu: &Rc<RefCell<Npu>>) -> Result<(), Whatever> {
+    let max_iters = config.soak.unwrap_or(u64::MAX);
+    let deadline = config.soak_secs.map(|secs| Instant::now() + Duration::from_secs(secs));
+    let start = Instant::now();
+    let mut last_progress = start;
+
+    let mut report = SoakReport {
+        script: config.file.clone(),
+        iterations: 0,
+        elapsed_secs: 0.0,
+        reference: None,
+        rss_first_kib: None,
+        rss_last_kib: None,
+        violations: Vec::new(),
+        stable: false,
+    };
+
+    while report.iterations < max_iters && deadline.is_none_or(|d| Instant::now() < d) {
+        let iter = report.iterations;
+        npu.borrow_mut().reset();
+        if let Err(e) = run_once(engine, ast, &config.file) {
+            report.violations.push(format!("iteration {iter}: {e}"));
+            break;
+        }
+        report.iterations += 1;
+
+        let sample = {
+            let npu = npu.borrow();
+            SoakSample {
+                cycles: npu.total_latency(),
+                instructions: npu.instruction_count(),
+                bank_checksum: npu.bank_checksum(),
+                dram_checksum: npu.dram_checksum(),
+            }
+        };
+        match report.reference {
+            None => report.reference = Some(sample),
+            Some(reference) if reference != sample => {
+                report.violations.push(format!(
+                    "iteration {iter}: end state {sample:?} differs from first run {reference:?}"
+                ));
+                break;
+            }
+            Some(_) => {}
+        }
+
+        report.rss_last_kib = rss_kib();
+        if iter == 0 {
+            report.rss_first_kib = report.rss_last_kib;
+        }
+        if let (Some(first), Some(last)) = (report.rss_first_kib, report.rss_last_kib) {
+            if last > first + SOAK_RSS_SLACK_KIB {
+                report
+                    .violations
+                    .push(format!("iteration {iter}: RSS grew from {first} KiB to {last} KiB"));
+                break;
+            }
+        }
+
+        if last_progress.elapsed() >= SOAK_PROGRESS_INTERVAL {
+            last_progress = Instant::now();
+            println!(
+                "[INFO] Soak: {} iterations, {:.0}s elapsed",
+                report.iterations,
+                start.elapsed().as_secs_f64()
+            );
+        }
+    }
+
+    report.elapsed_secs = start.elapsed().as_secs_f64();
+    report.stable = report.violations.is_empty();
+
+    if let Some(path) = &config.report {
+        let json = serde_json::to_string_pretty(&report).whatever_context("failed to serialize soak report")?;
+        std::fs::write(path, json).whatever_context("failed to write soak report")?;
+        println!("[INFO] Soak report: {}", path.display());
+    }
+
+    println!(
+        "[INFO] Soak finished: {} iterations in {:.1}s",
+        report.iterations, report.elapsed_secs
+    );
+    match report.violations.first() {
+        None => Ok(()),
+        Some(violation) => Err(Whatever::without_source(format!("soak failed: {violation}"))),
+    }
+}
+
+/// Resident set size of this process, from /proc/self/status.
+fn rss_kib() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    line.split_whitespace().nth(1)?.parse().ok()
+}
+
+fn build_engine(npu: &Rc<RefCell<Npu>>) -> Engine {
+    let mut engine = Engine::new();
+
+    let n = npu.clone();
+    engine.register_fn("inst", move |funct: i64, xs1: i64, xs2: i64| -> i64 {
+        n.borrow_mut().exec(funct as u32, xs1 as u64, xs2 as u64, 0) as i64
+    });
+
+    let n = npu.clone();
+    engine.register_fn("rocc", move |insn: i64, xs1: i64, xs2: i64| -> ScriptResult<i64> {
+        let rd = n
+            .borrow_mut()
+            .exec_rocc(insn as u32, xs1 as u64, xs2 as u64, 0)
+            .map_err(|e| e.to_string())?;
+        Ok(rd as i64)
+    });
+
+    let n = npu.clone();
+    engine.register_fn("reset", move || n.borrow_mut().reset());
+
+    let n = npu.clone();
+    engine.register_fn("snapshot", move |path: &str| -> ScriptResult<()> {
+        Ok(n.borrow().snapshot().save(Path::new(path))?)
+    });
+
+    let n = npu.clone();
+    engine.register_fn("dump_banks", move |path: &str, vbanks: Array| -> ScriptResult<()> {
+        let vbanks = to_vbanks(&vbanks)?;
+        Ok(n.borrow().dump_banks(&vbanks)?.save(Path::new(path))?)
+    });
+
+    let n = npu.clone();
+    engine.register_fn("save_checkpoint", move |path: &str| -> ScriptResult<()> {
+        Ok(n.borrow().save_checkpoint(Path::new(path))?)
+    });
+
+    let n = npu.clone();
+    engine.register_fn("load_checkpoint", move |path: &str| -> ScriptResult<()> {
+        Ok(n.borrow_mut().load_checkpoint(Path::new(path))?)
+    });
+
+    let n = npu.clone();
+    engine.register_fn("cycles", move || n.borrow().total_latency() as i64);
+
+    let n = npu.clone();
+    engine.register_fn("instructions", move || n.borrow().instruction_count() as i64);
+
+    let n = npu.clone();
+    engine.register_fn("dram_write", move |addr: i64, data: Array| -> ScriptResult<()> {
+        let bytes = to_bytes(&data)?;
+        n.borrow_mut().write_dram(addr as u64, &bytes);
+        Ok(())
+    });
+
+    let n = npu.clone();
+    engine.register_fn("dram_read", move |addr: i64, len: i64| -> Array {
+        from_bytes(&n.borrow().read_dram(addr as u64, len as usize))
+    });
+
+    let n = npu.clone();
+    engine.register_fn(
+        "bank_write",
+        move |vbank: i64, offset: i64, data: Array| -> ScriptResult<()> {
+            let bytes = to_bytes(&data)?;
+            let (vbank, offset) = bank_operand(vbank, offset)?;
+            n.borrow_mut()
+                .write_bank_range(vbank, offset, &bytes)
+                .map_err(|e| format!("bank_write: {e}").into())
+        },
+    );
+
+    let n = npu.clone();
+    engine.register_fn(
+        "bank_read",
+        move |vbank: i64, offset: i64, len: i64| -> ScriptResult<Array> {
+            let (vbank, offset) = bank_operand(vbank, offset)?;
+            let len = usize::try_from(len).map_err(|_| format!("bank_read: negative length {len}"))?;
+            let npu = n.borrow();
+            let bytes = npu
+                .bank_range(vbank, offset, len)
+                .map_err(|e| format!("bank_read: {e}"))?;
+            Ok(from_bytes(bytes))
+        },
+    );
+
+    engine.register_fn("assert", |cond: bool, msg: &str| -> ScriptResult<()> {
+        if cond {
+            Ok(())
+        } else {
+            Err(format!("assertion failed: {msg}").into())
+        }
+    });
+
+    engine.register_fn("assert_eq", |actual: Dynamic, expected: Dynamic| -> ScriptResult<()> {
+        if actual.to_string() == expected.to_string() {
+            Ok(())
+        } else {
+            Err(format!("assertion failed: {actual} != {expected}").into())
+        }
+    });
+
+    engine
+}
+
+/// A script's vbank and byte offset as `Npu::bank_range` takes them.
+fn bank_operand(vbank: i64, offset: i64) -> ScriptResult<(u32, usize)> {
+    let vbank = u32::try_from(vbank).map_err(|_| format!("vbank {vbank} out of range"))?;
+    let offset = usize::try_from(offset).map_err(|_| format!("negative bank offset {offset}"))?;
+    Ok((vbank, offset))
+}
+
+fn to_bytes(data: &Array) -> ScriptResult<Vec<u8>> {
+    data.iter()
+        .map(|v| {
+            v.as_int()
+                .map(|b| b as u8)
+                .map_err(|ty| format!("expected an array of integers, found {ty}").into())
+        })
+        .collect()
+}
+
+fn to_vbanks(data: &Array) -> ScriptResult<Vec<u32>> {
+    data.iter()
+        .map(|v| {
+            let id = v
+                .as_int()
+                .map_err(|ty| format!("expected an array of vbank ids, found {ty}"))?;
+            Ok(u32::try_from(id).map_err(|_| format!("vbank {id} out of range"))?)
+        })
+        .collect()
+}
+
+fn from_bytes(bytes: &[u8]) -> Array {
+    bytes.iter().map(|b| Dynamic::from_int(*b as i64)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run `script` once on a small model and return the model.
+    fn run_script(script: &str) -> Result<Npu, String> {
+        let npu = Rc::new(RefCell::new(Npu::new(1 << 20)));
+        let engine = build_engine(&npu);
+        let result = engine.run(script).map_err(|e| e.to_string());
+        drop(engine);
+        result.map(|()| Rc::into_inner(npu).unwrap().into_inner())
+    }
+
+    #[test]
+    fn script_drives_the_model() {
+        let npu = run_script(
+            r#"
+            inst(32, 1, (1 << 5) | (1 << 10));
+            dram_write(0x80001000, [1, 2, 3, 4]);
+            inst(33, 1 | (1 << 30), 0x80001000 | (1 << 39));
+            assert_eq(bank_read(1, 0, 4), [1, 2, 3, 4]);
+            bank_write(1, 0, [9, 8]);
+            rocc(0x20b5300b, 1 | (1 << 30), 0x80002000 | (1 << 39));
+            assert_eq(dram_read(0x80002000, 4), [9, 8, 3, 4]);
+            assert(cycles() > 0, "mvin takes time");
+            assert_eq(instructions(), 3);
+            "#,
+        )
+        .unwrap();
+        assert_eq!(npu.instruction_count(), 3);
+    }
+
+    #[test]
+    fn model_errors_and_failed_asserts_stop_the_script() {
+        let err = |script: &str| run_script(script).err().unwrap();
+        assert!(err("assert_eq(1, 2);").contains("assertion failed: 1 != 2"));
+        assert!(err(r#"assert(false, "no");"#).contains("assertion failed: no"));
+        assert!(err("bank_read(5, 0, 1);").contains("bank_read: bank 5 is not mapped"));
+        assert!(err("bank_write(-1, 0, [1]);").contains("vbank -1 out of range"));
+        assert!(err("rocc(0x13, 0, 0);").contains("not a RoCC custom instruction: 0x00000013"));
+        assert!(err("dram_write(0x80000000, [\"x\"]);").contains("expected an array of integers"));
+    }
+
+    /// Soak `script` for three iterations on a small model.
+    fn soak_script(name: &str, script: &str) -> Result<(), Whatever> {
+        let file = std::env::temp_dir().join(format!("bebop-soak-{name}-{}.rhai", std::process::id()));
+        let report = file.with_extension("json");
+        std::fs::write(&file, script).unwrap();
+        let config = ScriptConfig {
+            file: file.clone(),
+            soak: Some(3),
+            soak_secs: None,
+            report: Some(report.clone()),
+            model: crate::ModelArgs::default(),
+            fault_misaligned_dma: false,
+            coverage: None,
+        };
+        let npu = Rc::new(RefCell::new(Npu::new(1 << 20)));
+        let engine = build_engine(&npu);
+        let ast = engine.compile(script).unwrap();
+        let result = soak(&config, &engine, &ast, &npu);
+        let report: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&report).unwrap()).unwrap();
+        std::fs::remove_file(&file).unwrap();
+        std::fs::remove_file(config.report.unwrap()).unwrap();
+        assert_eq!(report["stable"], result.is_ok());
+        result
+    }
+
+    #[test]
+    fn soak_passes_a_repeatable_script() {
+        let script = r#"
+            inst(32, 1, 1056);
+            dram_write(0x80000000, [1, 2, 3, 4]);
+            inst(33, 1 | (1 << 30), 0x80000000 | (1 << 39));
+            assert_eq(bank_read(1, 0, 4), [1, 2, 3, 4]);
+        "#;
+        soak_script("stable", script).unwrap();
+    }
+
+    #[test]
+    fn soak_catches_dram_carried_between_runs() {
+        // Banks and counters match every run; only DRAM drifts.
+        let script = r#"
+            let b = dram_read(0x80000000, 1);
+            dram_write(0x80000000, [b[0] + 1]);
+        "#;
+        let err = soak_script("drift", script).unwrap_err().to_string();
+        assert!(err.starts_with("soak failed: iteration 1: end state"), "{err}");
+    }
+}
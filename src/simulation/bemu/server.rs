@@ -0,0 +1,690 @@
+//===------ server.rs ------- BEMU HTTP control server ---------------------===//
+//
+// Copyright 2026 The Aerospace Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===----------------------------------------------------------------------===//
+//
+// Serves BEMU accelerator models over HTTP so CI harnesses and notebooks
+// can drive them without a script file. Requests and responses are JSON.
+//
+// A fixed pool of threads reads connections, so a client that stalls while
+// sending does not hold up the others, and one that sends nothing for
+// READ_TIMEOUT is dropped. Further clients wait to be accepted until a thread
+// is free. Requests then take turns on the models: each runs to completion
+// before the next starts.
+//
+//   POST /inst         {"funct", "xs1", "xs2"}          -> {"rd", "cycles"}
+//   POST /rocc         {"insn", "xs1", "xs2"}           -> {"rd", "cycles"}
+//   POST /batch        {"insts": [inst or rocc bodies]} -> {"results": [...]}
+//   POST /dram/read    {"addr", "len"}                  -> {"bytes"}, len <= DRAM size
+//   POST /dram/write   {"addr", "bytes"}
+//   POST /bank/read    {"vbank", "offset", "len"}       -> {"bytes"}
+//   POST /bank/write   {"vbank", "offset", "bytes"}
+//   POST /reset
+//   GET  /stats        cycles, instructions, DMA statistics, warnings
+//   GET  /manifest     the model manifest
+//   GET  /version      {"protocol", "supported", "harts"}
+//
+// Clients pick the protocol with a `Bebop-Protocol: N` request header; every
+// response carries the version the server answered in. Without the header the
+// server speaks version 1. Both versions share the routes and success bodies
+// and differ in errors only:
+//
+//   v1  {"error": "..."}
+//   v2  {"error": {"code": "unmapped_bank", "message": "..."}}
+//
+// A `Bebop-Role: monitor` header marks a dashboard or debugger that must not
+// disturb the run: it may use GET routes and the /dram/read and /bank/read
+// routes, and anything else answers 403 with code "forbidden". Clients
+// without the header are drivers and may use every route.
+//
+// `--harts N` serves N models, one per RoCC-issuing core. A `Bebop-Hart: K`
+// header sends the request to model K; without it, requests go to hart 0.
+// Each model has its own banks, counters and cycle count, but they share one
+// guest DRAM, so data one hart moves out is visible to the next. A hart
+// number outside 0..N answers 400 with code "unknown_hart".
+//
+// /batch runs its instructions in order and answers once the last one has
+// retired, so a client streaming an instruction-dense kernel pays one round
+// trip per batch instead of one per instruction, and the response doubles as
+// a fence. The first instruction that fails stops the batch: the error names
+// its index, and the instructions before it keep their effect.
+//
+// Codes are listed in `ErrorCode`. /rocc checks the funct7 and the bank and
+// DMA operands before running, so an unknown funct7, an unmapped bank, a
+// misaligned transfer or a bank conflict there answers 400 with its own code
+// and leaves the model untouched. An instruction that makes the model abort
+// answers 500 with code "model_aborted"; the model keeps whatever state it
+// reached.
+//
+//===----------------------------------------------------------------------===//
+
+use bebop_bemu::{Npu, NpuError};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use snafu::{whatever, ResultExt, Whatever};
+use std::any::Any;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Requests larger than this are rejected rather than buffered, and
+/// /dram/read answers at most this many bytes.
+const MAX_BODY: usize = 64 << 20;
+
+/// Connections served at once.
+const WORKERS: usize = 16;
+
+/// How long a connection may go without sending before it is dropped.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Newest protocol version; the server also speaks every older one.
+const PROTOCOL: u32 = 2;
+
+/// Kinds of failure a v2 client can match on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ErrorCode {
+    /// Malformed HTTP, or a protocol version the server does not speak.
+    BadRequest,
+    BodyTooLarge,
+    /// The JSON body does not match the route.
+    InvalidBody,
+    NoRoute,
+    /// A monitor asked for a route that changes the model.
+    Forbidden,
+    /// `Bebop-Hart` names a model the server was not started with.
+    UnknownHart,
+    UnmappedBank,
+    OutOfBounds,
+    /// A DMA address off the beat boundary while misaligned DMA faults.
+    Misaligned,
+    /// An instruction writes a bank it also reads and cannot work in place.
+    BankConflict,
+    /// A bank outside the issuing tenant's partition.
+    CrossTenant,
+    /// `/rocc` got a word that is not a RoCC custom instruction.
+    InvalidInsn,
+    /// `/rocc` got a funct7 no instruction implements.
+    UnknownFunct,
+    ModelAborted,
+}
+
+impl ErrorCode {
+    fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::BadRequest => "bad_request",
+            ErrorCode::BodyTooLarge => "body_too_large",
+            ErrorCode::InvalidBody => "invalid_body",
+            ErrorCode::NoRoute => "no_route",
+            ErrorCode::Forbidden => "forbidden",
+            ErrorCode::UnknownHart => "unknown_hart",
+            ErrorCode::UnmappedBank => "unmapped_bank",
+            ErrorCode::OutOfBounds => "out_of_bounds",
+            ErrorCode::Misaligned => "misaligned",
+            ErrorCode::BankConflict => "bank_conflict",
+            ErrorCode::CrossTenant => "cross_tenant",
+            ErrorCode::InvalidInsn => "invalid_insn",
+            ErrorCode::UnknownFunct => "unknown_funct",
+            ErrorCode::ModelAborted => "model_aborted",
+        }
+    }
+
+    fn status(self) -> u16 {
+        match self {
+            ErrorCode::Forbidden => 403,
+            ErrorCode::NoRoute => 404,
+            ErrorCode::BodyTooLarge => 413,
+            ErrorCode::ModelAborted => 500,
+            _ => 400,
+        }
+    }
+
+    /// Error body in protocol `version`.
+    fn body(self, version: u32, msg: String) -> Value {
+        if version < 2 {
+            json!({ "error": msg })
+        } else {
+            json!({ "error": { "code": self.as_str(), "message": msg } })
+        }
+    }
+}
+
+type HandlerError = (ErrorCode, String);
+
+/// What a client declared itself as with the `Bebop-Role` header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Role {
+    Driver,
+    Monitor,
+}
+
+impl Role {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "driver" => Some(Role::Driver),
+            "monitor" => Some(Role::Monitor),
+            _ => None,
+        }
+    }
+
+    /// Whether this role may call `method path`.
+    fn allows(self, method: &str, path: &str) -> bool {
+        self == Role::Driver || method == "GET" || matches!(path, "/dram/read" | "/bank/read")
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+    role: Role,
+    hart: usize,
+}
+
+pub struct ServerConfig {
+    pub addr: String,
+    pub mem_size: usize,
+    /// Number of models; at least 1.
+    pub harts: usize,
+    /// `--arch` file the models are built from; the defaults when `None`.
+    pub arch: Option<super::ArchFile>,
+}
+
+/// One model per hart. Guest DRAM lives in whichever model last served a
+/// request and is handed over with `Npu::swap_dram` when another hart's
+/// request comes in.
+struct Harts {
+    npus: Vec<Npu>,
+    dram_at: usize,
+}
+
+impl Harts {
+    fn get(&mut self, hart: usize) -> &mut Npu {
+        if hart != self.dram_at {
+            let (lo, hi) = (hart.min(self.dram_at), hart.max(self.dram_at));
+            let (left, right) = self.npus.split_at_mut(hi);
+            left[lo].swap_dram(&mut right[0]);
+            self.dram_at = hart;
+        }
+        &mut self.npus[hart]
+    }
+}
+
+#[derive(Deserialize)]
+struct InstReq {
+    funct: u32,
+    xs1: u64,
+    xs2: u64,
+}
+
+#[derive(Deserialize)]
+struct RoccReq {
+    insn: u32,
+    xs1: u64,
+    xs2: u64,
+}
+
+/// One entry of a /batch body, shaped like an /inst or a /rocc body.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BatchInst {
+    Inst(InstReq),
+    Rocc(RoccReq),
+}
+
+#[derive(Deserialize)]
+struct BatchReq {
+    insts: Vec<BatchInst>,
+}
+
+#[derive(Deserialize)]
+struct DramReq {
+    addr: u64,
+    #[serde(default)]
+    len: usize,
+    #[serde(default)]
+    bytes: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+struct BankReq {
+    vbank: u32,
+    offset: usize,
+    #[serde(default)]
+    len: usize,
+    #[serde(default)]
+    bytes: Vec<u8>,
+}
+
+pub fn run(config: ServerConfig) -> Result<(), Whatever> {
+    if config.harts == 0 {
+        whatever!("--harts must be at least 1");
+    }
+    let mut npus = Vec::with_capacity(config.harts);
+    for hart in 0..config.harts {
+        // Only hart 0 starts out holding the shared DRAM.
+        let mut npu = Npu::new(if hart == 0 { config.mem_size } else { 0 });
+        if let Some(arch) = &config.arch {
+            arch.apply(&mut npu)?;
+        }
+        npus.push(npu);
+    }
+    if config.harts > 1 {
+        println!("[INFO] BEMU server: {} harts sharing one DRAM", config.harts);
+    }
+    let npu = Arc::new(Mutex::new(Harts { npus, dram_at: 0 }));
+    let listener =
+        TcpListener::bind(&config.addr).with_whatever_context(|_| format!("failed to bind {}", config.addr))?;
+    println!("[INFO] BEMU server listening on http://{}", config.addr);
+    let (queue, streams) = mpsc::sync_channel::<TcpStream>(WORKERS);
+    let streams = Arc::new(Mutex::new(streams));
+    for _ in 0..WORKERS {
+        let (streams, npu) = (Arc::clone(&streams), Arc::clone(&npu));
+        thread::spawn(move || loop {
+            let stream = streams.lock().unwrap_or_else(|e| e.into_inner()).recv();
+            let Ok(stream) = stream else {
+                return;
+            };
+            if let Err(e) = serve_one(stream, &npu) {
+                eprintln!("[WARN] BEMU server: {e}");
+            }
+        });
+    }
+    for stream in listener.incoming() {
+        let stream = match stream.and_then(|s| s.set_read_timeout(Some(READ_TIMEOUT)).map(|_| s)) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("[WARN] BEMU server: accept failed: {e}");
+                continue;
+            }
+        };
+        // Blocks while every worker is busy and the queue is full.
+        if queue.send(stream).is_err() {
+            whatever!("BEMU server: every worker thread has exited");
+        }
+    }
+    Ok(())
+}
+
+fn serve_one(stream: TcpStream, harts: &Mutex<Harts>) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let (version, status, body) = answer(&mut reader, harts);
+    let body = body.to_string();
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        413 => "Payload Too Large",
+        _ => "Internal Server Error",
+    };
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nBebop-Protocol: {version}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+/// Read one request from `reader` and run it on the hart it names; returns
+/// the protocol version answered in, the status and the JSON body.
+fn answer(reader: &mut impl BufRead, harts: &Mutex<Harts>) -> (u32, u16, Value) {
+    let mut version = 1;
+    let (status, body) = match read_request(reader, &mut version) {
+        Ok(req) if !req.role.allows(&req.method, &req.path) => {
+            let code = ErrorCode::Forbidden;
+            let msg = format!("monitor clients may not {} {}", req.method, req.path);
+            (code.status(), code.body(version, msg))
+        }
+        Ok(req) => {
+            // A model abort is caught inside `route`, so the lock is only
+            // poisoned by a bug in the server itself.
+            let mut harts = harts.lock().unwrap_or_else(|e| e.into_inner());
+            let count = harts.npus.len();
+            if req.hart >= count {
+                let code = ErrorCode::UnknownHart;
+                let msg = format!("hart {} is not served; harts are 0..{count}", req.hart);
+                (code.status(), code.body(version, msg))
+            } else {
+                let npu = harts.get(req.hart);
+                route(npu, count, version, &req.method, &req.path, &req.body)
+            }
+        }
+        Err((code, msg)) => (code.status(), code.body(version, msg)),
+    };
+    (version, status, body)
+}
+
+/// One HTTP/1.1 request. `version` is set from the `Bebop-Protocol` header as
+/// soon as it is read, so later errors are reported in the client's protocol.
+fn read_request(reader: &mut impl BufRead, version: &mut u32) -> Result<Request, HandlerError> {
+    let bad = |msg: String| (ErrorCode::BadRequest, msg);
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|e| bad(e.to_string()))?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Err(bad(format!("malformed request line: {line:?}")));
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+
+    let mut len = 0;
+    let mut role = Role::Driver;
+    let mut hart = 0;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).map_err(|e| bad(e.to_string()))?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                len = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| bad(format!("bad Content-Length: {value}")))?;
+            } else if name.eq_ignore_ascii_case("bebop-role") {
+                role = Role::parse(value.trim()).ok_or_else(|| bad(format!("unknown role {}", value.trim())))?;
+            } else if name.eq_ignore_ascii_case("bebop-hart") {
+                hart = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| bad(format!("bad Bebop-Hart: {}", value.trim())))?;
+            } else if name.eq_ignore_ascii_case("bebop-protocol") {
+                match value.trim().parse() {
+                    Ok(v) if v >= 1 => *version = PROTOCOL.min(v),
+                    _ => {
+                        *version = PROTOCOL;
+                        return Err(bad(format!("unsupported protocol version {}", value.trim())));
+                    }
+                }
+            }
+        }
+    }
+    if len > MAX_BODY {
+        return Err((
+            ErrorCode::BodyTooLarge,
+            format!("body of {len} bytes exceeds {MAX_BODY}"),
+        ));
+    }
+    let mut body = vec![0; len];
+    reader.read_exact(&mut body).map_err(|e| bad(e.to_string()))?;
+    Ok(Request {
+        method,
+        path,
+        body,
+        role,
+        hart,
+    })
+}
+
+fn route(npu: &mut Npu, harts: usize, version: u32, method: &str, path: &str, body: &[u8]) -> (u16, Value) {
+    let result = catch_unwind(AssertUnwindSafe(|| handle(npu, harts, method, path, body)));
+    let (code, msg) = match result {
+        Ok(Ok(value)) => return (200, value),
+        Ok(Err(e)) => e,
+        Err(panic) => (ErrorCode::ModelAborted, panic_message(panic)),
+    };
+    (code.status(), code.body(version, msg))
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    panic
+        .downcast_ref::<String>()
+        .cloned()
+        .or_else(|| panic.downcast_ref::<&str>().map(|s| s.to_string()))
+        .unwrap_or_else(|| "model aborted".to_string())
+}
+
+fn parse<'a, T: Deserialize<'a>>(body: &'a [u8]) -> Result<T, HandlerError> {
+    serde_json::from_slice(body).map_err(|e| (ErrorCode::InvalidBody, format!("invalid request body: {e}")))
+}
+
+fn npu_error(e: NpuError) -> HandlerError {
+    let code = match e {
+        NpuError::UnmappedBank { .. } => ErrorCode::UnmappedBank,
+        NpuError::OutOfBounds { .. } => ErrorCode::OutOfBounds,
+        NpuError::Misaligned { .. } => ErrorCode::Misaligned,
+        NpuError::BankConflict { .. } => ErrorCode::BankConflict,
+        NpuError::CrossTenant { .. } => ErrorCode::CrossTenant,
+        NpuError::InvalidInsn { .. } => ErrorCode::InvalidInsn,
+        NpuError::UnknownFunct { .. } => ErrorCode::UnknownFunct,
+    };
+    (code, e.to_string())
+}
+
+fn handle(npu: &mut Npu, harts: usize, method: &str, path: &str, body: &[u8]) -> Result<Value, HandlerError> {
+    match (method, path) {
+        ("POST", "/inst") => {
+            let r: InstReq = parse(body)?;
+            let rd = npu.exec(r.funct, r.xs1, r.xs2, 0);
+            Ok(json!({ "rd": rd, "cycles": npu.total_latency() }))
+        }
+        ("POST", "/rocc") => {
+            let r: RoccReq = parse(body)?;
+            let rd = npu.exec_rocc(r.insn, r.xs1, r.xs2, 0).map_err(npu_error)?;
+            Ok(json!({ "rd": rd, "cycles": npu.total_latency() }))
+        }
+        ("POST", "/batch") => {
+            let r: BatchReq = parse(body)?;
+            let mut results = Vec::with_capacity(r.insts.len());
+            for (i, inst) in r.insts.into_iter().enumerate() {
+                let rd = catch_unwind(AssertUnwindSafe(|| match inst {
+                    BatchInst::Inst(r) => Ok(npu.exec(r.funct, r.xs1, r.xs2, 0)),
+                    BatchInst::Rocc(r) => npu.exec_rocc(r.insn, r.xs1, r.xs2, 0).map_err(npu_error),
+                }))
+                .unwrap_or_else(|panic| Err((ErrorCode::ModelAborted, panic_message(panic))))
+                .map_err(|(code, msg)| (code, format!("batch stopped at inst {i}: {msg}")))?;
+                results.push(json!({ "rd": rd, "cycles": npu.total_latency() }));
+            }
+            Ok(json!({ "results": results }))
+        }
+        ("POST", "/dram/read") => {
+            let r: DramReq = parse(body)?;
+            let cap = MAX_BODY.min(npu.memory().len());
+            if r.len > cap {
+                let msg = format!("read of {} bytes exceeds the {cap}-byte limit", r.len);
+                return Err((ErrorCode::OutOfBounds, msg));
+            }
+            Ok(json!({ "bytes": npu.read_dram(r.addr, r.len) }))
+        }
+        ("POST", "/dram/write") => {
+            let r: DramReq = parse(body)?;
+            npu.write_dram(r.addr, &r.bytes);
+            Ok(json!({}))
+        }
+        ("POST", "/bank/read") => {
+            let r: BankReq = parse(body)?;
+            let bytes = npu.bank_range(r.vbank, r.offset, r.len).map_err(npu_error)?;
+            Ok(json!({ "bytes": bytes }))
+        }
+        ("POST", "/bank/write") => {
+            let r: BankReq = parse(body)?;
+            npu.write_bank_range(r.vbank, r.offset, &r.bytes).map_err(npu_error)?;
+            Ok(json!({}))
+        }
+        ("POST", "/reset") => {
+            npu.reset();
+            Ok(json!({}))
+        }
+        ("GET", "/stats") => {
+            let dma = npu.dma_stats();
+            Ok(json!({
+                "cycles": npu.total_latency(),
+                "instructions": npu.instruction_count(),
+                "dma": {
+                    "transfers": dma.transfers,
+                    "misaligned": dma.misaligned,
+                    "penalty_cycles": dma.penalty_cycles,
+                    "throttle_cycles": dma.throttle_cycles,
+                },
+                "cache": npu.cache_stats(),
+                "warnings": npu
+                    .warnings()
+                    .iter()
+                    .map(|(kind, s)| (kind.as_str().to_string(), json!(s.count)))
+                    .collect::<serde_json::Map<_, _>>(),
+            }))
+        }
+        ("GET", "/manifest") => Ok(json!(npu.manifest())),
+        ("GET", "/version") => Ok(json!({
+            "protocol": PROTOCOL,
+            "supported": (1..=PROTOCOL).collect::<Vec<_>>(),
+            "harts": harts,
+        })),
+        _ => Err((ErrorCode::NoRoute, format!("no route for {method} {path}"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MSET_BANK: &str = r#"{"funct": 32, "xs1": 1, "xs2": 1056}"#;
+
+    fn harts(n: usize) -> Mutex<Harts> {
+        let npus = (0..n)
+            .map(|hart| Npu::new(if hart == 0 { 1 << 20 } else { 0 }))
+            .collect();
+        Mutex::new(Harts { npus, dram_at: 0 })
+    }
+
+    fn send(harts: &Mutex<Harts>, method: &str, path: &str, headers: &[&str], body: &str) -> (u32, u16, Value) {
+        let mut text = format!("{method} {path} HTTP/1.1\r\nContent-Length: {}\r\n", body.len());
+        for h in headers {
+            text += &format!("{h}\r\n");
+        }
+        text += &format!("\r\n{body}");
+        answer(&mut text.as_bytes(), harts)
+    }
+
+    fn rocc(funct: u32) -> u32 {
+        (funct << 25) | (11 << 20) | (10 << 15) | (0b011 << 12) | 0x0b
+    }
+
+    #[test]
+    fn protocol_header_picks_the_error_body() {
+        let h = harts(1);
+        let (version, status, body) = send(&h, "GET", "/nope", &[], "");
+        assert_eq!((version, status), (1, 404));
+        assert_eq!(body, json!({ "error": "no route for GET /nope" }));
+
+        let (version, status, body) = send(&h, "GET", "/nope", &["Bebop-Protocol: 2"], "");
+        assert_eq!((version, status), (2, 404));
+        assert_eq!(body["error"]["code"], "no_route");
+        assert_eq!(body["error"]["message"], "no route for GET /nope");
+
+        // Newer clients are answered in the newest version the server speaks.
+        let (version, status, _) = send(&h, "GET", "/version", &["Bebop-Protocol: 9"], "");
+        assert_eq!((version, status), (PROTOCOL, 200));
+
+        let (version, status, body) = send(&h, "GET", "/version", &["Bebop-Protocol: 0"], "");
+        assert_eq!((version, status), (PROTOCOL, 400));
+        assert_eq!(body["error"]["code"], "bad_request");
+
+        let insn = rocc(127);
+        let rocc_body = format!(r#"{{"insn": {insn}, "xs1": 0, "xs2": 0}}"#);
+        let (_, status, body) = send(&h, "POST", "/rocc", &["Bebop-Protocol: 2"], &rocc_body);
+        assert_eq!(status, 400);
+        assert_eq!(body["error"]["code"], "unknown_funct");
+    }
+
+    #[test]
+    fn monitors_only_read() {
+        for (method, path) in [("GET", "/stats"), ("POST", "/dram/read"), ("POST", "/bank/read")] {
+            assert!(Role::Monitor.allows(method, path), "{method} {path}");
+        }
+        for (method, path) in [
+            ("POST", "/inst"),
+            ("POST", "/batch"),
+            ("POST", "/dram/write"),
+            ("POST", "/reset"),
+        ] {
+            assert!(!Role::Monitor.allows(method, path), "{method} {path}");
+            assert!(Role::Driver.allows(method, path), "{method} {path}");
+        }
+
+        let h = harts(1);
+        let monitor = ["Bebop-Role: monitor", "Bebop-Protocol: 2"];
+        let (_, status, body) = send(&h, "POST", "/inst", &monitor, MSET_BANK);
+        assert_eq!(status, 403);
+        assert_eq!(body["error"]["code"], "forbidden");
+        assert_eq!(h.lock().unwrap().npus[0].instruction_count(), 0);
+        let (_, status, _) = send(&h, "GET", "/stats", &monitor, "");
+        assert_eq!(status, 200);
+
+        let (_, status, _) = send(&h, "GET", "/stats", &["Bebop-Role: admin"], "");
+        assert_eq!(status, 400);
+    }
+
+    #[test]
+    fn harts_share_dram_and_keep_their_own_models() {
+        let h = harts(2);
+        let write = r#"{"addr": 2147483648, "bytes": [1, 2, 3]}"#;
+        assert_eq!(send(&h, "POST", "/dram/write", &["Bebop-Hart: 1"], write).1, 200);
+        assert_eq!(h.lock().unwrap().dram_at, 1);
+        assert_eq!(send(&h, "POST", "/inst", &["Bebop-Hart: 1"], MSET_BANK).1, 200);
+
+        let (_, status, body) = send(&h, "POST", "/dram/read", &[], r#"{"addr": 2147483648, "len": 3}"#);
+        assert_eq!(status, 200);
+        assert_eq!(body["bytes"], json!([1, 2, 3]));
+        // Reads are capped by the DRAM size rather than allocated blindly.
+        let huge = r#"{"addr": 2147483648, "len": 1099511627776}"#;
+        let (_, status, body) = send(&h, "POST", "/dram/read", &["Bebop-Protocol: 2"], huge);
+        assert_eq!(status, 400);
+        assert_eq!(body["error"]["code"], "out_of_bounds");
+        let (_, _, stats) = send(&h, "GET", "/stats", &[], "");
+        assert_eq!(stats["instructions"], 0);
+        let (_, _, stats) = send(&h, "GET", "/stats", &["Bebop-Hart: 1"], "");
+        assert_eq!(stats["instructions"], 1);
+
+        let (_, status, body) = send(&h, "GET", "/stats", &["Bebop-Hart: 2", "Bebop-Protocol: 2"], "");
+        assert_eq!(status, 400);
+        assert_eq!(body["error"]["code"], "unknown_hart");
+    }
+
+    #[test]
+    fn harts_get_moves_dram_to_the_hart_asked_for() {
+        let mut h = harts(3).into_inner().unwrap();
+        h.get(2).write_dram(bebop_bemu::DRAM_BASE, &[9]);
+        assert_eq!(h.dram_at, 2);
+        assert!(h.npus[0].memory().is_empty());
+        assert_eq!(h.get(1).read_dram(bebop_bemu::DRAM_BASE, 1), [9]);
+        assert!(h.npus[2].memory().is_empty());
+        assert_eq!(h.get(1).memory().len(), 1 << 20);
+    }
+
+    #[test]
+    fn batch_stops_at_the_first_failure() {
+        let h = harts(1);
+        let mvin_unmapped = format!(r#"{{"insn": {}, "xs1": 5, "xs2": 0}}"#, rocc(33));
+        let batch = format!(r#"{{"insts": [{MSET_BANK}, {mvin_unmapped}, {MSET_BANK}]}}"#);
+        let (_, status, body) = send(&h, "POST", "/batch", &["Bebop-Protocol: 2"], &batch);
+        assert_eq!(status, 400);
+        assert_eq!(body["error"]["code"], "unmapped_bank");
+        assert_eq!(
+            body["error"]["message"],
+            "batch stopped at inst 1: bank 5 is not mapped"
+        );
+        // The mset before the failure took effect; the one after never ran.
+        let npu = &h.lock().unwrap().npus[0];
+        assert_eq!(npu.instruction_count(), 1);
+        assert!(npu.bank(1).is_some());
+    }
+}
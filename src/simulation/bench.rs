@@ -0,0 +1,51 @@
+//===--- bench.rs ----- benchmark suite entry point ----------------------===//
+//
+// Copyright 2026 The Aerospace Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===----------------------------------------------------------------------===//
+
+use crate::BenchSuiteCommand;
+#[cfg(not(feature = "bemu-model"))]
+use snafu::FromString;
+use snafu::Whatever;
+
+pub fn bench_suite(command: BenchSuiteCommand) -> Result<(), Whatever> {
+    #[cfg(feature = "bemu-model")]
+    {
+        use crate::simulation::bemu::{bench, calibrate};
+        match command.calibrate {
+            Some(reference) => calibrate::run(calibrate::CalibrateConfig {
+                reference,
+                model: crate::ModelArgs {
+                    arch: command.arch,
+                    random_init: command.random_init,
+                    dram_timing: command.dram_timing,
+                    faults: command.faults,
+                    stats: None,
+                },
+                output: command.output,
+            }),
+            None => bench::run(bench::BenchConfig { output: command.output }),
+        }
+    }
+
+    #[cfg(not(feature = "bemu-model"))]
+    {
+        let _ = command;
+        Err(Whatever::without_source(
+            "bench suite is not compiled into this executable".to_string(),
+        ))
+    }
+}
@@ -0,0 +1,87 @@
+//===--- compare.rs ----- lockstep model comparison entry point -----------===//
+//
+// Copyright 2026 The Aerospace Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===----------------------------------------------------------------------===//
+
+use crate::CompareCommand;
+use snafu::{FromString, Whatever};
+
+/// Run a text instruction program on two BEMU models built from different
+/// `--arch` files and fail at the first instruction whose DRAM writes differ.
+pub fn compare(command: CompareCommand) -> Result<(), Whatever> {
+    #[cfg(feature = "bemu-model")]
+    {
+        use crate::simulation::bemu::{configure, init_seed, ArchFile};
+        use bebop_bemu::{Npu, Program, DEFAULT_MEM_SIZE};
+
+        let program = Program::load(&command.file).map_err(Whatever::without_source)?;
+        // One seed for both sides, so they start from the same garbage.
+        let seed = init_seed(command.random_init);
+        let model = |arch: &Option<std::path::PathBuf>| -> Result<Npu, Whatever> {
+            let model = crate::ModelArgs {
+                arch: arch.clone(),
+                random_init: None,
+                dram_timing: command.dram_timing.clone(),
+                faults: command.faults.clone(),
+                stats: None,
+            };
+            let mut npu = Npu::new(DEFAULT_MEM_SIZE);
+            configure(&mut npu, &model, &ArchFile::of(&model)?)?;
+            if let Some(seed) = seed {
+                npu.randomize(seed);
+            }
+            Ok(npu)
+        };
+        let mut left = model(&command.left_arch)?;
+        let mut right = model(&command.right_arch)?;
+        let name = |arch: &Option<std::path::PathBuf>| {
+            arch.as_ref()
+                .map_or_else(|| "defaults".to_string(), |p| p.display().to_string())
+        };
+        println!(
+            "[INFO] Comparing {} on {} (left) and {} (right)",
+            command.file.display(),
+            name(&command.left_arch),
+            name(&command.right_arch)
+        );
+
+        let result = bebop_bemu::compare(&program, &mut left, &mut right);
+        println!(
+            "{} of {} instructions, {} cycles left, {} cycles right",
+            result.executed,
+            program.insts.len(),
+            result.left_cycles,
+            result.right_cycles
+        );
+        if let Some(d) = result.divergence {
+            println!("[ERROR] models diverge at {d}");
+            return Err(Whatever::without_source(format!(
+                "models diverge at line {}",
+                d.inst.line
+            )));
+        }
+        println!("[INFO] DRAM writes match");
+        Ok(())
+    }
+
+    #[cfg(not(feature = "bemu-model"))]
+    {
+        let _ = command;
+        Err(Whatever::without_source(
+            "the compare mode is not compiled into this executable".to_string(),
+        ))
+    }
+}
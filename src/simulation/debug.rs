@@ -0,0 +1,98 @@
+//===--- debug.rs ------ interactive program debugger entry point ---------===//
+//
+// Copyright 2026 The Aerospace Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===----------------------------------------------------------------------===//
+
+use crate::DebugCommand;
+use snafu::{FromString, Whatever};
+
+/// Step a text instruction program on a fresh BEMU model under an
+/// interactive prompt with breakpoints and bank and DRAM dumps. With
+/// `--cycles` the program runs on the cycle-stepped NpuSim instead.
+pub fn debug(command: DebugCommand) -> Result<(), Whatever> {
+    #[cfg(feature = "bemu-model")]
+    {
+        use bebop_bemu::{CycleDebugger, Debugger, Npu, NpuSim, NpuSimConfig, Program, DEFAULT_MEM_SIZE};
+        use std::io::{BufRead, Write};
+
+        let arch = crate::simulation::bemu::ArchFile::of(&command.model)?;
+        let isa = arch.isa()?;
+        let program = Program::load_with(&command.file, &isa).map_err(Whatever::without_source)?;
+        println!(
+            "[INFO] Debugging {} ({} instructions); `quit` or end of input exits",
+            command.file.display(),
+            program.insts.len()
+        );
+        let (mut debugger, mut cycle_debugger) = match command.cycles {
+            Some(units) => {
+                let mut sim = NpuSim::new(NpuSimConfig {
+                    mem_size: DEFAULT_MEM_SIZE,
+                    units,
+                    ..NpuSimConfig::default()
+                });
+                crate::simulation::bemu::configure(sim.npu_mut(), &command.model, &arch)?;
+                (None, Some(CycleDebugger::new(sim, program)))
+            }
+            None => {
+                let mut npu = Npu::new(DEFAULT_MEM_SIZE);
+                crate::simulation::bemu::configure(&mut npu, &command.model, &arch)?;
+                (Some(Debugger::new(npu, program)), None)
+            }
+        };
+        let stdin = std::io::stdin();
+        let mut lines = stdin.lock().lines();
+        loop {
+            print!("(bemu) ");
+            let _ = std::io::stdout().flush();
+            let Some(line) = lines.next() else { break };
+            let line = line.map_err(|e| Whatever::without_source(format!("failed to read command: {e}")))?;
+            if matches!(line.trim(), "quit" | "q") {
+                break;
+            }
+            let out = match (&mut debugger, &mut cycle_debugger) {
+                (Some(d), _) => d.command(&line),
+                (_, Some(d)) => d.command(&line),
+                (None, None) => unreachable!("one debugger is always created"),
+            };
+            match out {
+                Ok(out) if out.is_empty() => {}
+                Ok(out) => println!("{out}"),
+                Err(e) => println!("error: {e}"),
+            }
+        }
+        println!();
+        let npu = match (&debugger, &cycle_debugger) {
+            (Some(d), _) => d.npu(),
+            (_, Some(d)) => d.sim().npu(),
+            (None, None) => unreachable!("one debugger is always created"),
+        };
+        let perf = npu.perf_report();
+        crate::simulation::bemu::print_summary(npu.warnings(), &perf);
+        crate::simulation::bemu::print_faults(npu);
+        if let Some(path) = &command.model.stats {
+            crate::simulation::bemu::save_stats(&perf, path)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "bemu-model"))]
+    {
+        let _ = command;
+        Err(Whatever::without_source(
+            "the debugger is not compiled into this executable".to_string(),
+        ))
+    }
+}
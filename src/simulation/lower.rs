@@ -0,0 +1,84 @@
+//===--- lower.rs ------ layer description lowering entry point -----------===//
+//
+// Copyright 2026 The Aerospace Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===----------------------------------------------------------------------===//
+
+use crate::LowerCommand;
+use snafu::{FromString, Whatever};
+
+/// Lower a JSON layer description to an instruction program, and optionally
+/// run it to estimate the latency of each layer.
+pub fn lower(command: LowerCommand) -> Result<(), Whatever> {
+    #[cfg(feature = "bemu-model")]
+    {
+        use bebop_bemu::{Npu, Program, DEFAULT_MEM_SIZE};
+        use std::collections::BTreeMap;
+
+        let json = std::fs::read_to_string(&command.file)
+            .map_err(|e| Whatever::without_source(format!("failed to read {}: {e}", command.file.display())))?;
+        let mut npu = Npu::new(DEFAULT_MEM_SIZE);
+        let arch = crate::simulation::bemu::ArchFile::of(&command.model)?;
+        crate::simulation::bemu::configure(&mut npu, &command.model, &arch)?;
+        let lowered = bebop_bemu::lower(&json, &npu.bank_geometry()).map_err(Whatever::without_source)?;
+        match &command.output {
+            Some(path) => {
+                std::fs::write(path, &lowered.program)
+                    .map_err(|e| Whatever::without_source(format!("failed to write {}: {e}", path.display())))?;
+                println!("[INFO] Wrote program to {}", path.display());
+            }
+            None if !command.run => print!("{}", lowered.program),
+            None => {}
+        }
+        if !command.run {
+            return Ok(());
+        }
+
+        let program = Program::parse(&lowered.program).map_err(Whatever::without_source)?;
+        let report = program.run(&mut npu);
+        // Labels sort as strings, so keep the program order instead.
+        let mut layers: Vec<(&str, u64, usize)> = Vec::new();
+        let mut index = BTreeMap::new();
+        for (inst, cycles) in &report.steps {
+            let label = inst.label.as_deref().unwrap_or("");
+            let i = *index.entry(label).or_insert_with(|| {
+                layers.push((label, 0, 0));
+                layers.len() - 1
+            });
+            layers[i].1 += cycles;
+            layers[i].2 += 1;
+        }
+        println!("{:<20} {:>8} {:>12}", "layer", "insts", "cycles");
+        for (label, cycles, insts) in layers {
+            println!("{label:<20} {insts:>8} {cycles:>12}");
+        }
+        println!("{} instructions, {} cycles", report.steps.len(), report.cycles);
+        let perf = npu.perf_report();
+        crate::simulation::bemu::print_summary(npu.warnings(), &perf);
+        crate::simulation::bemu::print_faults(&npu);
+        if let Some(path) = &command.model.stats {
+            crate::simulation::bemu::save_stats(&perf, path)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "bemu-model"))]
+    {
+        let _ = command;
+        Err(Whatever::without_source(
+            "the layer lowering is not compiled into this executable".to_string(),
+        ))
+    }
+}
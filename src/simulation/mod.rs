@@ -1,8 +1,26 @@
 pub mod bemu;
+pub mod bench;
 pub mod build;
+pub mod compare;
+pub mod debug;
+pub mod lower;
 pub mod p2e;
+pub mod program;
+pub mod replay;
 pub mod run;
+pub mod script;
+pub mod serve;
+pub mod snapshot;
 pub mod verilator;
 
+pub use bench::bench_suite;
 pub use build::build;
+pub use compare::compare;
+pub use debug::debug;
+pub use lower::lower;
+pub use program::program;
+pub use replay::replay;
 pub use run::run;
+pub use script::script;
+pub use serve::serve;
+pub use snapshot::{bank_diff, snapshot_diff};
@@ -0,0 +1,95 @@
+//===--- program.rs ----- instruction program entry point -----------------===//
+//
+// Copyright 2026 The Aerospace Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===----------------------------------------------------------------------===//
+
+use crate::ProgramCommand;
+use snafu::{FromString, Whatever};
+
+/// Run a text instruction program on a fresh BEMU model and print the cycles
+/// of every instruction.
+pub fn program(command: ProgramCommand) -> Result<(), Whatever> {
+    #[cfg(feature = "bemu-model")]
+    {
+        use bebop_bemu::{Npu, Program, DEFAULT_MEM_SIZE};
+
+        println!("[INFO] Running program: {}", command.file.display());
+        let mut npu = Npu::new(DEFAULT_MEM_SIZE);
+        let arch = crate::simulation::bemu::ArchFile::of(&command.model)?;
+        crate::simulation::bemu::configure(&mut npu, &command.model, &arch)?;
+        if command.golden_check {
+            npu.enable_golden_check();
+        }
+        if command.tile_tags {
+            npu.enable_tile_tags();
+        }
+        for spec in &command.watch {
+            let w: bebop_bemu::Watchpoint = spec.parse().map_err(Whatever::without_source)?;
+            npu.add_watchpoint(w);
+        }
+        let isa = arch.isa()?;
+        let program = Program::load_with(&command.file, &isa).map_err(Whatever::without_source)?;
+        match &command.tenants {
+            Some(path) => {
+                let config = crate::simulation::bemu::load_tenants(path)?;
+                npu.set_tenants(Some(config)).map_err(Whatever::without_source)?;
+                let mut programs = vec![program];
+                for file in &command.streams {
+                    programs.push(Program::load_with(file, &isa).map_err(Whatever::without_source)?);
+                }
+                let reports = Program::run_tenants(&programs, &mut npu).map_err(Whatever::without_source)?;
+                let stats = npu.tenant_stats().unwrap_or_default();
+                for ((name, stats), report) in stats.iter().zip(reports) {
+                    println!("[INFO] Tenant {name}:");
+                    print!("{report}");
+                    println!("[INFO] Tenant {name}: {stats}");
+                }
+            }
+            None => print!("{}", program.run(&mut npu)),
+        }
+        for hit in npu.take_watch_hits() {
+            println!("[WATCH] {hit}");
+        }
+        let perf = npu.perf_report();
+        crate::simulation::bemu::print_summary(npu.warnings(), &perf);
+        crate::simulation::bemu::print_faults(&npu);
+        if let Some(path) = &command.model.stats {
+            crate::simulation::bemu::save_stats(&perf, path)?;
+        }
+        for (format, error) in npu.format_errors() {
+            println!("[INFO] {format} rounding: {error}");
+        }
+        let mismatches = npu.golden_mismatches();
+        if !mismatches.is_empty() {
+            for m in mismatches {
+                println!("[ERROR] golden mismatch: {m}");
+            }
+            return Err(Whatever::without_source(format!(
+                "{} results disagree with the golden model",
+                mismatches.len()
+            )));
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "bemu-model"))]
+    {
+        let _ = command;
+        Err(Whatever::without_source(
+            "the program runner is not compiled into this executable".to_string(),
+        ))
+    }
+}
@@ -0,0 +1,62 @@
+//===--- replay.rs ----- recorded instruction replay entry point ----------===//
+//
+// Copyright 2026 The Aerospace Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===----------------------------------------------------------------------===//
+
+use crate::ReplayCommand;
+use snafu::{FromString, Whatever};
+
+/// Re-execute a `run bemu --record` recording on a fresh BEMU model, without
+/// Spike or the workload, and report where its timing departs from the
+/// recorded run.
+pub fn replay(command: ReplayCommand) -> Result<(), Whatever> {
+    #[cfg(feature = "bemu-model")]
+    {
+        use bebop_bemu::{Npu, Recording, DEFAULT_MEM_SIZE};
+
+        let recording = Recording::load(&command.file).map_err(Whatever::without_source)?;
+        let arch = crate::simulation::bemu::ArchFile::of(&command.model)?;
+        if command.disassemble {
+            let isa = arch.isa()?;
+            print!("{}", recording.disassemble(&isa));
+            return Ok(());
+        }
+        println!("[INFO] Replaying: {}", command.file.display());
+        let mut npu = Npu::new(DEFAULT_MEM_SIZE);
+        crate::simulation::bemu::configure(&mut npu, &command.model, &arch)?;
+        let report = recording.replay(&mut npu);
+        print!("{report}");
+        if let Some(path) = &command.snapshot {
+            npu.snapshot().save(path).map_err(Whatever::without_source)?;
+            println!("[INFO] BEMU snapshot: {}", path.display());
+        }
+        let perf = npu.perf_report();
+        crate::simulation::bemu::print_summary(npu.warnings(), &perf);
+        crate::simulation::bemu::print_faults(&npu);
+        if let Some(path) = &command.model.stats {
+            crate::simulation::bemu::save_stats(&perf, path)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "bemu-model"))]
+    {
+        let _ = command;
+        Err(Whatever::without_source(
+            "replay is not compiled into this executable".to_string(),
+        ))
+    }
+}
@@ -72,9 +72,31 @@ pub fn run(command: RunCommand) -> Result<(), Whatever> {
                 crate::simulation::verilator::run::run_unavailable()
             }
         }
-        RunTarget::Bemu { elf, log_dir, pk } => {
-            crate::simulation::bemu::run::run(crate::simulation::bemu::run::BemuRunConfig { elf, log_dir, pk })
-        }
+        RunTarget::Bemu {
+            elf,
+            log_dir,
+            pk,
+            model,
+            snapshot,
+            fault_misaligned_dma,
+            checkpoint_every,
+            checkpoint_keep,
+            resume,
+            coverage,
+            record,
+        } => crate::simulation::bemu::run::run(crate::simulation::bemu::run::BemuRunConfig {
+            elfs: elf,
+            log_dir,
+            pk,
+            model,
+            snapshot,
+            fault_misaligned_dma,
+            checkpoint_every,
+            checkpoint_keep,
+            resume,
+            coverage,
+            record,
+        }),
         RunTarget::P2e {
             image,
             bitstream,
@@ -0,0 +1,45 @@
+//===--- script.rs ----- stimulus script entry point ---------------------===//
+//
+// Copyright 2026 The Aerospace Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===----------------------------------------------------------------------===//
+
+use crate::ScriptCommand;
+#[cfg(not(feature = "script"))]
+use snafu::FromString;
+use snafu::Whatever;
+
+pub fn script(command: ScriptCommand) -> Result<(), Whatever> {
+    #[cfg(feature = "script")]
+    {
+        crate::simulation::bemu::script::run(crate::simulation::bemu::script::ScriptConfig {
+            file: command.file,
+            soak: command.soak,
+            soak_secs: command.soak_secs,
+            report: command.report,
+            model: command.model,
+            fault_misaligned_dma: command.fault_misaligned_dma,
+            coverage: command.coverage,
+        })
+    }
+
+    #[cfg(not(feature = "script"))]
+    {
+        let _ = command;
+        Err(Whatever::without_source(
+            "script runner is not compiled into this executable".to_string(),
+        ))
+    }
+}
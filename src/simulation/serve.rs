@@ -0,0 +1,51 @@
+//===--- serve.rs ----- BEMU HTTP server entry point ----------------------===//
+//
+// Copyright 2026 The Aerospace Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===----------------------------------------------------------------------===//
+
+use crate::ServeCommand;
+#[cfg(not(feature = "bemu-model"))]
+use snafu::FromString;
+use snafu::Whatever;
+
+#[cfg(feature = "bemu-model")]
+const DEFAULT_ADDR: &str = "127.0.0.1:7878";
+
+pub fn serve(command: ServeCommand) -> Result<(), Whatever> {
+    #[cfg(feature = "bemu-model")]
+    {
+        let arch = command
+            .arch
+            .as_deref()
+            .map(crate::simulation::bemu::ArchFile::load)
+            .transpose()?;
+        let addr = arch.as_ref().and_then(|a| a.serve_addr()).map(str::to_string);
+        crate::simulation::bemu::server::run(crate::simulation::bemu::server::ServerConfig {
+            addr: command.addr.or(addr).unwrap_or_else(|| DEFAULT_ADDR.to_string()),
+            mem_size: command.mem_size,
+            harts: command.harts,
+            arch,
+        })
+    }
+
+    #[cfg(not(feature = "bemu-model"))]
+    {
+        let _ = command;
+        Err(Whatever::without_source(
+            "the BEMU server is not compiled into this executable".to_string(),
+        ))
+    }
+}
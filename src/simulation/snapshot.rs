@@ -0,0 +1,89 @@
+//===--- snapshot.rs ----- snapshot and bank dump diff entry points -------===//
+//
+// Copyright 2026 The Aerospace Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//===----------------------------------------------------------------------===//
+
+use crate::SnapshotDiffCommand;
+use snafu::{FromString, Whatever};
+
+/// Print every field that differs between two snapshots, first divergence
+/// first. Fails when they differ so scripts can use the exit status.
+pub fn snapshot_diff(command: SnapshotDiffCommand) -> Result<(), Whatever> {
+    #[cfg(feature = "bemu-model")]
+    {
+        use bebop_bemu::Snapshot;
+
+        let left = Snapshot::load(&command.left).map_err(Whatever::without_source)?;
+        let right = Snapshot::load(&command.right).map_err(Whatever::without_source)?;
+        let diffs = left.diff(&right);
+        let Some(first) = diffs.first() else {
+            println!("[INFO] Snapshots are identical");
+            return Ok(());
+        };
+        println!("[INFO] First difference: {first}");
+        for d in &diffs[1..] {
+            println!("  {d}");
+        }
+        Err(Whatever::without_source(format!(
+            "{} field(s) differ between {} and {}",
+            diffs.len(),
+            command.left.display(),
+            command.right.display()
+        )))
+    }
+
+    #[cfg(not(feature = "bemu-model"))]
+    {
+        let _ = command;
+        Err(Whatever::without_source(
+            "snapshot diff is not compiled into this executable".to_string(),
+        ))
+    }
+}
+
+/// Print every element that differs between two bank dumps, with its bank
+/// offset and both decoded values. Fails when they differ.
+pub fn bank_diff(command: SnapshotDiffCommand) -> Result<(), Whatever> {
+    #[cfg(feature = "bemu-model")]
+    {
+        use bebop_bemu::BankDump;
+
+        let left = BankDump::load(&command.left).map_err(Whatever::without_source)?;
+        let right = BankDump::load(&command.right).map_err(Whatever::without_source)?;
+        let diffs = left.diff(&right).map_err(Whatever::without_source)?;
+        if diffs.is_empty() {
+            println!("[INFO] Bank dumps are identical");
+            return Ok(());
+        }
+        for d in &diffs {
+            println!("  {d}");
+        }
+        Err(Whatever::without_source(format!(
+            "{} element(s) differ between {} and {}",
+            diffs.len(),
+            command.left.display(),
+            command.right.display()
+        )))
+    }
+
+    #[cfg(not(feature = "bemu-model"))]
+    {
+        let _ = command;
+        Err(Whatever::without_source(
+            "bank diff is not compiled into this executable".to_string(),
+        ))
+    }
+}
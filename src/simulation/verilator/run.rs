@@ -38,7 +38,9 @@ use std::path::Path;
 use bebop_fd_redirect::FdRedirect;
 
 #[cfg(feature = "verilator")]
-use bebop_verilator::{exit_code, init_trace, setup_ctrlc_handler, should_exit, Simulator, TraceConfig};
+use bebop_verilator::{
+    exit_code, init_trace, setup_ctrlc_handler, should_exit, write_trace_summary, Simulator, TraceConfig,
+};
 
 #[cfg(feature = "verilator")]
 use super::console::ConsoleServer;
@@ -154,6 +156,7 @@ pub fn run(config: VerilatorRunConfig) -> Result<(), Whatever> {
     // Finish Simulation
     //===----------------------------------------------------------------------===//
     simulator.finalize();
+    write_trace_summary(&config.log_dir).whatever_context("failed to write Verilator RTL trace summary")?;
 
     drop(console);
     drop(stderr_guard);
@@ -1,28 +1,41 @@
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
 
 use super::RegressionResult;
 
 const ARTIFACT_ROOT: &str = "test-artifacts";
+/// Overrides ARTIFACT_ROOT, e.g. to give concurrent harness runs their own tree.
+const ENV_ARTIFACT_ROOT: &str = "BEBOP_ARTIFACT_DIR";
 const DIR_LOG: &str = "log";
 const DIR_FST: &str = "fst";
 const FILE_STDOUT: &str = "stdout.log";
 const FILE_STDERR: &str = "stderr.log";
 const FILE_WAVEFORM: &str = "waveform.fst";
 
+/// Per-process sequence number so trials of one harness never share a dir.
+static NEXT_ID: AtomicU32 = AtomicU32::new(0);
+
 fn workspace_root() -> PathBuf {
     std::env::var("CARGO_MANIFEST_DIR")
         .map(PathBuf::from)
         .unwrap_or_else(|_| PathBuf::from("."))
 }
 
+fn artifact_root() -> PathBuf {
+    match std::env::var_os(ENV_ARTIFACT_ROOT) {
+        Some(dir) => workspace_root().join(dir),
+        None => workspace_root().join(ARTIFACT_ROOT),
+    }
+}
+
 pub struct ArtifactManager {
     root: PathBuf,
 }
 
 impl ArtifactManager {
     pub fn clean_all() -> std::io::Result<()> {
-        let root = workspace_root().join(ARTIFACT_ROOT);
+        let root = artifact_root();
         if root.exists() {
             fs::remove_dir_all(root)?;
         }
@@ -30,12 +43,24 @@ impl ArtifactManager {
     }
 
     /// Create artifact directory with backend and timestamp prefix.
-    /// Format: <backend>-<YYYY-MM-DD-HH-MM-SS>-<workload-name>
+    /// Format: <backend>-<YYYY-MM-DD-HH-MM-SS>-<workload-name>-<pid>-<n>
+    ///
+    /// The pid and sequence number keep parallel trials and concurrent
+    /// harness processes apart when they start within the same second.
     pub fn create_with_backend(backend: &str, workload_name: &str) -> std::io::Result<Self> {
         let timestamp = chrono::Local::now().format("%Y-%m-%d-%H-%M-%S");
-        let dir_name = format!("{}-{}-{}", backend, timestamp, workload_name);
-        let root = workspace_root().join(ARTIFACT_ROOT).join(dir_name);
-        fs::create_dir_all(root.join(DIR_LOG))?;
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let dir_name = format!(
+            "{}-{}-{}-{}-{}",
+            backend,
+            timestamp,
+            workload_name,
+            std::process::id(),
+            id
+        );
+        let root = artifact_root().join(dir_name);
+        fs::create_dir_all(&root)?;
+        fs::create_dir(root.join(DIR_LOG))?;
         fs::create_dir_all(root.join(DIR_FST))?;
         Ok(ArtifactManager { root })
     }